@@ -0,0 +1,325 @@
+//! Crash-safe output writes.
+//!
+//! Outputs are written to a temporary file in the destination directory,
+//! synced to disk and then moved to their final name, after which the
+//! directory itself is synced so the new name survives a power loss. At no
+//! point can a crash leave a truncated original or a half-written output.
+//!
+//! Temporary files are named `.<name>.<16 hex digits>.pmi_tmp`, with long
+//! names cut short so the result stays within file name limits, and created
+//! exclusively, so concurrent runs and existing files never collide. They
+//! are removed when a write fails; only a killed run leaves one behind, and
+//! `pmi cleanup` finds those.
+
+use crate::error::{Error, Result};
+use std::collections::hash_map::RandomState;
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Extension of temporary files.
+pub const TEMP_EXTENSION: &str = "pmi_tmp";
+
+/// Number of random names to try before giving up.
+const ATTEMPTS: usize = 16;
+
+/// Longest part of the output name kept in a temporary name, in bytes. The
+/// rest of the name adds 26 bytes, well within the usual 255-byte limit.
+const MAX_NAME_PART: usize = 128;
+
+/// A temporary file next to an output, removed unless it is moved into
+/// place.
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf,
+    file: File,
+    persisted: bool,
+}
+
+impl TempFile {
+    /// Create an empty temporary file in the same directory as `target`.
+    pub fn new(target: &Path) -> Result<Self> {
+        let name = target.file_name().unwrap_or(OsStr::new("output"));
+        let mut last_error = None;
+        for _ in 0..ATTEMPTS {
+            let path = target.with_file_name(temp_name(name));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => {
+                    return Ok(TempFile {
+                        path,
+                        file,
+                        persisted: false,
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => last_error = Some(e),
+                Err(e) => return Err(Error::io_with_path(e, path)),
+            }
+        }
+        Err(Error::io_with_path(
+            last_error.unwrap_or_else(|| io::Error::from(io::ErrorKind::AlreadyExists)),
+            target,
+        ))
+    }
+
+    /// Get the open file.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Get the temporary path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write data to the file.
+    pub fn write_all(&mut self, data: &[u8]) -> Result<()> {
+        self.file
+            .write_all(data)
+            .map_err(|e| Error::io_with_path(e, &self.path))
+    }
+
+    /// Sync the file's data and metadata to disk.
+    fn sync(&self) -> Result<()> {
+        self.file
+            .sync_all()
+            .map_err(|e| Error::io_with_path(e, &self.path))
+    }
+
+    /// Move the file to `target`, replacing anything there.
+    pub fn persist(mut self, target: &Path) -> Result<()> {
+        self.sync()?;
+        fs::rename(&self.path, target).map_err(|e| Error::io_with_path(e, target))?;
+        self.persisted = true;
+        sync_parent(target)
+    }
+
+    /// Move the file to `target` unless something is already there.
+    ///
+    /// Returns whether the file was moved. On `false` the temporary file
+    /// is still there, so another name can be tried.
+    pub fn persist_new(&mut self, target: &Path) -> Result<bool> {
+        self.sync()?;
+        match fs::hard_link(&self.path, target) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Ok(false),
+            Err(_) => {
+                // Without hard links, reserve the name and then replace the
+                // reservation. A crash in between leaves an empty output
+                // rather than a damaged one.
+                match OpenOptions::new().write(true).create_new(true).open(target) {
+                    Ok(_) => {}
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Ok(false),
+                    Err(e) => return Err(Error::io_with_path(e, target)),
+                }
+                fs::rename(&self.path, target).map_err(|e| Error::io_with_path(e, target))?;
+                self.persisted = true;
+                return sync_parent(target).map(|()| true);
+            }
+        }
+        self.persisted = true;
+        fs::remove_file(&self.path).map_err(|e| Error::io_with_path(e, &self.path))?;
+        sync_parent(target).map(|()| true)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Get a fresh temporary name for a file called `name`.
+fn temp_name(name: &OsStr) -> String {
+    // Distinct within the process, and keyed per process.
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos()),
+    );
+    hasher.write_u32(std::process::id());
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+
+    let name = name.to_string_lossy();
+    let mut end = name.len().min(MAX_NAME_PART);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        ".{}.{:016x}.{}",
+        &name[..end],
+        hasher.finish(),
+        TEMP_EXTENSION
+    )
+}
+
+/// Check if a file name is one of ours: `.<name>.<16 hex digits>.pmi_tmp`.
+pub fn is_temp_name(name: &str) -> bool {
+    name.strip_prefix('.')
+        .and_then(|rest| rest.strip_suffix(TEMP_EXTENSION))
+        .and_then(|rest| rest.strip_suffix('.'))
+        .and_then(|rest| rest.rsplit_once('.'))
+        .is_some_and(|(original, random)| {
+            !original.is_empty()
+                && random.len() == 16
+                && random.bytes().all(|b| b.is_ascii_hexdigit())
+        })
+}
+
+/// Check if a file name is `<stem>.pmi_tmp`, as earlier versions named
+/// temporary files by replacing the image's extension. Anyone may have a
+/// file named like that, so it is not taken to be ours.
+pub fn is_legacy_temp_name(name: &str) -> bool {
+    name.strip_suffix(TEMP_EXTENSION)
+        .and_then(|rest| rest.strip_suffix('.'))
+        .is_some_and(|stem| !stem.is_empty() && !stem.starts_with('.'))
+}
+
+/// Sync the directory holding `path`, so a rename into it is durable.
+fn sync_parent(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(|e| Error::io_with_path(e, dir))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Replace `target` with `data`, atomically and durably, after `prepare`
+/// has had the chance to set the temporary file's metadata. Returns what
+/// `prepare` returned.
+pub fn replace<T>(
+    target: &Path,
+    data: &[u8],
+    prepare: impl FnOnce(&File) -> io::Result<T>,
+) -> Result<T> {
+    let mut temp = TempFile::new(target)?;
+    temp.write_all(data)?;
+    let prepared = prepare(temp.file()).map_err(|e| Error::io_with_path(e, temp.path()))?;
+    temp.persist(target)?;
+    Ok(prepared)
+}
+
+/// Find files under `path`, which may be a file or a directory, whose
+/// names `matches` accepts, such as [`is_temp_name`].
+pub fn find_temp_files(
+    path: &Path,
+    recursive: bool,
+    matches: fn(&str) -> bool,
+    found: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let metadata = fs::symlink_metadata(path).map_err(|e| Error::io_with_path(e, path))?;
+    if !metadata.is_dir() {
+        let name = path.file_name().and_then(OsStr::to_str).unwrap_or("");
+        if metadata.is_file() && matches(name) {
+            found.push(path.to_path_buf());
+        }
+        return Ok(());
+    }
+
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(path).map_err(|e| Error::io_with_path(e, path))? {
+        let entry = entry.map_err(|e| Error::io_with_path(e, path))?;
+        let file_type = entry
+            .file_type()
+            .map_err(|e| Error::io_with_path(e, path))?;
+        if file_type.is_file() || (recursive && file_type.is_dir()) {
+            entries.push(entry.path());
+        }
+    }
+    entries.sort();
+    for entry in entries {
+        find_temp_files(&entry, recursive, matches, found)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_names() {
+        let name = temp_name(OsStr::new("photo.jpg"));
+        assert!(name.starts_with(".photo.jpg."));
+        assert!(is_temp_name(&name));
+        assert_ne!(name, temp_name(OsStr::new("photo.jpg")));
+
+        assert!(!is_temp_name("photo.pmi_tmp"));
+        assert!(is_legacy_temp_name("photo.pmi_tmp"));
+        assert!(!is_legacy_temp_name(&name));
+        assert!(!is_legacy_temp_name(".pmi_tmp"));
+        assert!(!is_temp_name("photo.jpg"));
+        assert!(!is_temp_name(".pmi_tmp"));
+        assert!(!is_temp_name("..pmi_tmp"));
+        assert!(!is_temp_name(".photo.jpg.xyz.pmi_tmp"));
+
+        // Long names are cut on a character boundary.
+        let long = "€".repeat(100);
+        let name = temp_name(OsStr::new(&long));
+        assert!(name.starts_with(&format!(".{}.", "€".repeat(42))));
+        assert_eq!(name.len(), 42 * 3 + 26);
+        assert!(is_temp_name(&name));
+    }
+
+    #[test]
+    fn test_replace_long_name() {
+        let dir = std::env::temp_dir().join("pmi_atomic_long");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join(format!("{}.jpg", "x".repeat(246)));
+        assert_eq!(target.file_name().unwrap().len(), 250);
+
+        replace(&target, b"data", |_| Ok(())).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"data");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_replace_and_persist_new() {
+        let dir = std::env::temp_dir().join("pmi_atomic");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("photo.jpg");
+
+        replace(&target, b"first", |_| Ok(())).unwrap();
+        replace(&target, b"second", |_| Ok(())).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"second");
+
+        // A failure removes the temporary file and leaves the target alone.
+        let failed = replace::<()>(&target, b"third", |_| Err(io::Error::other("no")));
+        assert!(failed.is_err());
+        assert_eq!(fs::read(&target).unwrap(), b"second");
+
+        // An existing file is never replaced.
+        let mut temp = TempFile::new(&target).unwrap();
+        temp.write_all(b"new").unwrap();
+        assert!(!temp.persist_new(&target).unwrap());
+        let other = dir.join("other.jpg");
+        assert!(temp.persist_new(&other).unwrap());
+        drop(temp);
+        assert_eq!(fs::read(&other).unwrap(), b"new");
+
+        let mut found = Vec::new();
+        find_temp_files(&dir, true, is_temp_name, &mut found).unwrap();
+        assert!(found.is_empty(), "{:?}", found);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,382 @@
+//! Backups and the undo journal for in-place runs.
+//!
+//! With `--backup <DIR>`, each run gets a directory under `DIR` named after
+//! the time it started. Before a file is replaced its original is copied
+//! there, with its times, mode and extended attributes, and once the cleaned
+//! file is in place a line is added to the run's `journal.csv`. The journal
+//! records when each file was cleaned, where it and its backup are, and the
+//! SHA-256 of both versions.
+//!
+//! `pmi restore <JOURNAL>` puts the originals back. It only overwrites a
+//! file that still has the hash the run gave it, and only from a backup
+//! that still has the hash of the original, so files edited since the run
+//! are left alone.
+
+use crate::atomic::{self, TempFile};
+use crate::cli::Config;
+use crate::digest::{sha256, to_hex};
+use crate::error::{Error, Result};
+use crate::fsmeta::{FsMetadata, Plan};
+use crate::metadata::dates::Timestamp;
+use crate::report::{csv_row, csv_rows};
+use crate::terminal::{print_error, print_info, print_success, print_warning};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the journal in each run's directory.
+pub const JOURNAL_NAME: &str = "journal.csv";
+
+/// Journal columns, in order.
+const JOURNAL_COLUMNS: &[&str] = &[
+    "time",
+    "original",
+    "backup",
+    "input_sha256",
+    "output",
+    "output_sha256",
+];
+
+/// The backup directory of one run.
+#[derive(Debug, Clone)]
+pub struct Backup {
+    dir: PathBuf,
+}
+
+/// An original that has been backed up, before its journal entry is
+/// written.
+#[derive(Debug, Clone)]
+pub struct Saved {
+    original: PathBuf,
+    backup: PathBuf,
+    input_sha256: String,
+}
+
+impl Backup {
+    /// Create the directory for a new run under `root`, with an empty
+    /// journal.
+    ///
+    /// On Unix the directory is readable by its owner only, as the backups
+    /// still hold all their metadata.
+    pub fn start(root: &Path) -> Result<Self> {
+        fs::create_dir_all(root).map_err(|e| Error::io_with_path(e, root))?;
+
+        let now = Timestamp::now_utc();
+        let name = format!(
+            "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+            now.year, now.month, now.day, now.hour, now.minute, now.second
+        );
+        let mut attempt = 1;
+        let dir = loop {
+            let dir = match attempt {
+                1 => root.join(&name),
+                _ => root.join(format!("{}-{}", name, attempt)),
+            };
+            let mut builder = fs::DirBuilder::new();
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::DirBuilderExt;
+                builder.mode(0o700);
+            }
+            match builder.create(&dir) {
+                Ok(()) => break dir,
+                // Another run started in the same second.
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+                Err(e) => return Err(Error::io_with_path(e, dir)),
+            }
+        };
+
+        let backup = Backup {
+            dir: fs::canonicalize(&dir).map_err(|e| Error::io_with_path(e, &dir))?,
+        };
+        let header: Vec<String> = JOURNAL_COLUMNS.iter().map(|c| c.to_string()).collect();
+        backup.append(&header)?;
+        Ok(backup)
+    }
+
+    /// Get the run's directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the run's journal.
+    pub fn journal(&self) -> PathBuf {
+        self.dir.join(JOURNAL_NAME)
+    }
+
+    /// Copy an original into the backup directory before it is replaced.
+    ///
+    /// `index` is the file's position in the run, which keeps backups of
+    /// files with the same name apart.
+    pub fn save(&self, original: &Path, index: usize, data: &[u8]) -> Result<Saved> {
+        let name = original.file_name().map_or_else(
+            || String::from("image"),
+            |n| n.to_string_lossy().into_owned(),
+        );
+        let backup = self.dir.join(format!("{:04}-{}", index, name));
+
+        // A backup that cannot keep the original's owner is still a backup.
+        let plan = Plan::new(FsMetadata::Preserve, original, true)?;
+        let mut temp = TempFile::new(&backup)?;
+        temp.write_all(data)?;
+        plan.apply(temp.file())
+            .map_err(|e| Error::io_with_path(e, temp.path()))?;
+        if !temp.persist_new(&backup)? {
+            return Err(Error::OutputExists { path: backup });
+        }
+
+        Ok(Saved {
+            original: fs::canonicalize(original).map_err(|e| Error::io_with_path(e, original))?,
+            backup,
+            input_sha256: to_hex(&sha256(data)),
+        })
+    }
+
+    /// Add the journal entry for an original once its cleaned replacement
+    /// is in place.
+    pub fn record(&self, saved: Saved, output: &Path, output_data: &[u8]) -> Result<()> {
+        let entry = JournalEntry {
+            time: format!("{}Z", Timestamp::now_utc()),
+            original: saved.original,
+            backup: saved.backup,
+            input_sha256: saved.input_sha256,
+            output: fs::canonicalize(output).map_err(|e| Error::io_with_path(e, output))?,
+            output_sha256: to_hex(&sha256(output_data)),
+        };
+        self.append(&entry.cells())
+    }
+
+    /// Append a row to the journal and sync it, in one write so that
+    /// parallel workers do not interleave.
+    fn append(&self, cells: &[String]) -> Result<()> {
+        let path = self.journal();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| Error::io_with_path(e, &path))?;
+        file.write_all(format!("{}\n", csv_row(cells)).as_bytes())
+            .and_then(|()| file.sync_data())
+            .map_err(|e| Error::io_with_path(e, &path))
+    }
+}
+
+/// One replaced file, as recorded in the journal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// When the cleaned file was put in place, in UTC.
+    pub time: String,
+    /// Where the original was.
+    pub original: PathBuf,
+    /// Where its backup is.
+    pub backup: PathBuf,
+    /// SHA-256 of the original.
+    pub input_sha256: String,
+    /// Where the cleaned file is; the original path unless it was renamed.
+    pub output: PathBuf,
+    /// SHA-256 of the cleaned file.
+    pub output_sha256: String,
+}
+
+impl JournalEntry {
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.time.clone(),
+            self.original.display().to_string(),
+            self.backup.display().to_string(),
+            self.input_sha256.clone(),
+            self.output.display().to_string(),
+            self.output_sha256.clone(),
+        ]
+    }
+
+    fn from_cells(cells: &[String]) -> Option<Self> {
+        match cells {
+            [time, original, backup, input_sha256, output, output_sha256] => Some(JournalEntry {
+                time: time.clone(),
+                original: PathBuf::from(original),
+                backup: PathBuf::from(backup),
+                input_sha256: input_sha256.clone(),
+                output: PathBuf::from(output),
+                output_sha256: output_sha256.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Read the entries of a journal, in the order they were written.
+pub fn read_journal(path: &Path) -> Result<Vec<JournalEntry>> {
+    let text = fs::read_to_string(path).map_err(|e| Error::io_with_path(e, path))?;
+    let invalid = |reason: String| Error::InvalidArgument {
+        argument: path.display().to_string(),
+        reason,
+    };
+
+    let mut rows = csv_rows(&text).into_iter();
+    if rows.next().is_none_or(|header| header != JOURNAL_COLUMNS) {
+        return Err(invalid(String::from("Not a pmi journal")));
+    }
+    rows.enumerate()
+        .map(|(i, row)| {
+            JournalEntry::from_cells(&row)
+                .ok_or_else(|| invalid(format!("Entry {} is malformed", i + 1)))
+        })
+        .collect()
+}
+
+/// Outcome of restoring one journal entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreStatus {
+    /// The original was put back (or would be, in a dry run).
+    Restored,
+    /// The original is already in place.
+    AlreadyRestored,
+    /// The file has changed since the run, so it was left alone.
+    Changed(String),
+    /// The original could not be put back.
+    Failed(String),
+}
+
+/// Get the SHA-256 of a file, or `None` if it does not exist.
+fn file_sha256(path: &Path) -> Result<Option<String>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(to_hex(&sha256(&data)))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Error::io_with_path(e, path)),
+    }
+}
+
+/// Put back the original of one journal entry, after checking that the
+/// backup is intact and that nothing has changed since the run.
+pub fn restore_entry(entry: &JournalEntry, dry_run: bool) -> RestoreStatus {
+    match try_restore(entry, dry_run) {
+        Ok(status) => status,
+        Err(e) => RestoreStatus::Failed(e.to_string()),
+    }
+}
+
+fn try_restore(entry: &JournalEntry, dry_run: bool) -> Result<RestoreStatus> {
+    let renamed = entry.output != entry.original;
+    let original = file_sha256(&entry.original)?;
+    let output = if renamed {
+        file_sha256(&entry.output)?
+    } else {
+        original.clone()
+    };
+
+    if original.as_deref() == Some(entry.input_sha256.as_str()) && (!renamed || output.is_none()) {
+        return Ok(RestoreStatus::AlreadyRestored);
+    }
+    if output.as_deref() != Some(entry.output_sha256.as_str()) {
+        return Ok(RestoreStatus::Changed(match output {
+            Some(_) => format!("{} has changed since the run", entry.output.display()),
+            None => format!("{} no longer exists", entry.output.display()),
+        }));
+    }
+    if renamed && original.is_some() {
+        return Ok(RestoreStatus::Changed(format!(
+            "{} has been created since the run",
+            entry.original.display()
+        )));
+    }
+
+    let data = fs::read(&entry.backup).map_err(|e| Error::io_with_path(e, &entry.backup))?;
+    if to_hex(&sha256(&data)) != entry.input_sha256 {
+        return Ok(RestoreStatus::Failed(format!(
+            "backup {} does not match the journal",
+            entry.backup.display()
+        )));
+    }
+    if dry_run {
+        return Ok(RestoreStatus::Restored);
+    }
+
+    // The backup kept the original's times, mode and attributes. An owner
+    // the user may not restore is left to them, as when cleaning.
+    let plan = Plan::new(FsMetadata::Preserve, &entry.backup, true)?;
+    atomic::replace(&entry.original, &data, |file| plan.apply(file))?;
+    if renamed {
+        fs::remove_file(&entry.output).map_err(|e| Error::io_with_path(e, &entry.output))?;
+    }
+    Ok(RestoreStatus::Restored)
+}
+
+/// Restore every entry of the configured journal, newest first, printing
+/// what happened to each.
+pub fn restore(config: &Config) -> Result<Vec<(JournalEntry, RestoreStatus)>> {
+    let journal = config
+        .journal
+        .as_deref()
+        .ok_or_else(|| Error::MissingArgument {
+            argument: String::from("<JOURNAL>"),
+        })?;
+    let mut entries = read_journal(journal)?;
+    entries.reverse();
+
+    let mut results = Vec::new();
+    for entry in entries {
+        let status = restore_entry(&entry, config.dry_run);
+        let name = entry.original.display();
+        match &status {
+            RestoreStatus::Restored if config.dry_run => {
+                if !config.quiet {
+                    print_info(&format!("Would restore {}", name));
+                }
+            }
+            RestoreStatus::Restored => {
+                if !config.quiet {
+                    print_success(&format!("Restored {}", name));
+                }
+            }
+            RestoreStatus::AlreadyRestored => {
+                if config.verbose {
+                    print_info(&format!("{} is already the original", name));
+                }
+            }
+            RestoreStatus::Changed(reason) => {
+                print_warning(&format!("Left {} alone: {}", name, reason));
+            }
+            RestoreStatus::Failed(reason) => {
+                print_error(&format!("Failed to restore {}: {}", name, reason));
+            }
+        }
+        results.push((entry, status));
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_round_trip() {
+        let root = std::env::temp_dir().join("pmi_backup_journal");
+        let _ = fs::remove_dir_all(&root);
+        let input = root.join("in, \"quoted\".jpg");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(&input, b"original").unwrap();
+
+        let backup = Backup::start(&root.join("backups")).unwrap();
+        let second = Backup::start(&root.join("backups")).unwrap();
+        assert_ne!(backup.dir(), second.dir());
+
+        let saved = backup.save(&input, 3, b"original").unwrap();
+        fs::write(&input, b"cleaned").unwrap();
+        backup.record(saved, &input, b"cleaned").unwrap();
+
+        let entries = read_journal(&backup.journal()).unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.original, fs::canonicalize(&input).unwrap());
+        assert_eq!(entry.output, entry.original);
+        assert!(entry.backup.ends_with("0003-in, \"quoted\".jpg"));
+        assert_eq!(fs::read(&entry.backup).unwrap(), b"original");
+        assert_eq!(entry.input_sha256, to_hex(&sha256(b"original")));
+
+        assert!(read_journal(&input).is_err());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,335 @@
+//! Cache of cleaned files for incremental runs.
+//!
+//! With `--incremental`, each file a run cleans is recorded with its size,
+//! modification time and SHA-256, and a fingerprint of the settings that
+//! shaped the output. A later run under the same settings leaves a file
+//! alone while its entry still matches. An unchanged size and time are
+//! trusted as they are; a file whose time changed but whose size did not is
+//! hashed, so files touched or copied without changes are still skipped.
+//!
+//! For in-place runs the entry describes the cleaned file. Otherwise it
+//! describes the input, which is only skipped while its output exists.
+
+use crate::atomic;
+use crate::cli::Config;
+use crate::digest::{sha256, to_hex};
+use crate::error::{Error, Result};
+use crate::report::{csv_row, csv_rows};
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Cache columns, in order.
+const CACHE_COLUMNS: &[&str] = &["path", "size", "mtime_ns", "sha256", "policy", "output"];
+
+/// Settings that change what a run writes or where it writes it, and so
+/// what a cached file is clean of and where its output is.
+const POLICY_SETTINGS: &[&str] = &[
+    "in-place",
+    "output-dir",
+    "recursive",
+    "force",
+    "on-collision",
+    "hard-links",
+    "min-risk",
+    "gps",
+    "dates",
+    "stamp",
+    "icc",
+    "keep-orientation",
+    "allowlist",
+    "name-template",
+    "anonymize-names",
+    "fs-metadata",
+];
+
+/// A cleaned file, as recorded in the cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Canonical path of the file.
+    pub path: PathBuf,
+    /// Size in bytes.
+    pub size: u64,
+    /// Modification time, in nanoseconds since the Unix epoch.
+    pub mtime_ns: u128,
+    /// SHA-256 of the contents.
+    pub sha256: String,
+    /// Canonical path of the cleaned file; `path` for in-place runs.
+    pub output: PathBuf,
+}
+
+impl Entry {
+    /// Describe `path`, whose contents are `data`, after a run wrote
+    /// `output`.
+    pub fn new(path: &Path, data: &[u8], output: &Path) -> Result<Self> {
+        let canonical = |p: &Path| fs::canonicalize(p).map_err(|e| Error::io_with_path(e, p));
+        let metadata = fs::metadata(path).map_err(|e| Error::io_with_path(e, path))?;
+        Ok(Entry {
+            path: canonical(path)?,
+            size: metadata.len(),
+            mtime_ns: mtime_ns(&metadata).map_err(|e| Error::io_with_path(e, path))?,
+            sha256: to_hex(&sha256(data)),
+            output: canonical(output)?,
+        })
+    }
+}
+
+/// The cache of one run, loaded at the start and saved at the end.
+#[derive(Debug)]
+pub struct Cache {
+    path: PathBuf,
+    policy: String,
+    entries: HashMap<PathBuf, (Entry, String)>,
+    changed: bool,
+}
+
+impl Cache {
+    /// Get the default cache file: `$XDG_CACHE_HOME/pmi/cache.csv`, or
+    /// `~/.cache/pmi/cache.csv`.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        Some(base.join("pmi").join("cache.csv"))
+    }
+
+    /// Load the cache for a run. A missing cache file is an empty cache.
+    pub fn load(config: &Config) -> Result<Self> {
+        let path = config
+            .cache
+            .clone()
+            .or_else(Cache::default_path)
+            .ok_or_else(|| Error::InvalidArgument {
+                argument: String::from("--incremental"),
+                reason: String::from("No cache location; set --cache"),
+            })?;
+
+        let mut cache = Cache {
+            policy: policy(config),
+            entries: HashMap::new(),
+            changed: false,
+            path,
+        };
+        let text = match fs::read_to_string(&cache.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(cache),
+            Err(e) => return Err(Error::io_with_path(e, &cache.path)),
+        };
+
+        let invalid = |reason: &str| Error::InvalidArgument {
+            argument: cache.path.display().to_string(),
+            reason: String::from(reason),
+        };
+        let mut rows = csv_rows(&text).into_iter();
+        if rows.next().is_none_or(|header| header != CACHE_COLUMNS) {
+            return Err(invalid("Not a pmi cache"));
+        }
+        for row in rows {
+            let [path, size, mtime_ns, sha256, policy, output] = &row[..] else {
+                return Err(invalid("Malformed cache entry"));
+            };
+            let (Ok(size), Ok(mtime_ns)) = (size.parse(), mtime_ns.parse()) else {
+                return Err(invalid("Malformed cache entry"));
+            };
+            let entry = Entry {
+                path: PathBuf::from(path),
+                size,
+                mtime_ns,
+                sha256: sha256.clone(),
+                output: PathBuf::from(output),
+            };
+            cache
+                .entries
+                .insert(entry.path.clone(), (entry, policy.clone()));
+        }
+        Ok(cache)
+    }
+
+    /// Get the cache file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Check whether `path` is unchanged since a run under the same
+    /// settings cleaned it.
+    ///
+    /// A file found unchanged by its hash gets its new time recorded, so the
+    /// next run need not hash it again.
+    pub fn is_unchanged(&mut self, path: &Path) -> bool {
+        let Ok(canonical) = fs::canonicalize(path) else {
+            return false;
+        };
+        let Some((entry, policy)) = self.entries.get_mut(&canonical) else {
+            return false;
+        };
+        if *policy != self.policy || !entry.output.exists() {
+            return false;
+        }
+        let Ok(metadata) = fs::metadata(&canonical) else {
+            return false;
+        };
+        let Ok(mtime) = mtime_ns(&metadata) else {
+            return false;
+        };
+        if metadata.len() != entry.size {
+            return false;
+        }
+        if mtime == entry.mtime_ns {
+            return true;
+        }
+        match fs::read(&canonical) {
+            Ok(data) if to_hex(&sha256(&data)) == entry.sha256 => {
+                entry.mtime_ns = mtime;
+                self.changed = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Record a file cleaned by this run.
+    pub fn insert(&mut self, entry: Entry) {
+        self.entries
+            .insert(entry.path.clone(), (entry, self.policy.clone()));
+        self.changed = true;
+    }
+
+    /// Write the cache back if this run changed it.
+    pub fn save(&self) -> Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| Error::io_with_path(e, parent))?;
+        }
+
+        let mut entries: Vec<_> = self.entries.values().collect();
+        entries.sort_by(|a, b| a.0.path.cmp(&b.0.path));
+        let mut text = csv_row(
+            &CACHE_COLUMNS
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>(),
+        );
+        text.push('\n');
+        for (entry, policy) in entries {
+            text.push_str(&csv_row(&[
+                entry.path.display().to_string(),
+                entry.size.to_string(),
+                entry.mtime_ns.to_string(),
+                entry.sha256.clone(),
+                policy.clone(),
+                entry.output.display().to_string(),
+            ]));
+            text.push('\n');
+        }
+        atomic::replace(&self.path, text.as_bytes(), |_| Ok(()))
+    }
+}
+
+/// Fingerprint the settings that shape a run's output, and the version of
+/// pmi that applied them.
+fn policy(config: &Config) -> String {
+    let mut text = format!("pmi {}\n", env!("CARGO_PKG_VERSION"));
+    for (key, value) in config.settings() {
+        if POLICY_SETTINGS.contains(&key) {
+            let value = value.map(|v| v.to_string()).unwrap_or_default();
+            text.push_str(&format!("{}={}\n", key, value));
+        }
+    }
+    to_hex(&sha256(text.as_bytes())[..8])
+}
+
+/// Get a file's modification time in nanoseconds since the Unix epoch.
+fn mtime_ns(metadata: &Metadata) -> io::Result<u128> {
+    let modified = metadata.modified()?;
+    Ok(modified
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Environment;
+
+    #[test]
+    fn test_cache_round_trip() {
+        let dir = std::env::temp_dir().join("pmi_cache_unit");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("photo.jpg");
+        fs::write(&file, b"clean").unwrap();
+
+        let mut config = Config::parse_with(
+            ["pmi", "-i", "--incremental", "x.jpg"],
+            &Environment::default(),
+        )
+        .unwrap();
+        config.cache = Some(dir.join("cache").join("cache.csv"));
+
+        let mut cache = Cache::load(&config).unwrap();
+        assert!(!cache.is_unchanged(&file));
+        cache.insert(Entry::new(&file, b"clean", &file).unwrap());
+        cache.save().unwrap();
+
+        let mut cache = Cache::load(&config).unwrap();
+        assert!(cache.is_unchanged(&file));
+
+        // Touching a file without changing it keeps it cached.
+        let file_handle = fs::File::options().write(true).open(&file).unwrap();
+        file_handle.set_modified(UNIX_EPOCH).unwrap();
+        assert!(cache.is_unchanged(&file));
+        assert!(cache.changed);
+
+        // New contents or new settings do not.
+        fs::write(&file, b"dirty").unwrap();
+        assert!(!cache.is_unchanged(&file));
+        fs::write(&file, b"clean").unwrap();
+        config.apply_setting("gps", "coarsen=10").unwrap();
+        let mut cache = Cache::load(&config).unwrap();
+        assert!(!cache.is_unchanged(&file));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_output_location_settings_change_policy() {
+        let dir = std::env::temp_dir().join("pmi_cache_output");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("photo.jpg");
+        fs::write(&file, b"clean").unwrap();
+
+        let base = Config::parse_with(
+            ["pmi", "-o", "out", "--incremental", "x.jpg"],
+            &Environment::default(),
+        )
+        .unwrap();
+        let toggles = [
+            ("recursive", "true"),
+            ("output-dir", "elsewhere"),
+            ("force", "true"),
+            ("on-collision", "number"),
+            ("hard-links", "break"),
+        ];
+        for (key, value) in toggles {
+            let mut config = base.clone();
+            config.cache = Some(dir.join("cache.csv"));
+            let mut cache = Cache::load(&config).unwrap();
+            cache.insert(Entry::new(&file, b"clean", &file).unwrap());
+            cache.save().unwrap();
+            assert!(Cache::load(&config).unwrap().is_unchanged(&file), "{}", key);
+
+            config.apply_setting(key, value).unwrap();
+            let mut cache = Cache::load(&config).unwrap();
+            assert!(!cache.is_unchanged(&file), "{}", key);
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
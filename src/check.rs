@@ -0,0 +1,220 @@
+//! Read-only metadata check.
+//!
+//! `pmi check` inspects files with the same parsers used for stripping and
+//! reports which metadata categories each one contains, without writing
+//! anything. Distinct exit codes make it suitable for CI jobs and pre-commit
+//! hooks.
+
+use crate::cli::Config;
+use crate::error::Result;
+use crate::formats::inspect_metadata;
+use crate::metadata::{self, Category, MetadataItem};
+use crate::processor::Processor;
+use crate::terminal::format_size;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Exit code when every file is clean.
+pub const EXIT_CLEAN: u8 = 0;
+/// Exit code when at least one file contains metadata.
+pub const EXIT_METADATA_FOUND: u8 = 1;
+/// Exit code when at least one file could not be read or parsed.
+pub const EXIT_PARSE_ERROR: u8 = 2;
+
+/// Outcome of checking a single file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckStatus {
+    /// Stripping would not remove anything.
+    Clean,
+    /// Metadata was found; holds the items stripping would remove.
+    MetadataFound(Vec<MetadataItem>),
+    /// The file could not be read or parsed.
+    ParseError(String),
+}
+
+/// Result of checking a single file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    /// Path of the checked file.
+    pub path: PathBuf,
+    /// What was found.
+    pub status: CheckStatus,
+}
+
+impl CheckResult {
+    /// Get the distinct categories found, in display order.
+    pub fn categories(&self) -> Vec<Category> {
+        match &self.status {
+            CheckStatus::MetadataFound(items) => metadata::categories(items),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Format the one-line summary for this file.
+    pub fn summary_line(&self) -> String {
+        let detail = match &self.status {
+            CheckStatus::Clean => String::from("clean"),
+            CheckStatus::MetadataFound(_) => self
+                .categories()
+                .iter()
+                .map(Category::name)
+                .collect::<Vec<_>>()
+                .join(", "),
+            CheckStatus::ParseError(reason) => format!("error: {}", reason),
+        };
+        format!("{}: {}", self.path.display(), detail)
+    }
+}
+
+/// Check image data without modifying it.
+pub fn check_data(data: &[u8], path: &Path) -> CheckStatus {
+    match inspect_metadata(data, path) {
+        Ok(items) if items.is_empty() => CheckStatus::Clean,
+        Ok(items) => CheckStatus::MetadataFound(items),
+        Err(e) => CheckStatus::ParseError(e.to_string()),
+    }
+}
+
+/// Read and check a single file.
+pub fn check_file(path: &Path) -> CheckResult {
+    let status = match fs::read(path) {
+        Ok(data) => check_data(&data, path),
+        Err(e) => CheckStatus::ParseError(e.to_string()),
+    };
+
+    CheckResult {
+        path: path.to_path_buf(),
+        status,
+    }
+}
+
+/// Get the exit code for a set of results.
+///
+/// Parse errors take precedence over metadata, so a hook cannot mistake an
+/// unreadable file for one that merely needs cleaning.
+pub fn exit_code(results: &[CheckResult]) -> u8 {
+    let has = |f: fn(&CheckStatus) -> bool| results.iter().any(|r| f(&r.status));
+
+    if has(|s| matches!(s, CheckStatus::ParseError(_))) {
+        EXIT_PARSE_ERROR
+    } else if has(|s| matches!(s, CheckStatus::MetadataFound(_))) {
+        EXIT_METADATA_FOUND
+    } else {
+        EXIT_CLEAN
+    }
+}
+
+/// Check every file selected by the configuration.
+///
+/// Files are collected exactly as for stripping, so `check` and the default
+/// command always look at the same set of files.
+pub fn check_paths(config: &Config) -> Result<Vec<CheckResult>> {
+    let files = Processor::new(config.clone()).collect_files()?;
+    Ok(files.iter().map(|path| check_file(path)).collect())
+}
+
+/// Print one line per file, and each removable item in verbose mode.
+///
+/// Clean files are omitted in quiet mode.
+pub fn print_results(results: &[CheckResult], config: &Config) {
+    for result in results {
+        if config.quiet && result.status == CheckStatus::Clean {
+            continue;
+        }
+        println!("{}", result.summary_line());
+
+        if let (true, CheckStatus::MetadataFound(items)) = (config.verbose, &result.status) {
+            for item in items {
+                print_item(item);
+            }
+        }
+    }
+
+    if !config.quiet {
+        let count = |f: fn(&CheckStatus) -> bool| results.iter().filter(|r| f(&r.status)).count();
+        println!();
+        println!(
+            "{} files checked: {} clean, {} with metadata, {} errors",
+            results.len(),
+            count(|s| *s == CheckStatus::Clean),
+            count(|s| matches!(s, CheckStatus::MetadataFound(_))),
+            count(|s| matches!(s, CheckStatus::ParseError(_))),
+        );
+    }
+}
+
+/// Print a single metadata item with its decoded field names.
+fn print_item(item: &MetadataItem) {
+    let fields: Vec<&str> = item.fields.iter().map(|f| f.name.as_str()).collect();
+    if fields.is_empty() {
+        println!("  {} ({})", item.kind, format_size(item.size() as u64));
+    } else {
+        println!(
+            "  {} ({}): {}",
+            item.kind,
+            format_size(item.size() as u64),
+            fields.join(", ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{jpeg, png};
+
+    fn result(status: CheckStatus) -> CheckResult {
+        CheckResult {
+            path: PathBuf::from("a.png"),
+            status,
+        }
+    }
+
+    #[test]
+    fn test_check_data() {
+        let path = Path::new("test.png");
+        let data = png::create_png_with_metadata();
+        assert!(matches!(
+            check_data(&data, path),
+            CheckStatus::MetadataFound(_)
+        ));
+        assert_eq!(
+            check_data(&png::create_minimal_png(), path),
+            CheckStatus::Clean
+        );
+        assert!(matches!(
+            check_data(b"not an image", path),
+            CheckStatus::ParseError(_)
+        ));
+    }
+
+    #[test]
+    fn test_summary_line() {
+        let path = Path::new("test.jpg");
+        let found = CheckResult {
+            path: PathBuf::from("test.jpg"),
+            status: check_data(&jpeg::create_jpeg_with_exif(), path),
+        };
+        assert_eq!(found.summary_line(), "test.jpg: other");
+        assert_eq!(result(CheckStatus::Clean).summary_line(), "a.png: clean");
+        assert_eq!(
+            result(CheckStatus::ParseError(String::from("bad"))).summary_line(),
+            "a.png: error: bad"
+        );
+    }
+
+    #[test]
+    fn test_exit_code_precedence() {
+        let clean = result(CheckStatus::Clean);
+        let found = result(CheckStatus::MetadataFound(Vec::new()));
+        let error = result(CheckStatus::ParseError(String::from("bad")));
+
+        assert_eq!(exit_code(&[]), EXIT_CLEAN);
+        assert_eq!(exit_code(std::slice::from_ref(&clean)), EXIT_CLEAN);
+        assert_eq!(
+            exit_code(&[clean.clone(), found.clone()]),
+            EXIT_METADATA_FOUND
+        );
+        assert_eq!(exit_code(&[found, error, clean]), EXIT_PARSE_ERROR);
+    }
+}
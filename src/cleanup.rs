@@ -0,0 +1,83 @@
+//! Removal of temporary files left by interrupted runs.
+//!
+//! Writes go through temporary files (see [`crate::atomic`]) that are
+//! removed when a write fails, but a run that is killed or loses power can
+//! still leave one behind. `pmi cleanup` finds them by name and removes
+//! them. A run that is still going would lose the file it is writing, so
+//! cleanup should not run alongside one on the same files.
+//!
+//! Earlier versions named temporary files `<stem>.pmi_tmp`, a name anyone's
+//! file may have. Those are only listed, and removed with `--legacy`.
+
+use crate::atomic;
+use crate::cli::Config;
+use crate::error::{Error, Result};
+use crate::terminal::{print_info, print_success, print_warning};
+use std::fs;
+use std::path::PathBuf;
+
+/// Find the files under the configured paths whose names `matches`
+/// accepts, in name order.
+fn find_matching(config: &Config, matches: fn(&str) -> bool) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    for path in &config.paths {
+        atomic::find_temp_files(path, config.recursive, matches, &mut found)?;
+    }
+    Ok(found)
+}
+
+/// Find the temporary files under the configured paths, in name order.
+/// With `--legacy`, files named as earlier versions named them follow.
+pub fn find(config: &Config) -> Result<Vec<PathBuf>> {
+    let mut found = find_matching(config, atomic::is_temp_name)?;
+    if config.legacy {
+        found.extend(find_legacy(config)?);
+    }
+    Ok(found)
+}
+
+/// Find files named as earlier versions named temporary files.
+pub fn find_legacy(config: &Config) -> Result<Vec<PathBuf>> {
+    find_matching(config, atomic::is_legacy_temp_name)
+}
+
+/// Remove the temporary files under the configured paths, or only list
+/// them in a dry run.
+///
+/// Returns the files found.
+pub fn run(config: &Config) -> Result<Vec<PathBuf>> {
+    let found = find(config)?;
+    for path in &found {
+        if config.dry_run {
+            if !config.quiet {
+                print_info(&format!("Would remove {}", path.display()));
+            }
+            continue;
+        }
+        fs::remove_file(path).map_err(|e| Error::io_with_path(e, path))?;
+        if config.verbose {
+            print_info(&format!("Removed {}", path.display()));
+        }
+    }
+
+    if !config.legacy && !config.quiet {
+        for path in find_legacy(config)? {
+            print_warning(&format!(
+                "Not removing {}: named like an earlier version's temporary file \
+                 (remove with --legacy)",
+                path.display()
+            ));
+        }
+    }
+
+    if !config.quiet {
+        let count = found.len();
+        let files = if count == 1 { "file" } else { "files" };
+        match (count, config.dry_run) {
+            (0, _) => print_success("No temporary files found"),
+            (_, true) => print_info(&format!("{} temporary {} found", count, files)),
+            (_, false) => print_success(&format!("Removed {} temporary {}", count, files)),
+        }
+    }
+    Ok(found)
+}
@@ -0,0 +1,387 @@
+//! Shell completion scripts.
+//!
+//! Scripts are generated from the option registry, so every option and its
+//! values complete without the scripts being maintained by hand. Preset
+//! names, including user profiles, are written into the script when it is
+//! generated.
+
+use super::options::{self, Arg, COMMANDS, OPTIONS, OptionSpec};
+use super::{Command, NAME};
+use crate::error::{Error, Result};
+use crate::preset::Preset;
+
+/// A shell to generate completions for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    /// Parse a shell name.
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(Error::InvalidArgument {
+                argument: String::from(value),
+                reason: String::from("Expected bash, zsh or fish"),
+            }),
+        }
+    }
+
+    /// Get the shell name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+}
+
+/// Generate the completion script for a shell.
+pub fn script(shell: Shell, presets: &[Preset]) -> String {
+    let presets: Vec<&str> = presets.iter().map(|p| p.name.as_str()).collect();
+    match shell {
+        Shell::Bash => bash(&presets),
+        Shell::Zsh => zsh(&presets),
+        Shell::Fish => fish(&presets),
+    }
+}
+
+/// Get the words an option's value completes to, if it has a fixed set.
+fn values(spec: &OptionSpec, presets: &[&str]) -> Option<String> {
+    match spec.arg {
+        Arg::Choice(choices) => Some(choices.join(" ")),
+        Arg::Preset => Some(presets.join(" ")),
+        _ => None,
+    }
+}
+
+/// Get the names of every command.
+fn command_names() -> String {
+    COMMANDS
+        .iter()
+        .map(|spec| spec.command.name())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Get an option's names as typed, such as `-o --output-dir`.
+fn names(spec: &OptionSpec) -> Vec<String> {
+    let mut names: Vec<String> = spec.short.iter().map(|c| format!("-{}", c)).collect();
+    names.push(format!("--{}", spec.long));
+    names
+}
+
+fn bash(presets: &[&str]) -> String {
+    let mut values_case = String::new();
+    for spec in OPTIONS.iter().filter(|spec| spec.takes_value()) {
+        let reply = match (spec.arg, values(spec, presets)) {
+            (Arg::Dir, _) => String::from("COMPREPLY=($(compgen -d -- \"$cur\"))"),
+            (Arg::File, _) => String::from("COMPREPLY=($(compgen -f -- \"$cur\"))"),
+            (_, Some(words)) => format!("_{}_choose \"{}\"", NAME, words),
+            (_, None) => String::from("COMPREPLY=()"),
+        };
+        values_case.push_str(&format!(
+            "        {})\n            {}\n            return\n            ;;\n",
+            names(spec).join("|"),
+            reply
+        ));
+    }
+
+    let mut options_case = String::new();
+    for spec in COMMANDS {
+        let words: Vec<String> = options::options_for(spec.command).flat_map(names).collect();
+        options_case.push_str(&format!(
+            "        {}) opts=\"{}\" ;;\n",
+            spec.command.name(),
+            words.join(" ")
+        ));
+    }
+
+    format!(
+        r#"# bash completion for {name}
+
+_{name}_choose() {{
+    COMPREPLY=($(compgen -W "$1" -- "$cur"))
+    # Values such as coarsen= take more text after the '='.
+    if [[ ${{#COMPREPLY[@]}} -eq 1 && ${{COMPREPLY[0]}} == *= ]]; then
+        compopt -o nospace
+    fi
+}}
+
+_{name}() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    local command=strip opts
+
+    case "${{COMP_WORDS[1]}}" in
+        {commands_pattern}) command="${{COMP_WORDS[1]}}" ;;
+    esac
+
+    case "$prev" in
+{values_case}    esac
+
+    if [[ "$cur" == -* ]]; then
+        case "$command" in
+{options_case}        esac
+        COMPREPLY=($(compgen -W "$opts" -- "$cur"))
+        return
+    fi
+
+    if [[ $COMP_CWORD -eq 1 ]]; then
+        COMPREPLY=($(compgen -W "{commands}" -- "$cur"))
+    fi
+    case "$command" in
+        completions) COMPREPLY+=($(compgen -W "bash zsh fish" -- "$cur")) ;;
+        git-check) ;;
+        *) COMPREPLY+=($(compgen -f -- "$cur")) ;;
+    esac
+}}
+
+complete -o filenames -F _{name} {name}
+"#,
+        name = NAME,
+        commands_pattern = command_names().replace(' ', "|"),
+        commands = command_names(),
+        values_case = values_case,
+        options_case = options_case,
+    )
+}
+
+/// Escape a description for a single-quoted `_arguments` spec.
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+}
+
+fn zsh(presets: &[&str]) -> String {
+    let mut cases = String::new();
+    for spec in COMMANDS {
+        let mut lines: Vec<String> = options::options_for(spec.command)
+            .map(|option| {
+                let help = zsh_escape(&option.help.join(" "));
+                let action = match (option.arg, values(option, presets)) {
+                    (Arg::Flag, _) => String::new(),
+                    (Arg::Dir, _) => String::from(":dir:_files -/"),
+                    (Arg::File, _) => String::from(":file:_files"),
+                    (_, Some(words)) => format!(":value:({})", words),
+                    (_, None) => String::from(":value: "),
+                };
+                match option.short {
+                    Some(short) => format!(
+                        "'(-{short} --{long})'{{-{short},--{long}}}'[{help}]{action}'",
+                        short = short,
+                        long = option.long,
+                        help = help,
+                        action = action,
+                    ),
+                    None => format!("'--{}[{}]{}'", option.long, help, action),
+                }
+            })
+            .collect();
+        lines.push(match spec.command {
+            Command::GitCheck => String::from("'1:revision range: '"),
+            Command::Completions => String::from("'1:shell:(bash zsh fish)'"),
+            Command::Restore => String::from("'1:journal:_files'"),
+            _ => String::from("'*:path:_files'"),
+        });
+        cases.push_str(&format!(
+            "        {})\n            _arguments -s \\\n                {}\n            ;;\n",
+            spec.command.name(),
+            lines.join(" \\\n                ")
+        ));
+    }
+
+    let descriptions: Vec<String> = COMMANDS
+        .iter()
+        .map(|spec| {
+            format!(
+                "        '{}:{}'",
+                spec.command.name(),
+                spec.about.join(" ").replace('\'', "'\\''")
+            )
+        })
+        .collect();
+
+    format!(
+        r#"#compdef {name}
+
+_{name}() {{
+    local command=strip
+
+    if (( CURRENT == 2 )) && [[ $PREFIX != -* ]]; then
+        local -a commands
+        commands=(
+{descriptions}
+        )
+        _describe -t commands command commands
+        _files
+        return
+    fi
+
+    case $words[2] in
+        {commands_pattern})
+            command=$words[2]
+            words=($words[1] $words[3,-1])
+            (( CURRENT-- ))
+            ;;
+    esac
+
+    case $command in
+{cases}    esac
+}}
+
+_{name} "$@"
+"#,
+        name = NAME,
+        descriptions = descriptions.join("\n"),
+        commands_pattern = command_names().replace(' ', "|"),
+        cases = cases,
+    )
+}
+
+/// Quote text for fish.
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Get the fish condition that holds when an option applies.
+fn fish_condition(commands: &[Command]) -> Option<String> {
+    let names = |commands: Vec<&Command>| {
+        commands
+            .iter()
+            .map(|command| command.name())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    if commands.contains(&Command::Strip) {
+        // Strip is the default, so it applies unless another command was
+        // given.
+        let others: Vec<&Command> = COMMANDS
+            .iter()
+            .map(|spec| &spec.command)
+            .filter(|command| !commands.contains(command))
+            .collect();
+        (!others.is_empty()).then(|| format!("not __fish_seen_subcommand_from {}", names(others)))
+    } else {
+        Some(format!(
+            "__fish_seen_subcommand_from {}",
+            names(commands.iter().collect())
+        ))
+    }
+}
+
+fn fish(presets: &[&str]) -> String {
+    let mut text = format!("# fish completion for {}\n\n", NAME);
+
+    for spec in COMMANDS {
+        text.push_str(&format!(
+            "complete -c {} -n __fish_use_subcommand -f -a {} -d {}\n",
+            NAME,
+            spec.command.name(),
+            fish_quote(spec.about.join(" ").as_str())
+        ));
+    }
+    text.push('\n');
+
+    for spec in OPTIONS {
+        let mut line = format!("complete -c {}", NAME);
+        if let Some(condition) = fish_condition(spec.commands) {
+            line.push_str(&format!(" -n {}", fish_quote(&condition)));
+        }
+        if let Some(short) = spec.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        line.push_str(&format!(" -l {}", spec.long));
+        match (spec.arg, values(spec, presets)) {
+            (Arg::Flag, _) => {}
+            (Arg::Dir, _) => line.push_str(" -x -a '(__fish_complete_directories)'"),
+            (Arg::File, _) => line.push_str(" -r -F"),
+            (_, Some(words)) => line.push_str(&format!(" -x -a {}", fish_quote(&words))),
+            (_, None) => line.push_str(" -x"),
+        }
+        line.push_str(&format!(" -d {}\n", fish_quote(&spec.help.join(" "))));
+        text.push_str(&line);
+    }
+    text.push('\n');
+
+    text.push_str(&format!(
+        "complete -c {} -n '__fish_seen_subcommand_from completions' -f -a 'bash zsh fish'\n",
+        NAME
+    ));
+    text.push_str(&format!(
+        "complete -c {} -n '__fish_seen_subcommand_from git-check' -f\n",
+        NAME
+    ));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preset;
+
+    fn presets() -> Vec<Preset> {
+        let mut presets = preset::builtin();
+        presets.push(Preset {
+            name: String::from("family"),
+            description: String::new(),
+            settings: Vec::new(),
+            source: None,
+        });
+        presets
+    }
+
+    #[test]
+    fn test_shell_parse() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            assert_eq!(Shell::parse(shell.name()).unwrap(), shell);
+        }
+        assert!(Shell::parse("tcsh").is_err());
+    }
+
+    #[test]
+    fn test_bash_script() {
+        let text = script(Shell::Bash, &presets());
+        assert!(text.contains("complete -o filenames -F _pmi pmi"));
+        assert!(text.contains("        -o|--output-dir)\n            COMPREPLY=($(compgen -d"));
+        assert!(text.contains("_pmi_choose \"social journalist archive forensic-safe family\""));
+        assert!(text.contains("_pmi_choose \"remove coarsen=\""));
+        // Options are offered only to the commands that accept them.
+        let git_check = text
+            .lines()
+            .find(|l| l.contains("git-check) opts="))
+            .unwrap();
+        assert!(git_check.contains("--name-only"));
+        assert!(!git_check.contains("--gps"));
+    }
+
+    #[test]
+    fn test_zsh_script() {
+        let text = script(Shell::Zsh, &presets());
+        assert!(text.starts_with("#compdef pmi\n"));
+        assert!(text.contains("'(-o --output-dir)'{-o,--output-dir}'[Save cleaned images"));
+        assert!(text.contains(
+            "'--icc[Colour profiles: auto (default; keep in PNG, WebP and TIFF), keep or strip]:value:(auto keep strip)'"
+        ));
+        assert!(text.contains("'1:shell:(bash zsh fish)'"));
+    }
+
+    #[test]
+    fn test_fish_script() {
+        let text = script(Shell::Fish, &presets());
+        assert!(text.contains(
+            "complete -c pmi -n 'not __fish_seen_subcommand_from check git-check completions restore cleanup' -l preset -x -a 'social journalist archive forensic-safe family'"
+        ));
+        assert!(
+            text.contains("complete -c pmi -n '__fish_seen_subcommand_from git-check' -l staged")
+        );
+        assert!(text.contains("complete -c pmi -s h -l help -d 'Print this help message'"));
+    }
+}
@@ -0,0 +1,1543 @@
+//! Command-line argument parsing for PMI.
+//!
+//! This module provides a hand-rolled argument parser without external dependencies.
+//! Commands and options are described once in [`options`], which drives
+//! parsing, help text and [`completions`].
+
+pub mod completions;
+pub mod options;
+
+use crate::error::{Error, Result};
+use crate::formats::{FormatPolicy, IccMode, ImageFormat, StripOptions};
+use crate::fsmeta::{FsMetadata, HardLinks};
+use crate::metadata::dates::DateMode;
+use crate::metadata::gps::GpsMode;
+use crate::metadata::risk::RiskLevel;
+use crate::metadata::stamp::Stamp;
+use crate::naming::{Anonymize, Collision, NameTemplate};
+use crate::preset::{self, Preset, Setting};
+use crate::report::ReportFormat;
+use crate::settings::{Environment, Source};
+use crate::toml::Value;
+use completions::Shell;
+use options::OptionSpec;
+use std::path::PathBuf;
+
+/// Application version.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Application name.
+pub const NAME: &str = "pmi";
+
+/// Top-level command.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Command {
+    /// Strip metadata from images (the default).
+    #[default]
+    Strip,
+    /// Report metadata without modifying anything.
+    Check,
+    /// Check image blobs changed in git.
+    GitCheck,
+    /// Print a shell completion script.
+    Completions,
+    /// Remove temporary files left by interrupted runs.
+    Cleanup,
+    /// Put back the originals of an in-place run.
+    Restore,
+}
+
+impl Command {
+    /// Get the name used on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Strip => "strip",
+            Command::Check => "check",
+            Command::GitCheck => "git-check",
+            Command::Completions => "completions",
+            Command::Cleanup => "cleanup",
+            Command::Restore => "restore",
+        }
+    }
+}
+
+/// CLI configuration parsed from command-line arguments.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// Command to run.
+    pub command: Command,
+    /// The command was named on the command line rather than implied.
+    pub named_command: bool,
+    /// Shell to print a completion script for.
+    pub shell: Option<Shell>,
+    /// Input paths (files or directories).
+    pub paths: Vec<PathBuf>,
+    /// Output directory for cleaned images.
+    pub output_dir: Option<PathBuf>,
+    /// Process directories recursively.
+    pub recursive: bool,
+    /// Overwrite existing output files.
+    pub force: bool,
+    /// Modify files in place.
+    pub in_place: bool,
+    /// Show detailed processing info.
+    pub verbose: bool,
+    /// Suppress all output except errors.
+    pub quiet: bool,
+    /// Show what would be done without making changes.
+    pub dry_run: bool,
+    /// Re-parse cleaned output and refuse to write it unless it is intact.
+    pub verify: bool,
+    /// Decode frames before and after stripping and require identical pixels.
+    pub prove_lossless: bool,
+    /// Show help message.
+    pub help: bool,
+    /// Show version.
+    pub version: bool,
+    /// Number of parallel jobs (threads) for processing.
+    pub jobs: Option<usize>,
+    /// Check staged changes (git-check).
+    pub staged: bool,
+    /// Revision range to check (git-check).
+    pub rev_range: Option<String>,
+    /// Print only the paths of files that need cleaning (git-check).
+    pub name_only: bool,
+    /// Also remove temporary files named as earlier versions named them
+    /// (cleanup).
+    pub legacy: bool,
+    /// Write a machine-readable report in this format.
+    pub report_format: Option<ReportFormat>,
+    /// Write the report to this file instead of stdout.
+    pub report_file: Option<PathBuf>,
+    /// Only process files whose metadata is at least this sensitive.
+    pub min_risk: RiskLevel,
+    /// What to do with GPS positions when stripping.
+    pub gps: GpsMode,
+    /// What to do with timestamps when stripping.
+    pub dates: DateMode,
+    /// Rights metadata to write into cleaned images.
+    pub stamp: Stamp,
+    /// What to do with embedded colour profiles.
+    pub icc: IccMode,
+    /// Keep the EXIF orientation tag.
+    pub keep_orientation: bool,
+    /// Keep only the blocks each format's allowlist names.
+    pub allowlist: bool,
+    /// Template for output file names.
+    pub name_template: Option<NameTemplate>,
+    /// What to do when an output file already exists.
+    pub on_collision: Collision,
+    /// Replace file stems with anonymous ones.
+    pub anonymize_names: Option<Anonymize>,
+    /// Append original and anonymised names to this file.
+    pub name_map: Option<PathBuf>,
+    /// What to do with output files' times, permissions and attributes.
+    pub fs_metadata: FsMetadata,
+    /// What to do with hard-linked files in in-place mode.
+    pub hard_links: HardLinks,
+    /// Back up originals under this directory and journal the run.
+    pub backup: Option<PathBuf>,
+    /// Delete originals once their cleaned copies are written and verified.
+    pub remove_originals: bool,
+    /// Overwrite originals before deleting them.
+    pub shred: bool,
+    /// Skip files unchanged since an earlier run cleaned them.
+    pub incremental: bool,
+    /// Cache file for incremental runs.
+    pub cache: Option<PathBuf>,
+    /// Journal to restore originals from.
+    pub journal: Option<PathBuf>,
+    /// Preset or user profile the settings started from.
+    pub preset: Option<String>,
+    /// Print the effective settings and exit.
+    pub print_config: bool,
+    /// Where each setting not left at its default came from.
+    pub sources: Vec<(String, Source)>,
+}
+
+impl Config {
+    /// Parse configuration from command-line arguments, on top of the
+    /// standard config files and `PMI_*` environment variables.
+    pub fn parse<I, S>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::parse_with(args, &Environment::current())
+    }
+
+    /// Parse configuration from command-line arguments, on top of the given
+    /// config files and environment variables.
+    pub fn parse_with<I, S>(args: I, environment: &Environment) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let args: Vec<String> = args
+            .into_iter()
+            .skip(1)
+            .map(|arg| arg.as_ref().to_string())
+            .collect();
+
+        // A first pass finds the command and any preset named on the command
+        // line. Help, version and completions never depend on the config
+        // files.
+        let mut cli = Config::default();
+        cli.parse_args(&args)?;
+        if cli.help || cli.version || cli.command == Command::Completions {
+            cli.validate()?;
+            return Ok(cli);
+        }
+
+        let mut config = Config {
+            command: cli.command,
+            named_command: cli.named_command,
+            ..Config::default()
+        };
+        let files = environment.load_files()?;
+        for file in &files {
+            let source = Source::File(file.path.clone());
+            let trusted = environment.trusts(&source);
+            for setting in &file.settings {
+                config.apply(setting, &source, trusted)?;
+            }
+        }
+        for (var, setting) in environment.settings(is_setting) {
+            let source = Source::Env(var);
+            config.apply(&setting, &source, environment.trusts(&source))?;
+        }
+
+        let preset = cli.preset.clone().or_else(|| config.preset.clone());
+        if let (Some(name), Command::Strip) = (preset, config.command) {
+            let profiles: Vec<Preset> = files.into_iter().flat_map(|f| f.profiles).collect();
+            let preset = preset::find(&name, &profiles)?;
+            let trusted = environment.trusts(&preset_source(&preset));
+            config.apply_preset(&preset, trusted)?;
+        }
+
+        // The command line goes on top, so explicit options win.
+        let before = config.settings();
+        config.parse_args(&args)?;
+        for ((key, old), (_, new)) in before.into_iter().zip(config.settings()) {
+            if old != new {
+                config.record(key, Source::CommandLine);
+            }
+        }
+
+        // -q or -v on the command line overrides the other from a lower layer.
+        if config.quiet && config.verbose {
+            match (
+                config.source("quiet") == Source::CommandLine,
+                config.source("verbose") == Source::CommandLine,
+            ) {
+                (true, false) => config.verbose = false,
+                (false, true) => config.quiet = false,
+                _ => {}
+            }
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Apply each argument in turn, without validating the result.
+    fn parse_args(&mut self, args: &[String]) -> Result<()> {
+        let mut args = args.iter();
+
+        let mut first = true;
+        while let Some(arg) = args.next() {
+            let arg = arg.as_str();
+
+            if let Some(opt) = arg.strip_prefix("--") {
+                // Long option, as --option, --option value or --option=value.
+                let (long, inline) = match opt.split_once('=') {
+                    Some((long, value)) => (long, Some(value)),
+                    None => (opt, None),
+                };
+                let spec = options::find_long(long).ok_or_else(|| Error::InvalidArgument {
+                    argument: format!("--{}", long),
+                    reason: String::from("Unknown option"),
+                })?;
+                let value = match (inline, spec.takes_value()) {
+                    (Some(value), true) => Some(value),
+                    (Some(_), false) => {
+                        return Err(Error::InvalidArgument {
+                            argument: format!("--{}", long),
+                            reason: String::from("Option does not take a value"),
+                        });
+                    }
+                    (None, true) => {
+                        Some(Self::next_value(&mut args, &format!("--{}", long), spec)?)
+                    }
+                    (None, false) => None,
+                };
+                self.set_option(spec, value)?;
+            } else if let Some(short_opts) = arg.strip_prefix('-').filter(|s| !s.is_empty()) {
+                // Short option(s), which may be combined as -rv.
+                let chars: Vec<char> = short_opts.chars().collect();
+
+                for (i, c) in chars.iter().enumerate() {
+                    let name = format!("-{}", c);
+                    let spec = options::find_short(*c).ok_or_else(|| Error::InvalidArgument {
+                        argument: name.clone(),
+                        reason: String::from("Unknown option"),
+                    })?;
+                    let mut value = None;
+                    if spec.takes_value() {
+                        if i != chars.len() - 1 {
+                            return Err(Error::InvalidArgument {
+                                reason: format!(
+                                    "{} must be the last option in a combined flag",
+                                    name
+                                ),
+                                argument: name,
+                            });
+                        }
+                        value = Some(Self::next_value(&mut args, &name, spec)?);
+                    }
+                    self.set_option(spec, value)?;
+                }
+            } else if let Some(spec) = options::find_command(arg).filter(|_| first) {
+                // Subcommands are only recognised in first position.
+                self.command = spec.command;
+                self.named_command = true;
+            } else if self.command == Command::GitCheck {
+                // git-check takes a revision range instead of paths.
+                if self.rev_range.is_some() {
+                    return Err(Error::InvalidArgument {
+                        argument: String::from(arg),
+                        reason: String::from("git-check takes a single revision range"),
+                    });
+                }
+                self.rev_range = Some(String::from(arg));
+            } else if self.command == Command::Completions {
+                if self.shell.is_some() {
+                    return Err(Error::InvalidArgument {
+                        argument: String::from(arg),
+                        reason: String::from("completions takes a single shell"),
+                    });
+                }
+                self.shell = Some(Shell::parse(arg)?);
+            } else if self.command == Command::Restore {
+                if self.journal.is_some() {
+                    return Err(Error::InvalidArgument {
+                        argument: String::from(arg),
+                        reason: String::from("restore takes a single journal"),
+                    });
+                }
+                self.journal = Some(PathBuf::from(arg));
+            } else {
+                // Positional argument (path).
+                self.paths.push(PathBuf::from(arg));
+            }
+            first = false;
+        }
+        Ok(())
+    }
+
+    /// Take the value of an option from the next argument.
+    fn next_value<'a>(
+        args: &mut impl Iterator<Item = &'a String>,
+        name: &str,
+        spec: &OptionSpec,
+    ) -> Result<&'a str> {
+        args.next()
+            .map(String::as_str)
+            .ok_or_else(|| Error::MissingArgument {
+                argument: format!("{} {}", name, spec.placeholder),
+            })
+    }
+
+    /// Set an option given on the command line, after checking that the
+    /// command accepts it.
+    fn set_option(&mut self, spec: &OptionSpec, value: Option<&str>) -> Result<()> {
+        if !spec.commands.contains(&self.command) {
+            return Err(Error::InvalidArgument {
+                argument: format!("--{}", spec.long),
+                reason: match spec.commands {
+                    [Command::Strip] => String::from("Only valid when stripping"),
+                    [Command::GitCheck] => String::from("Only valid with git-check"),
+                    _ => format!("Not valid with {} {}", NAME, self.command.name()),
+                },
+            });
+        }
+        self.set(spec.long, value.unwrap_or("true"))
+    }
+
+    /// Check that the options make sense together.
+    fn validate(&mut self) -> Result<()> {
+        let config = self;
+
+        // Validate configuration.
+        if config.help || config.version {
+            return Ok(());
+        }
+
+        if config.command == Command::Completions && config.shell.is_none() {
+            return Err(Error::MissingArgument {
+                argument: String::from("<SHELL>"),
+            });
+        }
+
+        if config.command == Command::Restore && config.journal.is_none() && !config.print_config {
+            return Err(Error::MissingArgument {
+                argument: String::from("<JOURNAL>"),
+            });
+        }
+
+        let needs_paths = matches!(
+            config.command,
+            Command::Strip | Command::Check | Command::Cleanup
+        );
+        if needs_paths && !config.print_config && config.paths.is_empty() {
+            return Err(Error::MissingArgument {
+                argument: String::from("<PATHS>"),
+            });
+        }
+
+        // Cannot use both quiet and verbose.
+        if config.quiet && config.verbose {
+            return Err(Error::InvalidArgument {
+                argument: String::from("--quiet, --verbose"),
+                reason: String::from("Cannot use both --quiet and --verbose"),
+            });
+        }
+
+        if config.staged && config.rev_range.is_some() {
+            return Err(Error::InvalidArgument {
+                argument: String::from("--staged"),
+                reason: String::from("Cannot use both --staged and a revision range"),
+            });
+        }
+
+        if config.in_place && config.name_template.is_some() {
+            return Err(Error::InvalidArgument {
+                argument: String::from("--name-template"),
+                reason: String::from("Cannot rename files modified in place"),
+            });
+        }
+
+        if config.backup.is_some() && !config.in_place {
+            return Err(Error::InvalidArgument {
+                argument: String::from("--backup"),
+                reason: String::from("Only used with --in-place"),
+            });
+        }
+
+        if config.remove_originals && config.in_place {
+            return Err(Error::InvalidArgument {
+                argument: String::from("--remove-originals"),
+                reason: String::from("Cannot use with --in-place, which already replaces them"),
+            });
+        }
+
+        if config.shred && !config.remove_originals {
+            return Err(Error::InvalidArgument {
+                argument: String::from("--shred"),
+                reason: String::from("Only used with --remove-originals"),
+            });
+        }
+
+        if config.cache.is_some() && !config.incremental {
+            return Err(Error::InvalidArgument {
+                argument: String::from("--cache"),
+                reason: String::from("Only used with --incremental"),
+            });
+        }
+
+        if config.name_map.is_some() && config.anonymize_names.is_none() {
+            return Err(Error::InvalidArgument {
+                argument: String::from("--name-map"),
+                reason: String::from("Only used with --anonymize-names"),
+            });
+        }
+
+        config.validate_report()
+    }
+
+    /// Apply a preset's settings.
+    ///
+    /// Errors in a user profile point at the line of the config file.
+    fn apply_preset(&mut self, preset: &Preset, trusted: bool) -> Result<()> {
+        let source = preset_source(preset);
+        for setting in &preset.settings {
+            if setting.key == "preset" {
+                let err = Error::InvalidArgument {
+                    argument: String::from("preset"),
+                    reason: String::from("A preset cannot refer to another preset"),
+                };
+                return Err(source.error(setting.line, err));
+            }
+            self.apply(setting, &source, trusted)?;
+        }
+        Ok(())
+    }
+
+    /// Apply a setting from a config file, the environment or a preset, and
+    /// remember where it came from.
+    ///
+    /// A source that is not `trusted` may not set the options in
+    /// `TRUSTED_SETTINGS`.
+    fn apply(&mut self, setting: &Setting, source: &Source, trusted: bool) -> Result<()> {
+        if !trusted && TRUSTED_SETTINGS.contains(&setting.key.as_str()) {
+            let err = Error::InvalidArgument {
+                argument: setting.key.clone(),
+                reason: String::from(
+                    "Only accepted on the command line or in the system or user config file",
+                ),
+            };
+            return Err(source.error(setting.line, err));
+        }
+        // Files and the environment apply to every command; each takes
+        // only the settings it understands.
+        let applies = options::find_long(&setting.key)
+            .is_some_and(|spec| spec.commands.contains(&self.command));
+        if !applies && is_setting(&setting.key) {
+            return Ok(());
+        }
+        self.apply_setting(&setting.key, &setting.value)
+            .map_err(|e| source.error(setting.line, e))?;
+        self.record(&setting.key, source.clone());
+        Ok(())
+    }
+
+    /// Remember where a setting came from, replacing any earlier source.
+    fn record(&mut self, key: &str, source: Source) {
+        self.sources.retain(|(k, _)| k != key);
+        self.sources.push((key.to_string(), source));
+    }
+
+    /// Get where a setting came from.
+    pub fn source(&self, key: &str) -> Source {
+        self.sources
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, source)| source.clone())
+            .unwrap_or_default()
+    }
+
+    /// Get every setting's effective value, in the order `--print-config`
+    /// shows them. Options that are not set have no value.
+    pub fn settings(&self) -> Vec<(&'static str, Option<Value>)> {
+        let text = |value: String| Some(Value::String(value));
+        let path = |path: &Option<PathBuf>| path.as_ref().map(|p| p.display().to_string());
+        vec![
+            ("output-dir", path(&self.output_dir).and_then(text)),
+            ("recursive", Some(Value::Boolean(self.recursive))),
+            ("force", Some(Value::Boolean(self.force))),
+            ("in-place", Some(Value::Boolean(self.in_place))),
+            ("verbose", Some(Value::Boolean(self.verbose))),
+            ("quiet", Some(Value::Boolean(self.quiet))),
+            ("dry-run", Some(Value::Boolean(self.dry_run))),
+            ("verify", Some(Value::Boolean(self.verify))),
+            ("prove-lossless", Some(Value::Boolean(self.prove_lossless))),
+            ("jobs", self.jobs.map(|n| Value::Integer(n as i64))),
+            (
+                "report-format",
+                self.report_format.and_then(|f| text(f.name().to_string())),
+            ),
+            ("report-file", path(&self.report_file).and_then(text)),
+            ("min-risk", text(self.min_risk.to_string())),
+            ("gps", text(self.gps.to_string())),
+            ("dates", text(self.dates.to_string())),
+            (
+                "stamp",
+                (!self.stamp.is_empty())
+                    .then(|| self.stamp.to_string())
+                    .and_then(text),
+            ),
+            ("icc", text(self.icc.name().to_string())),
+            (
+                "keep-orientation",
+                Some(Value::Boolean(self.keep_orientation)),
+            ),
+            ("allowlist", Some(Value::Boolean(self.allowlist))),
+            (
+                "name-template",
+                self.name_template
+                    .as_ref()
+                    .and_then(|t| text(t.to_string())),
+            ),
+            ("on-collision", text(self.on_collision.name().to_string())),
+            (
+                "anonymize-names",
+                self.anonymize_names
+                    .and_then(|a| text(a.name().to_string())),
+            ),
+            ("name-map", path(&self.name_map).and_then(text)),
+            ("fs-metadata", text(self.fs_metadata.name().to_string())),
+            ("hard-links", text(self.hard_links.name().to_string())),
+            ("backup", path(&self.backup).and_then(text)),
+            (
+                "remove-originals",
+                Some(Value::Boolean(self.remove_originals)),
+            ),
+            ("shred", Some(Value::Boolean(self.shred))),
+            ("incremental", Some(Value::Boolean(self.incremental))),
+            ("cache", path(&self.cache).and_then(text)),
+            ("preset", self.preset.clone().and_then(text)),
+        ]
+    }
+
+    /// Describe the effective settings as TOML, with where each came from.
+    pub fn describe(&self) -> String {
+        let mut lines = Vec::new();
+        for (key, value) in self.settings() {
+            let Some(value) = value else {
+                continue;
+            };
+            let setting = format!("{} = {}", key, value.to_toml());
+            lines.push(format!("{:<32} # {}", setting, self.source(key)));
+        }
+        lines.join("\n")
+    }
+
+    /// Apply a single setting given as a long option name and its value.
+    ///
+    /// Flags take `true` or `false` (or `1` and `0`, as is usual in
+    /// environment variables).
+    pub fn apply_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        if !is_setting(key) {
+            return Err(Error::InvalidArgument {
+                argument: String::from(key),
+                reason: String::from("Unknown setting"),
+            });
+        }
+        self.set(key, value)
+    }
+
+    /// Get the options passed to the strippers.
+    pub fn strip_options(&self) -> StripOptions {
+        StripOptions {
+            gps: self.gps,
+            dates: self.dates,
+            stamp: self.stamp.clone(),
+            icc: self.icc,
+            keep_orientation: self.keep_orientation,
+            allowlist: self.allowlist,
+            check_image: self.verify || self.remove_originals,
+        }
+    }
+
+    /// Get the template for output file names.
+    pub fn name_template(&self) -> NameTemplate {
+        self.name_template
+            .clone()
+            .unwrap_or_else(|| NameTemplate::default_for(self.output_dir.is_some()))
+    }
+
+    /// Get what to do when an output file exists. `--force` is short for
+    /// `--on-collision overwrite`.
+    pub fn collision(&self) -> Collision {
+        match self.on_collision {
+            Collision::Fail if self.force => Collision::Overwrite,
+            policy => policy,
+        }
+    }
+
+    /// Get what to keep for one format under the chosen options.
+    pub fn policy(&self, format: ImageFormat) -> FormatPolicy {
+        self.strip_options().policy(format)
+    }
+
+    /// Check the report options and fill in the format from the file name.
+    fn validate_report(&mut self) -> Result<()> {
+        if self.report_format.is_none() && self.report_file.is_none() {
+            return Ok(());
+        }
+
+        if self.command != Command::Strip {
+            return Err(Error::InvalidArgument {
+                argument: String::from("--report-format"),
+                reason: String::from("Reports are only written when stripping"),
+            });
+        }
+
+        if let (None, Some(file)) = (self.report_format, &self.report_file) {
+            let format =
+                ReportFormat::from_extension(file).ok_or_else(|| Error::InvalidArgument {
+                    argument: String::from("--report-file"),
+                    reason: format!(
+                        "Cannot infer the format of '{}'; use --report-format",
+                        file.display()
+                    ),
+                })?;
+            self.report_format = Some(format);
+        }
+
+        // A report on stdout must not be mixed with human-readable output.
+        if self.report_file.is_none() {
+            if self.verbose {
+                return Err(Error::InvalidArgument {
+                    argument: String::from("--verbose"),
+                    reason: String::from(
+                        "Cannot use --verbose when the report is written to stdout",
+                    ),
+                });
+            }
+            self.quiet = true;
+        }
+
+        Ok(())
+    }
+
+    /// Set an option by its long name. Flags take `true` or `false` (or `1`
+    /// and `0`).
+    fn set(&mut self, long: &str, value: &str) -> Result<()> {
+        let flag = || match value {
+            "true" | "1" => Ok(true),
+            "false" | "0" => Ok(false),
+            _ => Err(Error::InvalidArgument {
+                argument: format!("--{}", long),
+                reason: format!("Expected true or false, not '{}'", value),
+            }),
+        };
+        match long {
+            "help" => self.help = flag()?,
+            "version" => self.version = flag()?,
+            "recursive" => self.recursive = flag()?,
+            "force" => self.force = flag()?,
+            "in-place" => self.in_place = flag()?,
+            "verbose" => self.verbose = flag()?,
+            "quiet" => self.quiet = flag()?,
+            "dry-run" => self.dry_run = flag()?,
+            "verify" => self.verify = flag()?,
+            "prove-lossless" => self.prove_lossless = flag()?,
+            "staged" => self.staged = flag()?,
+            "name-only" => self.name_only = flag()?,
+            "legacy" => self.legacy = flag()?,
+            "keep-orientation" => self.keep_orientation = flag()?,
+            "allowlist" => self.allowlist = flag()?,
+            "print-config" => self.print_config = flag()?,
+            "output-dir" => self.output_dir = Some(PathBuf::from(value)),
+            "jobs" => self.jobs = Some(parse_jobs(value)?),
+            "report-format" => self.report_format = Some(ReportFormat::parse(value)?),
+            "report-file" => self.report_file = Some(PathBuf::from(value)),
+            "min-risk" => self.min_risk = RiskLevel::parse(value)?,
+            "gps" => self.gps = GpsMode::parse(value)?,
+            "dates" => self.dates = DateMode::parse(value)?,
+            "stamp" => self.stamp = Stamp::parse(value)?,
+            "icc" => self.icc = IccMode::parse(value)?,
+            "name-template" => self.name_template = Some(NameTemplate::parse(value)?),
+            "on-collision" => self.on_collision = Collision::parse(value)?,
+            "anonymize-names" => self.anonymize_names = Some(Anonymize::parse(value)?),
+            "name-map" => self.name_map = Some(PathBuf::from(value)),
+            "fs-metadata" => self.fs_metadata = FsMetadata::parse(value)?,
+            "hard-links" => self.hard_links = HardLinks::parse(value)?,
+            "backup" => self.backup = Some(PathBuf::from(value)),
+            "remove-originals" => self.remove_originals = flag()?,
+            "shred" => self.shred = flag()?,
+            "incremental" => self.incremental = flag()?,
+            "cache" => self.cache = Some(PathBuf::from(value)),
+            "preset" => self.preset = Some(String::from(value)),
+            _ => {
+                return Err(Error::InvalidArgument {
+                    argument: format!("--{}", long),
+                    reason: String::from("Unknown option"),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Settings that delete or overwrite files, or name where output goes.
+///
+/// The project file and the environment may not set these, since the current
+/// directory may come from someone else.
+const TRUSTED_SETTINGS: &[&str] = &[
+    "force",
+    "in-place",
+    "on-collision",
+    "output-dir",
+    "remove-originals",
+    "shred",
+    "backup",
+    "report-file",
+    "name-map",
+    "cache",
+];
+
+/// Get where a preset's settings come from.
+fn preset_source(preset: &Preset) -> Source {
+    Source::Preset {
+        name: preset.name.clone(),
+        path: preset.source.clone(),
+    }
+}
+
+/// Check if a name is a setting that config files and the environment may set.
+fn is_setting(key: &str) -> bool {
+    Config::default().settings().iter().any(|(k, _)| *k == key)
+}
+
+/// Parse a jobs value (positive integer).
+fn parse_jobs(value: &str) -> Result<usize> {
+    value
+        .parse::<usize>()
+        .map_err(|_| Error::InvalidArgument {
+            argument: String::from("--jobs"),
+            reason: format!("'{}' is not a valid number", value),
+        })
+        .and_then(|n| {
+            if n == 0 {
+                Err(Error::InvalidArgument {
+                    argument: String::from("--jobs"),
+                    reason: String::from("Number of jobs must be at least 1"),
+                })
+            } else {
+                Ok(n)
+            }
+        })
+}
+
+/// Generate the help message.
+pub fn help_message() -> String {
+    format!(
+        r#"{name} {version} - Protect My Images
+
+Strip metadata from images to protect your privacy.
+
+USAGE:
+{usage}
+
+COMMANDS:
+{commands}
+
+ARGUMENTS:
+    <PATHS>...    Image files or directories to process
+
+OPTIONS:
+{options}
+
+CONFIGURATION:
+    Settings are read from /etc/pmi/config, ~/.config/pmi/config, .pmi.toml
+    and PMI_* environment variables (e.g. PMI_GPS=coarsen=10), each taking
+    precedence over the last. Options on the command line override them all.
+    Run `{name} <COMMAND> --help` for the options a command accepts.
+
+EXAMPLES:
+    {name} photo.jpg                      Create photo_clean.jpg
+    {name} -i photo.jpg                   Overwrite photo.jpg in place
+    {name} -o ./clean/ *.jpg              Output to ./clean/ directory
+    {name} -r ./photos/                   Process directory recursively
+    {name} -j 4 -r ./photos/              Process with 4 threads
+    {name} -n -v ./photos/                Dry run with verbose output
+    {name} -i --verify photo.jpg          Overwrite in place only if output verifies
+    {name} -v --prove-lossless image.png  Print per-frame pixel digests as evidence
+    {name} check -r ./photos/             List files that still contain metadata
+    {name} git-check origin/main..HEAD    Check images added by unpushed commits
+    {name} --report-file r.csv ./photos/  Record every file's outcome in r.csv
+    {name} -n -v --min-risk high -r .     List files leaking serials, times or GPS
+    {name} --gps coarsen=10 trip.jpg      Keep the location to within about 10 km
+    {name} --dates keep-date-only a.jpg   Keep the day a photo was taken, not the time
+    {name} --stamp "artist=Ann" -r out/   Credit the author in every cleaned image
+    {name} --preset social -r ./upload/   Strip for sharing, keep colours and rotation
+    {name} --print-config                 Show settings from config files and PMI_*
+    {name} completions bash               Print a bash completion script
+
+SUPPORTED FORMATS:
+    JPEG (.jpg, .jpeg)
+    PNG  (.png)
+    GIF  (.gif)
+    WebP (.webp)
+    TIFF (.tif, .tiff)
+"#,
+        name = NAME,
+        version = VERSION,
+        usage = options::usage_lines(),
+        commands = options::command_lines(),
+        options = options::option_lines(options::OPTIONS.iter()),
+    )
+}
+
+/// Generate the version message.
+pub fn version_message() -> String {
+    format!("{} {}", NAME, VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse arguments without reading config files or `PMI_*` variables.
+    fn parse<I, S>(args: I) -> Result<Config>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Config::parse_with(args, &Environment::default())
+    }
+
+    #[test]
+    fn test_parse_single_path() {
+        let config = parse(["pmi", "photo.jpg"]).unwrap();
+        assert_eq!(config.paths.len(), 1);
+        assert_eq!(config.paths[0], PathBuf::from("photo.jpg"));
+    }
+
+    #[test]
+    fn test_parse_multiple_paths() {
+        let config = parse(["pmi", "a.jpg", "b.png", "c.gif"]).unwrap();
+        assert_eq!(config.paths.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_help_short() {
+        let config = parse(["pmi", "-h"]).unwrap();
+        assert!(config.help);
+    }
+
+    #[test]
+    fn test_parse_help_long() {
+        let config = parse(["pmi", "--help"]).unwrap();
+        assert!(config.help);
+    }
+
+    #[test]
+    fn test_parse_version_short() {
+        let config = parse(["pmi", "-V"]).unwrap();
+        assert!(config.version);
+    }
+
+    #[test]
+    fn test_parse_version_long() {
+        let config = parse(["pmi", "--version"]).unwrap();
+        assert!(config.version);
+    }
+
+    #[test]
+    fn test_parse_recursive() {
+        let config = parse(["pmi", "-r", "dir/"]).unwrap();
+        assert!(config.recursive);
+    }
+
+    #[test]
+    fn test_parse_force() {
+        let config = parse(["pmi", "--force", "file.jpg"]).unwrap();
+        assert!(config.force);
+    }
+
+    #[test]
+    fn test_parse_in_place() {
+        let config = parse(["pmi", "-i", "file.jpg"]).unwrap();
+        assert!(config.in_place);
+    }
+
+    #[test]
+    fn test_parse_verbose() {
+        let config = parse(["pmi", "-v", "file.jpg"]).unwrap();
+        assert!(config.verbose);
+    }
+
+    #[test]
+    fn test_parse_quiet() {
+        let config = parse(["pmi", "-q", "file.jpg"]).unwrap();
+        assert!(config.quiet);
+    }
+
+    #[test]
+    fn test_parse_dry_run() {
+        let config = parse(["pmi", "-n", "file.jpg"]).unwrap();
+        assert!(config.dry_run);
+    }
+
+    #[test]
+    fn test_parse_verify() {
+        let config = parse(["pmi", "--verify", "file.jpg"]).unwrap();
+        assert!(config.verify);
+    }
+
+    #[test]
+    fn test_parse_prove_lossless() {
+        let config = parse(["pmi", "--prove-lossless", "file.png"]).unwrap();
+        assert!(config.prove_lossless);
+        assert!(!config.verify);
+    }
+
+    #[test]
+    fn test_parse_check_command() {
+        let config = parse(["pmi", "check", "-r", "photos"]).unwrap();
+        assert_eq!(config.command, Command::Check);
+        assert_eq!(config.paths, vec![PathBuf::from("photos")]);
+
+        // Only the first argument selects the command.
+        let config = parse(["pmi", "a.jpg", "check"]).unwrap();
+        assert_eq!(config.command, Command::Strip);
+        assert_eq!(config.paths.len(), 2);
+
+        assert!(parse(["pmi", "check"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_git_check() {
+        let config = parse(["pmi", "git-check"]).unwrap();
+        assert_eq!(config.command, Command::GitCheck);
+        assert!(config.rev_range.is_none());
+
+        let config = parse(["pmi", "git-check", "main..HEAD", "--name-only"]).unwrap();
+        assert_eq!(config.rev_range.as_deref(), Some("main..HEAD"));
+        assert!(config.name_only);
+
+        assert!(parse(["pmi", "git-check", "--staged"]).unwrap().staged);
+        assert!(parse(["pmi", "git-check", "--staged", "HEAD"]).is_err());
+        assert!(parse(["pmi", "git-check", "a..b", "c..d"]).is_err());
+        assert!(parse(["pmi", "--staged", "photo.jpg"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_strip_command() {
+        let config = parse(["pmi", "strip", "-i", "a.jpg"]).unwrap();
+        assert_eq!(config.command, Command::Strip);
+        assert!(config.named_command);
+        assert!(config.in_place);
+        assert_eq!(config.paths, vec![PathBuf::from("a.jpg")]);
+
+        let config = parse(["pmi", "-i", "a.jpg"]).unwrap();
+        assert_eq!(config.command, Command::Strip);
+        assert!(!config.named_command);
+
+        // `pmi check --help` asks for the help of that command.
+        let config = parse(["pmi", "check", "--help"]).unwrap();
+        assert!(config.help && config.named_command);
+    }
+
+    #[test]
+    fn test_parse_completions() {
+        let config = parse(["pmi", "completions", "zsh"]).unwrap();
+        assert_eq!(config.command, Command::Completions);
+        assert_eq!(config.shell, Some(Shell::Zsh));
+
+        assert!(matches!(
+            parse(["pmi", "completions"]),
+            Err(Error::MissingArgument { .. })
+        ));
+        assert!(parse(["pmi", "completions", "tcsh"]).is_err());
+        assert!(parse(["pmi", "completions", "bash", "fish"]).is_err());
+        assert!(parse(["pmi", "completions", "-r", "bash"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_backup_and_restore() {
+        let config = parse(["pmi", "-i", "--backup", "bak", "a.jpg"]).unwrap();
+        assert_eq!(config.backup, Some(PathBuf::from("bak")));
+        assert!(parse(["pmi", "--backup", "bak", "a.jpg"]).is_err());
+
+        let config = parse(["pmi", "restore", "-n", "bak/journal.csv"]).unwrap();
+        assert_eq!(config.command, Command::Restore);
+        assert_eq!(config.journal, Some(PathBuf::from("bak/journal.csv")));
+        assert!(config.dry_run);
+
+        assert!(matches!(
+            parse(["pmi", "restore"]),
+            Err(Error::MissingArgument { .. })
+        ));
+        assert!(parse(["pmi", "restore", "a.csv", "b.csv"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_remove_originals() {
+        let config = parse(["pmi", "--remove-originals", "--shred", "a.jpg"]).unwrap();
+        assert!(config.remove_originals && config.shred);
+        assert!(parse(["pmi", "--shred", "a.jpg"]).is_err());
+        assert!(parse(["pmi", "-i", "--remove-originals", "a.jpg"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_incremental() {
+        let config = parse(["pmi", "--incremental", "--cache", "c.csv", "-r", "photos"]).unwrap();
+        assert!(config.incremental);
+        assert_eq!(config.cache, Some(PathBuf::from("c.csv")));
+        assert!(parse(["pmi", "--cache", "c.csv", "a.jpg"]).is_err());
+    }
+
+    #[test]
+    fn test_options_are_checked_against_the_command() {
+        let reason = |args: &[&str]| match parse(args) {
+            Err(Error::InvalidArgument { argument, reason }) => format!("{}: {}", argument, reason),
+            other => panic!("expected an error for {:?}, got {:?}", args, other),
+        };
+        assert_eq!(
+            reason(&["pmi", "check", "-o", "out", "a.jpg"]),
+            "--output-dir: Only valid when stripping"
+        );
+        assert_eq!(
+            reason(&["pmi", "--name-only", "a.jpg"]),
+            "--name-only: Only valid with git-check"
+        );
+        assert_eq!(
+            reason(&["pmi", "git-check", "-r"]),
+            "--recursive: Not valid with pmi git-check"
+        );
+        assert_eq!(
+            reason(&["pmi", "--verify=yes", "a.jpg"]),
+            "--verify: Option does not take a value"
+        );
+    }
+
+    #[test]
+    fn test_parse_min_risk() {
+        let config = parse(["pmi", "a.jpg"]).unwrap();
+        assert_eq!(config.min_risk, RiskLevel::None);
+
+        let config = parse(["pmi", "--min-risk", "high", "a.jpg"]).unwrap();
+        assert_eq!(config.min_risk, RiskLevel::High);
+
+        let config = parse(["pmi", "--min-risk=Critical", "a.jpg"]).unwrap();
+        assert_eq!(config.min_risk, RiskLevel::Critical);
+
+        assert!(parse(["pmi", "--min-risk", "severe", "a.jpg"]).is_err());
+        assert!(parse(["pmi", "check", "--min-risk", "low", "a.jpg"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_gps() {
+        let config = parse(["pmi", "a.jpg"]).unwrap();
+        assert_eq!(config.gps, GpsMode::Remove);
+
+        let config = parse(["pmi", "--gps", "coarsen=25", "a.jpg"]).unwrap();
+        assert_eq!(config.gps, GpsMode::Coarsen(25.0));
+        assert_eq!(config.strip_options().gps, GpsMode::Coarsen(25.0));
+
+        let config = parse(["pmi", "--gps=remove", "a.jpg"]).unwrap();
+        assert_eq!(config.gps, GpsMode::Remove);
+
+        assert!(parse(["pmi", "--gps", "coarsen=0", "a.jpg"]).is_err());
+        assert!(parse(["pmi", "--gps", "a.jpg"]).is_err());
+        assert!(parse(["pmi", "check", "--gps=coarsen=5", "a.jpg"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_dates() {
+        let config = parse(["pmi", "a.jpg"]).unwrap();
+        assert_eq!(config.dates, DateMode::Remove);
+
+        let config = parse(["pmi", "--dates", "shift=-1d2h", "a.jpg"]).unwrap();
+        assert_eq!(config.dates, DateMode::Shift(-(86_400 + 7200)));
+        assert_eq!(config.strip_options().dates, config.dates);
+
+        let config = parse(["pmi", "--dates=keep-date-only", "a.jpg"]).unwrap();
+        assert_eq!(config.dates, DateMode::KeepDateOnly);
+
+        assert!(parse(["pmi", "--dates", "shift=soon", "a.jpg"]).is_err());
+        assert!(parse(["pmi", "--dates"]).is_err());
+        assert!(parse(["pmi", "check", "--dates=keep-date-only", "a.jpg"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_stamp() {
+        let config = parse(["pmi", "a.jpg"]).unwrap();
+        assert!(config.stamp.is_empty());
+
+        let config = parse([
+            "pmi",
+            "--stamp",
+            "copyright=Example, Inc.,license-url=https://example.org/cc-by",
+            "a.jpg",
+        ])
+        .unwrap();
+        assert_eq!(config.stamp.copyright.as_deref(), Some("Example, Inc."));
+        assert_eq!(config.stamp.artist, None);
+        assert_eq!(config.strip_options().stamp, config.stamp);
+
+        let config = parse(["pmi", "--stamp=artist=Jane", "a.jpg"]).unwrap();
+        assert_eq!(config.stamp.artist.as_deref(), Some("Jane"));
+
+        assert!(parse(["pmi", "--stamp", "owner=Jane", "a.jpg"]).is_err());
+        assert!(parse(["pmi", "--stamp"]).is_err());
+        assert!(parse(["pmi", "check", "--stamp=artist=Jane", "a.jpg"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_icc_and_orientation() {
+        let config = parse(["pmi", "a.jpg"]).unwrap();
+        assert_eq!(config.icc, IccMode::Auto);
+        assert!(!config.policy(ImageFormat::Jpeg).keep_icc);
+        assert!(config.policy(ImageFormat::Png).keep_icc);
+
+        let config = parse(["pmi", "--icc", "keep", "--keep-orientation", "a.jpg"]).unwrap();
+        let policy = config.policy(ImageFormat::Jpeg);
+        assert!(policy.keep_icc && policy.keep_orientation);
+        assert!(!config.policy(ImageFormat::Gif).keep_icc);
+
+        let config = parse(["pmi", "--icc=strip", "a.png"]).unwrap();
+        assert!(!config.policy(ImageFormat::Png).keep_icc);
+
+        assert!(parse(["pmi", "--icc", "maybe", "a.jpg"]).is_err());
+        assert!(parse(["pmi", "check", "--keep-orientation", "a.jpg"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_preset() {
+        let config = parse(["pmi", "--preset", "social", "a.jpg"]).unwrap();
+        assert_eq!(config.preset.as_deref(), Some("social"));
+        assert_eq!(config.icc, IccMode::Keep);
+        assert!(config.keep_orientation);
+
+        let config = parse(["pmi", "--preset=archive", "a.jpg"]).unwrap();
+        assert_eq!(config.gps, GpsMode::Coarsen(5.0));
+        assert_eq!(config.dates, DateMode::Keep);
+
+        let config = parse(["pmi", "--preset", "forensic-safe", "a.png"]).unwrap();
+        assert!(config.verify && config.prove_lossless);
+
+        let config = parse(["pmi", "--preset", "journalist", "a.png"]).unwrap();
+        let policy = config.policy(ImageFormat::Png);
+        assert!(policy.allowlist && !policy.keep_icc);
+        assert!(
+            !parse(["pmi", "a.png"])
+                .unwrap()
+                .policy(ImageFormat::Png)
+                .allowlist
+        );
+
+        // Explicit options win over the preset, wherever they appear.
+        let config = parse(["pmi", "--icc", "strip", "--preset", "social", "a.jpg"]).unwrap();
+        assert_eq!(config.icc, IccMode::Strip);
+        assert!(config.keep_orientation);
+
+        let err = parse(["pmi", "--preset", "holiday", "a.jpg"]).unwrap_err();
+        assert!(err.to_string().contains("journalist"));
+        assert!(parse(["pmi", "--preset"]).is_err());
+        assert!(parse(["pmi", "check", "--preset", "archive", "a.jpg"]).is_err());
+    }
+
+    #[test]
+    fn test_apply_preset_errors_point_at_the_profile() {
+        let setting = |key: &str, value: &str| preset::Setting {
+            key: String::from(key),
+            value: String::from(value),
+            line: 7,
+        };
+        let mut preset = Preset {
+            name: String::from("family"),
+            description: String::new(),
+            settings: vec![setting("verify", "yes")],
+            source: Some(PathBuf::from("config")),
+        };
+        let err = Config::default().apply_preset(&preset, true).unwrap_err();
+        assert!(matches!(err, Error::Config { line: 7, .. }));
+
+        preset.settings = vec![setting("preset", "social")];
+        assert!(Config::default().apply_preset(&preset, true).is_err());
+
+        let mut config = Config::default();
+        assert!(config.apply_setting("colour", "keep").is_err());
+        config.apply_setting("jobs", "3").unwrap();
+        config.apply_setting("verify", "1").unwrap();
+        assert_eq!(config.jobs, Some(3));
+        assert!(config.verify);
+    }
+
+    /// Write config files into a fresh directory and describe them as an
+    /// environment.
+    fn environment(name: &str, files: &[(&str, &str)], vars: &[(&str, &str)]) -> Environment {
+        let dir = std::env::temp_dir().join(format!("pmi_cli_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Environment {
+            files: files
+                .iter()
+                .map(|(file, text)| {
+                    let path = dir.join(file);
+                    std::fs::write(&path, text).unwrap();
+                    path
+                })
+                .collect(),
+            project: None,
+            vars: vars
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_layers() {
+        let mut env = environment(
+            "layers",
+            &[
+                ("system", "jobs = 2\nicc = \"strip\"\n"),
+                (
+                    "user",
+                    "jobs = 3\nverbose = true\n[profile.web]\nkeep-orientation = true\n",
+                ),
+                ("project", "recursive = true\ngps = \"coarsen=10\"\n"),
+            ],
+            &[("PMI_GPS", "coarsen=20"), ("PMI_RANK", "4")],
+        );
+        env.project = env.files.pop();
+        let parse = |args: &[&str]| Config::parse_with(["pmi"].iter().chain(args), &env);
+
+        let config = parse(&["a.jpg"]).unwrap();
+        assert_eq!(config.jobs, Some(3));
+        assert_eq!(config.icc, IccMode::Strip);
+        assert!(config.recursive && config.verbose);
+        assert_eq!(config.gps, GpsMode::Coarsen(20.0));
+        assert_eq!(config.source("jobs"), Source::File(env.files[1].clone()));
+        assert_eq!(config.source("gps"), Source::Env(String::from("PMI_GPS")));
+        assert_eq!(config.source("force"), Source::Default);
+
+        // The command line wins, and -q overrides verbose from a file.
+        let config = parse(&["-q", "-j", "8", "--preset", "web", "a.jpg"]).unwrap();
+        assert_eq!(config.jobs, Some(8));
+        assert!(config.quiet && !config.verbose);
+        assert!(config.keep_orientation);
+        assert_eq!(config.source("jobs"), Source::CommandLine);
+        assert_eq!(
+            config.source("keep-orientation"),
+            Source::Preset {
+                name: String::from("web"),
+                path: Some(env.files[1].clone()),
+            }
+        );
+
+        // Stripping options in files do not get in the way of other commands.
+        let config = parse(&["check", "a.jpg"]).unwrap();
+        assert_eq!(config.gps, GpsMode::Remove);
+        assert_eq!(config.icc, IccMode::Auto);
+
+        // Help works even with a broken config file.
+        let broken = environment("broken", &[("config", "jobs = zero\n")], &[]);
+        assert!(Config::parse_with(["pmi", "--help"], &broken).unwrap().help);
+        let err = Config::parse_with(["pmi", "a.jpg"], &broken).unwrap_err();
+        assert!(matches!(err, Error::Config { line: 1, .. }));
+
+        let bad_env = environment("bad_env", &[], &[("PMI_JOBS", "0")]);
+        let err = Config::parse_with(["pmi", "a.jpg"], &bad_env).unwrap_err();
+        assert!(err.to_string().contains("PMI_JOBS"));
+    }
+
+    #[test]
+    fn test_untrusted_sources_cannot_delete_files() {
+        let mut env = environment(
+            "untrusted",
+            &[
+                ("user", "[profile.tidy]\nremove-originals = true\n"),
+                ("project", "verify = true\nremove-originals = true\n"),
+            ],
+            &[],
+        );
+        env.project = env.files.pop();
+        let err = Config::parse_with(["pmi", "a.jpg"], &env).unwrap_err();
+        assert!(matches!(err, Error::Config { line: 2, .. }));
+        let message = err.to_string();
+        assert!(message.contains("project") && message.contains("remove-originals"));
+
+        // The project file may still choose a trusted profile.
+        let text = "verify = true\npreset = \"tidy\"\n";
+        std::fs::write(env.project.as_ref().unwrap(), text).unwrap();
+        let config = Config::parse_with(["pmi", "a.jpg"], &env).unwrap();
+        assert!(config.verify && config.remove_originals);
+
+        // But not define one of its own.
+        let text = "preset = \"wipe\"\n[profile.wipe]\nshred = true\n";
+        std::fs::write(env.project.as_ref().unwrap(), text).unwrap();
+        let err = Config::parse_with(["pmi", "a.jpg"], &env).unwrap_err();
+        assert!(matches!(err, Error::Config { line: 3, .. }));
+
+        let env = environment("untrusted_env", &[], &[("PMI_OUTPUT_DIR", "/tmp")]);
+        let err = Config::parse_with(["pmi", "a.jpg"], &env).unwrap_err();
+        assert!(err.to_string().contains("PMI_OUTPUT_DIR"));
+
+        // The command line and the user file are trusted.
+        let env = environment("trusted", &[("user", "in-place = true\n")], &[]);
+        assert!(Config::parse_with(["pmi", "a.jpg"], &env).unwrap().in_place);
+        let config = Config::parse_with(["pmi", "--in-place", "a.jpg"], &Environment::default());
+        assert!(config.unwrap().in_place);
+    }
+
+    #[test]
+    fn test_print_config() {
+        let env = environment("print", &[("config", "verify = true\n")], &[]);
+        let config =
+            Config::parse_with(["pmi", "--print-config", "--gps", "coarsen=5"], &env).unwrap();
+        assert!(config.print_config);
+
+        let text = config.describe();
+        let line = |key: &str| {
+            text.lines()
+                .find(|l| l.starts_with(&format!("{} =", key)))
+                .unwrap()
+                .to_string()
+        };
+        assert!(line("verify").starts_with("verify = true "));
+        assert!(line("verify").ends_with(&format!("# {}", env.files[0].display())));
+        assert!(line("gps").starts_with("gps = \"coarsen=5\""));
+        assert!(line("gps").ends_with("# command line"));
+        assert!(line("icc").ends_with("# default"));
+        assert!(!text.contains("output-dir"));
+    }
+
+    #[test]
+    fn test_parse_naming_options() {
+        let config = parse([
+            "pmi",
+            "--name-template",
+            "{hash8}.{ext}",
+            "--on-collision=skip",
+            "a.jpg",
+        ])
+        .unwrap();
+        assert_eq!(config.name_template().to_string(), "{hash8}.{ext}");
+        assert_eq!(config.collision(), Collision::Skip);
+
+        // --force is short for --on-collision overwrite.
+        let config = parse(["pmi", "-f", "a.jpg"]).unwrap();
+        assert_eq!(config.collision(), Collision::Overwrite);
+        assert_eq!(config.name_template().to_string(), "{stem}_clean.{ext}");
+
+        assert!(parse(["pmi", "--name-template", "{size}", "a.jpg"]).is_err());
+        assert!(parse(["pmi", "--on-collision", "rename", "a.jpg"]).is_err());
+        assert!(parse(["pmi", "-i", "--name-template", "{stem}", "a.jpg"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_report_options() {
+        // A report on stdout silences human-readable output.
+        let config = parse(["pmi", "--report-format", "ndjson", "a.jpg"]).unwrap();
+        assert_eq!(config.report_format, Some(ReportFormat::Ndjson));
+        assert!(config.quiet);
+
+        let config = parse(["pmi", "--report-file=out.csv", "a.jpg"]).unwrap();
+        assert_eq!(config.report_format, Some(ReportFormat::Csv));
+        assert!(!config.quiet);
+
+        let config = parse([
+            "pmi",
+            "--report-format=json",
+            "--report-file",
+            "r.log",
+            "a.jpg",
+        ])
+        .unwrap();
+        assert_eq!(config.report_format, Some(ReportFormat::Json));
+
+        assert!(parse(["pmi", "--report-file", "r.log", "a.jpg"]).is_err());
+        assert!(parse(["pmi", "--report-format", "xml", "a.jpg"]).is_err());
+        assert!(parse(["pmi", "check", "--report-format", "json", "a.jpg"]).is_err());
+        assert!(parse(["pmi", "-v", "--report-format", "json", "a.jpg"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_output_dir_short() {
+        let config = parse(["pmi", "-o", "/output", "file.jpg"]).unwrap();
+        assert_eq!(config.output_dir, Some(PathBuf::from("/output")));
+    }
+
+    #[test]
+    fn test_parse_output_dir_long() {
+        let config = parse(["pmi", "--output-dir", "/output", "file.jpg"]).unwrap();
+        assert_eq!(config.output_dir, Some(PathBuf::from("/output")));
+    }
+
+    #[test]
+    fn test_parse_output_dir_equals() {
+        let config = parse(["pmi", "--output-dir=/output", "file.jpg"]).unwrap();
+        assert_eq!(config.output_dir, Some(PathBuf::from("/output")));
+    }
+
+    #[test]
+    fn test_parse_combined_short_flags() {
+        let config = parse(["pmi", "-rfv", "dir/"]).unwrap();
+        assert!(config.recursive);
+        assert!(config.force);
+        assert!(config.verbose);
+    }
+
+    #[test]
+    fn test_parse_combined_flags_with_value() {
+        let config = parse(["pmi", "-rfo", "/output", "dir/"]).unwrap();
+        assert!(config.recursive);
+        assert!(config.force);
+        assert_eq!(config.output_dir, Some(PathBuf::from("/output")));
+    }
+
+    #[test]
+    fn test_missing_paths() {
+        let result = parse(["pmi"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_quiet_and_verbose_conflict() {
+        let result = parse(["pmi", "-q", "-v", "file.jpg"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_unknown_long_option() {
+        let result = parse(["pmi", "--unknown", "file.jpg"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_unknown_short_option() {
+        let result = parse(["pmi", "-x", "file.jpg"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_missing_output_dir_value() {
+        let result = parse(["pmi", "-o"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_help_message_contains_usage() {
+        let help = help_message();
+        assert!(help.contains("USAGE:"));
+        assert!(help.contains("OPTIONS:"));
+        assert!(help.contains("EXAMPLES:"));
+    }
+
+    #[test]
+    fn test_version_message() {
+        let version = version_message();
+        assert!(version.contains("pmi"));
+    }
+
+    #[test]
+    fn test_parse_jobs_short() {
+        let config = parse(["pmi", "-j", "4", "file.jpg"]).unwrap();
+        assert_eq!(config.jobs, Some(4));
+    }
+
+    #[test]
+    fn test_parse_jobs_long() {
+        let config = parse(["pmi", "--jobs", "8", "file.jpg"]).unwrap();
+        assert_eq!(config.jobs, Some(8));
+    }
+
+    #[test]
+    fn test_parse_jobs_equals() {
+        let config = parse(["pmi", "--jobs=12", "file.jpg"]).unwrap();
+        assert_eq!(config.jobs, Some(12));
+    }
+
+    #[test]
+    fn test_parse_jobs_invalid() {
+        let result = parse(["pmi", "-j", "abc", "file.jpg"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_jobs_zero() {
+        let result = parse(["pmi", "-j", "0", "file.jpg"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_jobs_missing_value() {
+        let result = parse(["pmi", "-j"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_jobs_combined_flags() {
+        let config = parse(["pmi", "-rvj", "4", "dir/"]).unwrap();
+        assert!(config.recursive);
+        assert!(config.verbose);
+        assert_eq!(config.jobs, Some(4));
+    }
+}
@@ -0,0 +1,676 @@
+//! Registry of commands and options.
+//!
+//! The parser, the help text and the completion scripts all read these
+//! tables, so a new option only has to be described once.
+
+use super::{Command, NAME};
+
+use Command::{Check, Cleanup, Completions, GitCheck, Restore, Strip};
+
+/// What an option takes after its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arg {
+    /// Nothing; the option is a flag.
+    Flag,
+    /// A directory.
+    Dir,
+    /// A file.
+    File,
+    /// A number.
+    Number,
+    /// Free text.
+    Text,
+    /// One of a fixed set of words. A trailing `=` marks a prefix that
+    /// takes a value of its own, such as `coarsen=`.
+    Choice(&'static [&'static str]),
+    /// The name of a preset or user profile.
+    Preset,
+}
+
+/// A command-line option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptionSpec {
+    /// Long name, without the leading dashes.
+    pub long: &'static str,
+    /// Single-letter name, if any.
+    pub short: Option<char>,
+    /// What the option takes.
+    pub arg: Arg,
+    /// Name of the value in help text, such as `<DIR>`.
+    pub placeholder: &'static str,
+    /// Help text, one entry per line.
+    pub help: &'static [&'static str],
+    /// Commands the option applies to.
+    pub commands: &'static [Command],
+}
+
+impl OptionSpec {
+    /// Check if the option takes a value.
+    pub fn takes_value(&self) -> bool {
+        self.arg != Arg::Flag
+    }
+
+    /// Get the option as written in help text, such as `-o, --output-dir <DIR>`.
+    pub fn synopsis(&self) -> String {
+        let mut text = match self.short {
+            Some(short) => format!("-{}, --{}", short, self.long),
+            None => format!("    --{}", self.long),
+        };
+        if self.takes_value() {
+            text.push(' ');
+            text.push_str(self.placeholder);
+        }
+        text
+    }
+}
+
+/// A subcommand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandSpec {
+    /// The command.
+    pub command: Command,
+    /// Usage after the command name.
+    pub usage: &'static str,
+    /// Summary, one entry per line.
+    pub about: &'static [&'static str],
+    /// Positional arguments and their descriptions.
+    pub arguments: &'static [(&'static str, &'static str)],
+}
+
+/// Every subcommand, in the order help lists them.
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        command: Strip,
+        usage: "[OPTIONS] <PATHS>...",
+        about: &[
+            "Strip metadata from images (the default when no command is",
+            "given)",
+        ],
+        arguments: &[("<PATHS>...", "Image files or directories to process")],
+    },
+    CommandSpec {
+        command: Check,
+        usage: "[OPTIONS] <PATHS>...",
+        about: &[
+            "Report metadata without modifying files. Exits 0 if all",
+            "files are clean, 1 if metadata was found, 2 on parse errors",
+        ],
+        arguments: &[("<PATHS>...", "Image files or directories to check")],
+    },
+    CommandSpec {
+        command: GitCheck,
+        usage: "[--staged | <REV-RANGE>] [--name-only]",
+        about: &[
+            "Like check, for image blobs added or modified in the index",
+            "(default) or in a revision range, read from the object store",
+        ],
+        arguments: &[("<REV-RANGE>", "Commits to check, such as origin/main..HEAD")],
+    },
+    CommandSpec {
+        command: Completions,
+        usage: "<SHELL>",
+        about: &["Print a completion script for bash, zsh or fish"],
+        arguments: &[("<SHELL>", "bash, zsh or fish")],
+    },
+    CommandSpec {
+        command: Restore,
+        usage: "[OPTIONS] <JOURNAL>",
+        about: &[
+            "Put back the originals of an in-place run from its --backup",
+            "journal, leaving files changed since the run alone",
+        ],
+        arguments: &[("<JOURNAL>", "journal.csv written by a run with --backup")],
+    },
+    CommandSpec {
+        command: Cleanup,
+        usage: "[OPTIONS] <PATHS>...",
+        about: &[
+            "Remove temporary files left by interrupted runs (list them",
+            "with --dry-run)",
+        ],
+        arguments: &[("<PATHS>...", "Files or directories to search")],
+    },
+];
+
+/// Commands that take files and directories.
+const FILES: &[Command] = &[Strip, Check, Cleanup];
+/// Commands that report on what they do.
+const REPORTING: &[Command] = &[Strip, Check, GitCheck, Restore, Cleanup];
+/// Every command.
+const ALL: &[Command] = &[Strip, Check, GitCheck, Completions, Restore, Cleanup];
+
+/// Every option, in the order help lists them.
+pub const OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        long: "output-dir",
+        short: Some('o'),
+        arg: Arg::Dir,
+        placeholder: "<DIR>",
+        help: &[
+            "Save cleaned images to specified directory (with -r,",
+            "mirroring the input directory tree)",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "recursive",
+        short: Some('r'),
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &["Process directories recursively"],
+        commands: FILES,
+    },
+    OptionSpec {
+        long: "force",
+        short: Some('f'),
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &["Overwrite existing output files (--on-collision overwrite)"],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "in-place",
+        short: Some('i'),
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &["Modify files in place (default: create *_clean suffix)"],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "name-template",
+        short: None,
+        arg: Arg::Text,
+        placeholder: "<TEMPLATE>",
+        help: &[
+            "Name outputs from {stem}, {ext}, {format}, {hash8},",
+            "{index} and {date} (default: {stem}_clean.{ext},",
+            "or {stem}.{ext} with --output-dir)",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "on-collision",
+        short: None,
+        arg: Arg::Choice(&["fail", "skip", "overwrite", "number"]),
+        placeholder: "<POLICY>",
+        help: &[
+            "When an output exists: fail (default), skip,",
+            "overwrite (same as --force) or number",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "anonymize-names",
+        short: None,
+        arg: Arg::Choice(&["random", "hash", "sequential"]),
+        placeholder: "<MODE>",
+        help: &[
+            "Replace file names, which often give away capture",
+            "times and devices: random, hash (of the cleaned",
+            "file) or sequential (image_0001, ...)",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "name-map",
+        short: None,
+        arg: Arg::File,
+        placeholder: "<FILE>",
+        help: &[
+            "Append original and anonymised names to a private",
+            "CSV file",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "fs-metadata",
+        short: None,
+        arg: Arg::Choice(&["preserve", "normalize", "strip"]),
+        placeholder: "<MODE>",
+        help: &[
+            "Output times, permissions and extended attributes:",
+            "strip (default), normalize (fixed 1980 times) or",
+            "preserve (copy from the input)",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "hard-links",
+        short: None,
+        arg: Arg::Choice(&["rewrite", "break", "skip"]),
+        placeholder: "<POLICY>",
+        help: &[
+            "Hard-linked files with -i: rewrite (default; clean",
+            "every name, not atomically), break (replace this",
+            "name only) or skip",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "backup",
+        short: None,
+        arg: Arg::Dir,
+        placeholder: "<DIR>",
+        help: &[
+            "With -i, copy originals into a new directory under DIR",
+            "and journal the run for pmi restore",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "remove-originals",
+        short: None,
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &[
+            "Delete each original once its cleaned copy is written",
+            "and verified",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "shred",
+        short: None,
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &[
+            "With --remove-originals, overwrite originals before",
+            "deleting them. Not reliable on SSDs or copy-on-write",
+            "filesystems (btrfs, ZFS, APFS), and snapshots or",
+            "backups keep their own copies",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "incremental",
+        short: None,
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &[
+            "Skip files unchanged since a run with the same",
+            "settings cleaned them",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "cache",
+        short: None,
+        arg: Arg::File,
+        placeholder: "<FILE>",
+        help: &[
+            "Cache for --incremental (default:",
+            "$XDG_CACHE_HOME/pmi/cache.csv)",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "jobs",
+        short: Some('j'),
+        arg: Arg::Number,
+        placeholder: "<N>",
+        help: &["Number of parallel threads (default: auto-detect CPU cores)"],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "verbose",
+        short: Some('v'),
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &["Show detailed processing information"],
+        commands: REPORTING,
+    },
+    OptionSpec {
+        long: "quiet",
+        short: Some('q'),
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &["Suppress all output except errors"],
+        commands: REPORTING,
+    },
+    OptionSpec {
+        long: "dry-run",
+        short: Some('n'),
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &["Show what would be done without making changes"],
+        commands: &[Strip, Restore, Cleanup],
+    },
+    OptionSpec {
+        long: "verify",
+        short: None,
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &[
+            "Re-parse cleaned output and only write it if it is",
+            "structurally intact and metadata-free",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "prove-lossless",
+        short: None,
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &[
+            "Decode every frame before and after stripping and",
+            "fail unless the pixels are identical (PNG, GIF, JPEG)",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "report-format",
+        short: None,
+        arg: Arg::Choice(&["json", "ndjson", "csv", "sarif", "junit"]),
+        placeholder: "<FORMAT>",
+        help: &[
+            "Write a per-file report: json, ndjson, csv,",
+            "sarif or junit",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "report-file",
+        short: None,
+        arg: Arg::File,
+        placeholder: "<FILE>",
+        help: &[
+            "Write the report to FILE instead of stdout (the",
+            "format is inferred from the extension if not given)",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "min-risk",
+        short: None,
+        arg: Arg::Choice(&["low", "medium", "high", "critical"]),
+        placeholder: "<LEVEL>",
+        help: &[
+            "Only process files whose most sensitive metadata is",
+            "at least LEVEL: low, medium, high or critical",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "gps",
+        short: None,
+        arg: Arg::Choice(&["remove", "coarsen="]),
+        placeholder: "<MODE>",
+        help: &[
+            "remove (default), or coarsen=<KM> to keep only the",
+            "position rounded to a KM grid (not GIF)",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "dates",
+        short: None,
+        arg: Arg::Choice(&["remove", "keep", "keep-date-only", "shift=", "fixed="]),
+        placeholder: "<MODE>",
+        help: &[
+            "remove (default), keep, keep-date-only,",
+            "shift=<DURATION> (e.g. -2h30m) or",
+            "fixed=<YYYY-MM-DD[THH:MM[:SS]]> to keep or rewrite",
+            "capture times instead (not GIF)",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "stamp",
+        short: None,
+        arg: Arg::Choice(&["copyright=", "artist=", "license-url="]),
+        placeholder: "<FIELDS>",
+        help: &[
+            "Write copyright=<TEXT>,artist=<NAME>,license-url=<URL>",
+            "into each cleaned image (not GIF)",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "icc",
+        short: None,
+        arg: Arg::Choice(&["auto", "keep", "strip"]),
+        placeholder: "<MODE>",
+        help: &[
+            "Colour profiles: auto (default; keep in PNG, WebP",
+            "and TIFF), keep or strip",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "keep-orientation",
+        short: None,
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &["Keep the EXIF orientation tag (not GIF)"],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "allowlist",
+        short: None,
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &[
+            "Keep only what each format needs to show the",
+            "image, dropping unknown blocks as well",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "preset",
+        short: None,
+        arg: Arg::Preset,
+        placeholder: "<NAME>",
+        help: &[
+            "Start from a preset: social, journalist, archive,",
+            "forensic-safe, or a [profile.NAME] table in",
+            "a config file",
+        ],
+        commands: &[Strip],
+    },
+    OptionSpec {
+        long: "print-config",
+        short: None,
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &[
+            "Print the effective settings and where each one",
+            "came from, then exit",
+        ],
+        commands: REPORTING,
+    },
+    OptionSpec {
+        long: "staged",
+        short: None,
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &["git-check: check the index (the default)"],
+        commands: &[GitCheck],
+    },
+    OptionSpec {
+        long: "name-only",
+        short: None,
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &["git-check: print only paths that need cleaning"],
+        commands: &[GitCheck],
+    },
+    OptionSpec {
+        long: "legacy",
+        short: None,
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &[
+            "cleanup: also remove <name>.pmi_tmp files as earlier",
+            "versions named them, which may not be pmi's",
+        ],
+        commands: &[Cleanup],
+    },
+    OptionSpec {
+        long: "help",
+        short: Some('h'),
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &["Print this help message"],
+        commands: ALL,
+    },
+    OptionSpec {
+        long: "version",
+        short: Some('V'),
+        arg: Arg::Flag,
+        placeholder: "",
+        help: &["Print version information"],
+        commands: ALL,
+    },
+];
+
+/// Column where descriptions start in help text.
+const DESCRIPTION_COLUMN: usize = 30;
+
+/// Find an option by its long name.
+pub fn find_long(long: &str) -> Option<&'static OptionSpec> {
+    OPTIONS.iter().find(|spec| spec.long == long)
+}
+
+/// Find an option by its single-letter name.
+pub fn find_short(short: char) -> Option<&'static OptionSpec> {
+    OPTIONS.iter().find(|spec| spec.short == Some(short))
+}
+
+/// Find a subcommand by name.
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.command.name() == name)
+}
+
+/// Get the registry entry for a command.
+pub fn command_spec(command: Command) -> &'static CommandSpec {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.command == command)
+        .expect("every command is registered")
+}
+
+/// Get the options a command accepts.
+pub fn options_for(command: Command) -> impl Iterator<Item = &'static OptionSpec> {
+    OPTIONS
+        .iter()
+        .filter(move |spec| spec.commands.contains(&command))
+}
+
+/// Format an indented term and its description, wrapping the term onto its
+/// own line when it reaches the description column.
+fn describe(term: &str, lines: &[&str], column: usize) -> String {
+    let mut text = format!("    {}", term);
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 || text.len() + 2 > column {
+            text.push('\n');
+            text.push_str(&" ".repeat(column));
+        } else {
+            text.push_str(&" ".repeat(column - text.len()));
+        }
+        text.push_str(line);
+    }
+    text
+}
+
+/// Format the usage lines for every command.
+pub fn usage_lines() -> String {
+    COMMANDS
+        .iter()
+        .map(|spec| match spec.command {
+            Strip => format!("    {} [strip] {}", NAME, spec.usage),
+            command => format!("    {} {} {}", NAME, command.name(), spec.usage),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Format the list of commands.
+pub fn command_lines() -> String {
+    COMMANDS
+        .iter()
+        .map(|spec| describe(spec.command.name(), spec.about, 18))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Format the help for a list of options.
+pub fn option_lines<'a>(options: impl Iterator<Item = &'a OptionSpec>) -> String {
+    options
+        .map(|spec| describe(&spec.synopsis(), spec.help, DESCRIPTION_COLUMN))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Generate the help for one command.
+pub fn command_help(command: Command) -> String {
+    let spec = command_spec(command);
+    let arguments: Vec<String> = spec
+        .arguments
+        .iter()
+        .map(|(name, about)| describe(name, &[about], 18))
+        .collect();
+    format!(
+        "{name} {command} - {about}\n\nUSAGE:\n    {name} {command} {usage}\n\nARGUMENTS:\n{arguments}\n\nOPTIONS:\n{options}\n",
+        name = NAME,
+        command = command.name(),
+        about = spec.about.join(" "),
+        usage = spec.usage,
+        arguments = arguments.join("\n"),
+        options = option_lines(options_for(command)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_are_unique() {
+        for (i, spec) in OPTIONS.iter().enumerate() {
+            assert!(
+                OPTIONS[i + 1..].iter().all(|o| o.long != spec.long),
+                "{}",
+                spec.long
+            );
+            if let Some(short) = spec.short {
+                assert_eq!(find_short(short), Some(spec));
+            }
+        }
+        for spec in COMMANDS {
+            assert_eq!(find_command(spec.command.name()), Some(spec));
+        }
+    }
+
+    #[test]
+    fn test_option_lines_layout() {
+        let text = option_lines(
+            ["in-place", "verify", "report-format"]
+                .iter()
+                .map(|long| find_long(long).unwrap()),
+        );
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "    -i, --in-place            Modify files in place (default: create *_clean suffix)",
+                "        --verify              Re-parse cleaned output and only write it if it is",
+                "                              structurally intact and metadata-free",
+                "        --report-format <FORMAT>",
+                "                              Write a per-file report: json, ndjson, csv,",
+                "                              sarif or junit",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_help_lists_only_its_options() {
+        let help = command_help(Command::GitCheck);
+        assert!(help.starts_with("pmi git-check - Like check"));
+        assert!(help.contains("--name-only"));
+        assert!(!help.contains("--output-dir"));
+
+        let help = command_help(Command::Check);
+        assert!(help.contains("--recursive"));
+        assert!(!help.contains("--gps"));
+    }
+}
@@ -0,0 +1,428 @@
+//! INFLATE decompression (RFC 1950/1951).
+//!
+//! A small, canonical-Huffman decoder in the style of zlib's `puff`. It trades
+//! speed for simplicity and is only used to read PNG image data for pixel
+//! comparison.
+
+use super::DecodeResult;
+
+/// Maximum bits in a Huffman code.
+const MAX_BITS: usize = 15;
+
+/// Base lengths for length codes 257..285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+
+/// Extra bits for length codes 257..285.
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Base distances for distance codes 0..29.
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+
+/// Extra bits for distance codes 0..29.
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Order of code length code lengths in a dynamic block header.
+const CLEN_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// LSB-first bit reader.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit_buf: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            bit_buf: 0,
+            bit_count: 0,
+        }
+    }
+
+    /// Read `need` bits (at most 16).
+    fn bits(&mut self, need: u32) -> DecodeResult<u32> {
+        while self.bit_count < need {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or("Unexpected end of deflate data")?;
+            self.pos += 1;
+            self.bit_buf |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let value = self.bit_buf & ((1u32 << need) - 1);
+        self.bit_buf >>= need;
+        self.bit_count -= need;
+        Ok(value)
+    }
+
+    /// Discard bits up to the next byte boundary.
+    fn align(&mut self) {
+        self.bit_buf = 0;
+        self.bit_count = 0;
+    }
+}
+
+/// Canonical Huffman decoding table.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Build a table from code lengths. Incomplete codes are allowed.
+    fn new(lengths: &[u8]) -> DecodeResult<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+
+        // Check for an over-subscribed code.
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left <<= 1;
+            left -= count as i32;
+            if left < 0 {
+                return Err("Over-subscribed Huffman code");
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    /// Decode one symbol.
+    fn decode(&self, reader: &mut BitReader<'_>) -> DecodeResult<u16> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for len in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+
+        Err("Invalid Huffman code")
+    }
+}
+
+/// Decompress a raw DEFLATE stream.
+///
+/// Fails if the output would exceed `max_output` bytes.
+pub fn inflate(data: &[u8], max_output: usize) -> DecodeResult<Vec<u8>> {
+    let mut reader = BitReader::new(data);
+    let mut output = Vec::new();
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => stored_block(&mut reader, &mut output, max_output)?,
+            1 => {
+                let (lit, dist) = fixed_tables()?;
+                codes(&mut reader, &mut output, &lit, &dist, max_output)?;
+            }
+            2 => {
+                let (lit, dist) = dynamic_tables(&mut reader)?;
+                codes(&mut reader, &mut output, &lit, &dist, max_output)?;
+            }
+            _ => return Err("Invalid deflate block type"),
+        }
+        if last {
+            break;
+        }
+    }
+
+    Ok(output)
+}
+
+/// Decompress a zlib stream (RFC 1950), checking the header and Adler-32.
+pub fn zlib_decompress(data: &[u8], max_output: usize) -> DecodeResult<Vec<u8>> {
+    if data.len() < 6 {
+        return Err("zlib stream too short");
+    }
+
+    let cmf = data[0];
+    let flg = data[1];
+    if cmf & 0x0F != 8 || cmf >> 4 > 7 {
+        return Err("Unsupported zlib compression method");
+    }
+    if ((cmf as u16) << 8 | flg as u16) % 31 != 0 {
+        return Err("Invalid zlib header check");
+    }
+    if flg & 0x20 != 0 {
+        return Err("zlib preset dictionary not supported");
+    }
+
+    let output = inflate(&data[2..], max_output)?;
+
+    // The Adler-32 checksum follows the deflate data; locate it from the end.
+    let tail = &data[data.len() - 4..];
+    let expected = u32::from_be_bytes([tail[0], tail[1], tail[2], tail[3]]);
+    if adler32(&output) != expected {
+        return Err("zlib Adler-32 mismatch");
+    }
+
+    Ok(output)
+}
+
+/// Compute the Adler-32 checksum.
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    // 5552 is the largest block that cannot overflow before the modulo.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+/// Copy a stored (uncompressed) block.
+fn stored_block(
+    reader: &mut BitReader<'_>,
+    output: &mut Vec<u8>,
+    max_output: usize,
+) -> DecodeResult<()> {
+    reader.align();
+    let pos = reader.pos;
+    let header = reader
+        .data
+        .get(pos..pos + 4)
+        .ok_or("Truncated stored block header")?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    let nlen = u16::from_le_bytes([header[2], header[3]]);
+    if len != !nlen {
+        return Err("Stored block length mismatch");
+    }
+
+    let start = pos + 4;
+    let block = reader
+        .data
+        .get(start..start + len as usize)
+        .ok_or("Truncated stored block")?;
+    if output.len() + block.len() > max_output {
+        return Err("Decompressed data exceeds expected size");
+    }
+    output.extend_from_slice(block);
+    reader.pos = start + len as usize;
+    Ok(())
+}
+
+/// Build the fixed literal/length and distance tables.
+fn fixed_tables() -> DecodeResult<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5u8; 30])?))
+}
+
+/// Read the dynamic Huffman tables from a block header.
+fn dynamic_tables(reader: &mut BitReader<'_>) -> DecodeResult<(Huffman, Huffman)> {
+    let nlen = reader.bits(5)? as usize + 257;
+    let ndist = reader.bits(5)? as usize + 1;
+    let ncode = reader.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err("Too many length or distance codes");
+    }
+
+    let mut clen_lengths = [0u8; 19];
+    for &index in CLEN_ORDER.iter().take(ncode) {
+        clen_lengths[index] = reader.bits(3)? as u8;
+    }
+    let clen = Huffman::new(&clen_lengths)?;
+
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut index = 0;
+    while index < nlen + ndist {
+        let symbol = clen.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => {
+                lengths[index] = symbol as u8;
+                index += 1;
+                continue;
+            }
+            16 => {
+                let previous = *lengths
+                    .get(index.wrapping_sub(1))
+                    .ok_or("Repeat with no previous length")?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if index + repeat > nlen + ndist {
+            return Err("Code length repeat overflows");
+        }
+        lengths[index..index + repeat].fill(value);
+        index += repeat;
+    }
+
+    if lengths[256] == 0 {
+        return Err("Missing end-of-block code");
+    }
+
+    Ok((
+        Huffman::new(&lengths[..nlen])?,
+        Huffman::new(&lengths[nlen..])?,
+    ))
+}
+
+/// Decode a block of Huffman-coded literals and back-references.
+fn codes(
+    reader: &mut BitReader<'_>,
+    output: &mut Vec<u8>,
+    lit: &Huffman,
+    dist: &Huffman,
+    max_output: usize,
+) -> DecodeResult<()> {
+    loop {
+        let symbol = lit.decode(reader)? as usize;
+        match symbol {
+            0..=255 => {
+                if output.len() >= max_output {
+                    return Err("Decompressed data exceeds expected size");
+                }
+                output.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            _ => {
+                let code = symbol - 257;
+                if code >= LENGTH_BASE.len() {
+                    return Err("Invalid length code");
+                }
+                let length =
+                    LENGTH_BASE[code] as usize + reader.bits(LENGTH_EXTRA[code] as u32)? as usize;
+
+                let dcode = dist.decode(reader)? as usize;
+                if dcode >= DIST_BASE.len() {
+                    return Err("Invalid distance code");
+                }
+                let distance =
+                    DIST_BASE[dcode] as usize + reader.bits(DIST_EXTRA[dcode] as u32)? as usize;
+
+                if distance > output.len() {
+                    return Err("Distance too far back");
+                }
+                if output.len() + length > max_output {
+                    return Err("Decompressed data exceeds expected size");
+                }
+                let start = output.len() - distance;
+                for i in 0..length {
+                    let byte = output[start + i];
+                    output.push(byte);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    }
+
+    #[test]
+    fn test_inflate_stored() {
+        // Final stored block containing "abc".
+        let data = [0x01, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c'];
+        assert_eq!(inflate(&data, 100).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn test_inflate_fixed() {
+        // zlib.compress(b"hello hello hello", 9)[2:-4]
+        let data = [0xCB, 0x48, 0xCD, 0xC9, 0xC9, 0x57, 0xC8, 0x40, 0x90, 0x00];
+        assert_eq!(inflate(&data, 100).unwrap(), b"hello hello hello");
+    }
+
+    #[test]
+    fn test_zlib_dynamic_block() {
+        // zlib.compress(bytes((i * i + i // 7) % 23 + 65 for i in range(200)), 9)
+        let data = [
+            0x78, 0xDA, 0x8D, 0xCE, 0x81, 0x01, 0x44, 0x11, 0x08, 0x00, 0xD0, 0xD9, 0x42, 0x8A,
+            0x88, 0xA2, 0x6F, 0xFF, 0x4D, 0x6E, 0x85, 0x7B, 0x13, 0x3C, 0x48, 0xD8, 0x2D, 0x2B,
+            0x5E, 0xED, 0xCC, 0x7D, 0x45, 0xDD, 0x78, 0x06, 0x63, 0x41, 0x9E, 0x97, 0x9C, 0x9F,
+            0x4D, 0x19, 0xEA, 0xD0, 0x0E, 0xBF, 0x2D, 0x4C, 0x2C, 0x06, 0x3D, 0x24, 0x5F, 0x5B,
+            0xCB, 0xA2, 0x8C, 0x37, 0xF2, 0x51, 0xE9, 0x63, 0xDD, 0x32, 0x41, 0x09, 0xAE, 0xFB,
+            0x05, 0xDE, 0x79, 0xD5, 0xCF, 0x96, 0x2E, 0x7F, 0xB4, 0xD1, 0x7B, 0x79, 0x11, 0x80,
+            0x72, 0xAA, 0xB7, 0x1C, 0x6E, 0x1E, 0x59, 0x2E, 0xC7, 0xA4, 0x9C, 0x52, 0x61, 0xFD,
+            0xDA, 0x37, 0x2B, 0xC4, 0x8D, 0x44, 0xFA, 0x04, 0xB6, 0x10, 0x22, 0x89, 0xE5, 0x99,
+            0x76, 0x2B, 0x00, 0xFF, 0x05, 0x7F, 0x3A, 0x2A, 0x3B, 0x09,
+        ];
+        let expected: Vec<u8> = (0..200u32)
+            .map(|i| ((i * i + i / 7) % 23 + 65) as u8)
+            .collect();
+        assert_eq!(zlib_decompress(&data, 200).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_zlib_decompress_png_scanline() {
+        // Filter byte 0 followed by a single zero sample.
+        let data = [0x78, 0x9C, 0x63, 0x60, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01];
+        assert_eq!(zlib_decompress(&data, 2).unwrap(), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_zlib_rejects_bad_checksum() {
+        let data = [0x78, 0x9C, 0x63, 0x60, 0x00, 0x00, 0x00, 0x02, 0x00, 0x02];
+        assert!(zlib_decompress(&data, 2).is_err());
+    }
+
+    #[test]
+    fn test_inflate_output_limit() {
+        let data = [0x01, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c'];
+        assert!(inflate(&data, 2).is_err());
+    }
+
+    #[test]
+    fn test_inflate_truncated() {
+        assert!(inflate(&[0xCB, 0x48], 100).is_err());
+    }
+}
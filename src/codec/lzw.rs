@@ -0,0 +1,164 @@
+//! GIF LZW decompression.
+//!
+//! Decodes the variable-length-code LZW stream used for GIF image data into
+//! palette indices.
+
+use super::DecodeResult;
+
+/// Maximum number of dictionary entries (12-bit codes).
+const MAX_CODES: usize = 4096;
+
+/// Decode GIF LZW data into palette indices.
+///
+/// `min_code_size` is the value stored before the image data sub-blocks and
+/// `data` is the concatenated sub-block payload. Decoding stops at the end
+/// code, at the end of the data, or once `max_pixels` indices are produced.
+pub fn decode(min_code_size: u8, data: &[u8], max_pixels: usize) -> DecodeResult<Vec<u8>> {
+    if !(1..=11).contains(&min_code_size) {
+        return Err("Invalid LZW minimum code size");
+    }
+
+    let clear_code = 1usize << min_code_size;
+    let end_code = clear_code + 1;
+
+    // Dictionary: each entry is (prefix code, suffix byte, first byte, length).
+    let mut prefix = vec![0u16; MAX_CODES];
+    let mut suffix = vec![0u8; MAX_CODES];
+    let mut first = vec![0u8; MAX_CODES];
+    let mut length = vec![0u16; MAX_CODES];
+    for code in 0..clear_code {
+        suffix[code] = code as u8;
+        first[code] = code as u8;
+        length[code] = 1;
+    }
+
+    let mut code_size = min_code_size as u32 + 1;
+    let mut next_code = end_code + 1;
+    let mut previous: Option<usize> = None;
+
+    // Reserve no more than the data can expand to: each code emits at most
+    // one full dictionary string. `max_pixels` comes from the image header
+    // and only limits the output.
+    let codes = data.len() * 8 / code_size as usize;
+    let mut output = Vec::with_capacity(max_pixels.min(codes.saturating_mul(MAX_CODES)));
+    let mut bit_buf: u32 = 0;
+    let mut bit_count: u32 = 0;
+    let mut bytes = data.iter();
+
+    while output.len() < max_pixels {
+        // Read the next code, LSB first.
+        while bit_count < code_size {
+            match bytes.next() {
+                Some(&byte) => {
+                    bit_buf |= (byte as u32) << bit_count;
+                    bit_count += 8;
+                }
+                None => return Ok(output),
+            }
+        }
+        let code = (bit_buf & ((1 << code_size) - 1)) as usize;
+        bit_buf >>= code_size;
+        bit_count -= code_size;
+
+        if code == clear_code {
+            code_size = min_code_size as u32 + 1;
+            next_code = end_code + 1;
+            previous = None;
+            continue;
+        }
+        if code == end_code {
+            break;
+        }
+
+        let Some(prev) = previous else {
+            if code >= clear_code {
+                return Err("First LZW code is not a literal");
+            }
+            output.push(code as u8);
+            previous = Some(code);
+            continue;
+        };
+
+        // Determine the first byte of the string to emit.
+        let lead = if code < next_code {
+            first[code]
+        } else if code == next_code {
+            first[prev]
+        } else {
+            return Err("LZW code out of range");
+        };
+
+        // Add a new dictionary entry: previous string + lead byte.
+        if next_code < MAX_CODES {
+            prefix[next_code] = prev as u16;
+            suffix[next_code] = lead;
+            first[next_code] = first[prev];
+            length[next_code] = length[prev] + 1;
+            next_code += 1;
+            if next_code == (1 << code_size) && code_size < 12 {
+                code_size += 1;
+            }
+        }
+
+        // Emit the string for `code` by walking its prefix chain backwards.
+        let len = length[code] as usize;
+        let start = output.len();
+        output.resize(start + len, 0);
+        let mut walk = code;
+        for i in (0..len).rev() {
+            output[start + i] = suffix[walk];
+            walk = prefix[walk] as usize;
+        }
+
+        previous = Some(code);
+    }
+
+    output.truncate(max_pixels);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_single_pixel() {
+        // Clear, 0, End with 3-bit codes.
+        assert_eq!(decode(2, &[0x44, 0x01], 1).unwrap(), vec![0]);
+    }
+
+    #[test]
+    fn test_decode_repeated_run() {
+        // Clear, 1, then code 6 twice: the first use of 6 is the
+        // not-yet-defined "previous + first byte" case (1,1), the second
+        // a dictionary hit. The final end code fits in the padding bits.
+        let codes = [4u32, 1, 6, 6, 5];
+        let mut bits: u64 = 0;
+        let mut count: u32 = 0;
+        for code in codes {
+            bits |= (code as u64) << count;
+            count += 3;
+        }
+        let bytes: Vec<u8> = (0..count.div_ceil(8))
+            .map(|i| (bits >> (i * 8)) as u8)
+            .collect();
+        assert_eq!(decode(2, &bytes, 10).unwrap(), vec![1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_decode_stops_at_max_pixels() {
+        assert_eq!(decode(2, &[0x44, 0x01], 0).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_decode_invalid_code() {
+        // Clear followed by an undefined code (7).
+        assert!(decode(2, &[0x3C], 10).is_err());
+    }
+
+    #[test]
+    fn test_decode_invalid_min_code_size() {
+        assert!(decode(0, &[0x00], 1).is_err());
+        assert!(decode(12, &[0x00], 1).is_err());
+    }
+}
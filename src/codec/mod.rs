@@ -0,0 +1,25 @@
+//! Pixel data decoders.
+//!
+//! Provides INFLATE (for PNG) and LZW (for GIF) decoders without external
+//! dependencies. These are used to prove that stripping leaves pixels intact;
+//! PMI never re-encodes image data.
+
+pub mod inflate;
+pub mod lzw;
+
+/// Result type for decoders, carrying a static description of the failure.
+pub type DecodeResult<T> = std::result::Result<T, &'static str>;
+
+/// A decoded image frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Frame width in pixels.
+    pub width: u32,
+    /// Frame height in pixels.
+    pub height: u32,
+    /// Format-specific description of how to interpret the pixels
+    /// (bit depth, colour type, palette, transparency, placement).
+    pub header: Vec<u8>,
+    /// Decoded pixel samples (or palette indices).
+    pub pixels: Vec<u8>,
+}
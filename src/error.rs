@@ -32,6 +32,16 @@ pub enum Error {
     PermissionDenied { path: PathBuf },
     /// Output file already exists and --force not specified.
     OutputExists { path: PathBuf },
+    /// Cleaned output failed post-strip verification.
+    VerificationFailed { path: PathBuf, reason: String },
+    /// A git command failed or produced unexpected output.
+    Git { command: String, reason: String },
+    /// Invalid setting in a configuration file.
+    Config {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
 }
 
 impl fmt::Display for Error {
@@ -73,6 +83,26 @@ impl fmt::Display for Error {
                     path.display()
                 )
             }
+            Error::VerificationFailed { path, reason } => {
+                write!(
+                    f,
+                    "Verification failed for '{}': {}",
+                    path.display(),
+                    reason
+                )
+            }
+            Error::Git { command, reason } => {
+                write!(f, "git {} failed: {}", command, reason)
+            }
+            Error::Config { path, line, reason } => {
+                write!(
+                    f,
+                    "Invalid config '{}' line {}: {}",
+                    path.display(),
+                    line,
+                    reason
+                )
+            }
         }
     }
 }
@@ -124,9 +154,52 @@ impl Error {
             detected: detected.map(String::from),
         }
     }
+
+    /// Create a verification failure error.
+    pub fn verification_failed(path: impl Into<PathBuf>, reason: impl Into<String>) -> Self {
+        Error::VerificationFailed {
+            path: path.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Get a stable, machine-readable name for the kind of error.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Io { .. } => "io",
+            Error::InvalidImage { .. } => "invalid-image",
+            Error::UnsupportedFormat { .. } => "unsupported-format",
+            Error::InvalidArgument { .. } => "invalid-argument",
+            Error::MissingArgument { .. } => "missing-argument",
+            Error::NotFound { .. } => "not-found",
+            Error::PermissionDenied { .. } => "permission-denied",
+            Error::OutputExists { .. } => "output-exists",
+            Error::VerificationFailed { .. } => "verification-failed",
+            Error::Git { .. } => "git",
+            Error::Config { .. } => "config",
+        }
+    }
+
+    /// Create a git command error.
+    pub fn git(command: impl Into<String>, reason: impl Into<String>) -> Self {
+        Error::Git {
+            command: command.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Create a configuration file error.
+    pub fn config(path: impl Into<PathBuf>, line: usize, reason: impl Into<String>) -> Self {
+        Error::Config {
+            path: path.into(),
+            line,
+            reason: reason.into(),
+        }
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::io_other_error)]
 mod tests {
     use super::*;
 
@@ -162,6 +235,32 @@ mod tests {
         assert!(err.to_string().contains("BMP"));
     }
 
+    #[test]
+    fn test_verification_failed_display() {
+        let err = Error::verification_failed("/test/image.png", "CRC mismatch in IDAT chunk");
+        assert!(err.to_string().contains("Verification failed"));
+        assert!(err.to_string().contains("CRC mismatch"));
+    }
+
+    #[test]
+    fn test_git_display() {
+        let err = Error::git("cat-file", "fatal: not a git repository");
+        assert_eq!(
+            err.to_string(),
+            "git cat-file failed: fatal: not a git repository"
+        );
+    }
+
+    #[test]
+    fn test_config_display() {
+        let err = Error::config("/home/a/.config/pmi/config", 3, "Expected a value");
+        assert_eq!(
+            err.to_string(),
+            "Invalid config '/home/a/.config/pmi/config' line 3: Expected a value"
+        );
+        assert_eq!(err.kind(), "config");
+    }
+
     #[test]
     fn test_from_io_error() {
         let io_err = io::Error::new(io::ErrorKind::Other, "test");
@@ -182,4 +281,20 @@ mod tests {
         let err = Error::io_with_path(io_err, "/test/path");
         assert!(matches!(err, Error::PermissionDenied { .. }));
     }
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(Error::git("log", "bad").kind(), "git");
+        assert_eq!(
+            Error::io_with_path(io::Error::other("x"), "/a").kind(),
+            "io"
+        );
+        assert_eq!(
+            Error::OutputExists {
+                path: PathBuf::from("a.jpg")
+            }
+            .kind(),
+            "output-exists"
+        );
+    }
 }
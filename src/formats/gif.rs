@@ -16,7 +16,11 @@
 //! - Plain Text Extension (0x21 0x01) - rarely used, but part of image
 //! - Image Descriptor and data (0x2C)
 
+use crate::codec::{Frame, lzw};
 use crate::error::{Error, Result};
+use crate::formats::StripReport;
+use crate::metadata::{self, Category, KeepReason, KeptItem, MetadataItem, xmp};
+use std::borrow::Cow;
 use std::path::Path;
 
 /// GIF header signatures.
@@ -96,8 +100,30 @@ fn is_netscape_extension(data: &[u8], pos: usize) -> bool {
     &data[pos + 1..pos + 12] == NETSCAPE_ID
 }
 
+/// Check if a report kind names a block that allowlist mode keeps: every
+/// block `strip` keeps except plain text, which is not image data or
+/// animation.
+pub fn is_listed(kind: &str) -> bool {
+    kind != "Plain Text Extension"
+}
+
 /// Strip metadata from GIF data.
-pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
+///
+/// Returns the input itself when nothing was dropped from it.
+pub fn strip<'a>(data: &'a [u8], path: &Path) -> Result<Cow<'a, [u8]>> {
+    retain_blocks(data, path, true)
+}
+
+/// Remove plain text extensions too.
+pub fn remove_unlisted(data: &[u8], path: &Path) -> Result<Vec<u8>> {
+    retain_blocks(data, path, false).map(Cow::into_owned)
+}
+
+/// Copy the image, animation and, if `keep_plain_text`, plain text blocks of
+/// GIF data.
+///
+/// Returns the input itself when nothing was dropped from it.
+fn retain_blocks<'a>(data: &'a [u8], path: &Path, keep_plain_text: bool) -> Result<Cow<'a, [u8]>> {
     // Validate minimum size.
     if data.len() < 13 {
         return Err(Error::invalid_image(
@@ -194,7 +220,7 @@ pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
                         output.extend_from_slice(&blocks);
                         pos = new_pos;
                     }
-                    extensions::PLAIN_TEXT => {
+                    extensions::PLAIN_TEXT if keep_plain_text => {
                         // Keep plain text extension.
                         output.extend_from_slice(&data[pos..pos + 2]);
                         pos += 2;
@@ -267,9 +293,331 @@ pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
     // Ensure trailer is present.
     if !output.ends_with(&[blocks::TRAILER]) {
         output.push(blocks::TRAILER);
+    } else if output.len() == data.len() {
+        // Every byte written was copied in order from the input, so an
+        // output of the same length is the input.
+        return Ok(Cow::Borrowed(data));
     }
 
-    Ok(output)
+    Ok(Cow::Owned(output))
+}
+
+/// Get the size in bytes of a color table from a packed field.
+fn color_table_size(packed: u8) -> usize {
+    if packed & 0x80 != 0 {
+        3 * (1 << ((packed & 0x07) + 1))
+    } else {
+        0
+    }
+}
+
+/// Verify that GIF data is structurally sound and free of metadata blocks.
+///
+/// Checks that every sub-block chain is terminated, that only image data,
+/// graphics control, plain text and NETSCAPE2.0 blocks remain, and that the
+/// file ends exactly at the trailer.
+pub fn verify(data: &[u8], path: &Path) -> Result<()> {
+    let fail = |reason: String| Error::verification_failed(path, reason);
+
+    if data.len() < 13 || (!data.starts_with(GIF87A) && !data.starts_with(GIF89A)) {
+        return Err(fail(String::from("Invalid GIF header")));
+    }
+
+    let mut pos = 13 + color_table_size(data[10]);
+    if pos > data.len() {
+        return Err(fail(String::from("Truncated Global Color Table")));
+    }
+
+    loop {
+        if pos >= data.len() {
+            return Err(fail(String::from("Missing GIF trailer")));
+        }
+
+        match data[pos] {
+            blocks::EXTENSION => {
+                if pos + 2 > data.len() {
+                    return Err(fail(String::from("Truncated extension block")));
+                }
+                let ext_type = data[pos + 1];
+                let allowed = match ext_type {
+                    extensions::GRAPHICS_CONTROL | extensions::PLAIN_TEXT => true,
+                    extensions::APPLICATION => is_netscape_extension(data, pos + 2),
+                    _ => false,
+                };
+                if !allowed {
+                    return Err(fail(format!(
+                        "Metadata extension 0x{:02X} remains",
+                        ext_type
+                    )));
+                }
+                pos = skip_sub_blocks(data, pos + 2)
+                    .ok_or_else(|| fail(format!("Unterminated extension 0x{:02X}", ext_type)))?;
+            }
+            blocks::IMAGE => {
+                if pos + 10 > data.len() {
+                    return Err(fail(String::from("Truncated image descriptor")));
+                }
+                pos += 10 + color_table_size(data[pos + 9]);
+                // LZW minimum code size.
+                pos += 1;
+                if pos > data.len() {
+                    return Err(fail(String::from("Truncated image data")));
+                }
+                pos = skip_sub_blocks(data, pos)
+                    .ok_or_else(|| fail(String::from("Unterminated image data")))?;
+            }
+            blocks::TRAILER => {
+                pos += 1;
+                if pos != data.len() {
+                    return Err(fail(format!(
+                        "{} bytes of trailing data after trailer",
+                        data.len() - pos
+                    )));
+                }
+                return Ok(());
+            }
+            other => {
+                return Err(fail(format!(
+                    "Unexpected block 0x{:02X} at position {}",
+                    other, pos
+                )));
+            }
+        }
+    }
+}
+
+/// Concatenate the payload of a sub-block chain, returning it with the
+/// position after the terminator.
+fn sub_block_payload(data: &[u8], mut pos: usize) -> Option<(Vec<u8>, usize)> {
+    let mut payload = Vec::new();
+
+    loop {
+        let block_size = *data.get(pos)? as usize;
+        pos += 1;
+        if block_size == 0 {
+            return Some((payload, pos));
+        }
+        payload.extend_from_slice(data.get(pos..pos + block_size)?);
+        pos += block_size;
+    }
+}
+
+/// Decode every image in a GIF to palette indices.
+///
+/// Each frame header records the image descriptor, the colour table in
+/// effect and the preceding Graphics Control Extension, so palette, placement
+/// and timing changes are detected as well as index changes. Interlaced
+/// images keep their stored row order.
+pub fn decode_frames(data: &[u8], path: &Path) -> Result<Vec<Frame>> {
+    if data.len() < 13 || (!data.starts_with(GIF87A) && !data.starts_with(GIF89A)) {
+        return Err(Error::invalid_image(path, "Invalid GIF header"));
+    }
+
+    let global_end = 13 + color_table_size(data[10]);
+    let global_table = data
+        .get(13..global_end)
+        .ok_or_else(|| Error::invalid_image(path, "Truncated Global Color Table"))?;
+
+    let mut frames = Vec::new();
+    let mut control: Option<&[u8]> = None;
+    let mut pos = global_end;
+
+    while let Some(&block) = data.get(pos) {
+        match block {
+            blocks::EXTENSION => {
+                let ext_type = *data
+                    .get(pos + 1)
+                    .ok_or_else(|| Error::invalid_image(path, "Truncated extension block"))?;
+                if ext_type == extensions::GRAPHICS_CONTROL {
+                    let size = data.get(pos + 2).copied().unwrap_or(0) as usize;
+                    control = data.get(pos + 3..pos + 3 + size);
+                }
+                pos = skip_sub_blocks(data, pos + 2)
+                    .ok_or_else(|| Error::invalid_image(path, "Truncated extension block"))?;
+            }
+            blocks::IMAGE => {
+                let descriptor = data
+                    .get(pos + 1..pos + 10)
+                    .ok_or_else(|| Error::invalid_image(path, "Truncated image descriptor"))?;
+                let width = u16::from_le_bytes([descriptor[4], descriptor[5]]) as u32;
+                let height = u16::from_le_bytes([descriptor[6], descriptor[7]]) as u32;
+
+                let table_end = pos + 10 + color_table_size(descriptor[8]);
+                let table = if table_end > pos + 10 {
+                    data.get(pos + 10..table_end)
+                        .ok_or_else(|| Error::invalid_image(path, "Truncated Local Color Table"))?
+                } else {
+                    global_table
+                };
+
+                let min_code_size = *data
+                    .get(table_end)
+                    .ok_or_else(|| Error::invalid_image(path, "Truncated image data"))?;
+                let (compressed, next) = sub_block_payload(data, table_end + 1)
+                    .ok_or_else(|| Error::invalid_image(path, "Truncated image data"))?;
+
+                let pixels =
+                    lzw::decode(min_code_size, &compressed, width as usize * height as usize)
+                        .map_err(|reason| Error::invalid_image(path, reason))?;
+
+                let mut header = descriptor.to_vec();
+                header.extend_from_slice(table);
+                if let Some(gce) = control.take() {
+                    header.extend_from_slice(gce);
+                }
+
+                frames.push(Frame {
+                    width,
+                    height,
+                    header,
+                    pixels,
+                });
+                pos = next;
+            }
+            blocks::TRAILER => break,
+            other => {
+                return Err(Error::invalid_image(
+                    path,
+                    format!("Unknown block type 0x{:02X} at position {}", other, pos),
+                ));
+            }
+        }
+    }
+
+    Ok(frames)
+}
+
+/// Describe an application extension that `strip` would remove.
+///
+/// `pos` is the position of the extension introducer.
+fn application_item(data: &[u8], pos: usize, end: usize) -> MetadataItem {
+    let identifier = data
+        .get(pos + 3..pos + 14)
+        .filter(|_| data.get(pos + 2) == Some(&0x0B))
+        .unwrap_or_default();
+    let name = String::from_utf8_lossy(identifier).into_owned();
+    let kind = format!("Application Extension {}", name.trim_end());
+
+    match identifier {
+        // XMP is stored raw after the identifier rather than in sub-blocks.
+        b"XMP DataXMP" => MetadataItem::new(kind, pos..end, Category::Other)
+            .with_fields(xmp::fields(&data[pos + 14..end])),
+        id if id.starts_with(b"ICCRGBG1") => MetadataItem::new(kind, pos..end, Category::Colour),
+        _ => MetadataItem::new(kind, pos..end, metadata::classify_keyword(&name)),
+    }
+}
+
+/// Describe the blocks that `strip` removes and keeps.
+///
+/// Comments and application extensions other than NETSCAPE2.0 are removed;
+/// XMP packets are decoded. Data after the trailer is reported as removed.
+/// Fails on the same structural errors as `strip`.
+pub fn report(data: &[u8], path: &Path) -> Result<StripReport> {
+    if data.len() < 13 {
+        return Err(Error::invalid_image(
+            path,
+            "File too small to be a valid GIF",
+        ));
+    }
+    if !data.starts_with(GIF87A) && !data.starts_with(GIF89A) {
+        return Err(Error::invalid_image(path, "Invalid GIF header"));
+    }
+
+    let mut pos = 13 + color_table_size(data[10]);
+    if pos > data.len() {
+        return Err(Error::invalid_image(path, "Truncated Global Color Table"));
+    }
+
+    let mut report = StripReport::default();
+    report
+        .kept
+        .push(KeptItem::new("Header", 0..6, KeepReason::Structure));
+    report.kept.push(KeptItem::new(
+        "Logical Screen Descriptor",
+        6..pos,
+        KeepReason::Decoding,
+    ));
+
+    while let Some(&block) = data.get(pos) {
+        match block {
+            blocks::EXTENSION => {
+                let ext_type = *data
+                    .get(pos + 1)
+                    .ok_or_else(|| Error::invalid_image(path, "Truncated extension block"))?;
+                let end = skip_sub_blocks(data, pos + 2)
+                    .ok_or_else(|| Error::invalid_image(path, "Truncated extension block"))?;
+
+                match ext_type {
+                    extensions::GRAPHICS_CONTROL => report.kept.push(KeptItem::new(
+                        "Graphics Control Extension",
+                        pos..end,
+                        KeepReason::Animation,
+                    )),
+                    extensions::PLAIN_TEXT => report.kept.push(KeptItem::new(
+                        "Plain Text Extension",
+                        pos..end,
+                        KeepReason::ImageData,
+                    )),
+                    extensions::APPLICATION if is_netscape_extension(data, pos + 2) => {
+                        report.kept.push(KeptItem::new(
+                            "Application Extension NETSCAPE2.0",
+                            pos..end,
+                            KeepReason::Animation,
+                        ));
+                    }
+                    extensions::APPLICATION => {
+                        report.removed.push(application_item(data, pos, end));
+                    }
+                    extensions::COMMENT => {
+                        report.removed.push(MetadataItem::new(
+                            "Comment Extension",
+                            pos..end,
+                            Category::Other,
+                        ));
+                    }
+                    other => {
+                        report.removed.push(MetadataItem::new(
+                            format!("Extension 0x{:02X}", other),
+                            pos..end,
+                            Category::Other,
+                        ));
+                    }
+                }
+                pos = end;
+            }
+            blocks::IMAGE => {
+                let packed = *data
+                    .get(pos + 9)
+                    .ok_or_else(|| Error::invalid_image(path, "Truncated image descriptor"))?;
+                let table_end = pos + 10 + color_table_size(packed);
+                let end = skip_sub_blocks(data, table_end + 1)
+                    .ok_or_else(|| Error::invalid_image(path, "Truncated image data"))?;
+                report
+                    .kept
+                    .push(KeptItem::new("Image", pos..end, KeepReason::ImageData));
+                pos = end;
+            }
+            blocks::TRAILER => {
+                report.kept.push(KeptItem::new(
+                    "Trailer",
+                    pos..pos + 1,
+                    KeepReason::Structure,
+                ));
+                if pos + 1 < data.len() {
+                    report.removed.push(MetadataItem::new(
+                        "Trailing data",
+                        pos + 1..data.len(),
+                        Category::Other,
+                    ));
+                }
+                break;
+            }
+            // Stray bytes are skipped, as in `strip`.
+            _ => pos += 1,
+        }
+    }
+
+    Ok(report)
 }
 
 /// Create a minimal valid GIF for testing.
@@ -356,6 +704,28 @@ mod tests {
         assert!(has_image, "Image descriptor should be preserved");
     }
 
+    #[test]
+    fn test_verify_stripped_gif() {
+        let data = create_gif_with_comment();
+        let result = strip(&data, &test_path()).unwrap();
+        assert!(verify(&result, &test_path()).is_ok());
+    }
+
+    #[test]
+    fn test_verify_rejects_comment() {
+        let data = create_gif_with_comment();
+        let err = verify(&data, &test_path()).unwrap_err();
+        assert!(err.to_string().contains("0xFE"));
+    }
+
+    #[test]
+    fn test_verify_rejects_unterminated_image_data() {
+        let mut data = create_minimal_gif();
+        // Drop the block terminator and trailer.
+        data.truncate(data.len() - 2);
+        assert!(verify(&data, &test_path()).is_err());
+    }
+
     #[test]
     fn test_invalid_too_small() {
         let data = b"GIF89a";
@@ -402,4 +772,132 @@ mod tests {
         assert_eq!(blocks, vec![0x03, b'a', b'b', b'c', 0x00]);
         assert_eq!(end, data.len());
     }
+
+    #[test]
+    fn test_decode_minimal_gif() {
+        let frames = decode_frames(&create_minimal_gif(), &test_path()).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!((frames[0].width, frames[0].height), (1, 1));
+        assert_eq!(frames[0].pixels, vec![0]);
+    }
+
+    #[test]
+    fn test_decode_unaffected_by_strip() {
+        let data = create_gif_with_comment();
+        let stripped = strip(&data, &test_path()).unwrap();
+        assert_eq!(
+            decode_frames(&data, &test_path()).unwrap(),
+            decode_frames(&stripped, &test_path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decode_records_graphics_control() {
+        let plain = create_minimal_gif();
+        let mut with_gce = plain[..13].to_vec();
+        with_gce.extend_from_slice(&[0x21, 0xF9, 0x04, 0x00, 0x0A, 0x00, 0x00, 0x00]);
+        with_gce.extend_from_slice(&plain[13..]);
+
+        let plain = decode_frames(&plain, &test_path()).unwrap();
+        let with_gce = decode_frames(&with_gce, &test_path()).unwrap();
+        assert_eq!(plain[0].pixels, with_gce[0].pixels);
+        assert_ne!(plain[0].header, with_gce[0].header);
+    }
+
+    #[test]
+    fn test_decode_huge_declared_size() {
+        let mut data = create_minimal_gif();
+        // Declare a 65535x65535 screen and frame around a single pixel.
+        data[6..10].copy_from_slice(&[0xFF; 4]);
+        data[18..22].copy_from_slice(&[0xFF; 4]);
+        let frames = decode_frames(&data, &test_path()).unwrap();
+        assert_eq!(frames[0].pixels, vec![0]);
+        assert!(frames[0].pixels.capacity() < 1 << 20);
+    }
+
+    #[test]
+    fn test_decode_truncated_image_data() {
+        let data = create_minimal_gif();
+        // Cut inside the image data sub-block.
+        assert!(decode_frames(&data[..data.len() - 4], &test_path()).is_err());
+    }
+
+    #[test]
+    fn test_report_gif_with_comment() {
+        let data = create_gif_with_comment();
+        let items = report(&data, &test_path()).unwrap().removed;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].kind, "Comment Extension");
+        assert_eq!(
+            items[0].size(),
+            data.len() - strip(&data, &test_path()).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn test_report_xmp_application_extension() {
+        let mut data = create_minimal_gif();
+        let mut ext = vec![0x21, 0xFF, 0x0B];
+        ext.extend_from_slice(b"XMP DataXMP");
+        ext.extend_from_slice(b"<x dc:creator='a'/>");
+        // The "magic trailer" lets readers walk raw XMP as sub-blocks.
+        ext.push(0x01);
+        ext.extend((0..=0xFF).rev());
+        ext.push(0x00);
+        data.splice(13..13, ext);
+
+        let items = report(&data, &test_path()).unwrap().removed;
+        assert_eq!(items[0].kind, "Application Extension XMP DataXMP");
+        assert_eq!(items[0].categories(), vec![Category::Authorship]);
+        assert_eq!(data[items[0].range.end], blocks::IMAGE);
+    }
+
+    #[test]
+    fn test_report_keeps_netscape() {
+        let mut data = create_minimal_gif();
+        let mut ext = vec![0x21, 0xFF, 0x0B];
+        ext.extend_from_slice(NETSCAPE_ID);
+        ext.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+        data.splice(13..13, ext);
+
+        let report = report(&data, &test_path()).unwrap();
+        assert!(report.removed.is_empty());
+        let kept: Vec<(&str, KeepReason)> = report
+            .kept
+            .iter()
+            .map(|i| (i.kind.as_str(), i.reason))
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                ("Header", KeepReason::Structure),
+                ("Logical Screen Descriptor", KeepReason::Decoding),
+                ("Application Extension NETSCAPE2.0", KeepReason::Animation),
+                ("Image", KeepReason::ImageData),
+                ("Trailer", KeepReason::Structure),
+            ]
+        );
+        assert_eq!(report.kept.last().unwrap().range.end, data.len());
+    }
+
+    #[test]
+    fn test_remove_unlisted() {
+        let minimal = create_minimal_gif();
+        let trailer = minimal.len() - 1;
+        let mut data = minimal[..trailer].to_vec();
+        data.extend_from_slice(&[0x21, extensions::PLAIN_TEXT, 12]);
+        data.extend_from_slice(&[0; 12]);
+        data.extend_from_slice(&[2, b'h', b'i', 0]);
+        data.push(blocks::TRAILER);
+
+        // A plain text extension survives the default strip.
+        assert_eq!(strip(&data, &test_path()).unwrap(), &data[..]);
+        let kept = report(&data, &test_path()).unwrap().kept;
+        assert!(kept.iter().any(|item| !is_listed(&item.kind)));
+
+        let result = remove_unlisted(&data, &test_path()).unwrap();
+        assert_eq!(result, minimal);
+        let kept = report(&result, &test_path()).unwrap().kept;
+        assert!(kept.iter().all(|item| is_listed(&item.kind)));
+    }
 }
@@ -18,7 +18,11 @@
 //! - SOS (FF DA): Start of scan (and all image data)
 //! - EOI (FF D9): End of image
 
+use crate::digest::{Digest, Sha256};
 use crate::error::{Error, Result};
+use crate::formats::StripReport;
+use crate::metadata::{Category, KeepReason, KeptItem, MetadataItem, exif, iptc, xmp};
+use std::borrow::Cow;
 use std::path::Path;
 
 /// JPEG marker bytes.
@@ -39,6 +43,7 @@ mod markers {
     pub const SOF5: u8 = 0xC5;
     pub const SOF6: u8 = 0xC6;
     pub const SOF7: u8 = 0xC7;
+    pub const JPG: u8 = 0xC8; // Reserved for JPEG extensions.
     pub const SOF9: u8 = 0xC9;
     pub const SOF10: u8 = 0xCA;
     pub const SOF11: u8 = 0xCB;
@@ -95,8 +100,51 @@ fn is_standalone_marker(marker: u8) -> bool {
     }
 }
 
+/// Check if a marker is a start-of-frame marker.
+fn is_frame_marker(marker: u8) -> bool {
+    (markers::SOF0..=markers::SOF15).contains(&marker)
+        && !matches!(marker, markers::DHT | markers::JPG | markers::DAC)
+}
+
+/// Check if a segment is kept in allowlist mode: the frame header, the
+/// tables and scans that decode it, and a JFIF APP0.
+fn is_listed_segment(marker: u8, payload: &[u8]) -> bool {
+    match marker {
+        markers::DQT | markers::DHT | markers::DAC | markers::DRI | markers::SOS => true,
+        markers::APP0 => payload.starts_with(b"JFIF\0"),
+        m => is_frame_marker(m),
+    }
+}
+
+/// Check if a report kind names a segment that allowlist mode keeps.
+pub fn is_listed(kind: &str) -> bool {
+    matches!(
+        kind,
+        "SOI" | "EOI" | "APP0 JFIF" | "DQT" | "DHT" | "DAC" | "DRI" | "SOS"
+    ) || kind.starts_with("SOF")
+}
+
 /// Strip metadata from JPEG data.
-pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
+///
+/// Returns the input itself when nothing was dropped from it.
+pub fn strip<'a>(data: &'a [u8], path: &Path) -> Result<Cow<'a, [u8]>> {
+    retain_segments(data, path, |marker, _| !is_metadata_marker(marker))
+}
+
+/// Remove every segment that `is_listed_segment` does not accept.
+pub fn remove_unlisted(data: &[u8], path: &Path) -> Result<Vec<u8>> {
+    retain_segments(data, path, is_listed_segment).map(Cow::into_owned)
+}
+
+/// Copy JPEG data up to EOI, keeping the segments `keep` accepts by marker
+/// and payload. Scans are always kept.
+///
+/// Returns the input itself when nothing was dropped from it.
+fn retain_segments<'a>(
+    data: &'a [u8],
+    path: &Path,
+    keep: impl Fn(u8, &[u8]) -> bool,
+) -> Result<Cow<'a, [u8]>> {
     // Validate minimum size and SOI marker.
     if data.len() < 4 {
         return Err(Error::invalid_image(
@@ -223,8 +271,8 @@ pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
         }
 
         // Copy or skip segment based on marker type.
-        if is_metadata_marker(marker) {
-            // Skip metadata segment.
+        if !keep(marker, &data[pos + 2..pos + length]) {
+            // Skip the segment.
             pos += length;
         } else {
             // Copy segment.
@@ -234,9 +282,506 @@ pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
         }
     }
 
+    // Every byte written is copied in order from the input, so an output of
+    // the same length is the input.
+    if output.len() == data.len() {
+        return Ok(Cow::Borrowed(data));
+    }
+    Ok(Cow::Owned(output))
+}
+
+/// Add an application segment to stripped JPEG data.
+///
+/// The segment goes after SOI and any application segments up to its own
+/// marker, so JFIF comes first, then EXIF and XMP, then ICC profiles, in
+/// the order readers expect to find them.
+fn insert_app(data: &[u8], marker: u8, header: &[u8], body: &[u8], path: &Path) -> Result<Vec<u8>> {
+    let length = 2 + header.len() + body.len();
+    if length > u16::MAX as usize {
+        return Err(Error::invalid_image(
+            path,
+            format!("Metadata too large for APP{}", marker - markers::APP0),
+        ));
+    }
+    if !data.starts_with(&[markers::MARKER_PREFIX, markers::SOI]) {
+        return Err(Error::invalid_image(path, "Missing JPEG SOI marker"));
+    }
+
+    let mut pos = 2;
+    while let Some(&[markers::MARKER_PREFIX, m, hi, lo]) = data.get(pos..pos + 4) {
+        if !(markers::APP0..=marker).contains(&m) {
+            break;
+        }
+        pos += 2 + u16::from_be_bytes([hi, lo]) as usize;
+    }
+    let pos = pos.min(data.len());
+
+    let mut output = Vec::with_capacity(data.len() + length + 2);
+    output.extend_from_slice(&data[..pos]);
+    output.extend_from_slice(&[markers::MARKER_PREFIX, marker]);
+    output.extend_from_slice(&(length as u16).to_be_bytes());
+    output.extend_from_slice(header);
+    output.extend_from_slice(body);
+    output.extend_from_slice(&data[pos..]);
+
     Ok(output)
 }
 
+/// Add an EXIF APP1 segment to stripped JPEG data.
+///
+/// `block` is a TIFF structure without the `Exif\0\0` identifier.
+pub fn insert_exif(data: &[u8], block: &[u8], path: &Path) -> Result<Vec<u8>> {
+    insert_app(data, markers::APP1, exif::EXIF_HEADER, block, path)
+}
+
+/// Add an XMP APP1 segment to stripped JPEG data.
+pub fn insert_xmp(data: &[u8], packet: &[u8], path: &Path) -> Result<Vec<u8>> {
+    insert_app(data, markers::APP1, xmp::XMP_HEADER, packet, path)
+}
+
+/// Add an ICC profile APP2 segment to stripped JPEG data.
+///
+/// `payload` is a whole segment payload, starting with `ICC_PROFILE\0` and
+/// the chunk number and count, as found in the original file.
+pub fn insert_icc(data: &[u8], payload: &[u8], path: &Path) -> Result<Vec<u8>> {
+    insert_app(data, markers::APP2, &[], payload, path)
+}
+
+/// Find the end of entropy-coded data starting at `pos`.
+///
+/// Returns the position of the first marker that is not a stuffed byte
+/// (FF 00) or a restart marker, or `None` if the data ends first.
+fn entropy_end(data: &[u8], mut pos: usize) -> Option<usize> {
+    while pos + 1 < data.len() {
+        if data[pos] == markers::MARKER_PREFIX {
+            let next = data[pos + 1];
+            if next == 0x00 || (markers::RST0..=markers::RST7).contains(&next) {
+                pos += 2;
+                continue;
+            }
+            return Some(pos);
+        }
+        pos += 1;
+    }
+    None
+}
+
+/// Verify that JPEG data is structurally sound and free of metadata segments.
+///
+/// Checks every segment length, requires a frame header before the first scan,
+/// requires each scan to be terminated by a marker, and requires the file to
+/// end exactly at EOI.
+pub fn verify(data: &[u8], path: &Path) -> Result<()> {
+    let fail = |reason: String| Error::verification_failed(path, reason);
+
+    if data.len() < 4 || data[0] != markers::MARKER_PREFIX || data[1] != markers::SOI {
+        return Err(fail(String::from("Missing JPEG SOI marker")));
+    }
+
+    let mut pos = 2;
+    let mut seen_frame = false;
+    let mut seen_scan = false;
+
+    loop {
+        if pos >= data.len() {
+            return Err(fail(String::from("Missing EOI marker")));
+        }
+        if data[pos] != markers::MARKER_PREFIX {
+            return Err(fail(format!("Expected marker at position {}", pos)));
+        }
+
+        // Skip fill bytes.
+        while pos < data.len() && data[pos] == markers::MARKER_PREFIX {
+            pos += 1;
+        }
+        if pos >= data.len() {
+            return Err(fail(String::from("Missing EOI marker")));
+        }
+
+        let marker = data[pos];
+        pos += 1;
+
+        if marker == markers::EOI {
+            if !seen_scan {
+                return Err(fail(String::from("No scan data before EOI")));
+            }
+            if pos != data.len() {
+                return Err(fail(format!(
+                    "{} bytes of trailing data after EOI",
+                    data.len() - pos
+                )));
+            }
+            return Ok(());
+        }
+
+        if marker == markers::SOI || marker == 0x00 {
+            return Err(fail(format!("Unexpected marker FF{:02X}", marker)));
+        }
+        if is_standalone_marker(marker) {
+            continue;
+        }
+
+        if pos + 2 > data.len() {
+            return Err(fail(String::from("Truncated segment header")));
+        }
+        let length = u16::from_be_bytes([data[pos], data[pos + 1]]) as usize;
+        if length < 2 || pos + length > data.len() {
+            return Err(fail(format!(
+                "Segment FF{:02X} has invalid length {}",
+                marker, length
+            )));
+        }
+
+        if is_metadata_marker(marker) {
+            return Err(fail(format!("Metadata segment FF{:02X} remains", marker)));
+        }
+
+        match marker {
+            m if is_frame_marker(m) => seen_frame = true,
+            markers::SOS => {
+                if !seen_frame {
+                    return Err(fail(String::from("Scan before frame header")));
+                }
+                seen_scan = true;
+                pos = entropy_end(data, pos + length)
+                    .ok_or_else(|| fail(String::from("Unterminated scan data")))?;
+                continue;
+            }
+            _ => {}
+        }
+
+        pos += length;
+    }
+}
+
+/// Digest of one segment that defines the decoded image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentDigest {
+    /// Segment marker (the byte following 0xFF).
+    pub marker: u8,
+    /// SHA-256 over the marker and segment bytes. For scans this includes
+    /// the entropy-coded data, stuffed bytes and restart markers.
+    pub digest: Digest,
+}
+
+/// Digests of the frame header, tables and scans of a JPEG.
+///
+/// Because these bytes fully determine the decoded image, equal digests
+/// before and after stripping prove that no pixels changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageDigest {
+    /// Image width from the frame header.
+    pub width: u16,
+    /// Image height from the frame header.
+    pub height: u16,
+    /// Image-defining segments in file order.
+    pub segments: Vec<SegmentDigest>,
+}
+
+impl ImageDigest {
+    /// Get the number of scans.
+    pub fn scan_count(&self) -> usize {
+        self.segments
+            .iter()
+            .filter(|s| s.marker == markers::SOS)
+            .count()
+    }
+
+    /// Get a single digest covering every segment.
+    pub fn combined(&self) -> Digest {
+        let mut hasher = Sha256::new();
+        for segment in &self.segments {
+            hasher.update(&[segment.marker]);
+            hasher.update(&segment.digest);
+        }
+        hasher.finalize()
+    }
+}
+
+/// Check if a segment affects the decoded image.
+fn is_image_segment(marker: u8) -> bool {
+    is_frame_marker(marker)
+        || matches!(
+            marker,
+            markers::DQT | markers::DHT | markers::DRI | markers::DAC | markers::SOS
+        )
+}
+
+/// Describe an image segment for error messages.
+fn describe_segment(segments: &[SegmentDigest], index: usize) -> String {
+    match segments[index].marker {
+        markers::SOS => {
+            let scan = segments[..=index]
+                .iter()
+                .filter(|s| s.marker == markers::SOS)
+                .count();
+            format!("scan {}", scan)
+        }
+        markers::DQT => String::from("quantization table"),
+        markers::DHT => String::from("Huffman table"),
+        markers::DRI => String::from("restart interval"),
+        markers::DAC => String::from("arithmetic conditioning table"),
+        _ => String::from("frame header"),
+    }
+}
+
+/// Compute digests of the frame header, DQT/DHT/DRI tables and every scan.
+///
+/// Handles progressive files with many scans, tables between scans and
+/// restart markers inside the entropy-coded data.
+pub fn image_digest(data: &[u8], path: &Path) -> Result<ImageDigest> {
+    if data.len() < 4 || data[0] != markers::MARKER_PREFIX || data[1] != markers::SOI {
+        return Err(Error::invalid_image(path, "Missing JPEG SOI marker"));
+    }
+
+    let mut size = None;
+    let mut segments = Vec::new();
+    let mut pos = 2;
+
+    while pos < data.len() {
+        if data[pos] != markers::MARKER_PREFIX {
+            return Err(Error::invalid_image(
+                path,
+                format!("Expected marker at position {}", pos),
+            ));
+        }
+
+        // Skip fill bytes.
+        while pos < data.len() && data[pos] == markers::MARKER_PREFIX {
+            pos += 1;
+        }
+        if pos >= data.len() {
+            break;
+        }
+
+        let marker = data[pos];
+        pos += 1;
+
+        if marker == markers::EOI {
+            break;
+        }
+        if is_standalone_marker(marker) {
+            continue;
+        }
+
+        if pos + 2 > data.len() {
+            return Err(Error::invalid_image(path, "Truncated segment header"));
+        }
+        let length = u16::from_be_bytes([data[pos], data[pos + 1]]) as usize;
+        if length < 2 || pos + length > data.len() {
+            return Err(Error::invalid_image(path, "Segment extends beyond file"));
+        }
+
+        let mut end = pos + length;
+        if is_frame_marker(marker) && length >= 7 {
+            let height = u16::from_be_bytes([data[pos + 3], data[pos + 4]]);
+            let width = u16::from_be_bytes([data[pos + 5], data[pos + 6]]);
+            size.get_or_insert((width, height));
+        }
+        if marker == markers::SOS {
+            // A scan without a terminating marker runs to the end of the file.
+            end = entropy_end(data, end).unwrap_or(data.len());
+        }
+
+        if is_image_segment(marker) {
+            let mut hasher = Sha256::new();
+            hasher.update(&[marker]);
+            hasher.update(&data[pos..end]);
+            segments.push(SegmentDigest {
+                marker,
+                digest: hasher.finalize(),
+            });
+        }
+
+        pos = end;
+    }
+
+    let (width, height) = size.ok_or_else(|| Error::invalid_image(path, "Missing frame header"))?;
+
+    Ok(ImageDigest {
+        width,
+        height,
+        segments,
+    })
+}
+
+/// Check that two image digests describe the same image.
+///
+/// Returns a verification error naming the first segment that differs.
+pub fn compare_image(original: &ImageDigest, cleaned: &ImageDigest, path: &Path) -> Result<()> {
+    let fail = |reason: String| Error::verification_failed(path, reason);
+
+    if original.scan_count() != cleaned.scan_count() {
+        return Err(fail(format!(
+            "Image has {} scans after stripping, expected {}",
+            cleaned.scan_count(),
+            original.scan_count()
+        )));
+    }
+
+    let differing = original
+        .segments
+        .iter()
+        .zip(&cleaned.segments)
+        .position(|(a, b)| a != b);
+    if let Some(index) = differing {
+        return Err(fail(format!(
+            "JPEG {} differs after stripping",
+            describe_segment(&original.segments, index)
+        )));
+    }
+
+    if original.segments.len() != cleaned.segments.len() {
+        return Err(fail(format!(
+            "Image has {} frame/table/scan segments after stripping, expected {}",
+            cleaned.segments.len(),
+            original.segments.len()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Check that cleaned data keeps the image data of the original.
+///
+/// Digests the frame header, tables and every scan in both, failing with a
+/// verification error if any of them differ or the cleaned data cannot be
+/// parsed. Returns the digest of the cleaned data.
+pub fn check_image(original: &[u8], cleaned: &[u8], path: &Path) -> Result<ImageDigest> {
+    let before = image_digest(original, path)?;
+    let after = image_digest(cleaned, path).map_err(|e| match e {
+        Error::InvalidImage { reason, .. } => {
+            Error::verification_failed(path, format!("Cleaned output does not decode: {}", reason))
+        }
+        other => other,
+    })?;
+    compare_image(&before, &after, path)?;
+    Ok(after)
+}
+
+/// Describe a metadata segment that `strip` would remove.
+fn metadata_item(marker: u8, payload: &[u8], range: std::ops::Range<usize>) -> MetadataItem {
+    match marker {
+        markers::APP1 if payload.starts_with(exif::EXIF_HEADER) => {
+            MetadataItem::new("APP1 EXIF", range, Category::Other)
+                .with_fields(exif::fields(payload))
+        }
+        markers::APP1 if payload.starts_with(b"http://ns.adobe.com/") => {
+            MetadataItem::new("APP1 XMP", range, Category::Other).with_fields(xmp::fields(payload))
+        }
+        markers::APP2 if payload.starts_with(b"ICC_PROFILE\0") => {
+            MetadataItem::new("APP2 ICC", range, Category::Colour)
+        }
+        markers::APP13 => MetadataItem::new("APP13 IPTC", range, Category::Other)
+            .with_fields(iptc::fields(payload)),
+        markers::COM => MetadataItem::new("COM", range, Category::Other),
+        m => MetadataItem::new(format!("APP{}", m - markers::APP0), range, Category::Other),
+    }
+}
+
+/// Describe a segment that `strip` keeps.
+fn kept_item(marker: u8, payload: &[u8], range: std::ops::Range<usize>) -> KeptItem {
+    match marker {
+        markers::APP0 if payload.starts_with(b"JFIF\0") => {
+            KeptItem::new("APP0 JFIF", range, KeepReason::Structure)
+        }
+        markers::APP0 => KeptItem::new("APP0", range, KeepReason::Structure),
+        markers::APP14 => KeptItem::new("APP14 Adobe", range, KeepReason::Rendering),
+        markers::DQT => KeptItem::new("DQT", range, KeepReason::Decoding),
+        markers::DHT => KeptItem::new("DHT", range, KeepReason::Decoding),
+        markers::DRI => KeptItem::new("DRI", range, KeepReason::Decoding),
+        markers::DAC => KeptItem::new("DAC", range, KeepReason::Decoding),
+        markers::SOS => KeptItem::new("SOS", range, KeepReason::ImageData),
+        m if is_frame_marker(m) => KeptItem::new(
+            format!("SOF{}", m - markers::SOF0),
+            range,
+            KeepReason::Decoding,
+        ),
+        m => KeptItem::new(format!("0x{:02X}", m), range, KeepReason::Structure),
+    }
+}
+
+/// Describe the segments that `strip` removes and keeps.
+///
+/// EXIF, XMP and IPTC segments are decoded far enough to name their fields.
+/// Each scan is reported with its entropy-coded data. Fails on the same
+/// structural errors as `strip`.
+pub fn report(data: &[u8], path: &Path) -> Result<StripReport> {
+    if data.len() < 4 || data[0] != markers::MARKER_PREFIX || data[1] != markers::SOI {
+        return Err(Error::invalid_image(path, "Missing JPEG SOI marker"));
+    }
+
+    let mut report = StripReport::default();
+    report
+        .kept
+        .push(KeptItem::new("SOI", 0..2, KeepReason::Structure));
+    let mut pos = 2;
+
+    while pos < data.len() {
+        if data[pos] != markers::MARKER_PREFIX {
+            return Err(Error::invalid_image(
+                path,
+                format!("Expected marker at position {}", pos),
+            ));
+        }
+
+        // Skip fill bytes.
+        while pos < data.len() && data[pos] == markers::MARKER_PREFIX {
+            pos += 1;
+        }
+        if pos >= data.len() {
+            break;
+        }
+
+        let start = pos - 1;
+        let marker = data[pos];
+        pos += 1;
+
+        if marker == markers::EOI {
+            report
+                .kept
+                .push(KeptItem::new("EOI", start..pos, KeepReason::Structure));
+            // Anything after the end of the image is dropped.
+            if pos < data.len() {
+                report.removed.push(MetadataItem::new(
+                    "Trailing data",
+                    pos..data.len(),
+                    Category::Other,
+                ));
+            }
+            break;
+        }
+        if is_standalone_marker(marker) {
+            continue;
+        }
+
+        if pos + 2 > data.len() {
+            return Err(Error::invalid_image(path, "Truncated segment header"));
+        }
+        let length = u16::from_be_bytes([data[pos], data[pos + 1]]) as usize;
+        if length < 2 || pos + length > data.len() {
+            return Err(Error::invalid_image(path, "Segment extends beyond file"));
+        }
+
+        let end = pos + length;
+        let payload = &data[pos + 2..end];
+        if is_metadata_marker(marker) {
+            report
+                .removed
+                .push(metadata_item(marker, payload, start..end));
+            pos = end;
+        } else if marker == markers::SOS {
+            pos = entropy_end(data, end).unwrap_or(data.len());
+            report.kept.push(kept_item(marker, payload, start..pos));
+        } else {
+            report.kept.push(kept_item(marker, payload, start..end));
+            pos = end;
+        }
+    }
+
+    Ok(report)
+}
+
 /// Create a minimal valid JPEG for testing.
 #[cfg(test)]
 pub fn create_minimal_jpeg() -> Vec<u8> {
@@ -350,6 +895,43 @@ mod tests {
         assert!(has_app0, "APP0 marker should be preserved");
     }
 
+    #[test]
+    fn test_verify_stripped_jpeg() {
+        let data = create_jpeg_with_exif();
+        let result = strip(&data, &test_path()).unwrap();
+        assert!(verify(&result, &test_path()).is_ok());
+    }
+
+    #[test]
+    fn test_verify_rejects_metadata() {
+        let data = create_jpeg_with_exif();
+        let err = verify(&data, &test_path()).unwrap_err();
+        assert!(err.to_string().contains("FFE1"));
+    }
+
+    #[test]
+    fn test_verify_rejects_missing_eoi() {
+        let mut data = create_minimal_jpeg();
+        data.truncate(data.len() - 2);
+        assert!(verify(&data, &test_path()).is_err());
+    }
+
+    #[test]
+    fn test_verify_rejects_bad_segment_length() {
+        let mut data = create_minimal_jpeg();
+        // Corrupt the DQT length so it runs past the end of the file.
+        let dqt = data.windows(2).position(|w| w == [0xFF, 0xDB]).unwrap();
+        data[dqt + 2] = 0xFF;
+        assert!(verify(&data, &test_path()).is_err());
+    }
+
+    #[test]
+    fn test_entropy_end_skips_stuffing_and_restarts() {
+        let data = [0x12, 0xFF, 0x00, 0x34, 0xFF, 0xD0, 0x56, 0xFF, 0xD9];
+        assert_eq!(entropy_end(&data, 0), Some(7));
+        assert_eq!(entropy_end(&data[..7], 0), None);
+    }
+
     #[test]
     fn test_invalid_too_small() {
         let data = [0xFF, 0xD8];
@@ -387,4 +969,273 @@ mod tests {
         assert!(!is_standalone_marker(markers::APP0));
         assert!(!is_standalone_marker(markers::DQT));
     }
+
+    /// Build a progressive JPEG with metadata, tables between scans and
+    /// restart markers inside the entropy-coded data.
+    fn create_progressive_jpeg(scans: u8) -> Vec<u8> {
+        let minimal = create_minimal_jpeg();
+        let dqt = minimal.windows(2).position(|w| w == [0xFF, 0xDB]).unwrap();
+        let sof = minimal.windows(2).position(|w| w == [0xFF, 0xC0]).unwrap();
+        let dht = minimal.windows(2).position(|w| w == [0xFF, 0xC4]).unwrap();
+        let sos = minimal.windows(2).position(|w| w == [0xFF, 0xDA]).unwrap();
+
+        let mut data = create_jpeg_with_exif();
+        data.truncate(data.windows(2).position(|w| w == [0xFF, 0xDB]).unwrap());
+        data.extend_from_slice(&minimal[dqt..sof]);
+        data.extend_from_slice(&[0xFF, markers::SOF2]);
+        data.extend_from_slice(&minimal[sof + 2..dht]);
+        data.extend_from_slice(&[0xFF, markers::DRI, 0x00, 0x04, 0x00, 0x01]);
+
+        for scan in 0..scans {
+            // Tables may be redefined between progressive scans.
+            data.extend_from_slice(&minimal[dht..sos]);
+            data.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, scan, 0x3F, 0x00]);
+            data.extend_from_slice(&[0x12, scan, 0xFF, 0x00, 0x34]);
+            data.extend_from_slice(&[0xFF, markers::RST0 + (scan % 8), 0x56, 0xFF, 0x00]);
+        }
+
+        data.extend_from_slice(&[0xFF, markers::EOI]);
+        data
+    }
+
+    #[test]
+    fn test_image_digest_progressive() {
+        let data = create_progressive_jpeg(10);
+        let digest = image_digest(&data, &test_path()).unwrap();
+        assert_eq!(digest.scan_count(), 10);
+        assert_eq!((digest.width, digest.height), (1, 1));
+        // DQT, SOF2, DRI, then two DHT tables and a scan per scan.
+        assert_eq!(digest.segments.len(), 3 + 10 * 3);
+    }
+
+    #[test]
+    fn test_image_digest_ignores_metadata() {
+        let data = create_jpeg_with_exif();
+        assert_eq!(
+            image_digest(&data, &test_path()).unwrap(),
+            image_digest(&create_minimal_jpeg(), &test_path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_check_image_progressive() {
+        let data = create_progressive_jpeg(10);
+        let result = strip(&data, &test_path()).unwrap();
+        let digest = check_image(&data, &result, &test_path()).unwrap();
+        assert_eq!(digest.scan_count(), 10);
+        assert!(verify(&result, &test_path()).is_ok());
+    }
+
+    #[test]
+    fn test_compare_detects_changed_scan() {
+        let data = create_progressive_jpeg(4);
+        let mut changed = data.clone();
+        // Alter the entropy data after the third restart marker.
+        let rst = changed
+            .windows(2)
+            .position(|w| w == [0xFF, markers::RST0 + 2])
+            .unwrap();
+        changed[rst + 2] ^= 0x01;
+
+        let before = image_digest(&data, &test_path()).unwrap();
+        let after = image_digest(&changed, &test_path()).unwrap();
+        let err = compare_image(&before, &after, &test_path()).unwrap_err();
+        assert!(err.to_string().contains("scan 3 differs"), "{}", err);
+    }
+
+    #[test]
+    fn test_compare_detects_dropped_scans() {
+        let data = create_progressive_jpeg(4);
+        // Simulate a strip that stops copying after the first scan.
+        let second_dht = data
+            .windows(2)
+            .enumerate()
+            .filter(|(_, w)| *w == [0xFF, markers::DHT])
+            .map(|(i, _)| i)
+            .nth(2)
+            .unwrap();
+        let mut truncated = data[..second_dht].to_vec();
+        truncated.extend_from_slice(&[0xFF, markers::EOI]);
+
+        let before = image_digest(&data, &test_path()).unwrap();
+        let after = image_digest(&truncated, &test_path()).unwrap();
+        let err = compare_image(&before, &after, &test_path()).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("1 scans after stripping, expected 4")
+        );
+    }
+
+    #[test]
+    fn test_compare_detects_changed_table() {
+        let data = create_minimal_jpeg();
+        let mut changed = data.clone();
+        let dqt = changed.windows(2).position(|w| w == [0xFF, 0xDB]).unwrap();
+        changed[dqt + 6] ^= 0x01;
+
+        let before = image_digest(&data, &test_path()).unwrap();
+        let after = image_digest(&changed, &test_path()).unwrap();
+        let err = compare_image(&before, &after, &test_path()).unwrap_err();
+        assert!(err.to_string().contains("quantization table"));
+    }
+
+    #[test]
+    fn test_report_jpeg_with_exif() {
+        let data = create_jpeg_with_exif();
+        let items = report(&data, &test_path()).unwrap().removed;
+        let kinds: Vec<&str> = items.iter().map(|i| i.kind.as_str()).collect();
+        assert_eq!(kinds, ["APP1 EXIF", "COM"]);
+
+        // The reported ranges are exactly what strip removes.
+        let removed: usize = items.iter().map(|i| i.size()).sum();
+        assert_eq!(
+            removed,
+            data.len() - strip(&data, &test_path()).unwrap().len()
+        );
+        assert_eq!(
+            &data[items[1].range.start..items[1].range.start + 2],
+            &[0xFF, 0xFE]
+        );
+    }
+
+    #[test]
+    fn test_report_kept_segments() {
+        let mut data = create_jpeg_with_exif();
+        data.extend_from_slice(b"trailer");
+        let report = report(&data, &test_path()).unwrap();
+
+        let kinds: Vec<&str> = report.kept.iter().map(|i| i.kind.as_str()).collect();
+        assert_eq!(
+            kinds,
+            [
+                "SOI",
+                "APP0 JFIF",
+                "DQT",
+                "SOF0",
+                "DHT",
+                "DHT",
+                "SOS",
+                "EOI"
+            ]
+        );
+        assert_eq!(report.kept[6].reason, KeepReason::ImageData);
+        assert_eq!(report.removed.last().unwrap().kind, "Trailing data");
+
+        // Removed and kept items tile the whole file.
+        let mut ranges: Vec<_> = report
+            .removed
+            .iter()
+            .map(|i| i.range.clone())
+            .chain(report.kept.iter().map(|i| i.range.clone()))
+            .collect();
+        ranges.sort_by_key(|r| r.start);
+        assert_eq!(ranges[0].start, 0);
+        assert!(ranges.windows(2).all(|w| w[0].end == w[1].start));
+        assert_eq!(ranges.last().unwrap().end, data.len());
+    }
+
+    #[test]
+    fn test_report_decodes_exif_fields() {
+        let exif = crate::metadata::exif::build_test_exif(
+            &[(0x010F, 2, 4, b"ACME")],
+            &[(0x0002, 5, 0, &[])],
+        );
+        let mut data = vec![0xFF, 0xD8, 0xFF, markers::APP1];
+        data.extend_from_slice(&((exif.len() + 2) as u16).to_be_bytes());
+        data.extend_from_slice(&exif);
+        data.extend_from_slice(&create_minimal_jpeg()[2..]);
+
+        let items = report(&data, &test_path()).unwrap().removed;
+        assert_eq!(
+            items[0].categories(),
+            vec![Category::Location, Category::Device]
+        );
+    }
+
+    #[test]
+    fn test_report_clean_jpeg() {
+        let data = strip(&create_progressive_jpeg(3), &test_path())
+            .unwrap()
+            .into_owned();
+        assert!(report(&data, &test_path()).unwrap().removed.is_empty());
+    }
+
+    #[test]
+    fn test_report_rejects_truncated_segment() {
+        let mut data = create_jpeg_with_exif();
+        data.truncate(12);
+        assert!(report(&data, &test_path()).is_err());
+    }
+
+    #[test]
+    fn test_image_digest_requires_frame() {
+        assert!(image_digest(&[0xFF, 0xD8, 0xFF, 0xD9], &test_path()).is_err());
+    }
+
+    #[test]
+    fn test_insert_exif_and_xmp_after_app0() {
+        let stripped = strip(&create_jpeg_with_exif(), &test_path())
+            .unwrap()
+            .into_owned();
+        let block = exif::build(&[]);
+        let result = insert_exif(&stripped, &block, &test_path()).unwrap();
+
+        let report = super::report(&result, &test_path()).unwrap();
+        assert_eq!(report.kept[1].kind, "APP0 JFIF");
+        assert_eq!(report.removed.len(), 1);
+        assert_eq!(report.removed[0].kind, "APP1 EXIF");
+        assert_eq!(report.removed[0].range.start, report.kept[1].range.end);
+
+        let packet = xmp::build(&[(String::from("xmp:CreateDate"), String::from("2024"))]);
+        let result = insert_xmp(&result, &packet, &test_path()).unwrap();
+        let removed = super::report(&result, &test_path()).unwrap().removed;
+        let kinds: Vec<&str> = removed.iter().map(|item| item.kind.as_str()).collect();
+        assert_eq!(kinds, ["APP1 EXIF", "APP1 XMP"]);
+        assert_eq!(strip(&result, &test_path()).unwrap(), stripped);
+    }
+
+    #[test]
+    fn test_insert_icc_after_app1() {
+        let stripped = strip(&create_jpeg_with_exif(), &test_path())
+            .unwrap()
+            .into_owned();
+        let mut icc = b"ICC_PROFILE\0\x01\x01".to_vec();
+        icc.extend_from_slice(&[0xAB; 32]);
+        let result = insert_icc(&stripped, &icc, &test_path()).unwrap();
+        let result = insert_exif(&result, &exif::build(&[]), &test_path()).unwrap();
+
+        let removed = super::report(&result, &test_path()).unwrap().removed;
+        let kinds: Vec<&str> = removed.iter().map(|item| item.kind.as_str()).collect();
+        assert_eq!(kinds, ["APP1 EXIF", "APP2 ICC"]);
+        assert_eq!(
+            &result[removed[1].range.start + 4..removed[1].range.end],
+            icc
+        );
+        assert_eq!(strip(&result, &test_path()).unwrap(), stripped);
+    }
+
+    #[test]
+    fn test_remove_unlisted() {
+        let minimal = create_minimal_jpeg();
+        let mut data = minimal[..2].to_vec();
+        data.extend_from_slice(&[0xFF, markers::APP14, 0x00, 0x0E]);
+        data.extend_from_slice(b"Adobe\0\x64\0\0\0\0\x01");
+        data.extend_from_slice(&minimal[2..]);
+
+        // An Adobe segment survives the default strip.
+        assert_eq!(strip(&data, &test_path()).unwrap(), &data[..]);
+        let kept = report(&data, &test_path()).unwrap().kept;
+        assert!(kept.iter().any(|item| !is_listed(&item.kind)));
+
+        let result = remove_unlisted(&data, &test_path()).unwrap();
+        assert_eq!(result, minimal);
+        let kept = report(&result, &test_path()).unwrap().kept;
+        assert!(kept.iter().all(|item| is_listed(&item.kind)));
+
+        // Only a JFIF APP0 is listed.
+        let mut jfxx = minimal.clone();
+        jfxx[6..10].copy_from_slice(b"JFXX");
+        let result = remove_unlisted(&jfxx, &test_path()).unwrap();
+        assert_eq!(result.len(), minimal.len() - 18);
+    }
 }
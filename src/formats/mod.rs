@@ -10,6 +10,12 @@ pub mod tiff;
 pub mod webp;
 
 use crate::error::{Error, Result};
+use crate::metadata::dates::{self, DateMode};
+use crate::metadata::exif::{self, ByteOrder, Ifd};
+use crate::metadata::gps::{self, GpsMode};
+use crate::metadata::stamp::{self, Stamp};
+use crate::metadata::{self, Category, KeptItem, MetadataItem, xmp};
+use std::borrow::Cow;
 use std::path::Path;
 
 /// Supported image formats.
@@ -118,6 +124,172 @@ pub fn detect_format_from_extension(path: &Path) -> Option<ImageFormat> {
     .find(|format| format.extensions().contains(&ext.as_str()))
 }
 
+/// What stripping removes from an image and what it keeps.
+///
+/// Built by each format's `report` function from the same walk of the file
+/// structure that `strip` performs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StripReport {
+    /// Blocks removed, with their decoded fields and categories.
+    pub removed: Vec<MetadataItem>,
+    /// Blocks kept, with the reason for keeping each one.
+    pub kept: Vec<KeptItem>,
+}
+
+impl StripReport {
+    /// Get the total size of the removed blocks.
+    pub fn removed_size(&self) -> u64 {
+        self.removed.iter().map(|item| item.size() as u64).sum()
+    }
+
+    /// Get the distinct categories removed, in display order.
+    pub fn categories(&self) -> Vec<Category> {
+        metadata::categories(&self.removed)
+    }
+}
+
+/// What to do with embedded colour profiles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IccMode {
+    /// Keep them where the format treats them as rendering data (PNG, WebP,
+    /// TIFF) and strip them where they share a container with metadata
+    /// (JPEG APP2, GIF application extensions).
+    #[default]
+    Auto,
+    /// Keep them in every format that can carry one back (not GIF).
+    Keep,
+    /// Strip them from every format. PNG, WebP and TIFF profiles survive
+    /// `strip`, so each of those formats removes them from the stripped data
+    /// with its `remove_icc`.
+    Strip,
+}
+
+impl IccMode {
+    /// Parse a mode, as given to `--icc`: `auto`, `keep` or `strip`.
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "auto" => Ok(IccMode::Auto),
+            "keep" => Ok(IccMode::Keep),
+            "strip" => Ok(IccMode::Strip),
+            _ => Err(Error::InvalidArgument {
+                argument: String::from("--icc"),
+                reason: String::from("Expected auto, keep or strip"),
+            }),
+        }
+    }
+
+    /// Get the mode name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            IccMode::Auto => "auto",
+            IccMode::Keep => "keep",
+            IccMode::Strip => "strip",
+        }
+    }
+}
+
+/// What stripping keeps from one format, resolved from `StripOptions`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FormatPolicy {
+    /// Keep the embedded colour profile.
+    pub keep_icc: bool,
+    /// Keep the EXIF orientation tag.
+    pub keep_orientation: bool,
+    /// Also drop every block the format's allowlist does not name.
+    pub allowlist: bool,
+}
+
+/// Options for what stripping writes back.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StripOptions {
+    /// What to do with the GPS position.
+    pub gps: GpsMode,
+    /// What to do with capture and modification timestamps.
+    pub dates: DateMode,
+    /// Rights metadata to write into every cleaned image.
+    pub stamp: Stamp,
+    /// What to do with embedded colour profiles.
+    pub icc: IccMode,
+    /// Keep the EXIF orientation tag so rotated photos still display upright.
+    pub keep_orientation: bool,
+    /// Keep only the blocks each format needs to show the image, instead of
+    /// removing the known metadata blocks. Unknown chunks, segments, tags and
+    /// extensions are dropped too; colour profiles still follow `icc`.
+    pub allowlist: bool,
+    /// Check that a cleaned JPEG keeps the frame header, tables and scans
+    /// of the original byte for byte, failing if stripping altered them.
+    pub check_image: bool,
+}
+
+impl StripOptions {
+    /// Resolve the options into what to keep from one format.
+    pub fn policy(&self, format: ImageFormat) -> FormatPolicy {
+        let keeps_icc = matches!(
+            format,
+            ImageFormat::Png | ImageFormat::WebP | ImageFormat::Tiff
+        );
+        FormatPolicy {
+            keep_icc: match self.icc {
+                IccMode::Auto => keeps_icc,
+                IccMode::Keep => format != ImageFormat::Gif,
+                IccMode::Strip => false,
+            },
+            // TIFF keeps orientation as an image tag; GIF has none.
+            keep_orientation: self.keep_orientation && format != ImageFormat::Gif,
+            allowlist: self.allowlist,
+        }
+    }
+}
+
+/// Metadata written back into a cleaned image.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Retained {
+    /// EXIF entries, little-endian.
+    pub exif: Vec<exif::Entry>,
+    /// XMP properties, as qualified name and value.
+    pub xmp: Vec<(String, String)>,
+    /// Body of a PNG `tIME` chunk.
+    pub png_time: Option<[u8; 7]>,
+    /// PNG `iTXt` chunks, as keyword and text.
+    pub png_text: Vec<(String, String)>,
+    /// JPEG APP2 ICC profile segment payloads, in order.
+    pub icc: Vec<Vec<u8>>,
+}
+
+impl Retained {
+    /// Check if nothing is written back.
+    pub fn is_empty(&self) -> bool {
+        self.exif.is_empty()
+            && self.xmp.is_empty()
+            && self.png_time.is_none()
+            && self.png_text.is_empty()
+            && self.icc.is_empty()
+    }
+
+    /// Get the names of the fields written back, as reports name them.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.exif.iter().map(exif::Entry::name).collect();
+        names.extend(self.xmp.iter().map(|(name, _)| name.clone()));
+        if self.png_time.is_some() {
+            names.push(String::from("tIME"));
+        }
+        names.extend(self.png_text.iter().map(|(keyword, _)| keyword.clone()));
+        if !self.icc.is_empty() {
+            names.push(String::from(JPEG_ICC));
+        }
+        names
+    }
+}
+
+/// A format's rewrite of stripped data, such as dropping its colour profile.
+type Rewrite = fn(&[u8], &Path) -> Result<Vec<u8>>;
+
+/// Report kind of a JPEG ICC profile segment.
+const JPEG_ICC: &str = "APP2 ICC";
+
+/// EXIF orientation tag.
+const ORIENTATION: u16 = 0x0112;
+
 /// Result of stripping metadata from an image.
 #[derive(Debug)]
 pub struct StripResult {
@@ -125,6 +297,13 @@ pub struct StripResult {
     pub data: Vec<u8>,
     /// Number of bytes of metadata removed.
     pub bytes_removed: u64,
+    /// The items removed and kept.
+    pub report: StripReport,
+    /// Metadata written back into the cleaned image.
+    pub retained: Retained,
+    /// The cleaned image is byte-for-byte the input, so nothing needs to
+    /// be written.
+    pub unchanged: bool,
 }
 
 impl StripResult {
@@ -133,33 +312,460 @@ impl StripResult {
         Self {
             data,
             bytes_removed,
+            report: StripReport::default(),
+            retained: Retained::default(),
+            unchanged: false,
         }
     }
+
+    /// Attach the report of removed and kept items.
+    pub fn with_report(mut self, report: StripReport) -> Self {
+        self.report = report;
+        self
+    }
 }
 
 /// Strip metadata from image data.
 ///
 /// Detects the image format and strips all metadata while preserving
-/// the image data.
+/// the image data. The result lists every removed and kept item.
 pub fn strip_metadata(data: &[u8], path: &Path) -> Result<StripResult> {
-    let format = detect_format(data).ok_or_else(|| {
-        let ext_format = detect_format_from_extension(path);
-        Error::unsupported_format(path, ext_format.map(|f| f.name()))
-    })?;
+    strip_metadata_with(data, path, &StripOptions::default())
+}
 
+/// Strip metadata from image data, writing back what the options keep.
+///
+/// With `GpsMode::Coarsen`, a file with a GPS position gets a new EXIF block
+/// holding only the rounded position. Any `DateMode` other than `Remove`
+/// writes back the rewritten timestamps, and a `Stamp` adds fresh rights
+/// fields. GIF has no EXIF or XMP container, so it is always stripped
+/// completely.
+pub fn strip_metadata_with(
+    data: &[u8],
+    path: &Path,
+    options: &StripOptions,
+) -> Result<StripResult> {
+    let format = detect(data, path)?;
     let original_size = data.len() as u64;
 
-    let result = match format {
-        ImageFormat::Jpeg => jpeg::strip(data, path)?,
-        ImageFormat::Png => png::strip(data, path)?,
-        ImageFormat::Gif => gif::strip(data, path)?,
-        ImageFormat::WebP => webp::strip(data, path)?,
-        ImageFormat::Tiff => tiff::strip(data, path)?,
+    let (mut result, mut report) = match format {
+        ImageFormat::Jpeg => (jpeg::strip(data, path)?, jpeg::report(data, path)?),
+        ImageFormat::Png => (png::strip(data, path)?, png::report(data, path)?),
+        ImageFormat::Gif => (gif::strip(data, path)?, gif::report(data, path)?),
+        ImageFormat::WebP => (webp::strip(data, path)?, webp::report(data, path)?),
+        ImageFormat::Tiff => (tiff::strip(data, path)?, tiff::report(data, path)?),
     };
 
+    let policy = options.policy(format);
+    if !policy.keep_icc {
+        if let Some(output) = remove_icc(format, &result, &mut report, path)? {
+            result = Cow::Owned(output);
+        }
+    }
+    if policy.allowlist {
+        if let Some(output) = remove_unlisted(format, &result, &mut report, path)? {
+            result = Cow::Owned(output);
+        }
+    }
+
+    let retained = retain(data, format, &report, options, path);
+    if !retained.is_empty() {
+        result = Cow::Owned(write_back(format, &result, &retained, path)?);
+    }
+
+    if options.check_image && format == ImageFormat::Jpeg {
+        jpeg::check_image(data, &result, path)?;
+    }
+
     let bytes_removed = original_size.saturating_sub(result.len() as u64);
 
-    Ok(StripResult::new(result, bytes_removed))
+    // Each format hands back the input itself when it finds nothing to
+    // remove, so a clean file is neither rebuilt nor rewritten.
+    let unchanged = matches!(result, Cow::Borrowed(_));
+    let mut result = StripResult::new(result.into_owned(), bytes_removed).with_report(report);
+    result.retained = retained;
+    result.unchanged = unchanged;
+    Ok(result)
+}
+
+/// Remove colour profiles that `strip` keeps, moving them to the removed list.
+///
+/// Returns `None` when the image holds no profile to remove.
+fn remove_icc(
+    format: ImageFormat,
+    data: &[u8],
+    report: &mut StripReport,
+    path: &Path,
+) -> Result<Option<Vec<u8>>> {
+    let (kind, remove): (&str, Rewrite) = match format {
+        ImageFormat::Png => ("iCCP", png::remove_icc),
+        ImageFormat::WebP => ("ICCP", webp::remove_icc),
+        ImageFormat::Tiff => ("InterColorProfile", tiff::remove_icc),
+        // Already stripped with the metadata.
+        ImageFormat::Jpeg | ImageFormat::Gif => return Ok(None),
+    };
+    if !report.kept.iter().any(|item| item.kind == kind) {
+        return Ok(None);
+    }
+    let output = remove(data, path)?;
+
+    let (icc, kept): (Vec<KeptItem>, Vec<KeptItem>) =
+        report.kept.drain(..).partition(|item| item.kind == kind);
+    report.kept = kept;
+    report.removed.extend(
+        icc.into_iter()
+            .map(|item| MetadataItem::new(item.kind, item.range, Category::Colour)),
+    );
+    report.removed.sort_by_key(|item| item.range.start);
+
+    Ok(Some(output))
+}
+
+/// Remove the blocks that `strip` keeps but the format's allowlist does not
+/// name, moving them to the removed list.
+///
+/// Returns `None` when the image holds nothing outside the allowlist.
+fn remove_unlisted(
+    format: ImageFormat,
+    data: &[u8],
+    report: &mut StripReport,
+    path: &Path,
+) -> Result<Option<Vec<u8>>> {
+    let (listed, remove): (fn(&str) -> bool, Rewrite) = match format {
+        ImageFormat::Jpeg => (jpeg::is_listed, jpeg::remove_unlisted),
+        ImageFormat::Png => (png::is_listed, png::remove_unlisted),
+        ImageFormat::Gif => (gif::is_listed, gif::remove_unlisted),
+        ImageFormat::WebP => (webp::is_listed, webp::remove_unlisted),
+        ImageFormat::Tiff => (tiff::is_listed, tiff::remove_unlisted),
+    };
+    if report.kept.iter().all(|item| listed(&item.kind)) {
+        return Ok(None);
+    }
+    let output = remove(data, path)?;
+
+    let (unlisted, kept): (Vec<KeptItem>, Vec<KeptItem>) =
+        report.kept.drain(..).partition(|item| !listed(&item.kind));
+    report.kept = kept;
+    report.removed.extend(
+        unlisted
+            .into_iter()
+            .map(|item| MetadataItem::new(item.kind, item.range, Category::Other)),
+    );
+    report.removed.sort_by_key(|item| item.range.start);
+
+    Ok(Some(output))
+}
+
+/// Work out what the options keep from the original image.
+fn retain(
+    data: &[u8],
+    format: ImageFormat,
+    report: &StripReport,
+    options: &StripOptions,
+    path: &Path,
+) -> Retained {
+    let mut retained = Retained::default();
+    let policy = options.policy(format);
+
+    if let Some(exif) = exif_block(data, format, report).and_then(exif::parse) {
+        // TIFF keeps orientation as an image tag.
+        let orientation = exif.find(Ifd::Primary, ORIENTATION);
+        if let Some(entry) = orientation.filter(|_| policy.keep_orientation) {
+            if format != ImageFormat::Tiff {
+                let mut entry = entry.clone();
+                if exif.byte_order == ByteOrder::Big {
+                    entry.swap_byte_order();
+                }
+                retained.exif.push(entry);
+            }
+        }
+        if let GpsMode::Coarsen(km) = options.gps {
+            if let Some(position) = gps::position(&exif) {
+                retained.exif.extend(gps::entries(position.coarsen(km)));
+            }
+        }
+        retained
+            .exif
+            .extend(dates::exif_entries(&exif, options.dates));
+    }
+
+    if options.dates != DateMode::Remove {
+        if let Some(packet) = xmp_packet(data, format, report, path) {
+            retained.xmp = dates::xmp_properties(&packet, options.dates);
+        }
+        if format == ImageFormat::Png {
+            retained.png_time =
+                png::time(data, path).and_then(|body| dates::png_time(&body, options.dates));
+        }
+    }
+
+    if policy.keep_icc && format == ImageFormat::Jpeg {
+        retained.icc = report
+            .removed
+            .iter()
+            .filter(|item| item.kind == JPEG_ICC)
+            .filter_map(|item| data.get(item.range.start + 4..item.range.end))
+            .map(<[u8]>::to_vec)
+            .collect();
+    }
+
+    let stamp = &options.stamp;
+    if !stamp.is_empty() && format != ImageFormat::Gif {
+        match format {
+            ImageFormat::Jpeg | ImageFormat::Tiff => {
+                retained.exif.extend(stamp::exif_entries(stamp));
+            }
+            ImageFormat::Png => retained.png_text = stamp::png_text(stamp),
+            _ => retained.xmp.extend(stamp::xmp_rights(stamp)),
+        }
+        retained.xmp.extend(stamp::xmp_license(stamp));
+    }
+
+    retained
+}
+
+/// Write retained metadata into stripped image data.
+///
+/// TIFF carries XMP as an IFD0 tag, so it goes in with the EXIF tags in a
+/// single rewrite.
+fn write_back(
+    format: ImageFormat,
+    data: &[u8],
+    retained: &Retained,
+    path: &Path,
+) -> Result<Vec<u8>> {
+    let mut output = data.to_vec();
+    let mut entries = retained.exif.clone();
+    let mut packet = (!retained.xmp.is_empty()).then(|| xmp::build(&retained.xmp));
+
+    if format == ImageFormat::Tiff {
+        if let Some(packet) = packet.take() {
+            let count = packet.len() as u32;
+            entries.push(exif::Entry::new(
+                Ifd::Primary,
+                exif::XMP_TAG,
+                1,
+                count,
+                packet,
+            ));
+        }
+    }
+    if !entries.is_empty() {
+        output = insert_exif(format, &output, &exif::build(&entries), path)?;
+    }
+    if let Some(packet) = packet {
+        output = insert_xmp(format, &output, &packet, path)?;
+    }
+    for (keyword, text) in &retained.png_text {
+        output = png::insert_text(&output, keyword, text, path)?;
+    }
+    if let Some(time) = retained.png_time {
+        output = png::insert_time(&output, time, path)?;
+    }
+    // Each segment goes after those already added, keeping the chunk order.
+    for payload in &retained.icc {
+        output = jpeg::insert_icc(&output, payload, path)?;
+    }
+
+    Ok(output)
+}
+
+/// Add an EXIF block to stripped image data.
+fn insert_exif(format: ImageFormat, data: &[u8], block: &[u8], path: &Path) -> Result<Vec<u8>> {
+    match format {
+        ImageFormat::Jpeg => jpeg::insert_exif(data, block, path),
+        ImageFormat::Png => png::insert_exif(data, block, path),
+        ImageFormat::WebP => webp::insert_exif(data, block, path),
+        ImageFormat::Tiff => tiff::insert_exif(data, block, path),
+        ImageFormat::Gif => Err(Error::unsupported_format(path, Some(format.name()))),
+    }
+}
+
+/// Add an XMP packet to stripped image data.
+fn insert_xmp(format: ImageFormat, data: &[u8], packet: &[u8], path: &Path) -> Result<Vec<u8>> {
+    match format {
+        ImageFormat::Jpeg => jpeg::insert_xmp(data, packet, path),
+        ImageFormat::Png => png::insert_xmp(data, packet, path),
+        ImageFormat::WebP => webp::insert_xmp(data, packet, path),
+        ImageFormat::Tiff | ImageFormat::Gif => {
+            Err(Error::unsupported_format(path, Some(format.name())))
+        }
+    }
+}
+
+/// Find the XMP packet of an image from its report.
+fn xmp_packet(
+    data: &[u8],
+    format: ImageFormat,
+    report: &StripReport,
+    path: &Path,
+) -> Option<Vec<u8>> {
+    // Bytes of segment or chunk framing before the payload.
+    let (kind, header) = match format {
+        ImageFormat::Png => return png::xmp_packet(data, path),
+        ImageFormat::Gif => return None,
+        ImageFormat::Jpeg => ("APP1 XMP", 4),
+        ImageFormat::WebP => ("XMP", 8),
+        ImageFormat::Tiff => ("XMP", 0),
+    };
+    let item = report.removed.iter().find(|item| item.kind == kind)?;
+    let packet = data.get(item.range.start + header..item.range.end)?;
+    Some(
+        packet
+            .strip_prefix(xmp::XMP_HEADER)
+            .unwrap_or(packet)
+            .to_vec(),
+    )
+}
+
+/// Find the EXIF structure of an image from its report.
+///
+/// For TIFF this is the whole file; for the other formats it is the payload
+/// of the removed EXIF segment or chunk.
+fn exif_block<'a>(data: &'a [u8], format: ImageFormat, report: &StripReport) -> Option<&'a [u8]> {
+    // Bytes of segment or chunk framing before and after the payload.
+    let (kind, header, trailer) = match format {
+        ImageFormat::Tiff => return Some(data),
+        ImageFormat::Gif => return None,
+        ImageFormat::Jpeg => ("APP1 EXIF", 4, 0),
+        ImageFormat::Png => ("eXIf", 8, 4),
+        ImageFormat::WebP => ("EXIF", 8, 0),
+    };
+    let item = report.removed.iter().find(|item| item.kind == kind)?;
+    data.get(item.range.start + header..item.range.end.checked_sub(trailer)?)
+}
+
+/// Detect the format of image data, failing for unsupported data.
+fn detect(data: &[u8], path: &Path) -> Result<ImageFormat> {
+    detect_format(data).ok_or_else(|| {
+        let ext_format = detect_format_from_extension(path);
+        Error::unsupported_format(path, ext_format.map(|f| f.name()))
+    })
+}
+
+/// Verify that stripped output is structurally sound and metadata-free.
+///
+/// Re-parses the data with the parser for its detected format, checking
+/// container integrity (segment/chunk lengths, CRCs, RIFF size, IFD offsets,
+/// block termination) and that no metadata structures remain.
+pub fn verify_stripped(data: &[u8], path: &Path) -> Result<()> {
+    let format = detect_format(data).ok_or_else(|| {
+        Error::verification_failed(path, "Output is not a recognised image format")
+    })?;
+
+    match format {
+        ImageFormat::Jpeg => jpeg::verify(data, path),
+        ImageFormat::Png => png::verify(data, path),
+        ImageFormat::Gif => gif::verify(data, path),
+        ImageFormat::WebP => webp::verify(data, path),
+        ImageFormat::Tiff => tiff::verify(data, path),
+    }
+}
+
+/// Verify the output of `strip_metadata_with`.
+///
+/// Output with nothing retained must pass `verify_stripped`. Otherwise its
+/// only metadata must be the retained fields, with the values recorded in
+/// the result, and stripping it again must give clean output.
+pub fn verify_result(result: &StripResult, path: &Path) -> Result<()> {
+    let retained = &result.retained;
+    if retained.is_empty() {
+        return verify_stripped(&result.data, path);
+    }
+
+    let fail = |reason: String| Error::verification_failed(path, reason);
+    let format = detect_format(&result.data)
+        .ok_or_else(|| fail(String::from("Output is not a recognised image format")))?;
+    let report = strip_report(&result.data, path).map_err(|e| fail(e.to_string()))?;
+
+    let names = retained.names();
+    for item in &report.removed {
+        let unexpected = if item.fields.is_empty() {
+            (!names.contains(&item.kind)).then_some(item.kind.as_str())
+        } else {
+            item.fields
+                .iter()
+                .find(|f| !names.contains(&f.name))
+                .map(|f| f.name.as_str())
+        };
+        if let Some(name) = unexpected {
+            return Err(fail(format!("Metadata {} remains", name)));
+        }
+    }
+
+    if !retained.exif.is_empty() {
+        let block = exif_block(&result.data, format, &report)
+            .and_then(exif::parse)
+            .ok_or_else(|| fail(String::from("Retained EXIF block is missing")))?;
+        for entry in &retained.exif {
+            let mut expected = entry.clone();
+            if block.byte_order == ByteOrder::Big {
+                expected.swap_byte_order();
+            }
+            if block.find(entry.ifd, entry.tag) != Some(&expected) {
+                return Err(fail(format!("{} was not written back", entry.name())));
+            }
+        }
+    }
+
+    if !retained.xmp.is_empty() {
+        let packet = xmp_packet(&result.data, format, &report, path)
+            .ok_or_else(|| fail(String::from("Retained XMP packet is missing")))?;
+        for (name, value) in &retained.xmp {
+            if xmp::value(&packet, name).as_ref() != Some(value) {
+                return Err(fail(format!("{} was not written back", name)));
+            }
+        }
+    }
+
+    if let Some(time) = retained.png_time {
+        if png::time(&result.data, path) != Some(time) {
+            return Err(fail(String::from("tIME was not written back")));
+        }
+    }
+
+    for (keyword, text) in &retained.png_text {
+        if png::text(&result.data, keyword, path).as_ref() != Some(text) {
+            return Err(fail(format!("{} was not written back", keyword)));
+        }
+    }
+
+    if !retained.icc.is_empty() {
+        let icc: Vec<&[u8]> = report
+            .removed
+            .iter()
+            .filter(|item| item.kind == JPEG_ICC)
+            .filter_map(|item| result.data.get(item.range.start + 4..item.range.end))
+            .collect();
+        if icc != retained.icc {
+            return Err(fail(String::from("ICC profile was not written back")));
+        }
+    }
+
+    let restripped = strip_metadata(&result.data, path).map_err(|e| fail(e.to_string()))?;
+    verify_stripped(&restripped.data, path)
+}
+
+/// Describe what stripping would remove and keep, without modifying anything.
+///
+/// Detects the image format and walks its structure with the same parser
+/// used for stripping.
+pub fn strip_report(data: &[u8], path: &Path) -> Result<StripReport> {
+    match detect(data, path)? {
+        ImageFormat::Jpeg => jpeg::report(data, path),
+        ImageFormat::Png => png::report(data, path),
+        ImageFormat::Gif => gif::report(data, path),
+        ImageFormat::WebP => webp::report(data, path),
+        ImageFormat::Tiff => tiff::report(data, path),
+    }
+}
+
+/// List the metadata that stripping would remove, without modifying anything.
+///
+/// A file that `inspect_metadata` reports as clean is left unchanged by
+/// `strip_metadata` apart from container rewriting.
+pub fn inspect_metadata(data: &[u8], path: &Path) -> Result<Vec<MetadataItem>> {
+    strip_report(data, path).map(|report| report.removed)
 }
 
 /// Check if a file appears to be a supported image format.
@@ -278,6 +884,502 @@ mod tests {
         assert!(ImageFormat::Png.extensions().contains(&"png"));
     }
 
+    #[test]
+    fn test_verify_stripped_all_formats() {
+        let samples = [
+            (jpeg::create_jpeg_with_exif(), "test.jpg"),
+            (png::create_png_with_metadata(), "test.png"),
+            (gif::create_gif_with_comment(), "test.gif"),
+            (webp::create_webp_with_exif(), "test.webp"),
+            (tiff::create_tiff_with_metadata(), "test.tiff"),
+        ];
+
+        for (data, name) in samples {
+            let path = Path::new(name);
+            let result = strip_metadata(&data, path).unwrap();
+            assert!(verify_stripped(&result.data, path).is_ok(), "{}", name);
+            assert!(verify_stripped(&data, path).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_strip_clean_image_is_unchanged() {
+        let samples = [
+            (jpeg::create_jpeg_with_exif(), "test.jpg"),
+            (png::create_png_with_metadata(), "test.png"),
+            (gif::create_gif_with_comment(), "test.gif"),
+            (webp::create_webp_with_exif(), "test.webp"),
+            (tiff::create_tiff_with_metadata(), "test.tiff"),
+        ];
+
+        for (data, name) in samples {
+            let path = Path::new(name);
+            let first = strip_metadata(&data, path).unwrap();
+            assert!(!first.unchanged, "{}", name);
+            let second = strip_metadata(&first.data, path).unwrap();
+            assert!(second.unchanged, "{}", name);
+            assert_eq!(second.data, first.data, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_allowlist_removes_unknown_blocks() {
+        let mut data = webp::create_minimal_webp();
+        data.extend_from_slice(b"UNKN\x02\0\0\0ab");
+        let riff_size = (data.len() - 8) as u32;
+        data[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        let path = Path::new("test.webp");
+
+        let result = strip_metadata(&data, path).unwrap();
+        assert!(result.unchanged);
+        assert!(result.report.kept.iter().any(|item| item.kind == "UNKN"));
+
+        let options = StripOptions {
+            allowlist: true,
+            ..StripOptions::default()
+        };
+        let result = strip_metadata_with(&data, path, &options).unwrap();
+        assert_eq!(result.data, webp::create_minimal_webp());
+        assert_eq!(result.bytes_removed, 10);
+        let removed: Vec<&str> = result
+            .report
+            .removed
+            .iter()
+            .map(|i| i.kind.as_str())
+            .collect();
+        assert_eq!(removed, ["UNKN"]);
+        assert!(!result.report.kept.iter().any(|item| item.kind == "UNKN"));
+
+        // Nothing outside the allowlist leaves a clean file alone.
+        let clean = webp::create_minimal_webp();
+        assert!(
+            strip_metadata_with(&clean, path, &options)
+                .unwrap()
+                .unchanged
+        );
+    }
+
+    #[test]
+    fn test_strip_clean_image_is_not_rebuilt() {
+        // A stale CRC in IEND would be recomputed by a rebuild.
+        let mut png = png::create_minimal_png();
+        let end = png.len();
+        png[end - 4..].copy_from_slice(&[0; 4]);
+
+        let samples = [
+            (jpeg::create_minimal_jpeg(), "test.jpg"),
+            (png, "test.png"),
+            (gif::create_minimal_gif(), "test.gif"),
+            (webp::create_minimal_webp(), "test.webp"),
+            (tiff::create_minimal_tiff(), "test.tiff"),
+        ];
+
+        for (data, name) in samples {
+            let result = strip_metadata(&data, Path::new(name)).unwrap();
+            assert!(result.unchanged, "{}", name);
+            assert_eq!(result.data, data, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_inspect_metadata_all_formats() {
+        let samples = [
+            (jpeg::create_jpeg_with_exif(), "test.jpg"),
+            (png::create_png_with_metadata(), "test.png"),
+            (gif::create_gif_with_comment(), "test.gif"),
+            (webp::create_webp_with_exif(), "test.webp"),
+            (tiff::create_tiff_with_metadata(), "test.tiff"),
+        ];
+
+        for (data, name) in samples {
+            let path = Path::new(name);
+            assert!(
+                !inspect_metadata(&data, path).unwrap().is_empty(),
+                "{}",
+                name
+            );
+            let cleaned = strip_metadata(&data, path).unwrap().data;
+            assert!(
+                inspect_metadata(&cleaned, path).unwrap().is_empty(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_strip_report_all_formats() {
+        let samples = [
+            (jpeg::create_jpeg_with_exif(), "test.jpg"),
+            (png::create_png_with_metadata(), "test.png"),
+            (gif::create_gif_with_comment(), "test.gif"),
+            (webp::create_webp_with_exif(), "test.webp"),
+            (tiff::create_tiff_with_metadata(), "test.tiff"),
+        ];
+
+        for (data, name) in samples {
+            let path = Path::new(name);
+            let result = strip_metadata(&data, path).unwrap();
+            assert_eq!(
+                result.report,
+                strip_report(&data, path).unwrap(),
+                "{}",
+                name
+            );
+            assert!(!result.report.removed.is_empty(), "{}", name);
+            assert!(
+                result
+                    .report
+                    .kept
+                    .iter()
+                    .any(|item| item.reason == metadata::KeepReason::ImageData),
+                "{}",
+                name
+            );
+            // TIFF is rewritten from scratch, so only the other formats
+            // shrink by exactly the removed blocks.
+            if name != "test.tiff" {
+                assert_eq!(
+                    result.report.removed_size(),
+                    result.bytes_removed,
+                    "{}",
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_strip_with_gps_coarsen_all_formats() {
+        let exif = exif::build_test_exif(
+            &[(0x010F, 2, 6, b"Canon\0")],
+            &[
+                (0x0001, 2, 2, b"N\0"),
+                (0x0002, 5, 3, &gps_rationals([48, 51, 3012])),
+                (0x0003, 2, 2, b"E\0"),
+                (0x0004, 5, 3, &gps_rationals([2, 17, 4041])),
+                (0x0006, 5, 1, &[35, 0, 0, 0, 1, 0, 0, 0]),
+            ],
+        );
+        let block = &exif[exif::EXIF_HEADER.len()..];
+        let samples = [
+            (
+                jpeg::insert_exif(&jpeg::create_minimal_jpeg(), block, Path::new("t")).unwrap(),
+                "test.jpg",
+            ),
+            (
+                png::insert_exif(&png::create_minimal_png(), block, Path::new("t")).unwrap(),
+                "test.png",
+            ),
+            (
+                webp::insert_exif(&webp::create_minimal_webp(), block, Path::new("t")).unwrap(),
+                "test.webp",
+            ),
+            (
+                tiff::insert_exif(&tiff::create_minimal_tiff(), block, Path::new("t")).unwrap(),
+                "test.tiff",
+            ),
+        ];
+        let options = StripOptions {
+            gps: GpsMode::Coarsen(10.0),
+            ..StripOptions::default()
+        };
+
+        for (data, name) in samples {
+            let path = Path::new(name);
+            let result = strip_metadata_with(&data, path, &options).unwrap();
+            assert_eq!(result.retained.exif.len(), 5, "{}", name);
+            assert!(verify_result(&result, path).is_ok(), "{}", name);
+
+            let report = strip_report(&result.data, path).unwrap();
+            let fields: Vec<String> = report
+                .removed
+                .iter()
+                .flat_map(|item| item.fields.iter().map(|f| f.name.clone()))
+                .collect();
+            assert_eq!(
+                fields,
+                [
+                    "GPSVersionID",
+                    "GPSLatitudeRef",
+                    "GPSLatitude",
+                    "GPSLongitudeRef",
+                    "GPSLongitude"
+                ],
+                "{}",
+                name
+            );
+
+            let position = exif_block(&result.data, detect_format(&data).unwrap(), &report)
+                .and_then(exif::parse)
+                .and_then(|exif| gps::position(&exif))
+                .unwrap();
+            let step = 10.0 / 111.32;
+            assert!((position.latitude - 48.8584).abs() <= step, "{}", name);
+            let cells = position.latitude / step;
+            assert!((cells - cells.round()).abs() < 1e-3, "{}", name);
+
+            // Without coarsening, or without a position, nothing is kept.
+            let removed = strip_metadata(&data, path).unwrap();
+            assert!(removed.retained.is_empty());
+            assert!(verify_result(&removed, path).is_ok(), "{}", name);
+        }
+
+        let gif = gif::create_gif_with_comment();
+        let result = strip_metadata_with(&gif, Path::new("test.gif"), &options).unwrap();
+        assert!(result.retained.is_empty());
+    }
+
+    #[test]
+    fn test_verify_result_rejects_extra_metadata() {
+        let data = jpeg::create_jpeg_with_exif();
+        let path = Path::new("test.jpg");
+        let mut result = strip_metadata(&data, path).unwrap();
+        result.retained.exif = gps::entries(gps::Position {
+            latitude: 1.0,
+            longitude: 1.0,
+        });
+        result.data = data;
+        assert!(verify_result(&result, path).is_err());
+    }
+
+    #[test]
+    fn test_strip_with_dates_shift_all_formats() {
+        use crate::metadata::dates::Timestamp;
+
+        let ascii = |ifd, tag, text: &str| {
+            let mut value = text.as_bytes().to_vec();
+            value.push(0);
+            exif::Entry::new(ifd, tag, 2, value.len() as u32, value)
+        };
+        let entries = [
+            ascii(Ifd::Primary, 0x010F, "Canon"),
+            ascii(Ifd::Primary, 0x0132, "2024:03:05 14:30:00"),
+            ascii(Ifd::Exif, 0x9003, "2024:03:01 00:15:00"),
+            ascii(Ifd::Exif, 0x9011, "+01:00"),
+        ];
+        let block = exif::build(&entries);
+        let packet = xmp::build(&[(
+            String::from("xmp:CreateDate"),
+            String::from("2024-03-01T00:15:00.5+01:00"),
+        )]);
+        let time = Timestamp::new(2024, 3, 5, 14, 30, 0).unwrap().to_png();
+
+        let t = Path::new("t");
+        let with_xmp = |data: Vec<u8>| {
+            let format = detect_format(&data).unwrap();
+            let data = insert_exif(format, &data, &block, t).unwrap();
+            insert_xmp(format, &data, &packet, t).unwrap()
+        };
+        let mut tiff_entries = entries.to_vec();
+        tiff_entries.push(exif::Entry::new(
+            Ifd::Primary,
+            exif::XMP_TAG,
+            1,
+            packet.len() as u32,
+            packet.clone(),
+        ));
+        let png = with_xmp(png::create_minimal_png());
+        let samples = [
+            (with_xmp(jpeg::create_minimal_jpeg()), "test.jpg"),
+            (png::insert_time(&png, time, t).unwrap(), "test.png"),
+            (with_xmp(webp::create_minimal_webp()), "test.webp"),
+            (
+                tiff::insert_exif(&tiff::create_minimal_tiff(), &exif::build(&tiff_entries), t)
+                    .unwrap(),
+                "test.tiff",
+            ),
+        ];
+        let options = StripOptions {
+            dates: DateMode::Shift(-86_400),
+            ..StripOptions::default()
+        };
+
+        for (data, name) in samples {
+            let path = Path::new(name);
+            let result = strip_metadata_with(&data, path, &options).unwrap();
+            assert!(verify_result(&result, path).is_ok(), "{}", name);
+
+            let mut expected = vec![
+                "DateTime",
+                "DateTimeOriginal",
+                "OffsetTimeOriginal",
+                "xmp:CreateDate",
+            ];
+            if name == "test.png" {
+                expected.push("tIME");
+            }
+            assert_eq!(result.retained.names(), expected, "{}", name);
+
+            let format = detect_format(&data).unwrap();
+            let report = strip_report(&result.data, path).unwrap();
+            let block = exif_block(&result.data, format, &report)
+                .and_then(exif::parse)
+                .unwrap();
+            let value = |ifd, tag| block.find(ifd, tag).and_then(exif::Entry::ascii);
+            assert_eq!(value(Ifd::Primary, 0x010F), None, "{}", name);
+            assert_eq!(
+                value(Ifd::Primary, 0x0132).as_deref(),
+                Some("2024:03:04 14:30:00"),
+                "{}",
+                name
+            );
+            assert_eq!(
+                value(Ifd::Exif, 0x9003).as_deref(),
+                Some("2024:02:29 00:15:00"),
+                "{}",
+                name
+            );
+
+            let packet = xmp_packet(&result.data, format, &report, path).unwrap();
+            assert_eq!(
+                xmp::property(&packet, "xmp:CreateDate").as_deref(),
+                Some("2024-02-29T00:15:00.5+01:00"),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_strip_with_dates_keep_date_only_drops_qualifiers() {
+        let mut data = jpeg::create_minimal_jpeg();
+        let block = exif::build(&[
+            exif::Entry::new(Ifd::Exif, 0x9003, 2, 20, b"2024:03:01 18:45:12\0".to_vec()),
+            exif::Entry::new(Ifd::Exif, 0x9011, 2, 7, b"+01:00\0".to_vec()),
+        ]);
+        data = jpeg::insert_exif(&data, &block, Path::new("t")).unwrap();
+        let options = StripOptions {
+            dates: DateMode::KeepDateOnly,
+            ..StripOptions::default()
+        };
+
+        let path = Path::new("test.jpg");
+        let result = strip_metadata_with(&data, path, &options).unwrap();
+        assert_eq!(result.retained.names(), ["DateTimeOriginal"]);
+        assert_eq!(
+            result.retained.exif[0].ascii().as_deref(),
+            Some("2024:03:01 00:00:00")
+        );
+        assert!(verify_result(&result, path).is_ok());
+    }
+
+    #[test]
+    fn test_strip_with_stamp_all_formats() {
+        let options = StripOptions {
+            stamp: Stamp::parse("copyright=© 2024 Example,artist=Jane,license-url=https://e.org/l")
+                .unwrap(),
+            ..StripOptions::default()
+        };
+        let samples = [
+            (jpeg::create_jpeg_with_exif(), "test.jpg"),
+            (png::create_png_with_metadata(), "test.png"),
+            (webp::create_webp_with_exif(), "test.webp"),
+            (tiff::create_tiff_with_metadata(), "test.tiff"),
+        ];
+
+        for (data, name) in samples {
+            let path = Path::new(name);
+            let result = strip_metadata_with(&data, path, &options).unwrap();
+            assert!(verify_result(&result, path).is_ok(), "{}", name);
+
+            let expected: &[&str] = match name {
+                "test.jpg" | "test.tiff" => &["Artist", "Copyright", "xmpRights:WebStatement"],
+                "test.png" => &["xmpRights:WebStatement", "Copyright", "Author"],
+                _ => &["dc:rights", "dc:creator", "xmpRights:WebStatement"],
+            };
+            assert_eq!(result.retained.names(), expected, "{}", name);
+
+            // The stamp replaces the original metadata rather than adding to it.
+            let restripped = strip_metadata(&result.data, path).unwrap();
+            assert_eq!(
+                restripped.data,
+                strip_metadata(&data, path).unwrap().data,
+                "{}",
+                name
+            );
+        }
+
+        let gif = gif::create_gif_with_comment();
+        let result = strip_metadata_with(&gif, Path::new("test.gif"), &options).unwrap();
+        assert!(result.retained.is_empty());
+    }
+
+    #[test]
+    fn test_strip_with_icc_and_orientation_policy() {
+        let t = Path::new("t");
+        let mut icc = b"ICC_PROFILE\0\x01\x01".to_vec();
+        icc.extend_from_slice(&[0xAB; 32]);
+        let block = exif::build(&[
+            exif::Entry::new(Ifd::Primary, ORIENTATION, 3, 1, vec![6, 0]),
+            exif::Entry::new(Ifd::Primary, 0x010F, 2, 6, b"Canon\0".to_vec()),
+        ]);
+        let data = jpeg::insert_exif(&jpeg::create_minimal_jpeg(), &block, t).unwrap();
+        let data = jpeg::insert_icc(&data, &icc, t).unwrap();
+
+        let path = Path::new("test.jpg");
+        let social = StripOptions {
+            icc: IccMode::Keep,
+            keep_orientation: true,
+            ..StripOptions::default()
+        };
+        let result = strip_metadata_with(&data, path, &social).unwrap();
+        assert!(verify_result(&result, path).is_ok());
+        assert_eq!(result.retained.names(), ["Orientation", "APP2 ICC"]);
+        assert_eq!(result.retained.icc, [icc.clone()]);
+
+        // The default strips the JPEG profile along with the metadata.
+        let result = strip_metadata(&data, path).unwrap();
+        assert!(result.retained.is_empty());
+
+        let profile = exif::Entry::new(Ifd::Primary, 0x8773, 7, 16, vec![0xAB; 16]);
+        let tiff =
+            tiff::insert_exif(&tiff::create_minimal_tiff(), &exif::build(&[profile]), t).unwrap();
+        let path = Path::new("test.tiff");
+        let strict = StripOptions {
+            icc: IccMode::Strip,
+            ..StripOptions::default()
+        };
+        let result = strip_metadata(&tiff, path).unwrap();
+        assert!(
+            result
+                .report
+                .kept
+                .iter()
+                .any(|i| i.kind == "InterColorProfile")
+        );
+        let result = strip_metadata_with(&tiff, path, &strict).unwrap();
+        assert!(verify_result(&result, path).is_ok());
+        let removed = result
+            .report
+            .removed
+            .iter()
+            .find(|i| i.kind == "InterColorProfile")
+            .unwrap();
+        assert_eq!(removed.category, Category::Colour);
+        assert!(
+            !strip_report(&result.data, path)
+                .unwrap()
+                .kept
+                .iter()
+                .any(|i| i.kind == "InterColorProfile")
+        );
+    }
+
+    /// Encode degrees, minutes and hundredths of seconds as GPS rationals.
+    fn gps_rationals([d, m, s]: [u32; 3]) -> Vec<u8> {
+        [(d, 1), (m, 1), (s, 100)]
+            .iter()
+            .flat_map(|&(n, q): &(u32, u32)| n.to_le_bytes().into_iter().chain(q.to_le_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_verify_stripped_unknown() {
+        let err = verify_stripped(&[0u8; 16], Path::new("x.jpg")).unwrap_err();
+        assert!(matches!(err, Error::VerificationFailed { .. }));
+    }
+
     #[test]
     fn test_is_supported_format() {
         let jpeg = [
@@ -21,7 +21,11 @@
 //! - IEND: Image end
 //! - All other ancillary chunks not in the strip list
 
+use crate::codec::{DecodeResult, Frame, inflate};
 use crate::error::{Error, Result};
+use crate::formats::StripReport;
+use crate::metadata::{self, Category, Field, KeepReason, KeptItem, MetadataItem, exif, xmp};
+use std::borrow::Cow;
 use std::path::Path;
 
 /// PNG signature bytes.
@@ -41,6 +45,19 @@ fn is_metadata_chunk(chunk_type: &[u8; 4]) -> bool {
     METADATA_CHUNKS.contains(&chunk_type)
 }
 
+/// Chunk types kept in allowlist mode.
+const LISTED_CHUNKS: &[&[u8; 4]] = &[
+    b"IHDR", b"PLTE", b"IDAT", b"IEND", // Critical.
+    b"tRNS", b"gAMA", b"cHRM", b"sRGB", b"pHYs", // Rendering.
+    b"iCCP", // Colour profile, governed by the ICC mode.
+    b"acTL", b"fcTL", b"fdAT", // APNG animation.
+];
+
+/// Check if a report kind names a chunk that allowlist mode keeps.
+pub fn is_listed(kind: &str) -> bool {
+    kind == "Signature" || LISTED_CHUNKS.iter().any(|c| &c[..] == kind.as_bytes())
+}
+
 /// Calculate CRC32 for PNG chunk validation/creation.
 /// Uses hardware-accelerated implementation when available.
 fn crc32(data: &[u8]) -> u32 {
@@ -125,7 +142,10 @@ fn parse_chunks<'a>(data: &'a [u8], path: &Path) -> Result<Vec<Chunk<'a>>> {
 }
 
 /// Strip metadata from PNG data.
-pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
+///
+/// Returns the input itself when it has no metadata chunks and nothing after
+/// IEND, so a clean file is not rebuilt.
+pub fn strip<'a>(data: &'a [u8], path: &Path) -> Result<Cow<'a, [u8]>> {
     // Validate signature.
     if data.len() < PNG_SIGNATURE.len() {
         return Err(Error::invalid_image(
@@ -152,6 +172,11 @@ pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
         return Err(Error::invalid_image(path, "Missing IEND chunk"));
     }
 
+    let end = PNG_SIGNATURE.len() + chunks.iter().map(|c| 12 + c.data.len()).sum::<usize>();
+    if end == data.len() && !chunks.iter().any(|c| is_metadata_chunk(&c.chunk_type)) {
+        return Ok(Cow::Borrowed(data));
+    }
+
     // Build output, filtering out metadata chunks.
     let mut output = Vec::with_capacity(data.len());
 
@@ -165,9 +190,540 @@ pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
         }
     }
 
+    Ok(Cow::Owned(output))
+}
+
+/// Remove the iCCP chunk.
+pub fn remove_icc(data: &[u8], path: &Path) -> Result<Vec<u8>> {
+    retain_chunks(data, path, |chunk_type| chunk_type != b"iCCP")
+}
+
+/// Remove every chunk not in `LISTED_CHUNKS`.
+pub fn remove_unlisted(data: &[u8], path: &Path) -> Result<Vec<u8>> {
+    retain_chunks(data, path, |chunk_type| LISTED_CHUNKS.contains(&chunk_type))
+}
+
+/// Rebuild PNG data from the chunks `keep` accepts.
+fn retain_chunks(data: &[u8], path: &Path, keep: impl Fn(&[u8; 4]) -> bool) -> Result<Vec<u8>> {
+    let chunks = parse_chunks(data, path)?;
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(&PNG_SIGNATURE);
+    for chunk in chunks.iter().filter(|c| keep(&c.chunk_type)) {
+        chunk.write_to(&mut output);
+    }
+    Ok(output)
+}
+
+/// Add a chunk to stripped PNG data.
+///
+/// It goes after IHDR and any metadata chunks already added, so chunks end
+/// up in the order they were inserted.
+fn insert_chunk(data: &[u8], chunk_type: &[u8; 4], body: &[u8], path: &Path) -> Result<Vec<u8>> {
+    if !data.starts_with(&PNG_SIGNATURE) {
+        return Err(Error::invalid_image(path, "Invalid PNG signature"));
+    }
+    let chunks = parse_chunks(data, path)?;
+    let position = chunks
+        .iter()
+        .position(|c| &c.chunk_type == b"IHDR")
+        .ok_or_else(|| Error::invalid_image(path, "Missing IHDR chunk"))?;
+    let position = position
+        + 1
+        + chunks[position + 1..]
+            .iter()
+            .take_while(|c| is_metadata_chunk(&c.chunk_type))
+            .count();
+
+    let mut output = Vec::with_capacity(data.len() + body.len() + 12);
+    output.extend_from_slice(&PNG_SIGNATURE);
+    for (i, chunk) in chunks.iter().enumerate() {
+        if i == position {
+            Chunk {
+                chunk_type: *chunk_type,
+                data: body,
+            }
+            .write_to(&mut output);
+        }
+        chunk.write_to(&mut output);
+    }
+
     Ok(output)
 }
 
+/// Add an eXIf chunk to stripped PNG data.
+///
+/// `block` is a TIFF structure without the `Exif\0\0` identifier, as the
+/// PNG specification requires.
+pub fn insert_exif(data: &[u8], block: &[u8], path: &Path) -> Result<Vec<u8>> {
+    insert_chunk(data, b"eXIf", block, path)
+}
+
+/// Add an uncompressed iTXt chunk holding an XMP packet to stripped PNG data.
+pub fn insert_xmp(data: &[u8], packet: &[u8], path: &Path) -> Result<Vec<u8>> {
+    insert_itxt(data, XMP_KEYWORD, packet, path)
+}
+
+/// Add an uncompressed iTXt chunk with the given keyword to stripped PNG data.
+pub fn insert_text(data: &[u8], keyword: &str, text: &str, path: &Path) -> Result<Vec<u8>> {
+    insert_itxt(data, keyword.as_bytes(), text.as_bytes(), path)
+}
+
+/// Add an uncompressed iTXt chunk to stripped PNG data.
+fn insert_itxt(data: &[u8], keyword: &[u8], text: &[u8], path: &Path) -> Result<Vec<u8>> {
+    // Keyword, compression flag and method, empty language and translated
+    // keyword, then the text.
+    let mut body = keyword.to_vec();
+    body.extend_from_slice(&[0, 0, 0, 0, 0]);
+    body.extend_from_slice(text);
+    insert_chunk(data, b"iTXt", &body, path)
+}
+
+/// Add a tIME chunk to stripped PNG data.
+pub fn insert_time(data: &[u8], time: [u8; 7], path: &Path) -> Result<Vec<u8>> {
+    insert_chunk(data, b"tIME", &time, path)
+}
+
+/// Get the body of the first tIME chunk.
+pub fn time(data: &[u8], path: &Path) -> Option<[u8; 7]> {
+    let chunks = parse_chunks(data, path).ok()?;
+    let chunk = chunks.iter().find(|c| &c.chunk_type == b"tIME")?;
+    chunk.data.try_into().ok()
+}
+
+/// Get the XMP packet of the first iTXt chunk that carries one.
+pub fn xmp_packet(data: &[u8], path: &Path) -> Option<Vec<u8>> {
+    itxt(data, XMP_KEYWORD, path)
+}
+
+/// Get the text of the first iTXt chunk with the given keyword.
+pub fn text(data: &[u8], keyword: &str, path: &Path) -> Option<String> {
+    itxt(data, keyword.as_bytes(), path).and_then(|text| String::from_utf8(text).ok())
+}
+
+/// Get the text of the first iTXt chunk with the given keyword, as bytes.
+fn itxt(data: &[u8], keyword: &[u8], path: &Path) -> Option<Vec<u8>> {
+    let chunks = parse_chunks(data, path).ok()?;
+    chunks
+        .iter()
+        .filter(|c| &c.chunk_type == b"iTXt")
+        .find_map(|c| itxt_text(c.data, keyword))
+}
+
+/// Decode the text of an iTXt chunk body, if it has the given keyword.
+fn itxt_text(body: &[u8], keyword: &[u8]) -> Option<Vec<u8>> {
+    let nul = body.iter().position(|&b| b == 0)?;
+    if &body[..nul] != keyword {
+        return None;
+    }
+    // Compression flag, method, then NUL-terminated language and
+    // translated keyword precede the text.
+    let rest = body.get(nul + 3..).unwrap_or_default();
+    let text = rest.splitn(3, |&b| b == 0).nth(2).unwrap_or_default();
+    if body.get(nul + 1) == Some(&1) {
+        inflate::zlib_decompress(text, MAX_TEXT_SIZE).ok()
+    } else {
+        Some(text.to_vec())
+    }
+}
+
+/// Keyword under which PNG text chunks carry an XMP packet.
+const XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+
+/// Upper bound on decompressed text chunk size when inspecting.
+const MAX_TEXT_SIZE: usize = 16 * 1024 * 1024;
+
+/// Decode the fields of a text chunk.
+///
+/// XMP packets are decoded into their properties; any other chunk yields a
+/// single field named after its keyword.
+fn text_fields(chunk_type: &[u8; 4], body: &[u8]) -> Vec<Field> {
+    let Some(nul) = body.iter().position(|&b| b == 0) else {
+        return Vec::new();
+    };
+    let keyword = &body[..nul];
+
+    if &chunk_type[..] == b"iTXt" && keyword == XMP_KEYWORD {
+        return itxt_text(body, XMP_KEYWORD)
+            .map(|text| xmp::fields(&text))
+            .unwrap_or_default();
+    }
+
+    let keyword = String::from_utf8_lossy(keyword).into_owned();
+    let category = metadata::classify_keyword(&keyword);
+    vec![Field::new(keyword, category)]
+}
+
+/// Get the reason `strip` keeps a chunk.
+fn keep_reason(chunk_type: &[u8; 4]) -> KeepReason {
+    match chunk_type {
+        b"IHDR" | b"PLTE" | b"tRNS" => KeepReason::Decoding,
+        b"IDAT" => KeepReason::ImageData,
+        b"gAMA" | b"cHRM" | b"sRGB" | b"iCCP" | b"sBIT" | b"bKGD" | b"pHYs" => {
+            KeepReason::Rendering
+        }
+        b"acTL" | b"fcTL" | b"fdAT" => KeepReason::Animation,
+        _ => KeepReason::Structure,
+    }
+}
+
+/// Describe the chunks that `strip` removes and keeps.
+///
+/// Text chunks are named by keyword (XMP packets are decoded), eXIf chunks
+/// are decoded as EXIF. Data after IEND is reported as removed. Fails on the
+/// same structural errors as `strip`.
+pub fn report(data: &[u8], path: &Path) -> Result<StripReport> {
+    if !data.starts_with(&PNG_SIGNATURE) {
+        return Err(Error::invalid_image(path, "Invalid PNG signature"));
+    }
+
+    let chunks = parse_chunks(data, path)?;
+    if !chunks.iter().any(|c| &c.chunk_type == b"IHDR") {
+        return Err(Error::invalid_image(path, "Missing IHDR chunk"));
+    }
+    if !chunks.iter().any(|c| &c.chunk_type == b"IEND") {
+        return Err(Error::invalid_image(path, "Missing IEND chunk"));
+    }
+
+    let mut report = StripReport::default();
+    report.kept.push(KeptItem::new(
+        "Signature",
+        0..PNG_SIGNATURE.len(),
+        KeepReason::Structure,
+    ));
+    let mut pos = PNG_SIGNATURE.len();
+
+    for chunk in &chunks {
+        let range = pos..pos + 12 + chunk.data.len();
+        pos = range.end;
+        let kind = String::from_utf8_lossy(&chunk.chunk_type).into_owned();
+
+        if !is_metadata_chunk(&chunk.chunk_type) {
+            let reason = keep_reason(&chunk.chunk_type);
+            report.kept.push(KeptItem::new(kind, range, reason));
+            continue;
+        }
+
+        let item = match &chunk.chunk_type {
+            b"eXIf" => MetadataItem::new(kind, range, Category::Other)
+                .with_fields(exif::fields(chunk.data)),
+            b"tIME" => MetadataItem::new(kind, range, Category::Time),
+            chunk_type => MetadataItem::new(kind, range, Category::Other)
+                .with_fields(text_fields(chunk_type, chunk.data)),
+        };
+        report.removed.push(item);
+    }
+
+    if pos < data.len() {
+        report.removed.push(MetadataItem::new(
+            "Trailing data",
+            pos..data.len(),
+            Category::Other,
+        ));
+    }
+
+    Ok(report)
+}
+
+/// Verify that PNG data is structurally sound and free of metadata chunks.
+///
+/// Checks every chunk length and CRC, requires IHDR first, at least one
+/// IDAT, and requires the file to end exactly at IEND.
+pub fn verify(data: &[u8], path: &Path) -> Result<()> {
+    let fail = |reason: String| Error::verification_failed(path, reason);
+
+    if !data.starts_with(&PNG_SIGNATURE) {
+        return Err(fail(String::from("Invalid PNG signature")));
+    }
+
+    let mut pos = PNG_SIGNATURE.len();
+    let mut first = true;
+    let mut has_idat = false;
+
+    loop {
+        if pos + 12 > data.len() {
+            return Err(fail(String::from("Missing IEND chunk")));
+        }
+
+        let length =
+            u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+        let chunk_type: [u8; 4] = [data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]];
+        let name = String::from_utf8_lossy(&chunk_type).into_owned();
+
+        if length > i32::MAX as usize || pos + 12 + length > data.len() {
+            return Err(fail(format!("Chunk {} extends beyond file", name)));
+        }
+
+        let crc_pos = pos + 8 + length;
+        let stored_crc = u32::from_be_bytes([
+            data[crc_pos],
+            data[crc_pos + 1],
+            data[crc_pos + 2],
+            data[crc_pos + 3],
+        ]);
+        if crc32(&data[pos + 4..crc_pos]) != stored_crc {
+            return Err(fail(format!("CRC mismatch in {} chunk", name)));
+        }
+
+        if first && &chunk_type != b"IHDR" {
+            return Err(fail(String::from("First chunk is not IHDR")));
+        }
+        first = false;
+
+        if is_metadata_chunk(&chunk_type) {
+            return Err(fail(format!("Metadata chunk {} remains", name)));
+        }
+
+        pos = crc_pos + 4;
+
+        match &chunk_type {
+            b"IDAT" => has_idat = true,
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+
+    if !has_idat {
+        return Err(fail(String::from("Missing IDAT chunk")));
+    }
+    if pos != data.len() {
+        return Err(fail(format!(
+            "{} bytes of trailing data after IEND",
+            data.len() - pos
+        )));
+    }
+
+    Ok(())
+}
+
+/// Adam7 interlace passes: (x start, y start, x step, y step).
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Read a big-endian u32 from the start of a slice.
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_be_bytes([data[0], data[1], data[2], data[3]])
+}
+
+/// Decode every frame of a PNG to unfiltered samples.
+///
+/// APNG animation frames are decoded alongside the default image. Interlaced
+/// images keep their Adam7 passes in pass order. Each frame header records
+/// IHDR, PLTE, tRNS and the frame control data (without its sequence number),
+/// so palette or placement changes are detected as well as sample changes.
+pub fn decode_frames(data: &[u8], path: &Path) -> Result<Vec<Frame>> {
+    if !data.starts_with(&PNG_SIGNATURE) {
+        return Err(Error::invalid_image(path, "Invalid PNG signature"));
+    }
+
+    let chunks = parse_chunks(data, path)?;
+    let ihdr = chunks
+        .iter()
+        .find(|c| &c.chunk_type == b"IHDR" && c.data.len() == 13)
+        .ok_or_else(|| Error::invalid_image(path, "Missing or invalid IHDR chunk"))?;
+
+    let channels = match ihdr.data[9] {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => return Err(Error::invalid_image(path, "Unsupported PNG colour type")),
+    };
+    let bits_per_pixel = channels * ihdr.data[8] as usize;
+    let interlaced = ihdr.data[12] == 1;
+
+    let mut base_header = ihdr.data.to_vec();
+    for chunk in &chunks {
+        if &chunk.chunk_type == b"PLTE" || &chunk.chunk_type == b"tRNS" {
+            base_header.extend_from_slice(&chunk.chunk_type);
+            base_header.extend_from_slice(chunk.data);
+        }
+    }
+
+    // Group compressed image data by frame: (frame control, zlib stream).
+    let mut groups: Vec<(Option<&[u8]>, Vec<u8>)> = Vec::new();
+    let mut idat_group: Option<usize> = None;
+    for chunk in &chunks {
+        match &chunk.chunk_type {
+            b"fcTL" => groups.push((Some(chunk.data), Vec::new())),
+            b"IDAT" => {
+                let index = match idat_group {
+                    Some(index) => index,
+                    None => {
+                        // A frame control directly before IDAT makes the
+                        // default image the first animation frame.
+                        if !matches!(groups.last(), Some((Some(_), d)) if d.is_empty()) {
+                            groups.push((None, Vec::new()));
+                        }
+                        groups.len() - 1
+                    }
+                };
+                idat_group = Some(index);
+                groups[index].1.extend_from_slice(chunk.data);
+            }
+            b"fdAT" => {
+                let group = groups
+                    .last_mut()
+                    .filter(|g| g.0.is_some())
+                    .ok_or_else(|| Error::invalid_image(path, "fdAT chunk without fcTL"))?;
+                if chunk.data.len() < 4 {
+                    return Err(Error::invalid_image(path, "Truncated fdAT chunk"));
+                }
+                group.1.extend_from_slice(&chunk.data[4..]);
+            }
+            _ => {}
+        }
+    }
+
+    if idat_group.is_none() {
+        return Err(Error::invalid_image(path, "Missing IDAT chunk"));
+    }
+
+    let mut frames = Vec::with_capacity(groups.len());
+    for (control, compressed) in groups {
+        let mut header = base_header.clone();
+        let (width, height) = match control {
+            Some(fctl) if fctl.len() >= 26 => {
+                header.extend_from_slice(&fctl[4..]);
+                (read_u32(&fctl[4..]), read_u32(&fctl[8..]))
+            }
+            Some(_) => return Err(Error::invalid_image(path, "Truncated fcTL chunk")),
+            None => (read_u32(ihdr.data), read_u32(&ihdr.data[4..])),
+        };
+
+        let pixels = decode_image(&compressed, width, height, bits_per_pixel, interlaced)
+            .map_err(|reason| Error::invalid_image(path, reason))?;
+
+        frames.push(Frame {
+            width,
+            height,
+            header,
+            pixels,
+        });
+    }
+
+    Ok(frames)
+}
+
+/// Inflate and unfilter one image (or animation frame).
+fn decode_image(
+    compressed: &[u8],
+    width: u32,
+    height: u32,
+    bits_per_pixel: usize,
+    interlaced: bool,
+) -> DecodeResult<Vec<u8>> {
+    let (width, height) = (width as usize, height as usize);
+    let passes: Vec<(usize, usize)> = if interlaced {
+        ADAM7
+            .iter()
+            .map(|&(x0, y0, dx, dy)| {
+                (
+                    width.saturating_sub(x0).div_ceil(dx),
+                    height.saturating_sub(y0).div_ceil(dy),
+                )
+            })
+            .filter(|&(w, h)| w > 0 && h > 0)
+            .collect()
+    } else {
+        vec![(width, height)]
+    };
+
+    let row_bytes = |w: usize| {
+        w.checked_mul(bits_per_pixel)
+            .map(|bits| bits.div_ceil(8))
+            .ok_or("Image dimensions too large")
+    };
+
+    let mut expected: usize = 0;
+    for &(w, h) in &passes {
+        expected = (row_bytes(w)? + 1)
+            .checked_mul(h)
+            .and_then(|size| expected.checked_add(size))
+            .ok_or("Image dimensions too large")?;
+    }
+
+    let raw = inflate::zlib_decompress(compressed, expected)?;
+    if raw.len() != expected {
+        return Err("Image data is shorter than expected");
+    }
+
+    let bpp = bits_per_pixel.div_ceil(8).max(1);
+    let mut pixels = Vec::with_capacity(expected);
+    let mut pos = 0;
+    for (w, h) in passes {
+        let stride = row_bytes(w)? + 1;
+        unfilter(&raw[pos..pos + stride * h], stride - 1, bpp, &mut pixels)?;
+        pos += stride * h;
+    }
+
+    Ok(pixels)
+}
+
+/// Reverse PNG scanline filtering, appending the reconstructed rows.
+fn unfilter(raw: &[u8], row_bytes: usize, bpp: usize, output: &mut Vec<u8>) -> DecodeResult<()> {
+    let mut prev = vec![0u8; row_bytes];
+
+    for row in raw.chunks_exact(row_bytes + 1) {
+        let mut cur = row[1..].to_vec();
+        match row[0] {
+            0 => {}
+            1 => {
+                for i in bpp..row_bytes {
+                    cur[i] = cur[i].wrapping_add(cur[i - bpp]);
+                }
+            }
+            2 => {
+                for i in 0..row_bytes {
+                    cur[i] = cur[i].wrapping_add(prev[i]);
+                }
+            }
+            3 => {
+                for i in 0..row_bytes {
+                    let left = if i >= bpp { cur[i - bpp] } else { 0 };
+                    cur[i] = cur[i].wrapping_add(((left as u16 + prev[i] as u16) / 2) as u8);
+                }
+            }
+            4 => {
+                for i in 0..row_bytes {
+                    let (left, up_left) = if i >= bpp {
+                        (cur[i - bpp], prev[i - bpp])
+                    } else {
+                        (0, 0)
+                    };
+                    cur[i] = cur[i].wrapping_add(paeth(left, prev[i], up_left));
+                }
+            }
+            _ => return Err("Invalid PNG filter type"),
+        }
+        output.extend_from_slice(&cur);
+        prev = cur;
+    }
+
+    Ok(())
+}
+
+/// The Paeth predictor.
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
 /// Create a minimal valid PNG for testing.
 #[cfg(test)]
 pub fn create_minimal_png() -> Vec<u8> {
@@ -196,7 +752,7 @@ pub fn create_minimal_png() -> Vec<u8> {
     // This is zlib-compressed: filter byte (0) + pixel value (0).
     let idat_data = [
         0x78, 0x9C, // zlib header
-        0x62, 0x60, 0x00, 0x00, // compressed data
+        0x63, 0x60, 0x00, 0x00, // compressed data
         0x00, 0x02, 0x00, 0x01, // adler32
     ];
     let idat = Chunk {
@@ -257,7 +813,7 @@ pub fn create_png_with_metadata() -> Vec<u8> {
     time.write_to(&mut data);
 
     // IDAT chunk.
-    let idat_data = [0x78, 0x9C, 0x62, 0x60, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01];
+    let idat_data = [0x78, 0x9C, 0x63, 0x60, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01];
     let idat = Chunk {
         chunk_type: *b"IDAT",
         data: &idat_data,
@@ -275,6 +831,7 @@ pub fn create_png_with_metadata() -> Vec<u8> {
 }
 
 #[cfg(test)]
+#[allow(clippy::borrow_deref_ref)]
 mod tests {
     use super::*;
     use std::path::PathBuf;
@@ -341,6 +898,36 @@ mod tests {
         assert!(types.contains(&&*b"IEND"));
     }
 
+    #[test]
+    fn test_verify_stripped_png() {
+        let data = create_png_with_metadata();
+        let result = strip(&data, &test_path()).unwrap();
+        assert!(verify(&result, &test_path()).is_ok());
+    }
+
+    #[test]
+    fn test_verify_rejects_metadata() {
+        let data = create_png_with_metadata();
+        let err = verify(&data, &test_path()).unwrap_err();
+        assert!(err.to_string().contains("tEXt"));
+    }
+
+    #[test]
+    fn test_verify_rejects_bad_crc() {
+        let mut data = create_minimal_png();
+        // Flip a bit in the IHDR CRC.
+        data[8 + 8 + 13] ^= 0x01;
+        let err = verify(&data, &test_path()).unwrap_err();
+        assert!(err.to_string().contains("CRC mismatch"));
+    }
+
+    #[test]
+    fn test_verify_rejects_trailing_data() {
+        let mut data = create_minimal_png();
+        data.extend_from_slice(b"trailer");
+        assert!(verify(&data, &test_path()).is_err());
+    }
+
     #[test]
     fn test_invalid_too_small() {
         let data = [0x89, 0x50];
@@ -377,4 +964,314 @@ mod tests {
         // Check data.
         assert_eq!(&output[8..11], &[0x01, 0x02, 0x03]);
     }
+
+    /// Wrap raw scanlines in a zlib stream using a single stored block.
+    fn zlib_stored(raw: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x78, 0x01, 0x01];
+        out.extend_from_slice(&(raw.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(!(raw.len() as u16)).to_le_bytes());
+        out.extend_from_slice(raw);
+        out.extend_from_slice(&inflate::adler32(raw).to_be_bytes());
+        out
+    }
+
+    /// Build a PNG from IHDR fields and a list of extra chunks.
+    fn build_png(
+        width: u32,
+        height: u32,
+        color: u8,
+        interlace: u8,
+        chunks: &[(&[u8; 4], Vec<u8>)],
+    ) -> Vec<u8> {
+        let mut data = PNG_SIGNATURE.to_vec();
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&width.to_be_bytes());
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        ihdr.extend_from_slice(&[8, color, 0, 0, interlace]);
+        Chunk {
+            chunk_type: *b"IHDR",
+            data: &ihdr,
+        }
+        .write_to(&mut data);
+        for (chunk_type, body) in chunks {
+            Chunk {
+                chunk_type: **chunk_type,
+                data: body,
+            }
+            .write_to(&mut data);
+        }
+        Chunk {
+            chunk_type: *b"IEND",
+            data: &[],
+        }
+        .write_to(&mut data);
+        data
+    }
+
+    #[test]
+    fn test_decode_minimal_png() {
+        let frames = decode_frames(&create_minimal_png(), &test_path()).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!((frames[0].width, frames[0].height), (1, 1));
+        assert_eq!(frames[0].pixels, vec![0]);
+    }
+
+    #[test]
+    fn test_decode_unaffected_by_strip() {
+        let data = create_png_with_metadata();
+        let stripped = strip(&data, &test_path()).unwrap();
+        assert_eq!(
+            decode_frames(&data, &test_path()).unwrap(),
+            decode_frames(&stripped, &test_path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decode_all_filter_types() {
+        // 3x4 grayscale, one row per filter: Sub, Up, Average, Paeth.
+        let raw = [1, 10, 5, 5, 2, 1, 1, 1, 3, 0, 0, 0, 4, 1, 1, 1];
+        let png = build_png(3, 4, 0, 0, &[(b"IDAT", zlib_stored(&raw))]);
+        let frames = decode_frames(&png, &test_path()).unwrap();
+        assert_eq!(
+            frames[0].pixels,
+            vec![10, 15, 20, 11, 16, 21, 5, 10, 15, 6, 11, 16]
+        );
+    }
+
+    #[test]
+    fn test_decode_interlaced() {
+        // 2x2 grayscale has Adam7 passes 1 (1x1), 6 (1x1) and 7 (2x1).
+        let raw = [0, 1, 0, 2, 0, 3, 4];
+        let png = build_png(2, 2, 0, 1, &[(b"IDAT", zlib_stored(&raw))]);
+        let frames = decode_frames(&png, &test_path()).unwrap();
+        assert_eq!(frames[0].pixels, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_decode_rejects_wrong_size() {
+        let raw = [0, 1, 0, 2];
+        let png = build_png(1, 1, 0, 0, &[(b"IDAT", zlib_stored(&raw))]);
+        assert!(decode_frames(&png, &test_path()).is_err());
+    }
+
+    #[test]
+    fn test_decode_apng_frames() {
+        let fctl = |seq: u32| {
+            let mut body = seq.to_be_bytes().to_vec();
+            body.extend_from_slice(&1u32.to_be_bytes()); // Width.
+            body.extend_from_slice(&1u32.to_be_bytes()); // Height.
+            body.extend_from_slice(&[0; 8]); // Offsets.
+            body.extend_from_slice(&[0, 1, 0, 10, 0, 0]); // Delay, dispose, blend.
+            body
+        };
+        let mut fdat = 2u32.to_be_bytes().to_vec();
+        fdat.extend_from_slice(&zlib_stored(&[0, 7]));
+
+        let png = build_png(
+            1,
+            1,
+            0,
+            0,
+            &[
+                (b"acTL", vec![0, 0, 0, 2, 0, 0, 0, 0]),
+                (b"fcTL", fctl(0)),
+                (b"IDAT", zlib_stored(&[0, 3])),
+                (b"fcTL", fctl(1)),
+                (b"fdAT", fdat),
+            ],
+        );
+        let frames = decode_frames(&png, &test_path()).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].pixels, vec![3]);
+        assert_eq!(frames[1].pixels, vec![7]);
+    }
+
+    #[test]
+    fn test_decode_palette_in_header() {
+        let idat = zlib_stored(&[0, 0]);
+        let red = build_png(
+            1,
+            1,
+            3,
+            0,
+            &[(b"PLTE", vec![255, 0, 0]), (b"IDAT", idat.clone())],
+        );
+        let blue = build_png(1, 1, 3, 0, &[(b"PLTE", vec![0, 0, 255]), (b"IDAT", idat)]);
+        let red = decode_frames(&red, &test_path()).unwrap();
+        let blue = decode_frames(&blue, &test_path()).unwrap();
+        assert_eq!(red[0].pixels, blue[0].pixels);
+        assert_ne!(red[0].header, blue[0].header);
+    }
+
+    #[test]
+    fn test_report_png_with_metadata() {
+        let data = create_png_with_metadata();
+        let items = report(&data, &test_path()).unwrap().removed;
+        let kinds: Vec<&str> = items.iter().map(|i| i.kind.as_str()).collect();
+        assert_eq!(kinds, ["tEXt", "tIME"]);
+        assert_eq!(items[0].fields[0].name, "Comment");
+        assert_eq!(
+            metadata::categories(&items),
+            vec![Category::Time, Category::Other]
+        );
+
+        let removed: usize = items.iter().map(|i| i.size()).sum();
+        assert_eq!(
+            removed,
+            data.len() - strip(&data, &test_path()).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn test_report_compressed_xmp() {
+        let packet = b"<rdf:Description exif:GPSLatitude='1'/>";
+        let mut body = XMP_KEYWORD.to_vec();
+        body.extend_from_slice(&[0, 1, 0, 0, 0]);
+        body.extend_from_slice(&zlib_stored(packet));
+        let idat = zlib_stored(&[0, 0]);
+        let png = build_png(1, 1, 0, 0, &[(b"iTXt", body), (b"IDAT", idat)]);
+
+        let items = report(&png, &test_path()).unwrap().removed;
+        assert_eq!(
+            items[0].fields,
+            vec![Field::new("exif:GPSLatitude", Category::Location)]
+        );
+    }
+
+    #[test]
+    fn test_report_clean_png() {
+        assert!(
+            report(&create_minimal_png(), &test_path())
+                .unwrap()
+                .removed
+                .is_empty()
+        );
+        assert!(report(b"not a png", &test_path()).is_err());
+    }
+
+    #[test]
+    fn test_report_kept_chunks() {
+        let mut data = create_png_with_metadata();
+        data.extend_from_slice(b"trailer");
+        let report = report(&data, &test_path()).unwrap();
+
+        let kept: Vec<(&str, KeepReason)> = report
+            .kept
+            .iter()
+            .map(|i| (i.kind.as_str(), i.reason))
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                ("Signature", KeepReason::Structure),
+                ("IHDR", KeepReason::Decoding),
+                ("IDAT", KeepReason::ImageData),
+                ("IEND", KeepReason::Structure),
+            ]
+        );
+        assert_eq!(report.removed.last().unwrap().kind, "Trailing data");
+        assert_eq!(
+            report.removed_size(),
+            (data.len() - strip(&data, &test_path()).unwrap().len()) as u64
+        );
+    }
+
+    #[test]
+    fn test_insert_exif_after_ihdr() {
+        let stripped = strip(&create_png_with_metadata(), &test_path())
+            .unwrap()
+            .into_owned();
+        let block = exif::build(&[]);
+        let result = insert_exif(&stripped, &block, &test_path()).unwrap();
+
+        let chunks = parse_chunks(&result, &test_path()).unwrap();
+        assert_eq!(&chunks[0].chunk_type, b"IHDR");
+        assert_eq!(&chunks[1].chunk_type, b"eXIf");
+        assert_eq!(chunks[1].data, block.as_slice());
+        assert_eq!(strip(&result, &test_path()).unwrap(), stripped);
+    }
+
+    #[test]
+    fn test_insert_xmp_and_time() {
+        let stripped = strip(&create_png_with_metadata(), &test_path())
+            .unwrap()
+            .into_owned();
+        let packet = xmp::build(&[(String::from("xmp:CreateDate"), String::from("2024-01-02"))]);
+        let time = [0x07, 0xE8, 1, 2, 0, 0, 0];
+        let result = insert_exif(&stripped, &exif::build(&[]), &test_path()).unwrap();
+        let result = insert_xmp(&result, &packet, &test_path()).unwrap();
+        let result = insert_time(&result, time, &test_path()).unwrap();
+
+        let chunks = parse_chunks(&result, &test_path()).unwrap();
+        let types: Vec<&[u8; 4]> = chunks.iter().take(4).map(|c| &c.chunk_type).collect();
+        assert_eq!(types, [b"IHDR", b"eXIf", b"iTXt", b"tIME"]);
+        assert_eq!(xmp_packet(&result, &test_path()), Some(packet));
+        assert_eq!(super::time(&result, &test_path()), Some(time));
+
+        let fields: Vec<String> = report(&result, &test_path())
+            .unwrap()
+            .removed
+            .iter()
+            .flat_map(|item| item.fields.iter().map(|f| f.name.clone()))
+            .collect();
+        assert_eq!(fields, ["xmp:CreateDate"]);
+        assert_eq!(strip(&result, &test_path()).unwrap(), stripped);
+    }
+
+    #[test]
+    fn test_insert_text() {
+        let stripped = strip(&create_png_with_metadata(), &test_path())
+            .unwrap()
+            .into_owned();
+        let result = insert_text(&stripped, "Copyright", "© 2024 Example", &test_path()).unwrap();
+        let result = insert_text(&result, "Author", "Jane Doe", &test_path()).unwrap();
+
+        assert_eq!(
+            text(&result, "Copyright", &test_path()).as_deref(),
+            Some("© 2024 Example")
+        );
+        assert_eq!(
+            text(&result, "Author", &test_path()).as_deref(),
+            Some("Jane Doe")
+        );
+        assert_eq!(text(&result, "Title", &test_path()), None);
+
+        let items = report(&result, &test_path()).unwrap().removed;
+        let fields: Vec<&str> = items
+            .iter()
+            .flat_map(|item| item.fields.iter().map(|f| f.name.as_str()))
+            .collect();
+        assert_eq!(fields, ["Copyright", "Author"]);
+        assert_eq!(strip(&result, &test_path()).unwrap(), stripped);
+    }
+
+    #[test]
+    fn test_remove_icc() {
+        let stripped = strip(&create_png_with_metadata(), &test_path())
+            .unwrap()
+            .into_owned();
+        let result = insert_chunk(&stripped, b"iCCP", b"sRGB\0\0\x78\x9c", &test_path()).unwrap();
+        let kept = report(&result, &test_path()).unwrap().kept;
+        assert!(kept.iter().any(|item| item.kind == "iCCP"));
+        assert_eq!(remove_icc(&result, &test_path()).unwrap(), stripped);
+    }
+
+    #[test]
+    fn test_remove_unlisted() {
+        let minimal = create_minimal_png();
+        let data = insert_chunk(&minimal, b"prVt", b"private", &test_path()).unwrap();
+
+        // An unknown ancillary chunk survives the default strip.
+        assert_eq!(strip(&data, &test_path()).unwrap(), &data[..]);
+        let kept = report(&data, &test_path()).unwrap().kept;
+        assert!(
+            kept.iter()
+                .any(|item| item.kind == "prVt" && !is_listed(&item.kind))
+        );
+
+        let result = remove_unlisted(&data, &test_path()).unwrap();
+        assert_eq!(result, minimal);
+        let kept = report(&result, &test_path()).unwrap().kept;
+        assert!(kept.iter().all(|item| is_listed(&item.kind)));
+    }
 }
@@ -28,9 +28,15 @@
 //!
 //! Note: TIFF stripping is complex due to the IFD structure. This implementation
 //! rewrites the file by copying essential tags and image data while skipping
-//! metadata tags.
+//! metadata tags. Out-of-line values and strip/tile data are relocated and all
+//! offsets rewritten, so nothing in the output points back into the original.
 
 use crate::error::{Error, Result};
+use crate::formats::StripReport;
+use crate::metadata::exif::{self, Ifd};
+use crate::metadata::{Category, Field, KeepReason, KeptItem, MetadataItem, iptc, xmp};
+use std::borrow::Cow;
+use std::ops::Range;
 use std::path::Path;
 
 /// Byte order markers.
@@ -78,24 +84,52 @@ impl ByteOrder {
 #[allow(dead_code)]
 mod tags {
     // Essential image tags to keep.
+    pub const NEW_SUBFILE_TYPE: u16 = 254;
+    pub const SUBFILE_TYPE: u16 = 255;
     pub const IMAGE_WIDTH: u16 = 256;
     pub const IMAGE_LENGTH: u16 = 257;
     pub const BITS_PER_SAMPLE: u16 = 258;
     pub const COMPRESSION: u16 = 259;
     pub const PHOTOMETRIC_INTERPRETATION: u16 = 262;
+    pub const THRESHHOLDING: u16 = 263;
+    pub const CELL_WIDTH: u16 = 264;
+    pub const CELL_LENGTH: u16 = 265;
+    pub const FILL_ORDER: u16 = 266;
     pub const STRIP_OFFSETS: u16 = 273;
+    pub const ORIENTATION: u16 = 274;
     pub const SAMPLES_PER_PIXEL: u16 = 277;
     pub const ROWS_PER_STRIP: u16 = 278;
     pub const STRIP_BYTE_COUNTS: u16 = 279;
+    pub const MIN_SAMPLE_VALUE: u16 = 280;
+    pub const MAX_SAMPLE_VALUE: u16 = 281;
     pub const X_RESOLUTION: u16 = 282;
     pub const Y_RESOLUTION: u16 = 283;
     pub const PLANAR_CONFIGURATION: u16 = 284;
+    pub const GRAY_RESPONSE_UNIT: u16 = 290;
+    pub const GRAY_RESPONSE_CURVE: u16 = 291;
+    pub const T4_OPTIONS: u16 = 292;
+    pub const T6_OPTIONS: u16 = 293;
     pub const RESOLUTION_UNIT: u16 = 296;
+    pub const PREDICTOR: u16 = 317;
+    pub const COLOR_MAP: u16 = 320;
     pub const TILE_WIDTH: u16 = 322;
     pub const TILE_LENGTH: u16 = 323;
     pub const TILE_OFFSETS: u16 = 324;
     pub const TILE_BYTE_COUNTS: u16 = 325;
+    pub const EXTRA_SAMPLES: u16 = 338;
     pub const SAMPLE_FORMAT: u16 = 339;
+    pub const JPEG_TABLES: u16 = 347;
+    pub const JPEG_INTERCHANGE_FORMAT: u16 = 513;
+    pub const JPEG_INTERCHANGE_FORMAT_LENGTH: u16 = 514;
+    pub const YCBCR_COEFFICIENTS: u16 = 529;
+    pub const YCBCR_SUB_SAMPLING: u16 = 530;
+    pub const YCBCR_POSITIONING: u16 = 531;
+    pub const REFERENCE_BLACK_WHITE: u16 = 532;
+
+    // Structural tags whose offsets cannot be relocated.
+    pub const FREE_OFFSETS: u16 = 288;
+    pub const FREE_BYTE_COUNTS: u16 = 289;
+    pub const SUB_IFDS: u16 = 330;
 
     // Metadata tags to strip.
     pub const IMAGE_DESCRIPTION: u16 = 270;
@@ -133,11 +167,105 @@ const METADATA_TAGS: &[u16] = &[
     tags::INTEROPERABILITY_IFD,
 ];
 
+/// Tags that point at file regions this rewriter does not relocate.
+///
+/// Their offsets would dangle after rewriting, so they are dropped. Free
+/// space holds no image data; SubIFDs do, so files with them are refused
+/// instead (see `reject_sub_ifds`).
+const UNRELOCATABLE_TAGS: &[u16] = &[tags::FREE_OFFSETS, tags::FREE_BYTE_COUNTS];
+
 /// Check if a tag is metadata that should be stripped.
 fn is_metadata_tag(tag: u16) -> bool {
     METADATA_TAGS.contains(&tag)
 }
 
+/// Tags kept in allowlist mode: the baseline image tags, the extension tags
+/// needed to decode tiled, predicted, JPEG-compressed and YCbCr images, and
+/// the colour profile, which is governed by the ICC mode.
+const LISTED_TAGS: &[u16] = &[
+    tags::NEW_SUBFILE_TYPE,
+    tags::SUBFILE_TYPE,
+    tags::IMAGE_WIDTH,
+    tags::IMAGE_LENGTH,
+    tags::BITS_PER_SAMPLE,
+    tags::COMPRESSION,
+    tags::PHOTOMETRIC_INTERPRETATION,
+    tags::THRESHHOLDING,
+    tags::CELL_WIDTH,
+    tags::CELL_LENGTH,
+    tags::FILL_ORDER,
+    tags::STRIP_OFFSETS,
+    tags::ORIENTATION,
+    tags::SAMPLES_PER_PIXEL,
+    tags::ROWS_PER_STRIP,
+    tags::STRIP_BYTE_COUNTS,
+    tags::MIN_SAMPLE_VALUE,
+    tags::MAX_SAMPLE_VALUE,
+    tags::X_RESOLUTION,
+    tags::Y_RESOLUTION,
+    tags::PLANAR_CONFIGURATION,
+    tags::GRAY_RESPONSE_UNIT,
+    tags::GRAY_RESPONSE_CURVE,
+    tags::T4_OPTIONS,
+    tags::T6_OPTIONS,
+    tags::RESOLUTION_UNIT,
+    tags::PREDICTOR,
+    tags::COLOR_MAP,
+    tags::TILE_WIDTH,
+    tags::TILE_LENGTH,
+    tags::TILE_OFFSETS,
+    tags::TILE_BYTE_COUNTS,
+    tags::EXTRA_SAMPLES,
+    tags::SAMPLE_FORMAT,
+    tags::JPEG_TABLES,
+    tags::JPEG_INTERCHANGE_FORMAT,
+    tags::JPEG_INTERCHANGE_FORMAT_LENGTH,
+    tags::YCBCR_COEFFICIENTS,
+    tags::YCBCR_SUB_SAMPLING,
+    tags::YCBCR_POSITIONING,
+    tags::REFERENCE_BLACK_WHITE,
+    tags::ICC_PROFILE,
+];
+
+/// Check if a report kind names a tag or block that allowlist mode keeps.
+pub fn is_listed(kind: &str) -> bool {
+    kind == "Header"
+        || ["IFD ", "Strip ", "Tile ", "JPEG stream "]
+            .iter()
+            .any(|prefix| kind.starts_with(prefix))
+        || LISTED_TAGS.iter().any(|&tag| tag_name(tag) == kind)
+}
+
+/// Get the name reports give a tag.
+fn tag_name(tag: u16) -> String {
+    exif::Entry {
+        ifd: Ifd::Primary,
+        tag,
+        field_type: 0,
+        count: 0,
+        value: Vec::new(),
+    }
+    .name()
+}
+
+/// Check if a tag references file regions that cannot be relocated.
+fn is_unrelocatable_tag(tag: u16) -> bool {
+    UNRELOCATABLE_TAGS.contains(&tag)
+}
+
+/// Refuse IFDs with SubIFDs, which hold images of their own (the full-size
+/// image of a DNG, the reduced resolutions of a pyramid TIFF). The rewriter
+/// does not relocate them, and dropping them would lose those images.
+fn reject_sub_ifds(entries: &[IfdEntry], path: &Path) -> Result<()> {
+    if entries.iter().any(|e| e.tag == tags::SUB_IFDS) {
+        return Err(Error::invalid_image(
+            path,
+            "SubIFDs are not supported; refusing to drop the images they hold",
+        ));
+    }
+    Ok(())
+}
+
 /// TIFF field type sizes.
 fn type_size(field_type: u16) -> usize {
     match field_type {
@@ -145,6 +273,7 @@ fn type_size(field_type: u16) -> usize {
         3 | 8 => 2,         // SHORT, SSHORT
         4 | 9 | 11 => 4,    // LONG, SLONG, FLOAT
         5 | 10 | 12 => 8,   // RATIONAL, SRATIONAL, DOUBLE
+        13 => 4,            // IFD
         _ => 1,
     }
 }
@@ -159,10 +288,22 @@ struct IfdEntry {
 }
 
 impl IfdEntry {
-    /// Check if the value is stored inline (within the 4-byte value field).
-    fn is_inline(&self) -> bool {
-        let size = type_size(self.field_type) * self.count as usize;
-        size <= 4
+    /// Size of the entry value in bytes, if it fits in memory.
+    fn value_size(&self) -> Option<usize> {
+        type_size(self.field_type).checked_mul(self.count as usize)
+    }
+
+    /// Get the raw value bytes, either inline or from the referenced offset.
+    ///
+    /// Returns `None` if the value lies outside the file.
+    fn value<'a>(&'a self, data: &'a [u8], byte_order: ByteOrder) -> Option<&'a [u8]> {
+        let size = self.value_size()?;
+        if size <= 4 {
+            Some(&self.value_offset[..size])
+        } else {
+            let offset = byte_order.read_u32(&self.value_offset) as usize;
+            data.get(offset..offset.checked_add(size)?)
+        }
     }
 }
 
@@ -210,8 +351,8 @@ fn parse_ifd(
     Ok((entries, next_ifd))
 }
 
-/// Strip metadata from TIFF data.
-pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
+/// Read the TIFF header, returning the byte order and first IFD offset.
+fn parse_header(data: &[u8], path: &Path) -> Result<(ByteOrder, usize)> {
     // Validate minimum size.
     if data.len() < 8 {
         return Err(Error::invalid_image(
@@ -241,24 +382,394 @@ pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
         return Err(Error::invalid_image(path, "IFD offset beyond file"));
     }
 
-    // Parse all IFDs and collect entries to keep.
-    let mut all_entries: Vec<(Vec<IfdEntry>, usize)> = Vec::new();
-    let mut current_offset = first_ifd_offset;
+    Ok((byte_order, first_ifd_offset))
+}
+
+/// Parse the chain of IFDs starting at `first_offset`.
+fn parse_ifd_chain(
+    data: &[u8],
+    first_offset: usize,
+    byte_order: ByteOrder,
+    path: &Path,
+) -> Result<Vec<Vec<IfdEntry>>> {
+    let mut ifds = Vec::new();
+    let mut visited = Vec::new();
+    let mut current_offset = first_offset;
+
+    while current_offset != 0 {
+        if current_offset >= data.len() {
+            return Err(Error::invalid_image(path, "IFD offset beyond file"));
+        }
+        if visited.contains(&current_offset) {
+            return Err(Error::invalid_image(path, "IFD chain contains a loop"));
+        }
+        visited.push(current_offset);
 
-    while current_offset != 0 && current_offset < data.len() {
         let (entries, next_ifd) = parse_ifd(data, current_offset, byte_order, path)?;
+        ifds.push(entries);
+        current_offset = next_ifd as usize;
+    }
+
+    Ok(ifds)
+}
+
+/// Read numeric values (SHORT or LONG) from an IFD entry.
+fn read_offset_values(data: &[u8], entry: &IfdEntry, byte_order: ByteOrder) -> Option<Vec<u32>> {
+    let value = entry.value(data, byte_order)?;
+
+    match entry.field_type {
+        // SHORT
+        3 => Some(
+            value
+                .chunks_exact(2)
+                .map(|c| byte_order.read_u16(c) as u32)
+                .collect(),
+        ),
+        // LONG or IFD
+        4 | 13 => Some(
+            value
+                .chunks_exact(4)
+                .map(|c| byte_order.read_u32(c))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Get the tag holding the byte counts for an offsets tag.
+fn byte_counts_tag(offsets_tag: u16) -> Option<u16> {
+    match offsets_tag {
+        tags::STRIP_OFFSETS => Some(tags::STRIP_BYTE_COUNTS),
+        tags::TILE_OFFSETS => Some(tags::TILE_BYTE_COUNTS),
+        tags::JPEG_INTERCHANGE_FORMAT => Some(tags::JPEG_INTERCHANGE_FORMAT_LENGTH),
+        _ => None,
+    }
+}
+
+/// Read the (offset, length) pairs of the image data blocks referenced by an offsets entry.
+fn read_data_blocks(
+    data: &[u8],
+    entries: &[IfdEntry],
+    offsets_entry: &IfdEntry,
+    byte_order: ByteOrder,
+    path: &Path,
+) -> Result<Vec<(usize, usize)>> {
+    let counts_tag = byte_counts_tag(offsets_entry.tag).unwrap_or_default();
+    let counts_entry = entries
+        .iter()
+        .find(|e| e.tag == counts_tag)
+        .ok_or_else(|| Error::invalid_image(path, "Image data offsets without byte counts"))?;
+
+    let offsets = read_offset_values(data, offsets_entry, byte_order)
+        .ok_or_else(|| Error::invalid_image(path, "Invalid image data offsets"))?;
+    let counts = read_offset_values(data, counts_entry, byte_order)
+        .ok_or_else(|| Error::invalid_image(path, "Invalid image data byte counts"))?;
+
+    if offsets.len() != counts.len() {
+        return Err(Error::invalid_image(
+            path,
+            "Mismatched image data offsets and byte counts",
+        ));
+    }
+
+    let blocks: Vec<(usize, usize)> = offsets
+        .iter()
+        .zip(counts.iter())
+        .map(|(&offset, &count)| (offset as usize, count as usize))
+        .collect();
+
+    if blocks
+        .iter()
+        .any(|&(offset, count)| offset.saturating_add(count) > data.len())
+    {
+        return Err(Error::invalid_image(path, "Image data extends beyond file"));
+    }
+
+    Ok(blocks)
+}
+
+/// Convert an output position to a 32-bit TIFF offset.
+fn to_offset(pos: usize, path: &Path) -> Result<u32> {
+    u32::try_from(pos).map_err(|_| Error::invalid_image(path, "Output exceeds TIFF 4 GiB limit"))
+}
+
+/// Pad output to a word boundary, as required for TIFF offsets.
+fn align_word(output: &mut Vec<u8>) {
+    if output.len() % 2 != 0 {
+        output.push(0);
+    }
+}
+
+/// Entries to add to an IFD, taken from a decoded EXIF block.
+///
+/// Their out-of-line values live in a buffer of their own, laid out so that
+/// `write_ifd` can copy them like the values of any other IFD.
+#[derive(Default)]
+struct Directory {
+    entries: Vec<IfdEntry>,
+    data: Vec<u8>,
+}
+
+impl Directory {
+    /// Lay out one directory of a decoded EXIF block in the file's byte order.
+    ///
+    /// Sub-IFD pointers are skipped; they are rebuilt by `write_ifd`.
+    fn new(block: &exif::Exif, ifd: Ifd, byte_order: ByteOrder) -> Self {
+        let swap = (block.byte_order == exif::ByteOrder::Big) != (byte_order == ByteOrder::Big);
+        let mut data = Vec::new();
+        let mut entries: Vec<IfdEntry> = block
+            .entries
+            .iter()
+            .filter(|entry| entry.ifd == ifd && !entry.is_pointer())
+            .map(|entry| {
+                let mut entry = entry.clone();
+                if swap {
+                    entry.swap_byte_order();
+                }
+                let mut value_offset = [0; 4];
+                if entry.value.len() <= 4 {
+                    value_offset[..entry.value.len()].copy_from_slice(&entry.value);
+                } else {
+                    align_word(&mut data);
+                    value_offset = byte_order.write_u32(data.len() as u32);
+                    data.extend_from_slice(&entry.value);
+                }
+                IfdEntry {
+                    tag: entry.tag,
+                    field_type: entry.field_type,
+                    count: entry.count,
+                    value_offset,
+                }
+            })
+            .collect();
+        entries.sort_by_key(|e| e.tag);
+
+        Self { entries, data }
+    }
+}
 
-        // Filter out metadata tags.
-        let filtered: Vec<IfdEntry> = entries
+/// A sub-IFD to add under a pointer tag.
+struct SubIfd {
+    tag: u16,
+    directory: Directory,
+}
+
+/// Entries and sub-IFDs to add to an IFD while writing it.
+#[derive(Default)]
+struct Additions {
+    entries: Directory,
+    sub_ifds: Vec<SubIfd>,
+}
+
+impl Additions {
+    /// Collect everything in a decoded EXIF block for the first IFD.
+    fn new(block: &exif::Exif, byte_order: ByteOrder) -> Self {
+        let sub_ifds = [(tags::EXIF_IFD, Ifd::Exif), (tags::GPS_IFD, Ifd::Gps)]
             .into_iter()
-            .filter(|e| !is_metadata_tag(e.tag))
+            .map(|(tag, ifd)| SubIfd {
+                tag,
+                directory: Directory::new(block, ifd, byte_order),
+            })
+            .filter(|sub| !sub.directory.entries.is_empty())
             .collect();
 
-        all_entries.push((filtered, current_offset));
-        current_offset = next_ifd as usize;
+        Self {
+            entries: Directory::new(block, Ifd::Primary, byte_order),
+            sub_ifds,
+        }
+    }
+
+    /// Check if an existing entry is replaced by one of the additions.
+    fn replaces(&self, tag: u16) -> bool {
+        self.entries.entries.iter().any(|e| e.tag == tag)
+            || self.sub_ifds.iter().any(|sub| sub.tag == tag)
     }
+}
+
+/// A row of an IFD table being written.
+enum Row<'a> {
+    Entry(&'a IfdEntry),
+    Added(&'a IfdEntry),
+    SubIfd(&'a SubIfd),
+}
 
-    // Build output.
+/// Write an IFD and everything it references, returning the position of its next-IFD pointer.
+///
+/// Out-of-line values and image data blocks are copied after the entry table and
+/// all offsets are rewritten to point at their new locations. The entries in
+/// `additions` are merged in tag order, and each of its sub-IFDs gets a pointer
+/// entry and is written after the image data.
+fn write_ifd(
+    output: &mut Vec<u8>,
+    entries: &[IfdEntry],
+    data: &[u8],
+    byte_order: ByteOrder,
+    path: &Path,
+    additions: &Additions,
+) -> Result<usize> {
+    let mut rows: Vec<Row> = entries.iter().map(Row::Entry).collect();
+    rows.extend(additions.entries.entries.iter().map(Row::Added));
+    rows.extend(additions.sub_ifds.iter().map(Row::SubIfd));
+    rows.sort_by_key(|row| match row {
+        Row::Entry(entry) | Row::Added(entry) => entry.tag,
+        Row::SubIfd(sub) => sub.tag,
+    });
+
+    let entries_start = output.len() + 2;
+    let next_ptr_pos = entries_start + rows.len() * 12;
+
+    output.extend_from_slice(&byte_order.write_u16(rows.len() as u16));
+    output.resize(next_ptr_pos + 4, 0);
+
+    // Offsets entries to patch once image data has been placed:
+    // (position of the offsets array, source blocks).
+    let mut pending_blocks: Vec<(usize, Vec<(usize, usize)>)> = Vec::new();
+    // Sub-IFD pointers to patch once the sub-IFDs have been written.
+    let mut pending_sub_ifds: Vec<(usize, &SubIfd)> = Vec::new();
+
+    for (i, row) in rows.into_iter().enumerate() {
+        let entry_pos = entries_start + i * 12;
+        let (entry, source) = match row {
+            Row::Entry(entry) => (entry, data),
+            Row::Added(entry) => (entry, additions.entries.data.as_slice()),
+            Row::SubIfd(sub) => {
+                output[entry_pos..entry_pos + 2].copy_from_slice(&byte_order.write_u16(sub.tag));
+                output[entry_pos + 2..entry_pos + 4].copy_from_slice(&byte_order.write_u16(4));
+                output[entry_pos + 4..entry_pos + 8].copy_from_slice(&byte_order.write_u32(1));
+                pending_sub_ifds.push((entry_pos + 8, sub));
+                continue;
+            }
+        };
+        output[entry_pos..entry_pos + 2].copy_from_slice(&byte_order.write_u16(entry.tag));
+
+        if byte_counts_tag(entry.tag).is_some() {
+            // Offsets are always rewritten as LONG values.
+            let blocks = read_data_blocks(data, entries, entry, byte_order, path)?;
+            output[entry_pos + 2..entry_pos + 4].copy_from_slice(&byte_order.write_u16(4));
+            output[entry_pos + 4..entry_pos + 8]
+                .copy_from_slice(&byte_order.write_u32(blocks.len() as u32));
+
+            let array_pos = if blocks.len() <= 1 {
+                entry_pos + 8
+            } else {
+                align_word(output);
+                let pos = output.len();
+                output.resize(pos + blocks.len() * 4, 0);
+                let offset = to_offset(pos, path)?;
+                output[entry_pos + 8..entry_pos + 12]
+                    .copy_from_slice(&byte_order.write_u32(offset));
+                pos
+            };
+            pending_blocks.push((array_pos, blocks));
+            continue;
+        }
+
+        let value = entry
+            .value(source, byte_order)
+            .ok_or_else(|| Error::invalid_image(path, "IFD entry value extends beyond file"))?;
+
+        output[entry_pos + 2..entry_pos + 4]
+            .copy_from_slice(&byte_order.write_u16(entry.field_type));
+        output[entry_pos + 4..entry_pos + 8].copy_from_slice(&byte_order.write_u32(entry.count));
+
+        if value.len() <= 4 {
+            output[entry_pos + 8..entry_pos + 8 + value.len()].copy_from_slice(value);
+        } else {
+            align_word(output);
+            let offset = to_offset(output.len(), path)?;
+            output.extend_from_slice(value);
+            output[entry_pos + 8..entry_pos + 12].copy_from_slice(&byte_order.write_u32(offset));
+        }
+    }
+
+    // Copy image strip/tile data and patch the offsets.
+    for (array_pos, blocks) in pending_blocks {
+        for (j, (offset, count)) in blocks.into_iter().enumerate() {
+            align_word(output);
+            let new_offset = to_offset(output.len(), path)?;
+            output.extend_from_slice(&data[offset..offset + count]);
+            let slot = array_pos + j * 4;
+            output[slot..slot + 4].copy_from_slice(&byte_order.write_u32(new_offset));
+        }
+    }
+
+    for (slot, sub) in pending_sub_ifds {
+        align_word(output);
+        let offset = to_offset(output.len(), path)?;
+        output[slot..slot + 4].copy_from_slice(&byte_order.write_u32(offset));
+        let directory = &sub.directory;
+        write_ifd(
+            output,
+            &directory.entries,
+            &directory.data,
+            byte_order,
+            path,
+            &Additions::default(),
+        )?;
+    }
+
+    Ok(next_ptr_pos)
+}
+
+/// Strip metadata from TIFF data.
+///
+/// Returns the input itself when no IFD holds a tag to remove, so a clean
+/// file keeps its own layout instead of the one `write_tiff` produces.
+pub fn strip<'a>(data: &'a [u8], path: &Path) -> Result<Cow<'a, [u8]>> {
+    let (byte_order, first_ifd_offset) = parse_header(data, path)?;
+    let chain = parse_ifd_chain(data, first_ifd_offset, byte_order, path)?;
+
+    let removes = |e: &IfdEntry| is_metadata_tag(e.tag) || is_unrelocatable_tag(e.tag);
+    if !chain.iter().flatten().any(removes) {
+        for entries in &chain {
+            reject_sub_ifds(entries, path)?;
+        }
+        return Ok(Cow::Borrowed(data));
+    }
+
+    // Collect the entries to keep from every IFD.
+    let ifds: Vec<Vec<IfdEntry>> = chain
+        .into_iter()
+        .map(|entries| entries.into_iter().filter(|e| !removes(e)).collect())
+        .collect();
+
+    write_tiff(&ifds, data, byte_order, path, &Additions::default()).map(Cow::Owned)
+}
+
+/// Remove the InterColorProfile tag.
+pub fn remove_icc(data: &[u8], path: &Path) -> Result<Vec<u8>> {
+    retain_tags(data, path, |tag| tag != tags::ICC_PROFILE)
+}
+
+/// Remove every tag not in `LISTED_TAGS`.
+pub fn remove_unlisted(data: &[u8], path: &Path) -> Result<Vec<u8>> {
+    retain_tags(data, path, |tag| LISTED_TAGS.contains(&tag))
+}
+
+/// Rewrite TIFF data with only the tags `keep` accepts.
+fn retain_tags(data: &[u8], path: &Path, keep: impl Fn(u16) -> bool) -> Result<Vec<u8>> {
+    let (byte_order, first_ifd_offset) = parse_header(data, path)?;
+    let ifds: Vec<Vec<IfdEntry>> = parse_ifd_chain(data, first_ifd_offset, byte_order, path)?
+        .into_iter()
+        .map(|entries| entries.into_iter().filter(|e| keep(e.tag)).collect())
+        .collect();
+
+    write_tiff(&ifds, data, byte_order, path, &Additions::default())
+}
+
+/// Write a TIFF file from the given IFDs, relocating everything they reference.
+///
+/// `additions` are merged into the first IFD.
+fn write_tiff(
+    ifds: &[Vec<IfdEntry>],
+    data: &[u8],
+    byte_order: ByteOrder,
+    path: &Path,
+    additions: &Additions,
+) -> Result<Vec<u8>> {
+    for entries in ifds {
+        reject_sub_ifds(entries, path)?;
+    }
     let mut output = Vec::with_capacity(data.len());
 
     // Write header.
@@ -269,126 +780,242 @@ pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
     }
     output.extend_from_slice(&byte_order.write_u16(42));
 
-    // Placeholder for first IFD offset.
-    let ifd_offset_pos = output.len();
+    // Pointer to the first IFD, patched as each IFD is written.
+    let mut pointer_pos = output.len();
     output.extend_from_slice(&[0, 0, 0, 0]);
 
-    // Track data that needs to be copied (image strips/tiles).
-    let mut data_to_copy: Vec<(usize, usize)> = Vec::new();
+    for (i, entries) in ifds.iter().enumerate() {
+        align_word(&mut output);
+        let ifd_offset = to_offset(output.len(), path)?;
+        output[pointer_pos..pointer_pos + 4].copy_from_slice(&byte_order.write_u32(ifd_offset));
+        let none = Additions::default();
+        let additions = if i == 0 { additions } else { &none };
+        pointer_pos = write_ifd(&mut output, entries, data, byte_order, path, additions)?;
+    }
 
-    // Write IFDs.
-    for (i, (entries, _original_offset)) in all_entries.iter().enumerate() {
-        let ifd_start = output.len();
+    Ok(output)
+}
 
-        // Update IFD offset pointer.
-        if i == 0 {
-            let offset_bytes = byte_order.write_u32(ifd_start as u32);
-            output[ifd_offset_pos..ifd_offset_pos + 4].copy_from_slice(&offset_bytes);
-        }
+/// Add the tags of an EXIF block to stripped TIFF data.
+///
+/// Its IFD0 tags are merged into the first IFD, replacing any with the same
+/// tag, and its EXIF and GPS directories become sub-IFDs of the first IFD.
+/// Everything is converted to the file's byte order.
+pub fn insert_exif(data: &[u8], block: &[u8], path: &Path) -> Result<Vec<u8>> {
+    let block =
+        exif::parse(block).ok_or_else(|| Error::invalid_image(path, "Invalid EXIF block"))?;
+    let (byte_order, first_ifd_offset) = parse_header(data, path)?;
+    let mut ifds = parse_ifd_chain(data, first_ifd_offset, byte_order, path)?;
+
+    let additions = Additions::new(&block, byte_order);
+    if let Some(first) = ifds.first_mut() {
+        first.retain(|e| !additions.replaces(e.tag));
+    }
 
-        // Write entry count.
-        output.extend_from_slice(&byte_order.write_u16(entries.len() as u16));
+    write_tiff(&ifds, data, byte_order, path, &additions)
+}
 
-        // Write entries.
-        for entry in entries {
-            output.extend_from_slice(&byte_order.write_u16(entry.tag));
-            output.extend_from_slice(&byte_order.write_u16(entry.field_type));
-            output.extend_from_slice(&byte_order.write_u32(entry.count));
-            output.extend_from_slice(&entry.value_offset);
-
-            // Track strip/tile offsets and byte counts for later copying.
-            if entry.tag == tags::STRIP_OFFSETS || entry.tag == tags::TILE_OFFSETS {
-                let offsets = read_offset_values(data, entry, byte_order);
-                let byte_counts_entry = entries
-                    .iter()
-                    .find(|e| e.tag == tags::STRIP_BYTE_COUNTS || e.tag == tags::TILE_BYTE_COUNTS);
-
-                if let Some(bc_entry) = byte_counts_entry {
-                    let counts = read_offset_values(data, bc_entry, byte_order);
-                    for (offset, count) in offsets.iter().zip(counts.iter()) {
-                        data_to_copy.push((*offset as usize, *count as usize));
-                    }
-                }
+/// Get the byte range of a sub-IFD's entry table.
+fn ifd_range(data: &[u8], offset: usize, byte_order: ByteOrder) -> Option<Range<usize>> {
+    let count = byte_order.read_u16(data.get(offset..offset + 2)?) as usize;
+    let end = offset + 2 + count * 12 + 4;
+    (end <= data.len()).then_some(offset..end)
+}
+
+/// Describe a metadata tag that `strip` would remove.
+///
+/// `entry_pos` is the position of the 12-byte IFD entry. The reported range
+/// is the out-of-line value (or sub-IFD) when there is one, and the entry
+/// itself otherwise.
+fn metadata_item(
+    data: &[u8],
+    entry: &IfdEntry,
+    entry_pos: usize,
+    byte_order: ByteOrder,
+    sub_ifds: &exif::Exif,
+) -> MetadataItem {
+    let value = entry.value(data, byte_order).unwrap_or_default();
+    let offset = byte_order.read_u32(&entry.value_offset) as usize;
+    let out_of_line = entry.value_size().is_some_and(|size| size > 4) && !value.is_empty();
+
+    let mut range = if out_of_line {
+        offset..offset + value.len()
+    } else {
+        entry_pos..entry_pos + 12
+    };
+
+    let sub_fields = |ifds: &[Ifd]| -> Vec<Field> {
+        sub_ifds
+            .entries
+            .iter()
+            .filter(|e| ifds.contains(&e.ifd) && !e.is_pointer())
+            .map(exif::Entry::field)
+            .collect()
+    };
+
+    let named = exif::Entry {
+        ifd: Ifd::Primary,
+        tag: entry.tag,
+        field_type: entry.field_type,
+        count: entry.count,
+        value: Vec::new(),
+    };
+
+    let (kind, category, fields) = match entry.tag {
+        tags::EXIF_IFD | tags::GPS_IFD | tags::INTEROPERABILITY_IFD => {
+            if let Some(table) = ifd_range(data, offset, byte_order) {
+                range = table;
+            }
+            match entry.tag {
+                tags::EXIF_IFD => (
+                    "EXIF IFD",
+                    Category::Other,
+                    sub_fields(&[Ifd::Exif, Ifd::Interop]),
+                ),
+                tags::GPS_IFD => ("GPS IFD", Category::Location, sub_fields(&[Ifd::Gps])),
+                _ => ("Interop IFD", Category::Other, sub_fields(&[Ifd::Interop])),
             }
         }
+        tags::XMP => ("XMP", Category::Other, xmp::fields(value)),
+        tags::IPTC => ("IPTC", Category::Other, iptc::fields(value)),
+        tags::PHOTOSHOP => ("Photoshop", Category::Other, iptc::fields(value)),
+        _ => return MetadataItem::new(named.name(), range, named.category()),
+    };
 
-        // Write next IFD pointer.
-        let next_ifd = if i + 1 < all_entries.len() {
-            // We'll update this later.
-            0u32
-        } else {
-            0u32
+    MetadataItem::new(kind, range, category).with_fields(fields)
+}
+
+/// Describe a tag that `strip` keeps, followed by its image data blocks.
+///
+/// The range is the out-of-line value when there is one, and the 12-byte
+/// IFD entry otherwise.
+fn kept_items(
+    data: &[u8],
+    entries: &[IfdEntry],
+    entry: &IfdEntry,
+    entry_pos: usize,
+    byte_order: ByteOrder,
+    path: &Path,
+) -> Result<Vec<KeptItem>> {
+    let reason = if entry.tag == tags::ICC_PROFILE {
+        KeepReason::Rendering
+    } else {
+        KeepReason::Decoding
+    };
+
+    let value_len = entry.value(data, byte_order).map_or(0, <[u8]>::len);
+    let range = if value_len > 4 {
+        let offset = byte_order.read_u32(&entry.value_offset) as usize;
+        offset..offset + value_len
+    } else {
+        entry_pos..entry_pos + 12
+    };
+    let mut items = vec![KeptItem::new(tag_name(entry.tag), range, reason)];
+
+    if byte_counts_tag(entry.tag).is_some() {
+        let blocks = read_data_blocks(data, entries, entry, byte_order, path)?;
+        let kind = match entry.tag {
+            tags::TILE_OFFSETS => "Tile",
+            tags::STRIP_OFFSETS => "Strip",
+            _ => "JPEG stream",
         };
-        output.extend_from_slice(&byte_order.write_u32(next_ifd));
+        items.extend(blocks.into_iter().enumerate().map(|(i, (offset, count))| {
+            KeptItem::new(
+                format!("{} {}", kind, i),
+                offset..offset + count,
+                KeepReason::ImageData,
+            )
+        }));
     }
 
-    // Copy non-inline entry data and image data.
-    // For simplicity, we append all original data that's referenced by entries.
-    // This is a conservative approach - a more optimized version would rebuild
-    // the file more carefully.
+    Ok(items)
+}
 
-    // Copy image strip/tile data.
-    for (offset, count) in data_to_copy {
-        if offset + count <= data.len() {
-            output.extend_from_slice(&data[offset..offset + count]);
+/// Describe the tags and blocks that `strip` removes and keeps.
+///
+/// EXIF and GPS sub-IFDs, XMP and IPTC blocks are decoded far enough to name
+/// their fields. Tags pointing at regions that cannot be relocated are
+/// reported as removed. Fails on the same structural errors as `strip`.
+pub fn report(data: &[u8], path: &Path) -> Result<StripReport> {
+    let (byte_order, first_ifd_offset) = parse_header(data, path)?;
+    let sub_ifds =
+        exif::parse(data).ok_or_else(|| Error::invalid_image(path, "Invalid TIFF header"))?;
+
+    let mut report = StripReport::default();
+    report
+        .kept
+        .push(KeptItem::new("Header", 0..8, KeepReason::Structure));
+    let mut visited = Vec::new();
+    let mut offset = first_ifd_offset;
+
+    while offset != 0 {
+        if offset >= data.len() {
+            return Err(Error::invalid_image(path, "IFD offset beyond file"));
         }
-    }
+        if visited.contains(&offset) {
+            return Err(Error::invalid_image(path, "IFD chain contains a loop"));
+        }
+        visited.push(offset);
+
+        let (entries, next_ifd) = parse_ifd(data, offset, byte_order, path)?;
+        reject_sub_ifds(&entries, path)?;
+        report.kept.push(KeptItem::new(
+            format!("IFD {}", visited.len() - 1),
+            offset..offset + 2 + entries.len() * 12 + 4,
+            KeepReason::Structure,
+        ));
 
-    // If output is smaller than a reasonable minimum, just return original data
-    // (stripping may have failed for complex TIFF structures).
-    if output.len() < 100 && data.len() > 100 {
-        return Ok(data.to_vec());
+        for (i, entry) in entries.iter().enumerate() {
+            let entry_pos = offset + 2 + i * 12;
+            if is_metadata_tag(entry.tag) || is_unrelocatable_tag(entry.tag) {
+                let item = metadata_item(data, entry, entry_pos, byte_order, &sub_ifds);
+                report.removed.push(item);
+            } else {
+                let items = kept_items(data, &entries, entry, entry_pos, byte_order, path)?;
+                report.kept.extend(items);
+            }
+        }
+        offset = next_ifd as usize;
     }
 
-    Ok(output)
+    Ok(report)
 }
 
-/// Read offset/count values from an IFD entry.
-fn read_offset_values(data: &[u8], entry: &IfdEntry, byte_order: ByteOrder) -> Vec<u32> {
-    let mut values = Vec::with_capacity(entry.count as usize);
+/// Verify that TIFF data is structurally sound and free of metadata tags.
+///
+/// Checks that every IFD, out-of-line value and image data block lies inside
+/// the file and that the IFD chain terminates.
+pub fn verify(data: &[u8], path: &Path) -> Result<()> {
+    let fail = |reason: String| Error::verification_failed(path, reason);
 
-    if entry.is_inline() {
-        // Values stored in the value_offset field directly.
-        match entry.field_type {
-            3 => {
-                // SHORT
-                for i in 0..entry.count.min(2) as usize {
-                    values.push(byte_order.read_u16(&entry.value_offset[i * 2..]) as u32);
-                }
-            }
-            4 => {
-                // LONG
-                if entry.count >= 1 {
-                    values.push(byte_order.read_u32(&entry.value_offset));
-                }
+    let (byte_order, first_ifd_offset) =
+        parse_header(data, path).map_err(|e| fail(e.to_string()))?;
+    let ifds = parse_ifd_chain(data, first_ifd_offset, byte_order, path)
+        .map_err(|e| fail(e.to_string()))?;
+
+    if ifds.is_empty() {
+        return Err(fail(String::from("No image file directories")));
+    }
+
+    for entries in &ifds {
+        for entry in entries {
+            if is_metadata_tag(entry.tag) {
+                return Err(fail(format!("Metadata tag {} remains", entry.tag)));
             }
-            _ => {}
-        }
-    } else {
-        // Values stored at offset.
-        let offset = byte_order.read_u32(&entry.value_offset) as usize;
-        match entry.field_type {
-            3 => {
-                // SHORT
-                for i in 0..entry.count as usize {
-                    if offset + i * 2 + 2 <= data.len() {
-                        values.push(byte_order.read_u16(&data[offset + i * 2..]) as u32);
-                    }
-                }
+            if entry.value(data, byte_order).is_none() {
+                return Err(fail(format!(
+                    "Value of tag {} points outside the file",
+                    entry.tag
+                )));
             }
-            4 => {
-                // LONG
-                for i in 0..entry.count as usize {
-                    if offset + i * 4 + 4 <= data.len() {
-                        values.push(byte_order.read_u32(&data[offset + i * 4..]));
-                    }
-                }
+            if byte_counts_tag(entry.tag).is_some() {
+                read_data_blocks(data, entries, entry, byte_order, path)
+                    .map_err(|e| fail(e.to_string()))?;
             }
-            _ => {}
         }
     }
 
-    values
+    Ok(())
 }
 
 /// Create a minimal valid TIFF for testing.
@@ -585,6 +1212,99 @@ mod tests {
         assert!(!result.is_empty(), "Result should not be empty");
     }
 
+    #[test]
+    fn test_strip_relocates_strip_data() {
+        let data = create_tiff_with_metadata();
+        let result = strip(&data, &test_path()).unwrap();
+
+        let (byte_order, ifd_offset) = parse_header(&result, &test_path()).unwrap();
+        let ifds = parse_ifd_chain(&result, ifd_offset, byte_order, &test_path()).unwrap();
+        assert_eq!(ifds.len(), 1);
+        assert!(ifds[0].iter().all(|e| !is_metadata_tag(e.tag)));
+
+        let offsets = ifds[0]
+            .iter()
+            .find(|e| e.tag == tags::STRIP_OFFSETS)
+            .unwrap();
+        let blocks =
+            read_data_blocks(&result, &ifds[0], offsets, byte_order, &test_path()).unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(
+            result[blocks[0].0], 0xFF,
+            "Strip offset should point at pixel data"
+        );
+    }
+
+    #[test]
+    fn test_strip_relocates_out_of_line_values() {
+        let mut data = create_minimal_tiff();
+        // Append an XResolution rational and point a new entry at it.
+        let rational_offset = data.len() as u32;
+        data.extend_from_slice(&72u32.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+
+        // Rebuild the IFD with 7 entries; the extra entry sits at the end.
+        let mut tiff = data[..8].to_vec();
+        tiff.extend_from_slice(&7u16.to_le_bytes());
+        tiff.extend_from_slice(&data[10..10 + 6 * 12]);
+        tiff.extend_from_slice(&tags::X_RESOLUTION.to_le_bytes());
+        tiff.extend_from_slice(&5u16.to_le_bytes());
+        tiff.extend_from_slice(&1u32.to_le_bytes());
+        tiff.extend_from_slice(&(rational_offset + 12).to_le_bytes());
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        // Shift strip offset to account for the extra entry.
+        let strip_entry = 10 + 4 * 12 + 8;
+        let old_strip = u32::from_le_bytes(tiff[strip_entry..strip_entry + 4].try_into().unwrap());
+        tiff[strip_entry..strip_entry + 4].copy_from_slice(&(old_strip + 12).to_le_bytes());
+        tiff.extend_from_slice(&data[10 + 6 * 12 + 4..]);
+
+        let result = strip(&tiff, &test_path()).unwrap();
+        verify(&result, &test_path()).unwrap();
+
+        let (byte_order, ifd_offset) = parse_header(&result, &test_path()).unwrap();
+        let ifds = parse_ifd_chain(&result, ifd_offset, byte_order, &test_path()).unwrap();
+        let x_res = ifds[0]
+            .iter()
+            .find(|e| e.tag == tags::X_RESOLUTION)
+            .unwrap();
+        let value = x_res.value(&result, byte_order).unwrap();
+        assert_eq!(&value[..4], &72u32.to_le_bytes());
+        assert_eq!(&value[4..], &1u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_verify_stripped_output() {
+        let data = create_tiff_with_metadata();
+        let result = strip(&data, &test_path()).unwrap();
+        assert!(verify(&result, &test_path()).is_ok());
+    }
+
+    #[test]
+    fn test_verify_rejects_metadata() {
+        let data = create_tiff_with_metadata();
+        let err = verify(&data, &test_path()).unwrap_err();
+        assert!(err.to_string().contains("Metadata tag"));
+    }
+
+    #[test]
+    fn test_verify_rejects_strip_outside_file() {
+        let mut data = create_minimal_tiff();
+        // Point StripOffsets past the end of the file.
+        let strip_entry = 10 + 4 * 12 + 8;
+        data[strip_entry..strip_entry + 4].copy_from_slice(&1000u32.to_le_bytes());
+        let err = verify(&data, &test_path()).unwrap_err();
+        assert!(err.to_string().contains("beyond file"));
+    }
+
+    #[test]
+    fn test_ifd_loop_rejected() {
+        let mut data = create_minimal_tiff();
+        // Point the next-IFD pointer back at the first IFD.
+        let next_ptr = 10 + 6 * 12;
+        data[next_ptr..next_ptr + 4].copy_from_slice(&8u32.to_le_bytes());
+        assert!(strip(&data, &test_path()).is_err());
+    }
+
     #[test]
     fn test_invalid_too_small() {
         let data = [0x49, 0x49];
@@ -615,4 +1335,179 @@ mod tests {
         assert_eq!(type_size(5), 8); // RATIONAL
         assert_eq!(type_size(12), 8); // DOUBLE
     }
+
+    #[test]
+    fn test_report_tiff_with_metadata() {
+        let data = create_tiff_with_metadata();
+        let items = report(&data, &test_path()).unwrap().removed;
+        let kinds: Vec<&str> = items.iter().map(|i| i.kind.as_str()).collect();
+        assert_eq!(kinds, ["Make", "Software"]);
+        assert_eq!(
+            crate::metadata::categories(&items),
+            vec![Category::Device, Category::Software]
+        );
+    }
+
+    #[test]
+    fn test_report_gps_ifd() {
+        let exif = crate::metadata::exif::build_test_exif(&[], &[(0x0004, 5, 0, &[])]);
+        let tiff = &exif[exif::EXIF_HEADER.len()..];
+        let items = report(tiff, &test_path()).unwrap().removed;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].kind, "GPS IFD");
+        assert_eq!(items[0].fields[0].name, "GPSLongitude");
+    }
+
+    #[test]
+    fn test_report_clean_tiff() {
+        let data = strip(&create_tiff_with_metadata(), &test_path())
+            .unwrap()
+            .into_owned();
+        assert!(report(&data, &test_path()).unwrap().removed.is_empty());
+    }
+
+    #[test]
+    fn test_report_kept_tags() {
+        let data = create_minimal_tiff();
+        let report = report(&data, &test_path()).unwrap();
+        let kept: Vec<(&str, KeepReason)> = report
+            .kept
+            .iter()
+            .map(|i| (i.kind.as_str(), i.reason))
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                ("Header", KeepReason::Structure),
+                ("IFD 0", KeepReason::Structure),
+                ("ImageWidth", KeepReason::Decoding),
+                ("ImageLength", KeepReason::Decoding),
+                ("Compression", KeepReason::Decoding),
+                ("PhotometricInterpretation", KeepReason::Decoding),
+                ("StripOffsets", KeepReason::Decoding),
+                ("Strip 0", KeepReason::ImageData),
+                ("StripByteCounts", KeepReason::Decoding),
+            ]
+        );
+        assert_eq!(report.kept[7].range, data.len() - 1..data.len());
+    }
+
+    #[test]
+    fn test_sub_ifds_rejected() {
+        let mut data = create_minimal_tiff();
+        // Turn Compression into SubIFDs, whose images would be lost.
+        data[8 + 2 + 2 * 12..8 + 2 + 2 * 12 + 2].copy_from_slice(&tags::SUB_IFDS.to_le_bytes());
+        let err = strip(&data, &test_path()).unwrap_err();
+        assert!(err.to_string().contains("SubIFDs"));
+        assert!(report(&data, &test_path()).is_err());
+    }
+
+    #[test]
+    fn test_report_unrelocatable_tag_is_removed() {
+        let mut data = create_minimal_tiff();
+        // Turn Compression into FreeOffsets, which `strip` drops.
+        data[8 + 2 + 2 * 12..8 + 2 + 2 * 12 + 2].copy_from_slice(&tags::FREE_OFFSETS.to_le_bytes());
+        let report = report(&data, &test_path()).unwrap();
+        assert_eq!(report.removed.len(), 1);
+        assert_eq!(report.removed[0].kind, "FreeOffsets");
+        assert_eq!(report.removed[0].range, 34..46);
+    }
+
+    /// Convert `create_minimal_tiff` to big-endian.
+    fn minimal_big_endian_tiff() -> Vec<u8> {
+        let le = create_minimal_tiff();
+        let mut data = BIG_ENDIAN.to_vec();
+        data.extend_from_slice(&42u16.to_be_bytes());
+        data.extend_from_slice(&8u32.to_be_bytes());
+        data.extend_from_slice(&6u16.to_be_bytes());
+        for entry in le[10..10 + 6 * 12].chunks_exact(12) {
+            let read_u16 = |at: usize| u16::from_le_bytes([entry[at], entry[at + 1]]);
+            data.extend_from_slice(&read_u16(0).to_be_bytes());
+            data.extend_from_slice(&read_u16(2).to_be_bytes());
+            data.extend_from_slice(&entry[4..8].iter().rev().copied().collect::<Vec<u8>>());
+            if read_u16(2) == 3 {
+                data.extend_from_slice(&read_u16(8).to_be_bytes());
+                data.extend_from_slice(&[0, 0]);
+            } else {
+                data.extend_from_slice(&entry[8..12].iter().rev().copied().collect::<Vec<u8>>());
+            }
+        }
+        data.extend_from_slice(&le[le.len() - 5..]);
+        data
+    }
+
+    #[test]
+    fn test_insert_exif_adds_gps_ifd() {
+        use crate::metadata::gps::{self, Position};
+
+        let position = Position {
+            latitude: 51.5,
+            longitude: -0.125,
+        };
+        let block = exif::build(&gps::entries(position));
+
+        for data in [create_minimal_tiff(), minimal_big_endian_tiff()] {
+            let stripped = strip(&data, &test_path()).unwrap();
+            let result = insert_exif(&stripped, &block, &test_path()).unwrap();
+
+            let parsed = exif::parse(&result).unwrap();
+            assert_eq!(&result[..2], &data[..2]);
+            assert_eq!(gps::position(&parsed), Some(position));
+            let gps_tags: Vec<u16> = parsed
+                .entries
+                .iter()
+                .filter(|e| e.ifd == Ifd::Gps)
+                .map(|e| e.tag)
+                .collect();
+            assert_eq!(gps_tags, [0, 1, 2, 3, 4]);
+
+            let removed = report(&result, &test_path()).unwrap().removed;
+            assert_eq!(removed.len(), 1);
+            assert_eq!(removed[0].kind, "GPS IFD");
+            assert_eq!(strip(&result, &test_path()).unwrap(), stripped);
+        }
+    }
+
+    #[test]
+    fn test_remove_icc() {
+        let stripped = strip(&create_minimal_tiff(), &test_path())
+            .unwrap()
+            .into_owned();
+        let profile = exif::Entry::new(Ifd::Primary, tags::ICC_PROFILE, 7, 16, vec![0xAB; 16]);
+        let result = insert_exif(&stripped, &exif::build(&[profile]), &test_path()).unwrap();
+        let kept = report(&result, &test_path()).unwrap().kept;
+        assert!(kept.iter().any(|item| item.kind == "InterColorProfile"));
+
+        let result = remove_icc(&result, &test_path()).unwrap();
+        let kept = report(&result, &test_path()).unwrap().kept;
+        assert!(!kept.iter().any(|item| item.kind == "InterColorProfile"));
+        assert!(verify(&result, &test_path()).is_ok());
+    }
+
+    #[test]
+    fn test_remove_unlisted() {
+        // Add a private tag after the last entry of the minimal TIFF.
+        let minimal = create_minimal_tiff();
+        let mut data = minimal.clone();
+        data[8] += 1;
+        let end = 10 + 6 * 12;
+        data.splice(end..end, [0x00, 0xC0, 3, 0, 1, 0, 0, 0, 7, 0, 0, 0]);
+        let strip_offset = u32::from_le_bytes(data[66..70].try_into().unwrap()) + 12;
+        data[66..70].copy_from_slice(&strip_offset.to_le_bytes());
+
+        // The private tag survives the default strip.
+        let stripped = strip(&data, &test_path()).unwrap();
+        assert_eq!(stripped, &data[..]);
+        let kept = report(&data, &test_path()).unwrap().kept;
+        assert!(kept.iter().any(|item| !is_listed(&item.kind)));
+
+        let result = remove_unlisted(&stripped, &test_path()).unwrap();
+        assert!(verify(&result, &test_path()).is_ok());
+        let kept = report(&result, &test_path()).unwrap().kept;
+        assert!(kept.iter().all(|item| is_listed(&item.kind)));
+        assert_eq!(
+            kept.len(),
+            report(&minimal, &test_path()).unwrap().kept.len()
+        );
+    }
 }
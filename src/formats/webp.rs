@@ -25,6 +25,9 @@
 //! - ICCP: ICC profile (considered essential for color accuracy)
 
 use crate::error::{Error, Result};
+use crate::formats::StripReport;
+use crate::metadata::{Category, KeepReason, KeptItem, MetadataItem, exif, xmp};
+use std::borrow::Cow;
 use std::path::Path;
 
 /// RIFF header.
@@ -39,11 +42,34 @@ const METADATA_CHUNKS: &[&[u8; 4]] = &[
     b"XMP ", // XMP metadata (note: padded with space).
 ];
 
+/// VP8X feature flags (first byte of the chunk).
+const FLAG_ICC: u8 = 0x20;
+const FLAG_ALPHA: u8 = 0x10;
+const FLAG_EXIF: u8 = 0x08;
+const FLAG_XMP: u8 = 0x04;
+
+/// VP8X flags announcing metadata chunks.
+const METADATA_FLAGS: u8 = FLAG_EXIF | FLAG_XMP;
+
 /// Check if a chunk is a metadata chunk that should be stripped.
 fn is_metadata_chunk(fourcc: &[u8; 4]) -> bool {
     METADATA_CHUNKS.contains(&fourcc)
 }
 
+/// Chunk types kept in allowlist mode: image data, animation and the colour
+/// profile, which is governed by the ICC mode.
+const LISTED_CHUNKS: &[&[u8; 4]] = &[
+    b"VP8 ", b"VP8L", b"VP8X", b"ALPH", b"ANIM", b"ANMF", b"ICCP",
+];
+
+/// Check if a report kind names a chunk that allowlist mode keeps.
+pub fn is_listed(kind: &str) -> bool {
+    kind == "RIFF header"
+        || LISTED_CHUNKS
+            .iter()
+            .any(|c| String::from_utf8_lossy(&c[..]).trim_end() == kind)
+}
+
 /// A WebP chunk.
 #[derive(Debug)]
 struct Chunk<'a> {
@@ -53,7 +79,6 @@ struct Chunk<'a> {
 
 impl<'a> Chunk<'a> {
     /// Get the padded size (chunks are padded to even length).
-    #[allow(dead_code)]
     fn padded_size(&self) -> usize {
         (self.data.len() + 1) & !1
     }
@@ -117,16 +142,18 @@ fn update_vp8x_flags(vp8x_data: &[u8]) -> Vec<u8> {
 
     let mut result = vp8x_data.to_vec();
 
-    // VP8X flags are in the first byte.
-    // Bit 3 (0x08): XMP metadata present.
-    // Bit 5 (0x20): EXIF metadata present.
-    result[0] &= !(0x08 | 0x20);
+    // VP8X flags are in the first byte. The ICC, alpha and animation flags
+    // describe chunks that are kept.
+    result[0] &= !METADATA_FLAGS;
 
     result
 }
 
 /// Strip metadata from WebP data.
-pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
+///
+/// Returns the input itself when it has no metadata chunks or flags and its
+/// RIFF size matches the chunks it holds, so a clean file is not rebuilt.
+pub fn strip<'a>(data: &'a [u8], path: &Path) -> Result<Cow<'a, [u8]>> {
     // Validate minimum size.
     if data.len() < 12 {
         return Err(Error::invalid_image(
@@ -157,6 +184,10 @@ pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
         return Err(Error::invalid_image(path, "Missing image data chunk"));
     }
 
+    if is_clean(data, &chunks) {
+        return Ok(Cow::Borrowed(data));
+    }
+
     // Build output, filtering out metadata chunks.
     let mut output = Vec::with_capacity(data.len());
 
@@ -188,9 +219,280 @@ pub fn strip(data: &[u8], path: &Path) -> Result<Vec<u8>> {
     let file_size = (output.len() - 8) as u32;
     output[4..8].copy_from_slice(&file_size.to_le_bytes());
 
+    Ok(Cow::Owned(output))
+}
+
+/// Check whether `strip` would have nothing to remove from the parsed file.
+fn is_clean(data: &[u8], chunks: &[Chunk]) -> bool {
+    let end = 12 + chunks.iter().map(|c| 8 + c.padded_size()).sum::<usize>();
+    let riff_size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+    end == data.len()
+        && riff_size == data.len() - 8
+        && chunks.iter().all(|c| match &c.fourcc {
+            b"VP8X" => c
+                .data
+                .first()
+                .is_none_or(|flags| flags & METADATA_FLAGS == 0),
+            fourcc => !is_metadata_chunk(fourcc),
+        })
+}
+
+/// Remove the ICCP chunk and clear the VP8X ICC flag.
+pub fn remove_icc(data: &[u8], path: &Path) -> Result<Vec<u8>> {
+    retain_chunks(data, path, |fourcc| fourcc != b"ICCP", FLAG_ICC)
+}
+
+/// Remove every chunk not in `LISTED_CHUNKS`.
+pub fn remove_unlisted(data: &[u8], path: &Path) -> Result<Vec<u8>> {
+    retain_chunks(data, path, |fourcc| LISTED_CHUNKS.contains(&fourcc), 0)
+}
+
+/// Rebuild WebP data from the chunks `keep` accepts, clearing `flags` in the
+/// VP8X chunk.
+fn retain_chunks(
+    data: &[u8],
+    path: &Path,
+    keep: impl Fn(&[u8; 4]) -> bool,
+    flags: u8,
+) -> Result<Vec<u8>> {
+    let chunks = parse_chunks(data, path)?;
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(RIFF);
+    output.extend_from_slice(&[0, 0, 0, 0]);
+    output.extend_from_slice(WEBP);
+
+    for chunk in chunks.iter().filter(|c| keep(&c.fourcc)) {
+        if &chunk.fourcc == b"VP8X" && !chunk.data.is_empty() {
+            let mut updated = chunk.data.to_vec();
+            updated[0] &= !flags;
+            Chunk {
+                fourcc: chunk.fourcc,
+                data: &updated,
+            }
+            .write_to(&mut output);
+        } else {
+            chunk.write_to(&mut output);
+        }
+    }
+
+    let file_size = (output.len() - 8) as u32;
+    output[4..8].copy_from_slice(&file_size.to_le_bytes());
+
+    Ok(output)
+}
+
+/// Read the canvas size and alpha flag from a VP8 or VP8L bitstream.
+fn canvas(chunk: &Chunk) -> Option<(u32, u32, bool)> {
+    let d = chunk.data;
+    match &chunk.fourcc {
+        b"VP8 " if d.len() >= 10 && d[3..6] == [0x9D, 0x01, 0x2A] => {
+            let width = u16::from_le_bytes([d[6], d[7]]) & 0x3FFF;
+            let height = u16::from_le_bytes([d[8], d[9]]) & 0x3FFF;
+            Some((u32::from(width), u32::from(height), false))
+        }
+        b"VP8L" if d.len() >= 5 && d[0] == 0x2F => {
+            let bits = u32::from_le_bytes([d[1], d[2], d[3], d[4]]);
+            let width = (bits & 0x3FFF) + 1;
+            let height = ((bits >> 14) & 0x3FFF) + 1;
+            Some((width, height, bits & (1 << 28) != 0))
+        }
+        _ => None,
+    }
+}
+
+/// Add an EXIF chunk to stripped WebP data.
+pub fn insert_exif(data: &[u8], block: &[u8], path: &Path) -> Result<Vec<u8>> {
+    insert_chunk(data, *b"EXIF", FLAG_EXIF, block, path)
+}
+
+/// Add an XMP chunk to stripped WebP data.
+pub fn insert_xmp(data: &[u8], packet: &[u8], path: &Path) -> Result<Vec<u8>> {
+    insert_chunk(data, *b"XMP ", FLAG_XMP, packet, path)
+}
+
+/// Append a metadata chunk and announce it in the VP8X header.
+///
+/// Simple-format files get a VP8X header built from the canvas size in
+/// their bitstream.
+fn insert_chunk(
+    data: &[u8],
+    fourcc: [u8; 4],
+    flag: u8,
+    payload: &[u8],
+    path: &Path,
+) -> Result<Vec<u8>> {
+    let chunks = parse_chunks(data, path)?;
+    let mut vp8x = match chunks.iter().find(|c| &c.fourcc == b"VP8X") {
+        Some(chunk) if chunk.data.len() >= 10 => chunk.data.to_vec(),
+        Some(_) => return Err(Error::invalid_image(path, "Truncated VP8X chunk")),
+        None => {
+            let (width, height, alpha) = chunks
+                .iter()
+                .find_map(canvas)
+                .ok_or_else(|| Error::invalid_image(path, "Cannot read canvas size"))?;
+            let mut header = vec![if alpha { FLAG_ALPHA } else { 0 }, 0, 0, 0];
+            header.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+            header.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+            header
+        }
+    };
+    vp8x[0] |= flag;
+
+    let mut output = Vec::with_capacity(data.len() + payload.len() + 26);
+    output.extend_from_slice(RIFF);
+    output.extend_from_slice(&[0, 0, 0, 0]);
+    output.extend_from_slice(WEBP);
+    Chunk {
+        fourcc: *b"VP8X",
+        data: &vp8x,
+    }
+    .write_to(&mut output);
+    for chunk in chunks.iter().filter(|c| &c.fourcc != b"VP8X") {
+        chunk.write_to(&mut output);
+    }
+    Chunk {
+        fourcc,
+        data: payload,
+    }
+    .write_to(&mut output);
+
+    let file_size = (output.len() - 8) as u32;
+    output[4..8].copy_from_slice(&file_size.to_le_bytes());
+
     Ok(output)
 }
 
+/// Get the reason `strip` keeps a chunk.
+fn keep_reason(fourcc: &[u8; 4]) -> KeepReason {
+    match fourcc {
+        b"VP8 " | b"VP8L" | b"ALPH" => KeepReason::ImageData,
+        b"ANIM" | b"ANMF" => KeepReason::Animation,
+        b"ICCP" => KeepReason::Rendering,
+        _ => KeepReason::Structure,
+    }
+}
+
+/// Describe the chunks that `strip` removes and keeps.
+///
+/// EXIF and XMP chunks are decoded far enough to name their fields. Fails on
+/// the same structural errors as `strip`.
+pub fn report(data: &[u8], path: &Path) -> Result<StripReport> {
+    if data.len() < 12 || !data.starts_with(RIFF) || &data[8..12] != WEBP {
+        return Err(Error::invalid_image(path, "Invalid WebP header"));
+    }
+
+    let chunks = parse_chunks(data, path)?;
+    if !chunks
+        .iter()
+        .any(|c| matches!(&c.fourcc, b"VP8 " | b"VP8L" | b"VP8X"))
+    {
+        return Err(Error::invalid_image(path, "Missing image data chunk"));
+    }
+
+    let mut report = StripReport::default();
+    report
+        .kept
+        .push(KeptItem::new("RIFF header", 0..12, KeepReason::Structure));
+    let mut pos = 12;
+
+    for chunk in chunks {
+        let end = (pos + 8 + chunk.padded_size()).min(data.len());
+        let range = pos..end;
+        pos = end;
+
+        match &chunk.fourcc {
+            b"EXIF" => report.removed.push(
+                MetadataItem::new("EXIF", range, Category::Other)
+                    .with_fields(exif::fields(chunk.data)),
+            ),
+            b"XMP " => report.removed.push(
+                MetadataItem::new("XMP", range, Category::Other)
+                    .with_fields(xmp::fields(chunk.data)),
+            ),
+            fourcc => {
+                let kind = String::from_utf8_lossy(fourcc).trim_end().to_string();
+                report
+                    .kept
+                    .push(KeptItem::new(kind, range, keep_reason(fourcc)));
+            }
+        }
+    }
+
+    if pos < data.len() {
+        report.removed.push(MetadataItem::new(
+            "Trailing data",
+            pos..data.len(),
+            Category::Other,
+        ));
+    }
+
+    Ok(report)
+}
+
+/// Verify that WebP data is structurally sound and free of metadata chunks.
+///
+/// Checks the RIFF size field, every chunk size and padding, and that the
+/// VP8X metadata flags are cleared.
+pub fn verify(data: &[u8], path: &Path) -> Result<()> {
+    let fail = |reason: String| Error::verification_failed(path, reason);
+
+    if data.len() < 12 || !data.starts_with(RIFF) || &data[8..12] != WEBP {
+        return Err(fail(String::from("Invalid RIFF/WebP header")));
+    }
+
+    let riff_size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+    if riff_size != data.len() - 8 {
+        return Err(fail(format!(
+            "RIFF size {} does not match file size {}",
+            riff_size,
+            data.len() - 8
+        )));
+    }
+
+    let mut pos = 12;
+    let mut has_image = false;
+
+    while pos < data.len() {
+        if pos + 8 > data.len() {
+            return Err(fail(String::from("Truncated chunk header")));
+        }
+        let fourcc: [u8; 4] = [data[pos], data[pos + 1], data[pos + 2], data[pos + 3]];
+        let size = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]])
+            as usize;
+        let name = String::from_utf8_lossy(&fourcc).into_owned();
+
+        let padded_end = pos + 8 + ((size + 1) & !1);
+        if padded_end > data.len() {
+            return Err(fail(format!("Chunk {} extends beyond file", name)));
+        }
+
+        if is_metadata_chunk(&fourcc) {
+            return Err(fail(format!("Metadata chunk {} remains", name.trim_end())));
+        }
+
+        match &fourcc {
+            b"VP8 " | b"VP8L" | b"ANMF" => has_image = true,
+            b"VP8X" => {
+                if size < 1 {
+                    return Err(fail(String::from("Truncated VP8X chunk")));
+                }
+                if data[pos + 8] & METADATA_FLAGS != 0 {
+                    return Err(fail(String::from("VP8X metadata flags still set")));
+                }
+            }
+            _ => {}
+        }
+
+        pos = padded_end;
+    }
+
+    if !has_image {
+        return Err(fail(String::from("Missing image data chunk")));
+    }
+
+    Ok(())
+}
+
 /// Create a minimal valid WebP for testing.
 #[cfg(test)]
 pub fn create_minimal_webp() -> Vec<u8> {
@@ -218,7 +520,7 @@ pub fn create_webp_with_exif() -> Vec<u8> {
     // VP8X chunk (extended format).
     data.extend_from_slice(b"VP8X");
     data.extend_from_slice(&10u32.to_le_bytes()); // Chunk size.
-    data.push(0x28); // Flags: EXIF (0x20) + XMP (0x08) present.
+    data.push(FLAG_EXIF | FLAG_XMP); // Flags: EXIF and XMP present.
     data.extend_from_slice(&[0, 0, 0]); // Reserved.
     data.extend_from_slice(&[0, 0, 0]); // Width - 1.
     data.extend_from_slice(&[0, 0, 0]); // Height - 1.
@@ -284,10 +586,36 @@ mod tests {
         let vp8x = chunks.iter().find(|c| &c.fourcc == b"VP8X");
         if let Some(vp8x) = vp8x {
             let flags = vp8x.data[0];
-            assert_eq!(flags & 0x28, 0, "EXIF and XMP flags should be cleared");
+            assert_eq!(
+                flags & METADATA_FLAGS,
+                0,
+                "EXIF and XMP flags should be cleared"
+            );
         }
     }
 
+    #[test]
+    fn test_verify_stripped_webp() {
+        let data = create_webp_with_exif();
+        let result = strip(&data, &test_path()).unwrap();
+        assert!(verify(&result, &test_path()).is_ok());
+    }
+
+    #[test]
+    fn test_verify_rejects_metadata() {
+        let data = create_webp_with_exif();
+        let err = verify(&data, &test_path()).unwrap_err();
+        assert!(err.to_string().contains("metadata"));
+    }
+
+    #[test]
+    fn test_verify_rejects_wrong_riff_size() {
+        let mut data = create_minimal_webp();
+        data[4] = data[4].wrapping_add(2);
+        let err = verify(&data, &test_path()).unwrap_err();
+        assert!(err.to_string().contains("RIFF size"));
+    }
+
     #[test]
     fn test_invalid_too_small() {
         let data = b"RIFF";
@@ -324,9 +652,10 @@ mod tests {
 
     #[test]
     fn test_update_vp8x_flags() {
-        let original = [0x28, 0x00, 0x00, 0x00]; // EXIF + XMP flags.
+        // ICC describes a chunk that is kept.
+        let original = [FLAG_ICC | FLAG_EXIF | FLAG_XMP, 0x00, 0x00, 0x00];
         let updated = update_vp8x_flags(&original);
-        assert_eq!(updated[0] & 0x28, 0);
+        assert_eq!(updated[0], FLAG_ICC);
     }
 
     #[test]
@@ -342,4 +671,119 @@ mod tests {
         // 4 (fourcc) + 4 (size) + 3 (data) + 1 (padding) = 12.
         assert_eq!(output.len(), 12);
     }
+
+    #[test]
+    fn test_report_webp_with_exif() {
+        let data = create_webp_with_exif();
+        let report = report(&data, &test_path()).unwrap();
+        let items = &report.removed;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].kind, "EXIF");
+        assert_eq!(
+            items[0].size(),
+            data.len() - strip(&data, &test_path()).unwrap().len()
+        );
+
+        let kept: Vec<(&str, KeepReason)> = report
+            .kept
+            .iter()
+            .map(|i| (i.kind.as_str(), i.reason))
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                ("RIFF header", KeepReason::Structure),
+                ("VP8X", KeepReason::Structure),
+                ("VP8", KeepReason::ImageData),
+            ]
+        );
+
+        assert!(
+            super::report(&create_minimal_webp(), &test_path())
+                .unwrap()
+                .removed
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_insert_exif_adds_vp8x() {
+        let data = create_minimal_webp();
+        let block = exif::build(&[]);
+        let result = insert_exif(&data, &block, &test_path()).unwrap();
+
+        let chunks = parse_chunks(&result, &test_path()).unwrap();
+        let fourccs: Vec<&[u8; 4]> = chunks.iter().map(|c| &c.fourcc).collect();
+        assert_eq!(fourccs, [b"VP8X", b"VP8 ", b"EXIF"]);
+        // 1x1 canvas, stored as width - 1 and height - 1.
+        assert_eq!(chunks[0].data, [FLAG_EXIF, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(chunks[2].data, block.as_slice());
+
+        let riff_size = u32::from_le_bytes([result[4], result[5], result[6], result[7]]);
+        assert_eq!(riff_size as usize, result.len() - 8);
+        assert!(verify(&strip(&result, &test_path()).unwrap(), &test_path()).is_ok());
+    }
+
+    #[test]
+    fn test_insert_exif_sets_existing_flag() {
+        let stripped = strip(&create_webp_with_exif(), &test_path())
+            .unwrap()
+            .into_owned();
+        let result = insert_exif(&stripped, &exif::build(&[]), &test_path()).unwrap();
+        let chunks = parse_chunks(&result, &test_path()).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].data[0], FLAG_EXIF);
+        assert_eq!(&chunks[2].fourcc, b"EXIF");
+    }
+
+    #[test]
+    fn test_insert_exif_then_xmp() {
+        let stripped = strip(&create_webp_with_exif(), &test_path())
+            .unwrap()
+            .into_owned();
+        let with_exif = insert_exif(&stripped, &exif::build(&[]), &test_path()).unwrap();
+        let result = insert_xmp(&with_exif, b"<x:xmpmeta/>", &test_path()).unwrap();
+        let chunks = parse_chunks(&result, &test_path()).unwrap();
+        assert_eq!(chunks[0].data[0], FLAG_EXIF | FLAG_XMP);
+        assert_eq!(&chunks[3].fourcc, b"XMP ");
+        assert_eq!(chunks[3].data, b"<x:xmpmeta/>");
+    }
+
+    #[test]
+    fn test_remove_icc() {
+        let stripped = strip(&create_webp_with_exif(), &test_path())
+            .unwrap()
+            .into_owned();
+        let result = insert_chunk(&stripped, *b"ICCP", FLAG_ICC, &[0xAB; 9], &test_path()).unwrap();
+        let result = remove_icc(&result, &test_path()).unwrap();
+
+        let chunks = parse_chunks(&result, &test_path()).unwrap();
+        let fourccs: Vec<&[u8; 4]> = chunks.iter().map(|c| &c.fourcc).collect();
+        assert_eq!(fourccs, [b"VP8X", b"VP8 "]);
+        assert_eq!(chunks[0].data[0] & FLAG_ICC, 0);
+        let riff_size = u32::from_le_bytes([result[4], result[5], result[6], result[7]]);
+        assert_eq!(riff_size as usize, result.len() - 8);
+    }
+
+    #[test]
+    fn test_remove_unlisted() {
+        let minimal = create_minimal_webp();
+        let mut data = minimal.clone();
+        data.extend_from_slice(b"UNKN\x02\0\0\0ab");
+        let riff_size = (data.len() - 8) as u32;
+        data[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+        // An unknown chunk survives the default strip.
+        assert_eq!(strip(&data, &test_path()).unwrap(), &data[..]);
+        let kept = report(&data, &test_path()).unwrap().kept;
+        assert!(
+            kept.iter()
+                .any(|item| item.kind == "UNKN" && !is_listed(&item.kind))
+        );
+
+        let result = remove_unlisted(&data, &test_path()).unwrap();
+        assert_eq!(result, minimal);
+        let kept = report(&result, &test_path()).unwrap().kept;
+        assert!(kept.iter().all(|item| is_listed(&item.kind)));
+    }
 }
@@ -0,0 +1,514 @@
+//! Filesystem metadata of output files.
+//!
+//! Stripping the image data is not enough when the file itself still says
+//! where it came from: browsers record the download URL in the
+//! `user.xdg.origin.url` and `user.xdg.referrer.url` extended attributes,
+//! and timestamps and permission bits can outlive a rewrite. This module
+//! decides what an output file's times, permissions and extended attributes
+//! should be, and sets them on the open file before it is closed. Files
+//! rewritten in place also keep their owner and mode, and hard links to them
+//! are handled by a [`HardLinks`] policy.
+//!
+//! Extended attributes are read and written with the C library's `f*xattr`
+//! calls on Linux. Only the `user.` namespace is touched; `security.` and
+//! `system.` attributes such as SELinux labels and ACLs are left to the
+//! system.
+
+use crate::error::{Error, Result};
+use std::fs::{File, FileTimes};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Time that `normalize` gives every output, 1980-01-01T00:00:00Z. It is
+/// the earliest time ZIP archives can store, so it survives being packed.
+const NORMALIZED_TIME: u64 = 315_532_800;
+
+/// What to do with an output file's times, permissions and extended
+/// attributes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FsMetadata {
+    /// Copy the input's times, permission bits and `user.` attributes.
+    Preserve,
+    /// Set times to 1980-01-01, permissions to no more than `rw-r--r--`,
+    /// and remove `user.` attributes, so outputs are reproducible.
+    Normalize,
+    /// Leave the times of the write, limit permissions as for `normalize`,
+    /// and remove `user.` attributes.
+    #[default]
+    Strip,
+}
+
+impl FsMetadata {
+    /// Parse a mode name.
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "preserve" => Ok(FsMetadata::Preserve),
+            "normalize" => Ok(FsMetadata::Normalize),
+            "strip" => Ok(FsMetadata::Strip),
+            _ => Err(Error::InvalidArgument {
+                argument: String::from("--fs-metadata"),
+                reason: String::from("Expected preserve, normalize or strip"),
+            }),
+        }
+    }
+
+    /// Get the mode name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FsMetadata::Preserve => "preserve",
+            FsMetadata::Normalize => "normalize",
+            FsMetadata::Strip => "strip",
+        }
+    }
+}
+
+/// What to do when a file rewritten in place has other hard links.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HardLinks {
+    /// Write the cleaned data into the shared file, so every name for it
+    /// is cleaned. The write is not atomic.
+    #[default]
+    Rewrite,
+    /// Replace only the given name; the other names keep the original,
+    /// metadata and all.
+    Break,
+    /// Leave hard-linked files alone.
+    Skip,
+}
+
+impl HardLinks {
+    /// Parse a policy name.
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "rewrite" => Ok(HardLinks::Rewrite),
+            "break" => Ok(HardLinks::Break),
+            "skip" => Ok(HardLinks::Skip),
+            _ => Err(Error::InvalidArgument {
+                argument: String::from("--hard-links"),
+                reason: String::from("Expected rewrite, break or skip"),
+            }),
+        }
+    }
+
+    /// Get the policy name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HardLinks::Rewrite => "rewrite",
+            HardLinks::Break => "break",
+            HardLinks::Skip => "skip",
+        }
+    }
+}
+
+/// Get the number of names a file has. It is always 1 where the platform
+/// does not say.
+pub fn link_count(path: &Path) -> Result<u64> {
+    let metadata = path.metadata().map_err(|e| Error::io_with_path(e, path))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Ok(metadata.nlink())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        Ok(1)
+    }
+}
+
+/// The filesystem metadata to give one output file, worked out from its
+/// input before anything is written.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    mode: FsMetadata,
+    times: Option<FileTimes>,
+    #[cfg(unix)]
+    permissions: u32,
+    /// User and group to give the output, when it replaces the input.
+    #[cfg(unix)]
+    owner: Option<(u32, u32)>,
+    #[cfg(not(unix))]
+    readonly: bool,
+    attributes: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Plan {
+    /// Read what the plan needs from the input file.
+    ///
+    /// An output that `replaces` its input is the same file to everything
+    /// that refers to it, so it keeps the input's owner and mode whatever
+    /// the mode.
+    pub fn new(mode: FsMetadata, input: &Path, replaces: bool) -> Result<Self> {
+        let metadata = input
+            .metadata()
+            .map_err(|e| Error::io_with_path(e, input))?;
+
+        let times = match mode {
+            FsMetadata::Preserve => {
+                let mut times = FileTimes::new();
+                if let Ok(accessed) = metadata.accessed() {
+                    times = times.set_accessed(accessed);
+                }
+                if let Ok(modified) = metadata.modified() {
+                    times = times.set_modified(modified);
+                }
+                Some(times)
+            }
+            FsMetadata::Normalize => Some(
+                FileTimes::new()
+                    .set_accessed(normalized_time())
+                    .set_modified(normalized_time()),
+            ),
+            FsMetadata::Strip => None,
+        };
+
+        let attributes = match mode {
+            FsMetadata::Preserve => {
+                let file = File::open(input).map_err(|e| Error::io_with_path(e, input))?;
+                xattr::user_attributes(&file).map_err(|e| Error::io_with_path(e, input))?
+            }
+            _ => Vec::new(),
+        };
+
+        Ok(Plan {
+            mode,
+            times,
+            #[cfg(unix)]
+            permissions: {
+                use std::os::unix::fs::PermissionsExt;
+                let bits = metadata.permissions().mode() & 0o7777;
+                if replaces || mode == FsMetadata::Preserve {
+                    bits
+                } else {
+                    // Owner read and write, plus read for group and others
+                    // only where the input allowed it.
+                    0o600 | (bits & 0o044)
+                }
+            },
+            #[cfg(unix)]
+            owner: replaces.then(|| {
+                use std::os::unix::fs::MetadataExt;
+                (metadata.uid(), metadata.gid())
+            }),
+            #[cfg(not(unix))]
+            readonly: (replaces || mode == FsMetadata::Preserve)
+                && metadata.permissions().readonly(),
+            attributes,
+        })
+    }
+
+    /// Set the planned metadata on an output file that has been written.
+    ///
+    /// Like `cp -p`, a file whose owner the user may not keep is still
+    /// written; the returned warning says so.
+    pub fn apply(&self, file: &File) -> io::Result<Option<String>> {
+        // An output written into an existing file keeps that file's
+        // attributes, so they are cleared unless they are preserved.
+        if self.mode == FsMetadata::Preserve {
+            for (name, value) in &self.attributes {
+                xattr::set(file, name, value)?;
+            }
+        } else {
+            for (name, _) in xattr::user_attributes(file)? {
+                xattr::remove(file, &name)?;
+            }
+        }
+
+        // The owner goes first, as changing it clears set-user-ID bits.
+        let mut warning = None;
+        #[cfg(unix)]
+        if let Some((uid, gid)) = self.owner {
+            use std::os::unix::fs::MetadataExt;
+            let current = file.metadata()?;
+            // Only ask for a change when one is needed, since most users
+            // may not give files away.
+            if (current.uid(), current.gid()) != (uid, gid) {
+                match std::os::unix::fs::fchown(file, Some(uid), Some(gid)) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                        warning = Some(format!("cannot keep owner {}:{} ({})", uid, gid, e));
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(self.permissions))?;
+        }
+        #[cfg(not(unix))]
+        if self.readonly {
+            let mut permissions = file.metadata()?.permissions();
+            permissions.set_readonly(true);
+            file.set_permissions(permissions)?;
+        }
+
+        if let Some(times) = self.times {
+            file.set_times(times)?;
+        }
+        Ok(warning)
+    }
+}
+
+/// Get the time `normalize` gives outputs.
+pub fn normalized_time() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(NORMALIZED_TIME)
+}
+
+#[cfg(target_os = "linux")]
+mod xattr {
+    //! `user.` extended attributes through the C library.
+
+    use std::ffi::{c_char, c_int, c_void};
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::AsRawFd;
+
+    /// Namespace of attributes that belong to the file's owner.
+    const USER_NAMESPACE: &[u8] = b"user.";
+
+    /// The filesystem has no extended attributes.
+    const ENOTSUP: i32 = 95;
+    /// The buffer was too small for the result.
+    const ERANGE: i32 = 34;
+
+    unsafe extern "C" {
+        fn flistxattr(fd: c_int, list: *mut c_char, size: usize) -> isize;
+        fn fgetxattr(fd: c_int, name: *const c_char, value: *mut c_void, size: usize) -> isize;
+        fn fsetxattr(
+            fd: c_int,
+            name: *const c_char,
+            value: *const c_void,
+            size: usize,
+            flags: c_int,
+        ) -> c_int;
+        fn fremovexattr(fd: c_int, name: *const c_char) -> c_int;
+    }
+
+    /// Call a `f*xattr` function that fills a buffer, growing the buffer
+    /// until the result fits.
+    fn read_buffer(call: impl Fn(*mut u8, usize) -> isize) -> io::Result<Vec<u8>> {
+        loop {
+            let size = call(std::ptr::null_mut(), 0);
+            if size < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut buffer = vec![0u8; size as usize];
+            let read = call(buffer.as_mut_ptr(), buffer.len());
+            if read >= 0 {
+                buffer.truncate(read as usize);
+                return Ok(buffer);
+            }
+            // The value grew between the two calls; ask again.
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() != Some(ERANGE) {
+                return Err(error);
+            }
+        }
+    }
+
+    /// Get a name as a C string.
+    fn c_name(name: &[u8]) -> Vec<u8> {
+        let mut c_name = name.to_vec();
+        c_name.push(0);
+        c_name
+    }
+
+    /// Get the names and values of the file's `user.` attributes.
+    pub fn user_attributes(file: &File) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let fd = file.as_raw_fd();
+        // SAFETY: the buffer pointer and size describe a live allocation,
+        // or are null and 0 to ask for the size.
+        let list = match read_buffer(|buffer, size| unsafe { flistxattr(fd, buffer.cast(), size) })
+        {
+            Ok(list) => list,
+            Err(e) if e.raw_os_error() == Some(ENOTSUP) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut attributes = Vec::new();
+        for name in list.split(|&b| b == 0) {
+            if !name.starts_with(USER_NAMESPACE) {
+                continue;
+            }
+            let c_name = c_name(name);
+            // SAFETY: as above, and `c_name` is NUL-terminated.
+            let value = read_buffer(|buffer, size| unsafe {
+                fgetxattr(fd, c_name.as_ptr().cast(), buffer.cast(), size)
+            })?;
+            attributes.push((name.to_vec(), value));
+        }
+        Ok(attributes)
+    }
+
+    /// Set an attribute.
+    pub fn set(file: &File, name: &[u8], value: &[u8]) -> io::Result<()> {
+        let c_name = c_name(name);
+        // SAFETY: `c_name` is NUL-terminated and `value` is a live slice.
+        let result = unsafe {
+            fsetxattr(
+                file.as_raw_fd(),
+                c_name.as_ptr().cast(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+            )
+        };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Remove an attribute.
+    pub fn remove(file: &File, name: &[u8]) -> io::Result<()> {
+        let c_name = c_name(name);
+        // SAFETY: `c_name` is NUL-terminated.
+        let result = unsafe { fremovexattr(file.as_raw_fd(), c_name.as_ptr().cast()) };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod xattr {
+    //! Extended attributes are only handled on Linux.
+
+    use std::fs::File;
+    use std::io;
+
+    pub fn user_attributes(_file: &File) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        Ok(Vec::new())
+    }
+
+    pub fn set(_file: &File, _name: &[u8], _value: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn remove(_file: &File, _name: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_parse() {
+        for mode in ["preserve", "normalize", "strip"] {
+            assert_eq!(FsMetadata::parse(mode).unwrap().name(), mode);
+        }
+        assert!(FsMetadata::parse("keep").is_err());
+        assert_eq!(FsMetadata::default(), FsMetadata::Strip);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_times_and_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join("pmi_fsmeta_apply");
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.jpg");
+        let output = dir.join("output.jpg");
+        fs::write(&input, b"in").unwrap();
+        fs::set_permissions(&input, fs::Permissions::from_mode(0o750)).unwrap();
+        let old = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        File::options()
+            .write(true)
+            .open(&input)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let check = |mode: FsMetadata| {
+            let _ = fs::remove_file(&output);
+            let file = File::create(&output).unwrap();
+            Plan::new(mode, &input, false)
+                .unwrap()
+                .apply(&file)
+                .unwrap();
+            let metadata = fs::metadata(&output).unwrap();
+            (
+                metadata.permissions().mode() & 0o7777,
+                metadata.modified().unwrap(),
+            )
+        };
+
+        assert_eq!(check(FsMetadata::Preserve), (0o750, old));
+        assert_eq!(check(FsMetadata::Normalize), (0o640, normalized_time()));
+        let (permissions, modified) = check(FsMetadata::Strip);
+        assert_eq!(permissions, 0o640);
+        assert!(modified > old);
+
+        // A file rewritten in place keeps its mode whatever the mode.
+        let file = File::create(&output).unwrap();
+        Plan::new(FsMetadata::Strip, &input, true)
+            .unwrap()
+            .apply(&file)
+            .unwrap();
+        let metadata = fs::metadata(&output).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o750);
+
+        // An owner the user may not give the file is a warning, not an
+        // error, as with `cp -p`. Root may give it away.
+        let mut plan = Plan::new(FsMetadata::Strip, &input, true).unwrap();
+        plan.owner = Some((65_534, 65_534));
+        let warning = plan.apply(&File::create(&output).unwrap()).unwrap();
+        assert!(warning.is_none_or(|w| w.contains("cannot keep owner 65534:65534")));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_apply_extended_attributes() {
+        let dir = std::env::temp_dir().join("pmi_fsmeta_xattr");
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.jpg");
+        let output = dir.join("output.jpg");
+        fs::write(&input, b"in").unwrap();
+        let file = File::options().write(true).open(&input).unwrap();
+        let url = b"https://example.com/photo.jpg";
+        if xattr::set(&file, b"user.xdg.origin.url", url).is_err() {
+            // The temporary directory does not support user attributes.
+            let _ = fs::remove_dir_all(&dir);
+            return;
+        }
+
+        let attributes = |path: &Path| xattr::user_attributes(&File::open(path).unwrap()).unwrap();
+        for (mode, expected) in [
+            (FsMetadata::Preserve, 2),
+            (FsMetadata::Strip, 0),
+            (FsMetadata::Normalize, 0),
+        ] {
+            // Overwriting an existing file keeps its attributes unless they
+            // are removed; `preserve` adds the input's and removes none.
+            fs::copy(&input, &output).unwrap();
+            xattr::set(&File::open(&output).unwrap(), b"user.xdg.origin.url", url).unwrap();
+            xattr::set(&File::open(&output).unwrap(), b"user.note", b"kept").unwrap();
+            let file = File::options().write(true).open(&output).unwrap();
+            Plan::new(mode, &input, false)
+                .unwrap()
+                .apply(&file)
+                .unwrap();
+            assert_eq!(attributes(&output).len(), expected, "{:?}", mode);
+        }
+        assert_eq!(
+            attributes(&output),
+            [] as [(Vec<u8>, Vec<u8>); 0],
+            "user attributes are removed"
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
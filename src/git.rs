@@ -0,0 +1,313 @@
+//! Git integration.
+//!
+//! `pmi git-check` lists the image blobs added or modified in the index or in
+//! a range of commits and checks their contents straight from the object
+//! store, so hooks can block metadata-bearing images that are not checked
+//! out in the working tree. All repository access goes through the local
+//! `git` binary.
+
+use crate::check::{CheckResult, CheckStatus, check_data};
+use crate::cli::Config;
+use crate::error::{Error, Result};
+use crate::formats::detect_format_from_extension;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Which changes to scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitTarget {
+    /// Changes staged in the index (for pre-commit hooks).
+    Staged,
+    /// Commits in a revision range such as `origin/main..HEAD`, or a single
+    /// commit (for pre-receive hooks and CI).
+    Range(String),
+}
+
+impl GitTarget {
+    /// Get the target selected by the configuration (staged by default).
+    pub fn from_config(config: &Config) -> Self {
+        match &config.rev_range {
+            Some(range) => GitTarget::Range(range.clone()),
+            None => GitTarget::Staged,
+        }
+    }
+}
+
+/// An image blob added or modified by the target changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedBlob {
+    /// Path relative to the repository root.
+    pub path: PathBuf,
+    /// Full object ID of the blob.
+    pub id: String,
+}
+
+/// Run git in `repo` and return its standard output.
+fn git(repo: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let command = args.first().copied().unwrap_or_default();
+    let output = Command::new("git")
+        .current_dir(repo)
+        .args(args)
+        .output()
+        .map_err(|e| Error::git(command, e.to_string()))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::git(command, stderr.trim()));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Parse NUL-separated `--raw` diff output into (blob ID, path) pairs.
+///
+/// Each record is `:<mode> <mode> <id> <id> <status>` followed by the path.
+/// Commit separators and other lines in between are ignored.
+pub fn parse_raw_diff(output: &[u8]) -> Vec<ChangedBlob> {
+    let mut blobs = Vec::new();
+    let mut fields = output.split(|&b| b == 0);
+
+    while let Some(field) = fields.next() {
+        let header = String::from_utf8_lossy(field);
+        let Some(header) = header.trim_start_matches('\n').strip_prefix(':') else {
+            continue;
+        };
+        let Some(path) = fields.next() else {
+            break;
+        };
+
+        let parts: Vec<&str> = header.split(' ').collect();
+        if parts.len() < 5 {
+            continue;
+        }
+        let blob = ChangedBlob {
+            path: PathBuf::from(String::from_utf8_lossy(path).into_owned()),
+            id: parts[3].to_string(),
+        };
+        if !blobs.contains(&blob) {
+            blobs.push(blob);
+        }
+    }
+
+    blobs
+}
+
+/// List the image blobs added or modified by the target changes.
+///
+/// Files are selected by extension, as when collecting files from disk.
+/// A blob that appears in several commits of a range is listed once per path.
+pub fn changed_images(repo: &Path, target: &GitTarget) -> Result<Vec<ChangedBlob>> {
+    const RAW: &[&str] = &[
+        "--raw",
+        "-z",
+        "--no-abbrev",
+        "--no-renames",
+        "--diff-filter=AM",
+    ];
+
+    let output = match target {
+        GitTarget::Staged => {
+            let mut args = vec!["diff", "--cached"];
+            args.extend_from_slice(RAW);
+            git(repo, &args)?
+        }
+        GitTarget::Range(range) => {
+            // Walk every commit so that blobs replaced later in the range
+            // are still checked; a single revision means just that commit.
+            // Merges are diffed against their first parent, so a blob that
+            // only a merge brings in, such as a conflict resolution, is seen.
+            let mut args = vec!["log", "--format=", "--diff-merges=first-parent"];
+            if !range.contains("..") {
+                args.push("-1");
+            }
+            args.extend_from_slice(RAW);
+            args.push(range);
+            args.push("--");
+            git(repo, &args)?
+        }
+    };
+
+    Ok(parse_raw_diff(&output)
+        .into_iter()
+        .filter(|blob| detect_format_from_extension(&blob.path).is_some())
+        .collect())
+}
+
+/// Parse `git cat-file --batch` output into blob contents, in request order.
+///
+/// Missing objects yield `None`.
+pub fn parse_batch(mut output: &[u8], count: usize) -> Result<Vec<Option<Vec<u8>>>> {
+    let malformed = || Error::git("cat-file", "malformed --batch output");
+    let mut contents = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        let newline = output
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(malformed)?;
+        let header = String::from_utf8_lossy(&output[..newline]).into_owned();
+        output = &output[newline + 1..];
+
+        if header.ends_with(" missing") || header.ends_with(" ambiguous") {
+            contents.push(None);
+            continue;
+        }
+
+        let size: usize = header
+            .rsplit(' ')
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(malformed)?;
+        let body = output.get(..size).ok_or_else(malformed)?;
+        contents.push(Some(body.to_vec()));
+        // Skip the content and its trailing newline.
+        output = output.get(size + 1..).unwrap_or_default();
+    }
+
+    Ok(contents)
+}
+
+/// Read blob contents from the object store.
+pub fn read_blobs(repo: &Path, ids: &[&str]) -> Result<Vec<Option<Vec<u8>>>> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut child = Command::new("git")
+        .current_dir(repo)
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::git("cat-file", e.to_string()))?;
+
+    // Feed IDs from another thread so a full stdout pipe cannot deadlock us.
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let request: String = ids.iter().map(|id| format!("{}\n", id)).collect();
+    let writer = thread::spawn(move || stdin.write_all(request.as_bytes()));
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| Error::git("cat-file", e.to_string()))?;
+    writer
+        .join()
+        .expect("cat-file writer panicked")
+        .map_err(|e| Error::git("cat-file", e.to_string()))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::git("cat-file", stderr.trim()));
+    }
+
+    parse_batch(&output.stdout, ids.len())
+}
+
+/// Check every image blob changed by the target.
+pub fn check_changes(repo: &Path, target: &GitTarget) -> Result<Vec<CheckResult>> {
+    let blobs = changed_images(repo, target)?;
+    let ids: Vec<&str> = blobs.iter().map(|b| b.id.as_str()).collect();
+    let contents = read_blobs(repo, &ids)?;
+
+    Ok(blobs
+        .into_iter()
+        .zip(contents)
+        .map(|(blob, data)| {
+            let status = match data {
+                Some(data) => check_data(&data, &blob.path),
+                None => CheckStatus::ParseError(format!("blob {} not found", blob.id)),
+            };
+            CheckResult {
+                path: blob.path,
+                status,
+            }
+        })
+        .collect())
+}
+
+/// Print the paths of files that need cleaning, one per line.
+///
+/// Paths are relative to the repository root, like `git diff --name-only`,
+/// so the list can be fed to `pmi -i` and `git add` from there.
+pub fn print_name_only(results: &[CheckResult]) {
+    for result in results {
+        if matches!(result.status, CheckStatus::MetadataFound(_)) {
+            println!("{}", result.path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID_A: &str = "1111111111111111111111111111111111111111";
+    const ID_B: &str = "2222222222222222222222222222222222222222";
+    const ZERO: &str = "0000000000000000000000000000000000000000";
+
+    #[test]
+    fn test_parse_raw_diff() {
+        let output = format!(
+            ":000000 100644 {z} {a} A\0photos/a.jpg\0:100644 100644 {a} {b} M\0b c.png\0",
+            z = ZERO,
+            a = ID_A,
+            b = ID_B
+        );
+        let blobs = parse_raw_diff(output.as_bytes());
+        assert_eq!(
+            blobs,
+            vec![
+                ChangedBlob {
+                    path: PathBuf::from("photos/a.jpg"),
+                    id: ID_A.to_string()
+                },
+                ChangedBlob {
+                    path: PathBuf::from("b c.png"),
+                    id: ID_B.to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_raw_diff_log_output() {
+        // `git log --format=` separates commits with newlines.
+        let record = format!(":000000 100644 {} {} A\0a.gif\0", ZERO, ID_A);
+        let output = format!("{}\n{}", record, record);
+        assert_eq!(parse_raw_diff(output.as_bytes()).len(), 1);
+        assert!(parse_raw_diff(b"").is_empty());
+    }
+
+    #[test]
+    fn test_parse_batch() {
+        let mut output = format!("{} blob 3\nabc\n", ID_A).into_bytes();
+        output.extend_from_slice(format!("{} missing\n", ID_B).as_bytes());
+        output.extend_from_slice(format!("{} blob 2\n\n\n\n", ID_A).as_bytes());
+
+        let contents = parse_batch(&output, 3).unwrap();
+        assert_eq!(
+            contents,
+            vec![Some(b"abc".to_vec()), None, Some(b"\n\n".to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_parse_batch_truncated() {
+        let output = format!("{} blob 10\nabc", ID_A);
+        assert!(parse_batch(output.as_bytes(), 1).is_err());
+        assert!(parse_batch(b"", 1).is_err());
+    }
+
+    #[test]
+    fn test_target_from_config() {
+        let mut config = Config::default();
+        assert_eq!(GitTarget::from_config(&config), GitTarget::Staged);
+        config.rev_range = Some(String::from("main..HEAD"));
+        assert_eq!(
+            GitTarget::from_config(&config),
+            GitTarget::Range(String::from("main..HEAD"))
+        );
+    }
+}
@@ -28,17 +28,40 @@
 //! println!("Processed {} files", stats.processed);
 //! ```
 
+pub mod atomic;
+pub mod backup;
+pub mod cache;
+pub mod check;
+pub mod cleanup;
 pub mod cli;
+pub mod codec;
+pub mod digest;
 pub mod error;
 pub mod formats;
+pub mod fsmeta;
+pub mod git;
+pub mod lossless;
+pub mod metadata;
+pub mod naming;
 pub mod parallel;
+pub mod preset;
 pub mod processor;
+pub mod report;
+pub mod settings;
+pub mod shred;
 pub mod simd;
 pub mod terminal;
+pub mod toml;
 
+pub use check::{CheckResult, CheckStatus, check_file};
 pub use cli::Config;
 pub use error::{Error, Result};
-pub use formats::{ImageFormat, detect_format, strip_metadata};
+pub use formats::{
+    ImageFormat, Retained, StripOptions, StripReport, detect_format, inspect_metadata,
+    strip_metadata, strip_metadata_with, strip_report, verify_result, verify_stripped,
+};
+pub use lossless::{FrameDigest, frame_digests, prove_lossless};
+pub use metadata::{Category, KeepReason, KeptItem, MetadataItem};
 pub use parallel::{ThreadPool, available_parallelism};
 pub use processor::Processor;
 pub use simd::acceleration_report;
@@ -0,0 +1,229 @@
+//! Pixel-identity proof.
+//!
+//! Decodes every frame of an image before and after stripping and compares
+//! the results. The per-frame SHA-256 digests can be recorded as evidence that
+//! stripping did not change any pixels.
+//!
+//! Proofs are available for PNG (including APNG) and GIF, whose image data
+//! can be decoded with the built-in INFLATE and LZW decoders, and for JPEG,
+//! where byte equality of the frame header, tables and entropy-coded scans
+//! is enough to show the decoded image is unchanged.
+
+use crate::codec::Frame;
+use crate::digest::{self, Digest, Sha256};
+use crate::error::{Error, Result};
+use crate::formats::{self, ImageFormat, gif, jpeg, png};
+use std::path::Path;
+
+/// Digest of a single decoded frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameDigest {
+    /// Zero-based frame index.
+    pub index: usize,
+    /// Frame width in pixels.
+    pub width: u32,
+    /// Frame height in pixels.
+    pub height: u32,
+    /// SHA-256 over the frame dimensions, header and decoded pixels.
+    pub digest: Digest,
+}
+
+impl FrameDigest {
+    /// Get the digest as a lowercase hex string.
+    pub fn hex(&self) -> String {
+        digest::to_hex(&self.digest)
+    }
+}
+
+/// Check whether pixel-identity proofs are available for a format.
+pub fn supports(format: ImageFormat) -> bool {
+    matches!(
+        format,
+        ImageFormat::Png | ImageFormat::Gif | ImageFormat::Jpeg
+    )
+}
+
+/// Decode every frame of an image.
+pub fn decode_frames(data: &[u8], path: &Path) -> Result<Vec<Frame>> {
+    match formats::detect_format(data) {
+        Some(ImageFormat::Png) => png::decode_frames(data, path),
+        Some(ImageFormat::Gif) => gif::decode_frames(data, path),
+        other => Err(Error::unsupported_format(path, other.map(|f| f.name()))),
+    }
+}
+
+/// Build the single frame digest of a JPEG.
+fn jpeg_frame_digest(image: &jpeg::ImageDigest) -> FrameDigest {
+    FrameDigest {
+        index: 0,
+        width: image.width as u32,
+        height: image.height as u32,
+        digest: image.combined(),
+    }
+}
+
+/// Convert a decode failure of cleaned output into a verification error.
+fn undecodable(path: &Path) -> impl Fn(Error) -> Error + '_ {
+    move |e| match e {
+        Error::InvalidImage { reason, .. } => {
+            Error::verification_failed(path, format!("Cleaned output does not decode: {}", reason))
+        }
+        other => other,
+    }
+}
+
+/// Compute a digest for every frame of an image.
+pub fn frame_digests(data: &[u8], path: &Path) -> Result<Vec<FrameDigest>> {
+    if formats::detect_format(data) == Some(ImageFormat::Jpeg) {
+        return Ok(vec![jpeg_frame_digest(&jpeg::image_digest(data, path)?)]);
+    }
+
+    let frames = decode_frames(data, path)?;
+
+    Ok(frames
+        .iter()
+        .enumerate()
+        .map(|(index, frame)| {
+            let mut hasher = Sha256::new();
+            hasher.update(&frame.width.to_be_bytes());
+            hasher.update(&frame.height.to_be_bytes());
+            hasher.update(&(frame.header.len() as u64).to_be_bytes());
+            hasher.update(&frame.header);
+            hasher.update(&frame.pixels);
+
+            FrameDigest {
+                index,
+                width: frame.width,
+                height: frame.height,
+                digest: hasher.finalize(),
+            }
+        })
+        .collect())
+}
+
+/// Prove that cleaned output decodes to exactly the same frames as the original.
+///
+/// Returns the per-frame digests on success, or a verification error naming
+/// the first frame that differs.
+pub fn prove_lossless(original: &[u8], cleaned: &[u8], path: &Path) -> Result<Vec<FrameDigest>> {
+    if formats::detect_format(original) == Some(ImageFormat::Jpeg) {
+        let after = jpeg::check_image(original, cleaned, path)?;
+        return Ok(vec![jpeg_frame_digest(&after)]);
+    }
+
+    let before = frame_digests(original, path)?;
+    let after = frame_digests(cleaned, path).map_err(undecodable(path))?;
+
+    if before.len() != after.len() {
+        return Err(Error::verification_failed(
+            path,
+            format!(
+                "Frame count changed from {} to {}",
+                before.len(),
+                after.len()
+            ),
+        ));
+    }
+
+    if let Some(frame) = before.iter().zip(&after).find(|(b, a)| b != a) {
+        return Err(Error::verification_failed(
+            path,
+            format!("Pixels of frame {} differ after stripping", frame.0.index),
+        ));
+    }
+
+    Ok(after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{gif, jpeg, png};
+    use std::path::PathBuf;
+
+    fn test_path() -> PathBuf {
+        PathBuf::from("test.png")
+    }
+
+    #[test]
+    fn test_supports() {
+        assert!(supports(ImageFormat::Png));
+        assert!(supports(ImageFormat::Gif));
+        assert!(supports(ImageFormat::Jpeg));
+        assert!(!supports(ImageFormat::WebP));
+        assert!(!supports(ImageFormat::Tiff));
+    }
+
+    #[test]
+    fn test_prove_png() {
+        let data = png::create_png_with_metadata();
+        let cleaned = png::strip(&data, &test_path()).unwrap();
+        let digests = prove_lossless(&data, &cleaned, &test_path()).unwrap();
+        assert_eq!(digests.len(), 1);
+        assert_eq!(digests[0].hex().len(), 64);
+    }
+
+    #[test]
+    fn test_prove_gif() {
+        let data = gif::create_gif_with_comment();
+        let cleaned = gif::strip(&data, &test_path()).unwrap();
+        assert_eq!(
+            prove_lossless(&data, &cleaned, &test_path()).unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_prove_detects_changed_pixels() {
+        let original = gif::create_minimal_gif();
+        let mut changed = original.clone();
+        // Encode index 1 instead of 0 (clear, 1, end with 3-bit codes).
+        let data_pos = changed.len() - 4;
+        changed[data_pos] = 0x4C;
+
+        let err = prove_lossless(&original, &changed, &test_path()).unwrap_err();
+        assert!(matches!(err, Error::VerificationFailed { .. }));
+        assert!(err.to_string().contains("frame 0"));
+    }
+
+    #[test]
+    fn test_prove_detects_undecodable_output() {
+        let original = png::create_minimal_png();
+        let mut broken = original.clone();
+        // Corrupt the zlib header inside IDAT.
+        let idat = broken.windows(4).position(|w| w == b"IDAT").unwrap();
+        broken[idat + 4] = 0x00;
+
+        let err = prove_lossless(&original, &broken, &test_path()).unwrap_err();
+        assert!(err.to_string().contains("does not decode"));
+    }
+
+    #[test]
+    fn test_prove_jpeg() {
+        let data = jpeg::create_jpeg_with_exif();
+        let cleaned = jpeg::strip(&data, &test_path()).unwrap();
+        let digests = prove_lossless(&data, &cleaned, &test_path()).unwrap();
+        assert_eq!(digests, frame_digests(&data, &test_path()).unwrap());
+        assert_eq!((digests[0].width, digests[0].height), (1, 1));
+    }
+
+    #[test]
+    fn test_prove_jpeg_detects_changed_scan() {
+        let data = jpeg::create_minimal_jpeg();
+        let mut changed = data.clone();
+        let last = changed.len() - 3;
+        changed[last] ^= 0x01;
+
+        let err = prove_lossless(&data, &changed, &test_path()).unwrap_err();
+        assert!(err.to_string().contains("scan 1 differs"));
+    }
+
+    #[test]
+    fn test_frame_digests_unsupported_format() {
+        let webp = crate::formats::webp::create_minimal_webp();
+        assert!(matches!(
+            frame_digests(&webp, &test_path()),
+            Err(Error::UnsupportedFormat { .. })
+        ));
+    }
+}
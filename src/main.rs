@@ -2,10 +2,20 @@
 //!
 //! A CLI tool that strips metadata from images to protect user privacy.
 
-use pmi::cli::{Config, help_message, version_message};
+use pmi::backup::{self, RestoreStatus};
+use pmi::check::{self, EXIT_PARSE_ERROR};
+use pmi::cleanup;
+use pmi::cli::completions;
+use pmi::cli::options::command_help;
+use pmi::cli::{Command, Config, help_message, version_message};
+use pmi::git::{self, GitTarget};
+use pmi::preset::{self, Preset};
 use pmi::processor::Processor;
+use pmi::report;
+use pmi::settings::Environment;
 use pmi::terminal::{print_error, print_summary};
 use std::env;
+use std::path::Path;
 use std::process::ExitCode;
 
 fn main() -> ExitCode {
@@ -18,13 +28,21 @@ fn main() -> ExitCode {
             print_error(&e.to_string());
             eprintln!();
             eprintln!("For more information, try '--help'");
+            // Keep exit code 1 unambiguous for `check`: it means metadata was found.
+            if matches!(args.get(1).map(String::as_str), Some("check" | "git-check")) {
+                return ExitCode::from(EXIT_PARSE_ERROR);
+            }
             return ExitCode::from(1);
         }
     };
 
-    // Handle help flag.
+    // Handle help flag, for one command if it was named.
     if config.help {
-        println!("{}", help_message());
+        if config.named_command {
+            println!("{}", command_help(config.command));
+        } else {
+            println!("{}", help_message());
+        }
         return ExitCode::SUCCESS;
     }
 
@@ -34,6 +52,91 @@ fn main() -> ExitCode {
         return ExitCode::SUCCESS;
     }
 
+    // Print a completion script offering user profiles as well as the
+    // built-in presets. A broken config file only loses the profiles.
+    if let Some(shell) = config.shell {
+        let files = Environment::current().load_files().unwrap_or_default();
+        let profiles: Vec<Preset> = files.into_iter().flat_map(|f| f.profiles).collect();
+        print!(
+            "{}",
+            completions::script(shell, &preset::available(&profiles))
+        );
+        return ExitCode::SUCCESS;
+    }
+
+    // Show the effective settings.
+    if config.print_config {
+        println!("{}", config.describe());
+        return ExitCode::SUCCESS;
+    }
+
+    // Read-only check.
+    if config.command == Command::Check {
+        return match check::check_paths(&config) {
+            Ok(results) => {
+                check::print_results(&results, &config);
+                ExitCode::from(check::exit_code(&results))
+            }
+            Err(e) => {
+                print_error(&e.to_string());
+                ExitCode::from(EXIT_PARSE_ERROR)
+            }
+        };
+    }
+
+    // Read-only check of image blobs changed in git.
+    if config.command == Command::GitCheck {
+        let target = GitTarget::from_config(&config);
+        return match git::check_changes(Path::new("."), &target) {
+            Ok(results) => {
+                if config.name_only {
+                    git::print_name_only(&results);
+                } else {
+                    check::print_results(&results, &config);
+                }
+                ExitCode::from(check::exit_code(&results))
+            }
+            Err(e) => {
+                print_error(&e.to_string());
+                ExitCode::from(EXIT_PARSE_ERROR)
+            }
+        };
+    }
+
+    // Put back the originals of an in-place run.
+    if config.command == Command::Restore {
+        return match backup::restore(&config) {
+            Ok(results) => {
+                let done = |status: &RestoreStatus| {
+                    matches!(
+                        status,
+                        RestoreStatus::Restored | RestoreStatus::AlreadyRestored
+                    )
+                };
+                if results.iter().all(|(_, status)| done(status)) {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::from(1)
+                }
+            }
+            Err(e) => {
+                print_error(&e.to_string());
+                ExitCode::from(1)
+            }
+        };
+    }
+
+    // Remove temporary files left by interrupted runs.
+    if config.command == Command::Cleanup {
+        return match cleanup::run(&config) {
+            Ok(_) => ExitCode::SUCCESS,
+            Err(e) => {
+                print_error(&e.to_string());
+                ExitCode::from(1)
+            }
+        };
+    }
+
     // Run the processor.
     let mut processor = Processor::new(config.clone());
     match processor.run() {
@@ -41,6 +144,12 @@ fn main() -> ExitCode {
             // Print summary.
             print_summary(&stats, config.quiet);
 
+            // Write the machine-readable report, if requested.
+            if let Err(e) = report::output_report(&config, processor.records(), &stats) {
+                print_error(&e.to_string());
+                return ExitCode::from(1);
+            }
+
             // Exit with failure if any files failed.
             if stats.failed > 0 {
                 ExitCode::from(1)
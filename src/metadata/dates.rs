@@ -0,0 +1,607 @@
+//! Timestamp rewriting.
+//!
+//! For `--dates`, capture and modification times are rewritten instead of
+//! removed, so photo libraries can still sort by date. The same rule is
+//! applied to every timestamp a file carries: the EXIF date/time tags, the
+//! PNG `tIME` chunk and XMP `xmp:CreateDate`. Sub-second and time zone tags
+//! are kept only when the times are kept or shifted, since they mean nothing
+//! for a date alone or a fixed time.
+
+use super::exif::{Entry, Exif, Ifd};
+use super::xmp;
+use crate::error::{Error, Result};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// EXIF date/time tags, as "YYYY:MM:DD HH:MM:SS".
+const DATE_TIME_TAGS: &[(Ifd, u16)] = &[
+    (Ifd::Primary, 0x0132), // DateTime.
+    (Ifd::Exif, 0x9003),    // DateTimeOriginal.
+    (Ifd::Exif, 0x9004),    // DateTimeDigitized.
+];
+
+/// EXIF tags refining a date/time: time zone offsets and sub-seconds.
+const QUALIFIER_TAGS: &[(Ifd, u16)] = &[
+    (Ifd::Exif, 0x9010), // OffsetTime.
+    (Ifd::Exif, 0x9011), // OffsetTimeOriginal.
+    (Ifd::Exif, 0x9012), // OffsetTimeDigitized.
+    (Ifd::Exif, 0x9290), // SubSecTime.
+    (Ifd::Exif, 0x9291), // SubSecTimeOriginal.
+    (Ifd::Exif, 0x9292), // SubSecTimeDigitized.
+];
+
+/// XMP properties rewritten along with the EXIF tags.
+const XMP_PROPERTIES: &[&str] = &["xmp:CreateDate"];
+
+/// Seconds per day.
+const DAY: i64 = 86_400;
+
+/// What to do with timestamps when stripping.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DateMode {
+    /// Remove them along with all other metadata.
+    #[default]
+    Remove,
+    /// Keep them unchanged.
+    Keep,
+    /// Keep the date and set the time to midnight.
+    KeepDateOnly,
+    /// Move every timestamp by this many seconds.
+    Shift(i64),
+    /// Replace every timestamp with this one.
+    Fixed(Timestamp),
+}
+
+impl DateMode {
+    /// Parse a mode, as given to `--dates`: `remove`, `keep`,
+    /// `keep-date-only`, `shift=<duration>` or `fixed=<timestamp>`.
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = |reason: String| Error::InvalidArgument {
+            argument: String::from("--dates"),
+            reason,
+        };
+
+        if value == "remove" {
+            Ok(DateMode::Remove)
+        } else if value == "keep" {
+            Ok(DateMode::Keep)
+        } else if value == "keep-date-only" {
+            Ok(DateMode::KeepDateOnly)
+        } else if let Some(duration) = value.strip_prefix("shift=") {
+            parse_duration(duration)
+                .map(DateMode::Shift)
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "'{}' is not a duration such as -2h, 3d or 1d12h30m",
+                        duration
+                    ))
+                })
+        } else if let Some(timestamp) = value.strip_prefix("fixed=") {
+            Timestamp::parse_iso(timestamp)
+                .filter(|(_, rest)| rest.is_empty())
+                .map(|(t, _)| DateMode::Fixed(t))
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "'{}' is not a timestamp such as 2024-01-31 or 2024-01-31T12:00:00",
+                        timestamp
+                    ))
+                })
+        } else {
+            Err(invalid(String::from(
+                "Expected remove, keep, keep-date-only, shift=<duration> or fixed=<timestamp>",
+            )))
+        }
+    }
+
+    /// Apply the mode to a timestamp. Returns `None` if it should be dropped.
+    pub fn apply(&self, timestamp: Timestamp) -> Option<Timestamp> {
+        match *self {
+            DateMode::Remove => None,
+            DateMode::Keep => Some(timestamp),
+            DateMode::KeepDateOnly => Some(timestamp.date_only()),
+            DateMode::Shift(seconds) => timestamp.shift(seconds),
+            DateMode::Fixed(fixed) => Some(fixed),
+        }
+    }
+}
+
+impl fmt::Display for DateMode {
+    /// Format the mode as `--dates` takes it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            DateMode::Remove => f.write_str("remove"),
+            DateMode::Keep => f.write_str("keep"),
+            DateMode::KeepDateOnly => f.write_str("keep-date-only"),
+            DateMode::Shift(seconds) => write!(f, "shift={}", format_duration(seconds)),
+            DateMode::Fixed(fixed) => write!(f, "fixed={}", fixed),
+        }
+    }
+}
+
+/// Format a duration in the largest units `parse_duration` reads back.
+fn format_duration(seconds: i64) -> String {
+    let mut text = String::from(if seconds < 0 { "-" } else { "" });
+    let mut rest = seconds.unsigned_abs();
+    for (unit, size) in [('d', DAY as u64), ('h', 3600), ('m', 60), ('s', 1)] {
+        if rest >= size {
+            text.push_str(&format!("{}{}", rest / size, unit));
+            rest %= size;
+        }
+    }
+    if seconds == 0 {
+        text.push_str("0s");
+    }
+    text
+}
+
+/// Parse a signed duration made of `<n>w`, `<n>d`, `<n>h`, `<n>m` and `<n>s` parts.
+fn parse_duration(text: &str) -> Option<i64> {
+    let (sign, mut rest) = match text.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, text.strip_prefix('+').unwrap_or(text)),
+    };
+    if rest.is_empty() {
+        return None;
+    }
+
+    let mut total: i64 = 0;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let amount: i64 = rest[..digits].parse().ok()?;
+        let unit = match rest[digits..].chars().next()? {
+            'w' => 7 * DAY,
+            'd' => DAY,
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        total = total.checked_add(amount.checked_mul(unit)?)?;
+        rest = &rest[digits + 1..];
+    }
+    Some(sign * total)
+}
+
+/// A calendar date and time of day, without a time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl Timestamp {
+    /// Create a timestamp, checking that it is a real date between years 1 and 9999.
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Option<Self> {
+        let valid = (1..=9999).contains(&year)
+            && (1..=12).contains(&month)
+            && day >= 1
+            && day <= days_in_month(year, month)
+            && hour < 24
+            && minute < 60
+            && second < 61;
+        valid.then_some(Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        })
+    }
+
+    /// Get the current time in UTC.
+    pub fn now_utc() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let epoch = Timestamp::new(1970, 1, 1, 0, 0, 0).expect("the epoch is a valid date");
+        epoch
+            .shift(i64::try_from(seconds).unwrap_or(0))
+            .unwrap_or(epoch)
+    }
+
+    /// Parse an EXIF date/time: `YYYY:MM:DD HH:MM:SS`.
+    pub fn parse_exif(text: &str) -> Option<Self> {
+        let bytes = text.as_bytes();
+        if bytes.len() < 19 || bytes[4] != b':' || bytes[7] != b':' || bytes[10] != b' ' {
+            return None;
+        }
+        Self::from_parts(text, b':')
+    }
+
+    /// Parse an ISO 8601 date or date and time, as used by XMP.
+    ///
+    /// Returns the timestamp and whatever follows the seconds (fraction and
+    /// time zone). A date alone is taken as midnight.
+    pub fn parse_iso(text: &str) -> Option<(Self, &str)> {
+        let bytes = text.as_bytes();
+        if bytes.len() < 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+            return None;
+        }
+        if bytes.len() == 10 {
+            let date = format!("{}T00:00:00", text);
+            return Self::from_parts(&date, b'-').map(|t| (t, ""));
+        }
+        if bytes[10] != b'T' && bytes[10] != b' ' {
+            return None;
+        }
+        // Seconds are optional in XMP dates.
+        if bytes.len() == 16 || bytes.get(16).is_some_and(|&b| b != b':') {
+            let padded = format!("{}:00", text.get(..16)?);
+            return Self::from_parts(&padded, b'-').map(|t| (t, &text[16..]));
+        }
+        let timestamp = Self::from_parts(text.get(..19)?, b'-')?;
+        Some((timestamp, &text[19..]))
+    }
+
+    /// Parse the fixed-width fields shared by the EXIF and ISO layouts.
+    fn from_parts(text: &str, date_separator: u8) -> Option<Self> {
+        let bytes = text.as_bytes();
+        if bytes.len() < 19
+            || bytes[4] != date_separator
+            || bytes[7] != date_separator
+            || bytes[13] != b':'
+            || bytes[16] != b':'
+        {
+            return None;
+        }
+        let number = |range: std::ops::Range<usize>| -> Option<u16> {
+            let part = text.get(range)?;
+            part.bytes()
+                .all(|b| b.is_ascii_digit())
+                .then(|| part.parse().ok())?
+        };
+        Self::new(
+            number(0..4)?,
+            number(5..7)? as u8,
+            number(8..10)? as u8,
+            number(11..13)? as u8,
+            number(14..16)? as u8,
+            number(17..19)? as u8,
+        )
+    }
+
+    /// Read a PNG `tIME` chunk body.
+    pub fn from_png(body: &[u8]) -> Option<Self> {
+        match *body {
+            [y0, y1, month, day, hour, minute, second] => Self::new(
+                u16::from_be_bytes([y0, y1]),
+                month,
+                day,
+                hour,
+                minute,
+                second,
+            ),
+            _ => None,
+        }
+    }
+
+    /// Encode as a PNG `tIME` chunk body.
+    pub fn to_png(&self) -> [u8; 7] {
+        let [y0, y1] = self.year.to_be_bytes();
+        [
+            y0,
+            y1,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+        ]
+    }
+
+    /// Format as an EXIF date/time.
+    pub fn to_exif(&self) -> String {
+        format!(
+            "{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+
+    /// Get the same date at midnight.
+    pub fn date_only(&self) -> Self {
+        Self {
+            hour: 0,
+            minute: 0,
+            second: 0,
+            ..*self
+        }
+    }
+
+    /// Move the timestamp by a number of seconds, if the result is in range.
+    pub fn shift(&self, seconds: i64) -> Option<Self> {
+        let total = self.to_seconds().checked_add(seconds)?;
+        let days = total.div_euclid(DAY);
+        let time = total.rem_euclid(DAY);
+        let (year, month, day) = civil_from_days(days);
+        Self::new(
+            u16::try_from(year).ok()?,
+            month,
+            day,
+            (time / 3600) as u8,
+            (time / 60 % 60) as u8,
+            (time % 60) as u8,
+        )
+    }
+
+    /// Seconds since 1970-01-01T00:00:00.
+    fn to_seconds(self) -> i64 {
+        let days = days_from_civil(i64::from(self.year), self.month, self.day);
+        days * DAY
+            + i64::from(self.hour) * 3600
+            + i64::from(self.minute) * 60
+            + i64::from(self.second).min(59)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    /// Format as ISO 8601, as used by XMP.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Check for a Gregorian leap year.
+fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// Get the number of days in a month.
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a Gregorian date.
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Gregorian date of a number of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Rewrite the date/time tags of an EXIF block.
+///
+/// Returns little-endian entries for the rewritten tags, plus the sub-second
+/// and time zone tags when keeping or shifting. Unreadable dates are dropped.
+pub fn exif_entries(exif: &Exif, mode: DateMode) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for entry in &exif.entries {
+        let key = (entry.ifd, entry.tag);
+        if DATE_TIME_TAGS.contains(&key) {
+            let rewritten = entry
+                .ascii()
+                .and_then(|text| Timestamp::parse_exif(&text))
+                .and_then(|t| mode.apply(t));
+            if let Some(timestamp) = rewritten {
+                let mut value = timestamp.to_exif().into_bytes();
+                value.push(0);
+                entries.push(Entry::new(
+                    entry.ifd,
+                    entry.tag,
+                    2,
+                    value.len() as u32,
+                    value,
+                ));
+            }
+        } else if QUALIFIER_TAGS.contains(&key)
+            && matches!(mode, DateMode::Keep | DateMode::Shift(_))
+            && entry.field_type == 2
+        {
+            entries.push(entry.clone());
+        }
+    }
+    entries
+}
+
+/// Rewrite the timestamps of an XMP packet, as (property, value) pairs.
+pub fn xmp_properties(packet: &[u8], mode: DateMode) -> Vec<(String, String)> {
+    XMP_PROPERTIES
+        .iter()
+        .filter_map(|&name| {
+            let value = xmp::property(packet, name)?;
+            let (timestamp, rest) = Timestamp::parse_iso(&value)?;
+            let rewritten = mode.apply(timestamp)?;
+            let value = match mode {
+                DateMode::KeepDateOnly => rewritten.to_string()[..10].to_string(),
+                // Keep the fraction and time zone of the original.
+                DateMode::Keep | DateMode::Shift(_) => format!("{}{}", rewritten, rest),
+                _ => rewritten.to_string(),
+            };
+            Some((name.to_string(), value))
+        })
+        .collect()
+}
+
+/// Rewrite a PNG `tIME` chunk body.
+pub fn png_time(body: &[u8], mode: DateMode) -> Option<[u8; 7]> {
+    Timestamp::from_png(body)
+        .and_then(|t| mode.apply(t))
+        .map(|t| t.to_png())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::exif;
+
+    fn timestamp(text: &str) -> Timestamp {
+        Timestamp::parse_exif(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        for mode in [
+            "remove",
+            "keep",
+            "keep-date-only",
+            "shift=-1d12h",
+            "shift=1m30s",
+            "shift=0s",
+            "fixed=2020-02-29T08:30:00",
+        ] {
+            assert_eq!(DateMode::parse(mode).unwrap().to_string(), mode);
+        }
+        assert_eq!(
+            DateMode::parse("shift=+90m").unwrap().to_string(),
+            "shift=1h30m"
+        );
+
+        assert_eq!(DateMode::parse("remove").unwrap(), DateMode::Remove);
+        assert_eq!(DateMode::parse("keep").unwrap(), DateMode::Keep);
+        assert_eq!(
+            DateMode::parse("keep-date-only").unwrap(),
+            DateMode::KeepDateOnly
+        );
+        assert_eq!(
+            DateMode::parse("shift=-1d12h").unwrap(),
+            DateMode::Shift(-(DAY + 12 * 3600))
+        );
+        assert_eq!(
+            DateMode::parse("shift=+90m").unwrap(),
+            DateMode::Shift(5400)
+        );
+        assert_eq!(
+            DateMode::parse("fixed=2020-02-29").unwrap(),
+            DateMode::Fixed(timestamp("2020:02:29 00:00:00"))
+        );
+        assert_eq!(
+            DateMode::parse("fixed=2020-02-29T08:30:00").unwrap(),
+            DateMode::Fixed(timestamp("2020:02:29 08:30:00"))
+        );
+        assert!(DateMode::parse("shift=").is_err());
+        assert!(DateMode::parse("shift=5").is_err());
+        assert!(DateMode::parse("shift=2y").is_err());
+        assert!(DateMode::parse("fixed=2021-02-29").is_err());
+        assert!(DateMode::parse("fixed=2021-02-01T10:00:00Z").is_err());
+        assert_eq!(DateMode::parse("keep").unwrap(), DateMode::Keep);
+        assert!(DateMode::parse("keep-time").is_err());
+    }
+
+    #[test]
+    fn test_shift() {
+        let t = timestamp("2024:02:28 23:30:00");
+        assert_eq!(t.shift(3600), Some(timestamp("2024:02:29 00:30:00")));
+        assert_eq!(t.shift(2 * DAY), Some(timestamp("2024:03:01 23:30:00")));
+        assert_eq!(
+            t.shift(-(365 * DAY)),
+            Some(timestamp("2023:02:28 23:30:00"))
+        );
+        assert_eq!(
+            timestamp("1970:01:01 00:00:00").shift(-1),
+            Some(timestamp("1969:12:31 23:59:59"))
+        );
+        assert_eq!(timestamp("9999:12:31 23:59:59").shift(1), None);
+    }
+
+    #[test]
+    fn test_parse_iso() {
+        let (t, rest) = Timestamp::parse_iso("2024-01-02T03:04:05.25+01:00").unwrap();
+        assert_eq!(t, timestamp("2024:01:02 03:04:05"));
+        assert_eq!(rest, ".25+01:00");
+        let (t, rest) = Timestamp::parse_iso("2024-01-02T03:04Z").unwrap();
+        assert_eq!(t, timestamp("2024:01:02 03:04:00"));
+        assert_eq!(rest, "Z");
+        assert!(Timestamp::parse_iso("2024").is_none());
+        assert!(Timestamp::parse_iso("2024-13-01").is_none());
+    }
+
+    #[test]
+    fn test_exif_entries() {
+        let ascii = |ifd, tag, text: &str| {
+            let mut value = text.as_bytes().to_vec();
+            value.push(0);
+            Entry::new(ifd, tag, 2, value.len() as u32, value)
+        };
+        let block = exif::build(&[
+            ascii(Ifd::Primary, 0x010F, "Canon"),
+            ascii(Ifd::Primary, 0x0132, "2024:05:06 07:08:09"),
+            ascii(Ifd::Exif, 0x9003, "2024:05:06 07:08:09"),
+            ascii(Ifd::Exif, 0x9004, "    :  :     :  :  "),
+            ascii(Ifd::Exif, 0x9011, "+02:00"),
+            ascii(Ifd::Exif, 0x9291, "42"),
+        ]);
+        let exif = exif::parse(&block).unwrap();
+
+        let values = |mode| -> Vec<(u16, String)> {
+            exif_entries(&exif, mode)
+                .iter()
+                .map(|e| (e.tag, e.ascii().unwrap()))
+                .collect()
+        };
+        assert_eq!(
+            values(DateMode::KeepDateOnly),
+            [
+                (0x0132, String::from("2024:05:06 00:00:00")),
+                (0x9003, String::from("2024:05:06 00:00:00")),
+            ]
+        );
+        assert_eq!(
+            values(DateMode::Shift(-DAY)),
+            [
+                (0x0132, String::from("2024:05:05 07:08:09")),
+                (0x9003, String::from("2024:05:05 07:08:09")),
+                (0x9011, String::from("+02:00")),
+                (0x9291, String::from("42")),
+            ]
+        );
+        assert_eq!(values(DateMode::Keep), values(DateMode::Shift(0)));
+        assert!(values(DateMode::Remove).is_empty());
+    }
+
+    #[test]
+    fn test_xmp_properties() {
+        let packet = xmp::build(&[(
+            String::from("xmp:CreateDate"),
+            String::from("2024-01-02T03:04:05+01:00"),
+        )]);
+        let value = |mode| xmp_properties(&packet, mode)[0].1.clone();
+        assert_eq!(value(DateMode::KeepDateOnly), "2024-01-02");
+        assert_eq!(value(DateMode::Shift(3600)), "2024-01-02T04:04:05+01:00");
+        assert_eq!(
+            value(DateMode::Fixed(timestamp("2000:01:01 12:00:00"))),
+            "2000-01-01T12:00:00"
+        );
+    }
+
+    #[test]
+    fn test_png_time() {
+        let body = timestamp("2024:12:31 23:59:59").to_png();
+        assert_eq!(
+            png_time(&body, DateMode::Shift(1)),
+            Some(timestamp("2025:01:01 00:00:00").to_png())
+        );
+        assert_eq!(png_time(&body[..6], DateMode::Shift(1)), None);
+    }
+}
@@ -0,0 +1,687 @@
+//! EXIF decoding.
+//!
+//! Reads the TIFF-structured directories inside an EXIF block (or a whole
+//! TIFF file) and names each tag. Parsing is lenient: whatever can be read
+//! is returned and damaged directories are skipped, since the goal is to
+//! describe what is present rather than to validate it.
+
+use super::{Category, Field};
+
+/// EXIF block identifier used by JPEG APP1 and optionally by WebP/PNG.
+pub const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+/// Tag pointing to the EXIF sub-IFD.
+pub const EXIF_IFD_POINTER: u16 = 0x8769;
+/// Tag pointing to the GPS sub-IFD.
+pub const GPS_IFD_POINTER: u16 = 0x8825;
+/// Tag pointing to the interoperability sub-IFD.
+pub const INTEROP_IFD_POINTER: u16 = 0xA005;
+/// Tag holding an XMP packet in a TIFF file's IFD0.
+pub const XMP_TAG: u16 = 0x02BC;
+
+/// Maximum number of IFDs followed, guarding against loops.
+const MAX_IFDS: usize = 16;
+
+/// Directory an entry was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ifd {
+    /// IFD0, describing the main image.
+    Primary,
+    /// IFD1, describing the embedded thumbnail.
+    Thumbnail,
+    /// EXIF sub-IFD (capture settings).
+    Exif,
+    /// GPS sub-IFD.
+    Gps,
+    /// Interoperability sub-IFD.
+    Interop,
+}
+
+/// Byte order of a TIFF structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// "II" (Intel).
+    Little,
+    /// "MM" (Motorola).
+    Big,
+}
+
+impl ByteOrder {
+    /// Read a u16 from the start of a slice.
+    pub fn read_u16(&self, data: &[u8]) -> u16 {
+        match self {
+            ByteOrder::Little => u16::from_le_bytes([data[0], data[1]]),
+            ByteOrder::Big => u16::from_be_bytes([data[0], data[1]]),
+        }
+    }
+
+    /// Read a u32 from the start of a slice.
+    pub fn read_u32(&self, data: &[u8]) -> u32 {
+        match self {
+            ByteOrder::Little => u32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+            ByteOrder::Big => u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+        }
+    }
+}
+
+/// A decoded IFD entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Directory the entry was read from.
+    pub ifd: Ifd,
+    /// Tag ID.
+    pub tag: u16,
+    /// TIFF field type.
+    pub field_type: u16,
+    /// Number of values.
+    pub count: u32,
+    /// Raw value bytes in the block's byte order (empty if unreadable).
+    pub value: Vec<u8>,
+}
+
+impl Entry {
+    /// Create an entry from value bytes in the byte order of its block.
+    pub fn new(ifd: Ifd, tag: u16, field_type: u16, count: u32, value: Vec<u8>) -> Self {
+        Self {
+            ifd,
+            tag,
+            field_type,
+            count,
+            value,
+        }
+    }
+
+    /// Check if this entry is a pointer to a sub-IFD.
+    pub fn is_pointer(&self) -> bool {
+        matches!(
+            self.tag,
+            EXIF_IFD_POINTER | GPS_IFD_POINTER | INTEROP_IFD_POINTER
+        ) && matches!(self.ifd, Ifd::Primary | Ifd::Thumbnail | Ifd::Exif)
+    }
+
+    /// Get the tag name, or a hex placeholder for unknown tags.
+    pub fn name(&self) -> String {
+        match tag_info(self.ifd, self.tag) {
+            Some((name, _)) => name.to_string(),
+            None => format!("Tag0x{:04X}", self.tag),
+        }
+    }
+
+    /// Get the privacy category of the tag.
+    pub fn category(&self) -> Category {
+        match tag_info(self.ifd, self.tag) {
+            Some((_, category)) => category,
+            None if self.ifd == Ifd::Gps => Category::Location,
+            None => Category::Other,
+        }
+    }
+
+    /// Get the entry as a named field.
+    pub fn field(&self) -> Field {
+        Field::new(self.name(), self.category())
+    }
+
+    /// Reverse the byte order of each value, for copying between blocks.
+    pub fn swap_byte_order(&mut self) {
+        let width = match self.field_type {
+            3 | 8 => 2,
+            4 | 5 | 9 | 10 | 11 | 13 => 4,
+            12 => 8,
+            _ => return,
+        };
+        for unit in self.value.chunks_exact_mut(width) {
+            unit.reverse();
+        }
+    }
+
+    /// Get an ASCII value with trailing NULs removed.
+    pub fn ascii(&self) -> Option<String> {
+        if self.field_type != 2 {
+            return None;
+        }
+        let text = self.value.split(|&b| b == 0).next().unwrap_or_default();
+        Some(String::from_utf8_lossy(text).into_owned())
+    }
+}
+
+/// A decoded EXIF block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exif {
+    /// Byte order of the block.
+    pub byte_order: ByteOrder,
+    /// All entries found, in directory order.
+    pub entries: Vec<Entry>,
+}
+
+impl Exif {
+    /// Get the named fields, excluding sub-IFD pointers.
+    pub fn fields(&self) -> Vec<Field> {
+        self.entries
+            .iter()
+            .filter(|e| !e.is_pointer())
+            .map(Entry::field)
+            .collect()
+    }
+
+    /// Find an entry by directory and tag.
+    pub fn find(&self, ifd: Ifd, tag: u16) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.ifd == ifd && e.tag == tag)
+    }
+}
+
+/// Get the size in bytes of one value of a TIFF field type.
+fn type_size(field_type: u16) -> Option<usize> {
+    match field_type {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 | 13 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+/// Parse an EXIF block or TIFF file.
+///
+/// Accepts data starting with the TIFF header, optionally preceded by the
+/// `Exif\0\0` identifier. Returns `None` if the header is not recognised.
+pub fn parse(data: &[u8]) -> Option<Exif> {
+    let data = data.strip_prefix(EXIF_HEADER).unwrap_or(data);
+    if data.len() < 8 {
+        return None;
+    }
+
+    let byte_order = match &data[0..2] {
+        b"II" => ByteOrder::Little,
+        b"MM" => ByteOrder::Big,
+        _ => return None,
+    };
+    if byte_order.read_u16(&data[2..]) != 42 {
+        return None;
+    }
+
+    let mut entries = Vec::new();
+    let mut visited = Vec::new();
+    let mut pending = vec![(Ifd::Primary, byte_order.read_u32(&data[4..]) as usize)];
+
+    while let Some((ifd, offset)) = pending.pop() {
+        if offset == 0 || visited.contains(&offset) || visited.len() >= MAX_IFDS {
+            continue;
+        }
+        visited.push(offset);
+
+        let Some(next) = read_ifd(data, offset, ifd, byte_order, &mut entries) else {
+            continue;
+        };
+        if ifd == Ifd::Primary {
+            pending.push((Ifd::Thumbnail, next));
+        }
+    }
+
+    // Follow sub-IFD pointers found in the main directories.
+    let mut index = 0;
+    while index < entries.len() {
+        let entry = &entries[index];
+        let target = match (entry.ifd, entry.tag) {
+            (Ifd::Primary | Ifd::Thumbnail, EXIF_IFD_POINTER) => Some(Ifd::Exif),
+            (Ifd::Primary | Ifd::Thumbnail, GPS_IFD_POINTER) => Some(Ifd::Gps),
+            (Ifd::Exif, INTEROP_IFD_POINTER) => Some(Ifd::Interop),
+            _ => None,
+        };
+        if let Some(target) = target.filter(|_| entry.value.len() >= 4) {
+            let offset = byte_order.read_u32(&entry.value) as usize;
+            if offset != 0 && !visited.contains(&offset) && visited.len() < MAX_IFDS {
+                visited.push(offset);
+                read_ifd(data, offset, target, byte_order, &mut entries);
+            }
+        }
+        index += 1;
+    }
+
+    Some(Exif {
+        byte_order,
+        entries,
+    })
+}
+
+/// Convenience wrapper returning the named fields of an EXIF block.
+pub fn fields(data: &[u8]) -> Vec<Field> {
+    parse(data).map(|exif| exif.fields()).unwrap_or_default()
+}
+
+/// Read one IFD, appending its entries. Returns the next-IFD offset.
+fn read_ifd(
+    data: &[u8],
+    offset: usize,
+    ifd: Ifd,
+    byte_order: ByteOrder,
+    entries: &mut Vec<Entry>,
+) -> Option<usize> {
+    let count = byte_order.read_u16(data.get(offset..offset + 2)?) as usize;
+    let table_end = offset + 2 + count * 12;
+    if table_end > data.len() {
+        return None;
+    }
+
+    for i in 0..count {
+        let pos = offset + 2 + i * 12;
+        let tag = byte_order.read_u16(&data[pos..]);
+        let field_type = byte_order.read_u16(&data[pos + 2..]);
+        let value_count = byte_order.read_u32(&data[pos + 4..]);
+
+        let value = type_size(field_type)
+            .and_then(|size| size.checked_mul(value_count as usize))
+            .and_then(|size| {
+                if size <= 4 {
+                    data.get(pos + 8..pos + 8 + size)
+                } else {
+                    let start = byte_order.read_u32(&data[pos + 8..]) as usize;
+                    data.get(start..start.checked_add(size)?)
+                }
+            })
+            .map(<[u8]>::to_vec)
+            .unwrap_or_default();
+
+        entries.push(Entry {
+            ifd,
+            tag,
+            field_type,
+            count: value_count,
+            value,
+        });
+    }
+
+    data.get(table_end..table_end + 4)
+        .map(|next| byte_order.read_u32(next) as usize)
+}
+
+/// Build a little-endian EXIF block (without the `Exif\0\0` identifier).
+///
+/// Primary entries go in IFD0, and pointers to the EXIF and GPS sub-IFDs
+/// are added for any entries in those directories. Values must already be
+/// little-endian; entries are written in tag order as TIFF requires.
+pub fn build(entries: &[Entry]) -> Vec<u8> {
+    let select = |ifd: Ifd| -> Vec<&Entry> { entries.iter().filter(|e| e.ifd == ifd).collect() };
+    let exif = select(Ifd::Exif);
+    let gps = select(Ifd::Gps);
+
+    let pointer = |tag: u16| Entry::new(Ifd::Primary, tag, 4, 1, vec![0; 4]);
+    let exif_pointer = pointer(EXIF_IFD_POINTER);
+    let gps_pointer = pointer(GPS_IFD_POINTER);
+    let mut primary: Vec<&Entry> = select(Ifd::Primary)
+        .into_iter()
+        .filter(|e| !e.is_pointer())
+        .collect();
+    if !exif.is_empty() {
+        primary.push(&exif_pointer);
+    }
+    if !gps.is_empty() {
+        primary.push(&gps_pointer);
+    }
+
+    let mut out = b"II\x2A\x00\x08\x00\x00\x00".to_vec();
+    let slots = write_ifd(&mut out, primary);
+    for (tag, sub) in [(EXIF_IFD_POINTER, exif), (GPS_IFD_POINTER, gps)] {
+        if let Some(&(_, slot)) = slots.iter().find(|(t, _)| *t == tag) {
+            let offset = out.len() as u32;
+            out[slot..slot + 4].copy_from_slice(&offset.to_le_bytes());
+            write_ifd(&mut out, sub);
+        }
+    }
+    out
+}
+
+/// Write one little-endian IFD followed by its out-of-line values.
+///
+/// Returns the position of each entry's value field, by tag, so pointers
+/// can be patched once their targets are written.
+fn write_ifd(out: &mut Vec<u8>, mut entries: Vec<&Entry>) -> Vec<(u16, usize)> {
+    entries.sort_by_key(|e| e.tag);
+
+    let table = out.len();
+    let mut extra = table + 2 + entries.len() * 12 + 4;
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.resize(extra, 0);
+
+    let mut slots = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        let pos = table + 2 + i * 12;
+        out[pos..pos + 2].copy_from_slice(&entry.tag.to_le_bytes());
+        out[pos + 2..pos + 4].copy_from_slice(&entry.field_type.to_le_bytes());
+        out[pos + 4..pos + 8].copy_from_slice(&entry.count.to_le_bytes());
+        slots.push((entry.tag, pos + 8));
+
+        if entry.value.len() <= 4 {
+            out[pos + 8..pos + 8 + entry.value.len()].copy_from_slice(&entry.value);
+        } else {
+            // Values start on a word boundary.
+            extra += extra % 2;
+            out.resize(extra, 0);
+            out[pos + 8..pos + 12].copy_from_slice(&(extra as u32).to_le_bytes());
+            out.extend_from_slice(&entry.value);
+            extra = out.len();
+        }
+    }
+    slots
+}
+
+/// Get the name and category of a known tag.
+fn tag_info(ifd: Ifd, tag: u16) -> Option<(&'static str, Category)> {
+    use Category::*;
+
+    if ifd == Ifd::Gps {
+        let name = match tag {
+            0x00 => "GPSVersionID",
+            0x01 => "GPSLatitudeRef",
+            0x02 => "GPSLatitude",
+            0x03 => "GPSLongitudeRef",
+            0x04 => "GPSLongitude",
+            0x05 => "GPSAltitudeRef",
+            0x06 => "GPSAltitude",
+            0x07 => return Some(("GPSTimeStamp", Time)),
+            0x08 => "GPSSatellites",
+            0x09 => "GPSStatus",
+            0x0A => "GPSMeasureMode",
+            0x0B => "GPSDOP",
+            0x0C => "GPSSpeedRef",
+            0x0D => "GPSSpeed",
+            0x0E => "GPSTrackRef",
+            0x0F => "GPSTrack",
+            0x10 => "GPSImgDirectionRef",
+            0x11 => "GPSImgDirection",
+            0x12 => "GPSMapDatum",
+            0x13 => "GPSDestLatitudeRef",
+            0x14 => "GPSDestLatitude",
+            0x15 => "GPSDestLongitudeRef",
+            0x16 => "GPSDestLongitude",
+            0x17 => "GPSDestBearingRef",
+            0x18 => "GPSDestBearing",
+            0x19 => "GPSDestDistanceRef",
+            0x1A => "GPSDestDistance",
+            0x1B => "GPSProcessingMethod",
+            0x1C => "GPSAreaInformation",
+            0x1D => return Some(("GPSDateStamp", Time)),
+            0x1E => "GPSDifferential",
+            0x1F => "GPSHPositioningError",
+            _ => return None,
+        };
+        return Some((name, Location));
+    }
+
+    if ifd == Ifd::Interop {
+        return match tag {
+            0x0001 => Some(("InteroperabilityIndex", Other)),
+            0x0002 => Some(("InteroperabilityVersion", Other)),
+            _ => None,
+        };
+    }
+
+    let info = match tag {
+        0x0100 => ("ImageWidth", Other),
+        0x0101 => ("ImageLength", Other),
+        0x0102 => ("BitsPerSample", Other),
+        0x0103 => ("Compression", Other),
+        0x0106 => ("PhotometricInterpretation", Other),
+        0x010E => ("ImageDescription", Other),
+        0x010F => ("Make", Device),
+        0x0110 => ("Model", Device),
+        0x0111 => ("StripOffsets", Other),
+        0x0112 => ("Orientation", Other),
+        0x0115 => ("SamplesPerPixel", Other),
+        0x0116 => ("RowsPerStrip", Other),
+        0x0117 => ("StripByteCounts", Other),
+        0x011A => ("XResolution", Other),
+        0x011B => ("YResolution", Other),
+        0x011C => ("PlanarConfiguration", Other),
+        0x0120 => ("FreeOffsets", Other),
+        0x0121 => ("FreeByteCounts", Other),
+        0x0128 => ("ResolutionUnit", Other),
+        0x0131 => ("Software", Software),
+        0x0132 => ("DateTime", Time),
+        0x013B => ("Artist", Authorship),
+        0x013C => ("HostComputer", Device),
+        0x013E => ("WhitePoint", Colour),
+        0x013F => ("PrimaryChromaticities", Colour),
+        0x0142 => ("TileWidth", Other),
+        0x0143 => ("TileLength", Other),
+        0x0144 => ("TileOffsets", Other),
+        0x0145 => ("TileByteCounts", Other),
+        0x014A => ("SubIFDs", Other),
+        0x0153 => ("SampleFormat", Other),
+        0x0201 => ("JPEGInterchangeFormat", Other),
+        0x0202 => ("JPEGInterchangeFormatLength", Other),
+        0x0211 => ("YCbCrCoefficients", Colour),
+        0x0213 => ("YCbCrPositioning", Other),
+        0x02BC => ("XMP", Other),
+        0x8298 => ("Copyright", Authorship),
+        0x829A => ("ExposureTime", Other),
+        0x829D => ("FNumber", Other),
+        0x83BB => ("IPTC", Other),
+        0x8649 => ("Photoshop", Other),
+        0x8769 => ("ExifIFDPointer", Other),
+        0x8773 => ("InterColorProfile", Colour),
+        0x8822 => ("ExposureProgram", Other),
+        0x8825 => ("GPSInfoIFDPointer", Location),
+        0x8827 => ("PhotographicSensitivity", Other),
+        0x9000 => ("ExifVersion", Other),
+        0x9003 => ("DateTimeOriginal", Time),
+        0x9004 => ("DateTimeDigitized", Time),
+        0x9010 => ("OffsetTime", Time),
+        0x9011 => ("OffsetTimeOriginal", Time),
+        0x9012 => ("OffsetTimeDigitized", Time),
+        0x9101 => ("ComponentsConfiguration", Other),
+        0x9201 => ("ShutterSpeedValue", Other),
+        0x9202 => ("ApertureValue", Other),
+        0x9204 => ("ExposureBiasValue", Other),
+        0x9207 => ("MeteringMode", Other),
+        0x9209 => ("Flash", Other),
+        0x920A => ("FocalLength", Other),
+        0x927C => ("MakerNote", Device),
+        0x9286 => ("UserComment", Other),
+        0x9290 => ("SubSecTime", Time),
+        0x9291 => ("SubSecTimeOriginal", Time),
+        0x9292 => ("SubSecTimeDigitized", Time),
+        0x9C9B => ("XPTitle", Other),
+        0x9C9C => ("XPComment", Other),
+        0x9C9D => ("XPAuthor", Authorship),
+        0x9C9E => ("XPKeywords", Other),
+        0x9C9F => ("XPSubject", Other),
+        0xA000 => ("FlashpixVersion", Other),
+        0xA001 => ("ColorSpace", Colour),
+        0xA002 => ("PixelXDimension", Other),
+        0xA003 => ("PixelYDimension", Other),
+        0xA005 => ("InteropIFDPointer", Other),
+        0xA402 => ("ExposureMode", Other),
+        0xA403 => ("WhiteBalance", Other),
+        0xA406 => ("SceneCaptureType", Other),
+        0xA420 => ("ImageUniqueID", Device),
+        0xA430 => ("CameraOwnerName", Authorship),
+        0xA431 => ("BodySerialNumber", Device),
+        0xA432 => ("LensSpecification", Device),
+        0xA433 => ("LensMake", Device),
+        0xA434 => ("LensModel", Device),
+        0xA435 => ("LensSerialNumber", Device),
+        0xC4A5 => ("PrintIM", Other),
+        _ => return None,
+    };
+    Some(info)
+}
+
+/// Build a little-endian EXIF block from (IFD, tag, type, count, value) entries.
+///
+/// Only used by tests to create fixtures with known content.
+#[cfg(test)]
+pub fn build_test_exif(
+    primary: &[(u16, u16, u32, &[u8])],
+    gps: &[(u16, u16, u32, &[u8])],
+) -> Vec<u8> {
+    fn write_ifd(out: &mut Vec<u8>, entries: &[(u16, u16, u32, &[u8])], next: u32) {
+        let table_len = 2 + entries.len() * 12 + 4;
+        let mut extra_offset = out.len() + table_len;
+        let mut extra = Vec::new();
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for &(tag, field_type, count, value) in entries {
+            out.extend_from_slice(&tag.to_le_bytes());
+            out.extend_from_slice(&field_type.to_le_bytes());
+            out.extend_from_slice(&count.to_le_bytes());
+            if value.len() <= 4 {
+                let mut inline = [0u8; 4];
+                inline[..value.len()].copy_from_slice(value);
+                out.extend_from_slice(&inline);
+            } else {
+                out.extend_from_slice(&(extra_offset as u32).to_le_bytes());
+                extra.extend_from_slice(value);
+                extra_offset += value.len();
+            }
+        }
+        out.extend_from_slice(&next.to_le_bytes());
+        out.extend_from_slice(&extra);
+    }
+
+    let mut data = b"II\x2A\x00\x08\x00\x00\x00".to_vec();
+    let mut primary = primary.to_vec();
+    let gps_pointer_pos = if gps.is_empty() {
+        None
+    } else {
+        primary.push((GPS_IFD_POINTER, 4, 1, &[0, 0, 0, 0]));
+        Some(primary.len() - 1)
+    };
+    write_ifd(&mut data, &primary, 0);
+
+    if let Some(index) = gps_pointer_pos {
+        let gps_offset = data.len() as u32;
+        let value_pos = 8 + 2 + index * 12 + 8;
+        data[value_pos..value_pos + 4].copy_from_slice(&gps_offset.to_le_bytes());
+        write_ifd(&mut data, gps, 0);
+    }
+
+    let mut block = EXIF_HEADER.to_vec();
+    block.extend_from_slice(&data);
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        build_test_exif(
+            &[
+                (0x010F, 2, 6, b"Canon\0"),
+                (0x0132, 2, 20, b"2024:01:02 03:04:05\0"),
+            ],
+            &[
+                (0x0001, 2, 2, b"N\0"),
+                (
+                    0x0002,
+                    5,
+                    3,
+                    &[
+                        1, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 3, 0, 0, 0, 1, 0, 0, 0,
+                    ],
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        let exif = parse(&sample()).unwrap();
+        assert_eq!(exif.byte_order, ByteOrder::Little);
+        assert_eq!(
+            exif.find(Ifd::Primary, 0x010F).unwrap().ascii().unwrap(),
+            "Canon"
+        );
+        assert_eq!(exif.find(Ifd::Gps, 0x0002).unwrap().value.len(), 24);
+    }
+
+    #[test]
+    fn test_fields_and_categories() {
+        let fields = fields(&sample());
+        let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["Make", "DateTime", "GPSLatitudeRef", "GPSLatitude"]);
+        assert_eq!(fields[0].category, Category::Device);
+        assert_eq!(fields[1].category, Category::Time);
+        assert_eq!(fields[3].category, Category::Location);
+    }
+
+    #[test]
+    fn test_parse_without_exif_header() {
+        let block = sample();
+        assert!(parse(&block[EXIF_HEADER.len()..]).is_some());
+    }
+
+    #[test]
+    fn test_parse_rejects_garbage() {
+        assert!(parse(b"Exif\0\0garbage").is_none());
+        assert!(parse(b"").is_none());
+    }
+
+    #[test]
+    fn test_parse_tolerates_bad_offsets() {
+        let mut block = sample();
+        // Point the first IFD outside the block.
+        block[10..14].copy_from_slice(&0xFFFF_0000u32.to_le_bytes());
+        assert!(parse(&block).unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn test_parse_ifd_loop() {
+        // IFD0 whose next pointer refers back to itself.
+        let mut data = b"II\x2A\x00\x08\x00\x00\x00".to_vec();
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&8u32.to_le_bytes());
+        assert!(parse(&data).unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_tag_name() {
+        let entry = Entry {
+            ifd: Ifd::Exif,
+            tag: 0xBEEF,
+            field_type: 1,
+            count: 0,
+            value: Vec::new(),
+        };
+        assert_eq!(entry.name(), "Tag0xBEEF");
+        assert_eq!(entry.category(), Category::Other);
+    }
+
+    #[test]
+    fn test_build_round_trip() {
+        let entries = vec![
+            Entry::new(Ifd::Gps, 0x0002, 5, 1, vec![1, 0, 0, 0, 2, 0, 0, 0]),
+            Entry::new(Ifd::Primary, 0x013B, 2, 4, b"Ann\0".to_vec()),
+            Entry::new(Ifd::Gps, 0x0001, 2, 2, b"N\0".to_vec()),
+            Entry::new(Ifd::Exif, 0x9003, 2, 20, b"2024:01:02 03:04:05\0".to_vec()),
+        ];
+        let block = build(&entries);
+        let exif = parse(&block).unwrap();
+
+        let tags: Vec<(Ifd, u16)> = exif.entries.iter().map(|e| (e.ifd, e.tag)).collect();
+        assert_eq!(
+            tags,
+            [
+                (Ifd::Primary, 0x013B),
+                (Ifd::Primary, EXIF_IFD_POINTER),
+                (Ifd::Primary, GPS_IFD_POINTER),
+                (Ifd::Exif, 0x9003),
+                (Ifd::Gps, 0x0001),
+                (Ifd::Gps, 0x0002),
+            ]
+        );
+        for entry in &entries {
+            assert_eq!(exif.find(entry.ifd, entry.tag), Some(entry));
+        }
+    }
+
+    #[test]
+    fn test_swap_byte_order() {
+        let mut rational = Entry::new(Ifd::Gps, 2, 5, 1, vec![1, 0, 0, 0, 2, 0, 0, 0]);
+        rational.swap_byte_order();
+        assert_eq!(rational.value, [0, 0, 0, 1, 0, 0, 0, 2]);
+
+        let mut text = Entry::new(Ifd::Gps, 1, 2, 2, b"N\0".to_vec());
+        text.swap_byte_order();
+        assert_eq!(text.value, b"N\0");
+    }
+}
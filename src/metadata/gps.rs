@@ -0,0 +1,242 @@
+//! GPS position decoding and coarsening.
+//!
+//! Reads the latitude and longitude from an EXIF GPS directory and, for
+//! `--gps coarsen=<km>`, snaps them to a grid of roughly that size. The
+//! coarsened position is written back as a minimal GPS directory holding
+//! only the version, the two references and the two coordinates; altitude,
+//! direction, speed, timestamps and everything else are dropped.
+
+use super::exif::{Entry, Exif, Ifd};
+use crate::error::{Error, Result};
+use std::fmt;
+
+/// Kilometres per degree of latitude (and of longitude at the equator).
+const KM_PER_DEGREE: f64 = 111.32;
+
+/// GPS tags written back by coarsening.
+const VERSION_ID: u16 = 0x0000;
+const LATITUDE_REF: u16 = 0x0001;
+const LATITUDE: u16 = 0x0002;
+const LONGITUDE_REF: u16 = 0x0003;
+const LONGITUDE: u16 = 0x0004;
+
+/// What to do with GPS metadata when stripping.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum GpsMode {
+    /// Remove it along with all other metadata.
+    #[default]
+    Remove,
+    /// Keep the position, rounded to a grid of this many kilometres.
+    Coarsen(f64),
+}
+
+impl GpsMode {
+    /// Parse a mode, as given to `--gps`: `remove` or `coarsen=<km>`.
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidArgument {
+            argument: String::from("--gps"),
+            reason: reason.to_string(),
+        };
+
+        if value == "remove" {
+            return Ok(GpsMode::Remove);
+        }
+        let km = value
+            .strip_prefix("coarsen=")
+            .ok_or_else(|| invalid("Expected 'remove' or 'coarsen=<km>'"))?;
+        match km.parse::<f64>() {
+            Ok(km) if km.is_finite() && km > 0.0 => Ok(GpsMode::Coarsen(km)),
+            _ => Err(invalid(&format!(
+                "'{}' is not a positive number of kilometres",
+                km
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for GpsMode {
+    /// Format the mode as `--gps` takes it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GpsMode::Remove => f.write_str("remove"),
+            GpsMode::Coarsen(km) => write!(f, "coarsen={}", km),
+        }
+    }
+}
+
+/// A position in decimal degrees, north and east positive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Position {
+    /// Snap the position to a grid with cells of roughly `km` kilometres.
+    ///
+    /// Longitude cells widen towards the poles so they stay about `km`
+    /// across on the ground.
+    pub fn coarsen(&self, km: f64) -> Position {
+        let lat_step = km / KM_PER_DEGREE;
+        let latitude = ((self.latitude / lat_step).round() * lat_step).clamp(-90.0, 90.0);
+
+        let scale = latitude.to_radians().cos().max(0.01);
+        let lon_step = (km / (KM_PER_DEGREE * scale)).min(360.0);
+        let mut longitude = (self.longitude / lon_step).round() * lon_step;
+        if longitude > 180.0 {
+            longitude -= 360.0;
+        } else if longitude < -180.0 {
+            longitude += 360.0;
+        }
+
+        Position {
+            latitude,
+            longitude,
+        }
+    }
+}
+
+/// Decode a coordinate from its degrees/minutes/seconds rationals and ref.
+fn coordinate(exif: &Exif, tag: u16, ref_tag: u16, negative: &str) -> Option<f64> {
+    let entry = exif.find(Ifd::Gps, tag)?;
+    if entry.field_type != 5 || entry.value.len() < 24 {
+        return None;
+    }
+
+    let mut degrees = 0.0;
+    for (i, unit) in [1.0, 60.0, 3600.0].into_iter().enumerate() {
+        let num = exif.byte_order.read_u32(&entry.value[i * 8..]);
+        let den = exif.byte_order.read_u32(&entry.value[i * 8 + 4..]);
+        if den == 0 {
+            return None;
+        }
+        degrees += f64::from(num) / f64::from(den) / unit;
+    }
+
+    let reference = exif.find(Ifd::Gps, ref_tag)?.ascii()?;
+    Some(if reference.trim() == negative {
+        -degrees
+    } else {
+        degrees
+    })
+}
+
+/// Read the position recorded in an EXIF block, if it has a usable one.
+pub fn position(exif: &Exif) -> Option<Position> {
+    let position = Position {
+        latitude: coordinate(exif, LATITUDE, LATITUDE_REF, "S")?,
+        longitude: coordinate(exif, LONGITUDE, LONGITUDE_REF, "W")?,
+    };
+    let valid = position.latitude.abs() <= 90.0 && position.longitude.abs() <= 180.0;
+    valid.then_some(position)
+}
+
+/// Encode a coordinate as degrees, minutes and hundredths of seconds.
+fn rationals(degrees: f64) -> Vec<u8> {
+    let total = (degrees.abs() * 360_000.0).round() as u32;
+    [
+        (total / 360_000, 1),
+        (total / 6000 % 60, 1),
+        (total % 6000, 100),
+    ]
+    .iter()
+    .flat_map(|&(num, den): &(u32, u32)| num.to_le_bytes().into_iter().chain(den.to_le_bytes()))
+    .collect()
+}
+
+/// Build the little-endian entries of a minimal GPS directory.
+pub fn entries(position: Position) -> Vec<Entry> {
+    let reference = |tag: u16, letter: u8| Entry::new(Ifd::Gps, tag, 2, 2, vec![letter, 0]);
+    let north = if position.latitude < 0.0 { b'S' } else { b'N' };
+    let east = if position.longitude < 0.0 { b'W' } else { b'E' };
+
+    vec![
+        Entry::new(Ifd::Gps, VERSION_ID, 1, 4, vec![2, 3, 0, 0]),
+        reference(LATITUDE_REF, north),
+        Entry::new(Ifd::Gps, LATITUDE, 5, 3, rationals(position.latitude)),
+        reference(LONGITUDE_REF, east),
+        Entry::new(Ifd::Gps, LONGITUDE, 5, 3, rationals(position.longitude)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::exif;
+
+    fn parse_built(entries: &[Entry]) -> Exif {
+        exif::parse(&exif::build(entries)).unwrap()
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(GpsMode::parse("remove").unwrap(), GpsMode::Remove);
+        assert_eq!(GpsMode::parse("coarsen=5").unwrap(), GpsMode::Coarsen(5.0));
+        assert_eq!(
+            GpsMode::parse("coarsen=0.5").unwrap(),
+            GpsMode::Coarsen(0.5)
+        );
+        assert!(GpsMode::parse("coarsen=0").is_err());
+        assert!(GpsMode::parse("coarsen=-1").is_err());
+        assert!(GpsMode::parse("coarsen=inf").is_err());
+        assert!(GpsMode::parse("keep").is_err());
+
+        assert_eq!(GpsMode::Remove.to_string(), "remove");
+        assert_eq!(GpsMode::Coarsen(0.5).to_string(), "coarsen=0.5");
+        assert_eq!(GpsMode::Coarsen(10.0).to_string(), "coarsen=10");
+    }
+
+    #[test]
+    fn test_entries_round_trip() {
+        let original = Position {
+            latitude: -33.856_78,
+            longitude: 151.215_3,
+        };
+        let exif = parse_built(&entries(original));
+        let decoded = position(&exif).unwrap();
+        assert!((decoded.latitude - original.latitude).abs() < 1e-5);
+        assert!((decoded.longitude - original.longitude).abs() < 1e-5);
+
+        let tags: Vec<u16> = exif
+            .entries
+            .iter()
+            .filter(|e| e.ifd == Ifd::Gps)
+            .map(|e| e.tag)
+            .collect();
+        assert_eq!(tags, [0, 1, 2, 3, 4]);
+        assert_eq!(exif.find(Ifd::Gps, 1).unwrap().ascii().unwrap(), "S");
+    }
+
+    #[test]
+    fn test_coarsen_snaps_to_grid() {
+        let paris = Position {
+            latitude: 48.858_37,
+            longitude: 2.294_48,
+        };
+        let coarse = paris.coarsen(10.0);
+        let lat_step = 10.0 / KM_PER_DEGREE;
+        assert!((coarse.latitude / lat_step - (coarse.latitude / lat_step).round()).abs() < 1e-9);
+        // Within half a cell of the original in each direction.
+        assert!((coarse.latitude - paris.latitude).abs() <= lat_step / 2.0);
+        assert!((coarse.longitude - paris.longitude).abs() <= 10.0 / 111.32 / 0.6);
+        assert_ne!(coarse, paris);
+
+        // Nearby points share a cell.
+        let nearby = Position {
+            latitude: 48.860_6,
+            longitude: 2.337_6,
+        };
+        assert_eq!(nearby.coarsen(10.0), coarse);
+    }
+
+    #[test]
+    fn test_coarsen_near_poles_and_antimeridian() {
+        let pole = Position {
+            latitude: 89.99,
+            longitude: 179.9,
+        }
+        .coarsen(50.0);
+        assert!(pole.latitude <= 90.0);
+        assert!((-180.0..=180.0).contains(&pole.longitude));
+    }
+}
@@ -0,0 +1,221 @@
+//! IPTC decoding.
+//!
+//! IPTC-IIM records are stored either raw (TIFF tag 33723) or inside a
+//! Photoshop image resource block (JPEG APP13). Each record is named by its
+//! record and dataset numbers.
+
+use super::{Category, Field};
+
+/// Identifier of a Photoshop APP13 segment.
+pub const PHOTOSHOP_HEADER: &[u8] = b"Photoshop 3.0\0";
+
+/// Photoshop resource ID holding IPTC-IIM data.
+const IPTC_RESOURCE: u16 = 0x0404;
+
+/// Get the named records of a Photoshop resource block or raw IIM data.
+pub fn fields(data: &[u8]) -> Vec<Field> {
+    // TIFF stores the resource block without the APP13 identifier.
+    let resources = data
+        .strip_prefix(PHOTOSHOP_HEADER)
+        .or_else(|| data.starts_with(b"8BIM").then_some(data));
+
+    match resources {
+        Some(resources) => photoshop_resources(resources)
+            .into_iter()
+            .flat_map(|(id, payload)| {
+                if id == IPTC_RESOURCE {
+                    iim_fields(payload)
+                } else {
+                    vec![Field::new(
+                        format!("Photoshop 0x{:04X}", id),
+                        resource_category(id),
+                    )]
+                }
+            })
+            .collect(),
+        None => iim_fields(data),
+    }
+}
+
+/// Split a Photoshop image resource block into (ID, payload) pairs.
+fn photoshop_resources(mut data: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut resources = Vec::new();
+
+    while data.len() >= 12 && &data[0..4] == b"8BIM" {
+        let id = u16::from_be_bytes([data[4], data[5]]);
+
+        // Pascal string name, padded to an even length including the length byte.
+        let name_len = data[6] as usize;
+        let mut pos = 7 + name_len;
+        if (1 + name_len) % 2 != 0 {
+            pos += 1;
+        }
+
+        let Some(size) = data.get(pos..pos + 4) else {
+            break;
+        };
+        let size = u32::from_be_bytes([size[0], size[1], size[2], size[3]]) as usize;
+        pos += 4;
+
+        let Some(payload) = data.get(pos..pos + size) else {
+            break;
+        };
+        resources.push((id, payload));
+
+        pos += size + size % 2;
+        data = data.get(pos..).unwrap_or_default();
+    }
+
+    resources
+}
+
+/// Get the named datasets of raw IPTC-IIM data.
+fn iim_fields(mut data: &[u8]) -> Vec<Field> {
+    let mut fields: Vec<Field> = Vec::new();
+
+    while data.len() >= 5 && data[0] == 0x1C {
+        let record = data[1];
+        let dataset = data[2];
+        let length = u16::from_be_bytes([data[3], data[4]]);
+
+        // Extended datasets (high bit set) carry a length-of-length; skip them.
+        let (header, size) = if length & 0x8000 != 0 {
+            let count = (length & 0x7FFF) as usize;
+            let Some(bytes) = data.get(5..5 + count) else {
+                break;
+            };
+            let size = bytes.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+            (5 + count, size)
+        } else {
+            (5, length as usize)
+        };
+
+        let (name, category) = dataset_info(record, dataset);
+        if !fields.iter().any(|f| f.name == name) {
+            fields.push(Field::new(name, category));
+        }
+
+        data = data.get(header + size..).unwrap_or_default();
+    }
+
+    fields
+}
+
+/// Get the name and category of an IIM dataset.
+fn dataset_info(record: u8, dataset: u8) -> (String, Category) {
+    use Category::*;
+
+    let known = match (record, dataset) {
+        (2, 5) => Some(("ObjectName", Other)),
+        (2, 25) => Some(("Keywords", Other)),
+        (2, 55) => Some(("DateCreated", Time)),
+        (2, 60) => Some(("TimeCreated", Time)),
+        (2, 62) => Some(("DigitalCreationDate", Time)),
+        (2, 63) => Some(("DigitalCreationTime", Time)),
+        (2, 65) => Some(("OriginatingProgram", Software)),
+        (2, 70) => Some(("ProgramVersion", Software)),
+        (2, 80) => Some(("By-line", Authorship)),
+        (2, 85) => Some(("By-lineTitle", Authorship)),
+        (2, 90) => Some(("City", Location)),
+        (2, 92) => Some(("Sub-location", Location)),
+        (2, 95) => Some(("Province-State", Location)),
+        (2, 100) => Some(("Country-PrimaryLocationCode", Location)),
+        (2, 101) => Some(("Country-PrimaryLocationName", Location)),
+        (2, 105) => Some(("Headline", Other)),
+        (2, 110) => Some(("Credit", Authorship)),
+        (2, 115) => Some(("Source", Authorship)),
+        (2, 116) => Some(("CopyrightNotice", Authorship)),
+        (2, 118) => Some(("Contact", Authorship)),
+        (2, 120) => Some(("Caption-Abstract", Other)),
+        (2, 122) => Some(("Writer-Editor", Authorship)),
+        _ => None,
+    };
+
+    match known {
+        Some((name, category)) => (format!("IPTC:{}", name), category),
+        None => (format!("IPTC:{}:{}", record, dataset), Other),
+    }
+}
+
+/// Classify a non-IPTC Photoshop resource.
+fn resource_category(id: u16) -> Category {
+    match id {
+        // ICC profile and colour settings.
+        0x040F | 0x0419 | 0x03ED => Category::Colour,
+        // EXIF data 1 / 3.
+        0x0422 | 0x0423 => Category::Device,
+        _ => Category::Other,
+    }
+}
+
+/// Build an IIM dataset for tests.
+#[cfg(test)]
+pub fn build_test_dataset(record: u8, dataset: u8, value: &[u8]) -> Vec<u8> {
+    let mut data = vec![0x1C, record, dataset];
+    data.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    data.extend_from_slice(value);
+    data
+}
+
+/// Wrap IIM data in a Photoshop APP13 resource block for tests.
+#[cfg(test)]
+pub fn build_test_photoshop(iim: &[u8]) -> Vec<u8> {
+    let mut data = PHOTOSHOP_HEADER.to_vec();
+    data.extend_from_slice(b"8BIM");
+    data.extend_from_slice(&IPTC_RESOURCE.to_be_bytes());
+    data.extend_from_slice(&[0, 0]);
+    data.extend_from_slice(&(iim.len() as u32).to_be_bytes());
+    data.extend_from_slice(iim);
+    if iim.len() % 2 != 0 {
+        data.push(0);
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_iim() -> Vec<u8> {
+        let mut iim = build_test_dataset(2, 80, b"Jane Doe");
+        iim.extend(build_test_dataset(2, 90, b"Paris"));
+        iim.extend(build_test_dataset(2, 25, b"holiday"));
+        iim.extend(build_test_dataset(2, 25, b"beach"));
+        iim.extend(build_test_dataset(2, 200, b"x"));
+        iim
+    }
+
+    #[test]
+    fn test_raw_iim() {
+        let fields = fields(&sample_iim());
+        let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["IPTC:By-line", "IPTC:City", "IPTC:Keywords", "IPTC:2:200"]
+        );
+        assert_eq!(fields[0].category, Category::Authorship);
+        assert_eq!(fields[1].category, Category::Location);
+    }
+
+    #[test]
+    fn test_photoshop_block() {
+        let block = build_test_photoshop(&sample_iim());
+        assert_eq!(fields(&block).len(), 4);
+    }
+
+    #[test]
+    fn test_photoshop_other_resource() {
+        let mut block = PHOTOSHOP_HEADER.to_vec();
+        block.extend_from_slice(b"8BIM\x04\x0F\x00\x00\x00\x00\x00\x02ab");
+        let fields = fields(&block);
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].category, Category::Colour);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let iim = sample_iim();
+        assert!(fields(&iim[..7]).len() <= 1);
+        assert!(fields(b"\x1C").is_empty());
+    }
+}
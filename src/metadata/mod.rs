@@ -0,0 +1,326 @@
+//! Metadata detection and classification.
+//!
+//! Decodes the contents of EXIF, XMP and IPTC blocks far enough to name the
+//! fields they carry and sort them into privacy categories. The format
+//! modules use this to describe what a file contains without modifying it.
+
+pub mod dates;
+pub mod exif;
+pub mod gps;
+pub mod iptc;
+pub mod risk;
+pub mod stamp;
+pub mod xmp;
+
+use std::fmt;
+use std::ops::Range;
+
+/// Privacy category of a metadata field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    /// GPS coordinates, place names.
+    Location,
+    /// Camera make, model, serial numbers, lens.
+    Device,
+    /// Capture and modification timestamps.
+    Time,
+    /// Author, owner, copyright.
+    Authorship,
+    /// Editing software and history.
+    Software,
+    /// ICC profiles and colour settings.
+    Colour,
+    /// Comments, descriptions and anything else.
+    Other,
+}
+
+impl Category {
+    /// All categories in display order.
+    pub const ALL: [Category; 7] = [
+        Category::Location,
+        Category::Device,
+        Category::Time,
+        Category::Authorship,
+        Category::Software,
+        Category::Colour,
+        Category::Other,
+    ];
+
+    /// Get the lowercase name of the category.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Location => "location",
+            Category::Device => "device",
+            Category::Time => "time",
+            Category::Authorship => "authorship",
+            Category::Software => "software",
+            Category::Colour => "colour",
+            Category::Other => "other",
+        }
+    }
+
+    /// Get a one-line description of what the category covers.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Category::Location => "GPS coordinates and place names",
+            Category::Device => "Camera make, model, serial numbers and lens",
+            Category::Time => "Capture and modification timestamps",
+            Category::Authorship => "Author, owner and copyright",
+            Category::Software => "Editing software and history",
+            Category::Colour => "ICC profiles and colour settings",
+            Category::Other => "Comments, descriptions and other metadata",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A named field decoded from a metadata block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    /// Field name, e.g. `GPSLatitude` or `dc:creator`.
+    pub name: String,
+    /// Privacy category of the field.
+    pub category: Category,
+}
+
+impl Field {
+    /// Create a new field.
+    pub fn new(name: impl Into<String>, category: Category) -> Self {
+        Self {
+            name: name.into(),
+            category,
+        }
+    }
+}
+
+/// A metadata block found in an image file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataItem {
+    /// Format-specific kind, e.g. `APP1 EXIF`, `tEXt`, `Comment Extension`.
+    pub kind: String,
+    /// Byte range of the block within the file.
+    pub range: Range<usize>,
+    /// Category of the block as a whole.
+    pub category: Category,
+    /// Fields decoded from the block, if any.
+    pub fields: Vec<Field>,
+}
+
+impl MetadataItem {
+    /// Create a new item with no decoded fields.
+    pub fn new(kind: impl Into<String>, range: Range<usize>, category: Category) -> Self {
+        Self {
+            kind: kind.into(),
+            range,
+            category,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Attach decoded fields to the item.
+    pub fn with_fields(mut self, fields: Vec<Field>) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Get the size of the block in bytes.
+    pub fn size(&self) -> usize {
+        self.range.len()
+    }
+
+    /// Get the categories present in this item.
+    ///
+    /// Uses the decoded fields when there are any, and the category of the
+    /// block as a whole otherwise.
+    pub fn categories(&self) -> Vec<Category> {
+        if self.fields.is_empty() {
+            return vec![self.category];
+        }
+        let mut categories: Vec<Category> = self.fields.iter().map(|f| f.category).collect();
+        categories.sort();
+        categories.dedup();
+        categories
+    }
+}
+
+/// Why `strip` kept a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeepReason {
+    /// Signatures, headers, directories and terminators.
+    Structure,
+    /// Encoded pixel data.
+    ImageData,
+    /// Tables and parameters needed to decode the pixels.
+    Decoding,
+    /// Colour and display information that changes how the image looks.
+    Rendering,
+    /// Frame timing and looping.
+    Animation,
+}
+
+impl KeepReason {
+    /// Get the lowercase name of the reason.
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeepReason::Structure => "structure",
+            KeepReason::ImageData => "image-data",
+            KeepReason::Decoding => "decoding",
+            KeepReason::Rendering => "rendering",
+            KeepReason::Animation => "animation",
+        }
+    }
+
+    /// Get a one-line explanation of why such blocks are kept.
+    pub fn description(&self) -> &'static str {
+        match self {
+            KeepReason::Structure => "Required by the file format",
+            KeepReason::ImageData => "Encoded pixel data",
+            KeepReason::Decoding => "Needed to decode the pixels",
+            KeepReason::Rendering => "Changes how the image is displayed",
+            KeepReason::Animation => "Controls animation timing and looping",
+        }
+    }
+}
+
+impl fmt::Display for KeepReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A block found in an image file that stripping keeps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeptItem {
+    /// Format-specific kind, e.g. `DQT`, `IDAT`, `NETSCAPE2.0`.
+    pub kind: String,
+    /// Byte range of the block within the file.
+    pub range: Range<usize>,
+    /// Why the block is kept.
+    pub reason: KeepReason,
+}
+
+impl KeptItem {
+    /// Create a new kept item.
+    pub fn new(kind: impl Into<String>, range: Range<usize>, reason: KeepReason) -> Self {
+        Self {
+            kind: kind.into(),
+            range,
+            reason,
+        }
+    }
+
+    /// Get the size of the block in bytes.
+    pub fn size(&self) -> usize {
+        self.range.len()
+    }
+}
+
+/// Get the distinct categories present across a set of items, in display order.
+pub fn categories(items: &[MetadataItem]) -> Vec<Category> {
+    let mut found: Vec<Category> = items.iter().flat_map(|i| i.categories()).collect();
+    found.sort();
+    found.dedup();
+    found
+}
+
+/// Classify a free-form text key (PNG text keyword, GIF application id).
+pub fn classify_keyword(keyword: &str) -> Category {
+    let lower = keyword.to_ascii_lowercase();
+    let contains = |words: &[&str]| words.iter().any(|w| lower.contains(w));
+
+    if contains(&[
+        "gps",
+        "location",
+        "latitude",
+        "longitude",
+        "city",
+        "country",
+    ]) {
+        Category::Location
+    } else if contains(&[
+        "author",
+        "artist",
+        "copyright",
+        "creator",
+        "owner",
+        "rights",
+    ]) {
+        Category::Authorship
+    } else if contains(&["time", "date"]) {
+        Category::Time
+    } else if contains(&["software", "history", "tool"]) {
+        Category::Software
+    } else if contains(&["make", "model", "serial", "camera", "lens", "device"]) {
+        Category::Device
+    } else if contains(&["icc", "colour", "color"]) {
+        Category::Colour
+    } else {
+        Category::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_category_names() {
+        let names: Vec<&str> = Category::ALL.iter().map(|c| c.name()).collect();
+        assert_eq!(
+            names,
+            [
+                "location",
+                "device",
+                "time",
+                "authorship",
+                "software",
+                "colour",
+                "other"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_item_categories_from_fields() {
+        let item = MetadataItem::new("APP1 EXIF", 0..10, Category::Other).with_fields(vec![
+            Field::new("GPSLatitude", Category::Location),
+            Field::new("Make", Category::Device),
+            Field::new("GPSLongitude", Category::Location),
+        ]);
+        assert_eq!(
+            item.categories(),
+            vec![Category::Location, Category::Device]
+        );
+        assert_eq!(item.size(), 10);
+    }
+
+    #[test]
+    fn test_item_categories_without_fields() {
+        let item = MetadataItem::new("COM", 4..8, Category::Other);
+        assert_eq!(item.categories(), vec![Category::Other]);
+    }
+
+    #[test]
+    fn test_categories_across_items() {
+        let items = vec![
+            MetadataItem::new("tIME", 0..1, Category::Time),
+            MetadataItem::new("iCCP", 0..1, Category::Colour),
+            MetadataItem::new("tEXt", 0..1, Category::Time),
+        ];
+        assert_eq!(categories(&items), vec![Category::Time, Category::Colour]);
+    }
+
+    #[test]
+    fn test_classify_keyword() {
+        assert_eq!(classify_keyword("Author"), Category::Authorship);
+        assert_eq!(classify_keyword("Creation Time"), Category::Time);
+        assert_eq!(classify_keyword("Software"), Category::Software);
+        assert_eq!(classify_keyword("Comment"), Category::Other);
+        assert_eq!(classify_keyword("ICCRGBG1"), Category::Colour);
+    }
+}
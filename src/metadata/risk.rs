@@ -0,0 +1,223 @@
+//! Privacy risk classification.
+//!
+//! Grades decoded metadata by how much it reveals about the people behind an
+//! image: GPS positions and owner names are critical, serial numbers and
+//! timestamps are high, camera models and free-text comments are medium, and
+//! technical tags such as resolution or colour settings are low. A file's
+//! risk is that of its most sensitive field.
+
+use super::{Category, Field, MetadataItem};
+use crate::error::{Error, Result};
+use std::fmt;
+
+/// How much a piece of metadata reveals.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RiskLevel {
+    /// No metadata at all.
+    #[default]
+    None,
+    /// Technical tags: resolution, colour settings, software.
+    Low,
+    /// Camera make and model, comments and descriptions, undecoded blocks.
+    Medium,
+    /// Serial numbers, unique IDs and timestamps.
+    High,
+    /// GPS positions, place names and owner or author names.
+    Critical,
+}
+
+impl RiskLevel {
+    /// All levels, from least to most sensitive.
+    pub const ALL: [RiskLevel; 5] = [
+        RiskLevel::None,
+        RiskLevel::Low,
+        RiskLevel::Medium,
+        RiskLevel::High,
+        RiskLevel::Critical,
+    ];
+
+    /// Get the lowercase name of the level.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RiskLevel::None => "none",
+            RiskLevel::Low => "low",
+            RiskLevel::Medium => "medium",
+            RiskLevel::High => "high",
+            RiskLevel::Critical => "critical",
+        }
+    }
+
+    /// Parse a level name, as given to `--min-risk`.
+    pub fn parse(value: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|level| level.name() == value.to_ascii_lowercase())
+            .ok_or_else(|| Error::InvalidArgument {
+                argument: String::from("--min-risk"),
+                reason: format!("'{}' is not one of low, medium, high, critical", value),
+            })
+    }
+}
+
+impl fmt::Display for RiskLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The risk of a file and the fields that set it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Assessment {
+    /// Level of the most sensitive field.
+    pub level: RiskLevel,
+    /// Names of the fields (or undecoded blocks) at that level.
+    pub reasons: Vec<String>,
+}
+
+/// Check whether a name contains any of the given words, ignoring case.
+fn mentions(name: &str, words: &[&str]) -> bool {
+    let lower = name.to_ascii_lowercase();
+    words.iter().any(|w| lower.contains(w))
+}
+
+/// Words in field names that hold text written by a person.
+const FREE_TEXT: &[&str] = &[
+    "comment",
+    "description",
+    "caption",
+    "title",
+    "keyword",
+    "subject",
+    "headline",
+    "label",
+];
+
+/// Grade a single decoded field.
+pub fn field_risk(field: &Field) -> RiskLevel {
+    let name = field.name.as_str();
+    match field.category {
+        Category::Location => RiskLevel::Critical,
+        // Published rights statements are meant to be seen; names are not.
+        Category::Authorship if mentions(name, &["copyright", "rights", "credit", "source"]) => {
+            RiskLevel::Medium
+        }
+        Category::Authorship => RiskLevel::Critical,
+        Category::Time => RiskLevel::High,
+        // Maker notes routinely carry body serial numbers.
+        Category::Device if mentions(name, &["serial", "uniqueid", "makernote"]) => RiskLevel::High,
+        Category::Device => RiskLevel::Medium,
+        Category::Other if mentions(name, &["serial", "uniqueid"]) => RiskLevel::High,
+        Category::Other if mentions(name, FREE_TEXT) => RiskLevel::Medium,
+        Category::Other | Category::Software | Category::Colour => RiskLevel::Low,
+    }
+}
+
+/// Grade a block that could not be decoded into fields.
+///
+/// Its contents are unknown, so anything that is not clearly technical is
+/// treated as at least medium.
+fn block_risk(item: &MetadataItem) -> RiskLevel {
+    match item.category {
+        Category::Location | Category::Authorship => RiskLevel::Critical,
+        Category::Time => RiskLevel::High,
+        Category::Device | Category::Other => RiskLevel::Medium,
+        Category::Software | Category::Colour => RiskLevel::Low,
+    }
+}
+
+/// Assess the risk of a file from the metadata stripping would remove.
+pub fn assess(items: &[MetadataItem]) -> Assessment {
+    let mut assessment = Assessment::default();
+
+    let mut note = |level: RiskLevel, name: &str| {
+        if level > assessment.level {
+            assessment.level = level;
+            assessment.reasons.clear();
+        }
+        if level == assessment.level && !assessment.reasons.iter().any(|r| r == name) {
+            assessment.reasons.push(name.to_string());
+        }
+    };
+
+    for item in items {
+        if item.fields.is_empty() {
+            note(block_risk(item), &item.kind);
+        }
+        for field in &item.fields {
+            note(field_risk(field), &field.name);
+        }
+    }
+
+    assessment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(fields: &[(&str, Category)]) -> MetadataItem {
+        MetadataItem::new("APP1 EXIF", 0..10, Category::Other).with_fields(
+            fields
+                .iter()
+                .map(|(name, category)| Field::new(*name, *category))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_field_risk() {
+        let risk = |name: &str, category| field_risk(&Field::new(name, category));
+        assert_eq!(risk("GPSLatitude", Category::Location), RiskLevel::Critical);
+        assert_eq!(
+            risk("CameraOwnerName", Category::Authorship),
+            RiskLevel::Critical
+        );
+        assert_eq!(
+            risk("dc:creator", Category::Authorship),
+            RiskLevel::Critical
+        );
+        assert_eq!(risk("Copyright", Category::Authorship), RiskLevel::Medium);
+        assert_eq!(risk("BodySerialNumber", Category::Device), RiskLevel::High);
+        assert_eq!(risk("DateTimeOriginal", Category::Time), RiskLevel::High);
+        assert_eq!(risk("Model", Category::Device), RiskLevel::Medium);
+        assert_eq!(risk("UserComment", Category::Other), RiskLevel::Medium);
+        assert_eq!(risk("XResolution", Category::Other), RiskLevel::Low);
+        assert_eq!(risk("Software", Category::Software), RiskLevel::Low);
+    }
+
+    #[test]
+    fn test_assess_takes_most_sensitive_field() {
+        let items = [
+            item(&[
+                ("Make", Category::Device),
+                ("DateTime", Category::Time),
+                ("GPSLatitude", Category::Location),
+                ("GPSLongitude", Category::Location),
+            ]),
+            item(&[("GPSLatitude", Category::Location)]),
+        ];
+        let assessment = assess(&items);
+        assert_eq!(assessment.level, RiskLevel::Critical);
+        assert_eq!(assessment.reasons, ["GPSLatitude", "GPSLongitude"]);
+    }
+
+    #[test]
+    fn test_assess_undecoded_blocks() {
+        assert_eq!(assess(&[]).level, RiskLevel::None);
+        let comment = MetadataItem::new("COM", 0..10, Category::Other);
+        let icc = MetadataItem::new("APP2 ICC", 10..20, Category::Colour);
+        let assessment = assess(std::slice::from_ref(&icc));
+        assert_eq!(assessment.level, RiskLevel::Low);
+        let assessment = assess(&[icc, comment]);
+        assert_eq!(assessment.level, RiskLevel::Medium);
+        assert_eq!(assessment.reasons, ["COM"]);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(RiskLevel::parse("HIGH").unwrap(), RiskLevel::High);
+        assert_eq!(RiskLevel::parse("critical").unwrap(), RiskLevel::Critical);
+        assert!(RiskLevel::parse("severe").is_err());
+        assert!(RiskLevel::Low < RiskLevel::Medium);
+    }
+}
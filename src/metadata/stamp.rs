@@ -0,0 +1,206 @@
+//! Replacement rights metadata.
+//!
+//! For `--stamp`, a cleaned image gets a fresh, minimal set of rights fields
+//! in place of whatever it carried before. Each format uses the fields its
+//! readers expect: EXIF `Copyright` and `Artist` where there is an EXIF
+//! container, PNG `iTXt` keywords, and XMP `dc:rights` and `dc:creator`
+//! otherwise. The licence URL has no EXIF or PNG equivalent, so it always
+//! goes in XMP as `xmpRights:WebStatement`.
+
+use super::exif::{Entry, Ifd};
+use crate::error::{Error, Result};
+use std::fmt;
+
+/// EXIF tags written by stamping.
+const ARTIST: u16 = 0x013B;
+const COPYRIGHT: u16 = 0x8298;
+
+/// Keys accepted by `--stamp`.
+const KEYS: &[&str] = &["copyright", "artist", "license-url"];
+
+/// Rights metadata to write into cleaned images.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stamp {
+    /// Copyright notice.
+    pub copyright: Option<String>,
+    /// Name of the author.
+    pub artist: Option<String>,
+    /// URL of the licence terms.
+    pub license_url: Option<String>,
+}
+
+impl Stamp {
+    /// Parse the fields given to `--stamp`, as `key=value` pairs separated by
+    /// commas.
+    ///
+    /// A comma that is not followed by a known key is part of the value, so
+    /// `copyright=Example, Inc.,artist=Jane` needs no quoting.
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = |reason: String| Error::InvalidArgument {
+            argument: String::from("--stamp"),
+            reason,
+        };
+
+        let mut pairs: Vec<(&str, String)> = Vec::new();
+        for part in value.split(',') {
+            let key = part.split_once('=').map(|(key, _)| key);
+            match (key, pairs.last_mut()) {
+                (Some(key), _) if KEYS.contains(&key) => {
+                    pairs.push((key, part[key.len() + 1..].to_string()));
+                }
+                (_, Some((_, value))) => {
+                    value.push(',');
+                    value.push_str(part);
+                }
+                (_, None) => {
+                    return Err(invalid(format!(
+                        "'{}' is not one of copyright=, artist= or license-url=",
+                        part
+                    )));
+                }
+            }
+        }
+
+        let mut stamp = Stamp::default();
+        for (key, value) in pairs {
+            let value = value.trim().to_string();
+            if value.is_empty() {
+                return Err(invalid(format!("Empty value for {}", key)));
+            }
+            let field = match key {
+                "copyright" => &mut stamp.copyright,
+                "artist" => &mut stamp.artist,
+                _ => &mut stamp.license_url,
+            };
+            if field.replace(value).is_some() {
+                return Err(invalid(format!("{} given more than once", key)));
+            }
+        }
+        Ok(stamp)
+    }
+
+    /// Check if there is nothing to write.
+    pub fn is_empty(&self) -> bool {
+        self.copyright.is_none() && self.artist.is_none() && self.license_url.is_none()
+    }
+}
+
+impl fmt::Display for Stamp {
+    /// Format the fields as `--stamp` takes them.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields = [
+            ("copyright", &self.copyright),
+            ("artist", &self.artist),
+            ("license-url", &self.license_url),
+        ];
+        let pairs: Vec<String> = fields
+            .iter()
+            .filter_map(|(key, value)| Some(format!("{}={}", key, value.as_ref()?)))
+            .collect();
+        f.write_str(&pairs.join(","))
+    }
+}
+
+/// Build the little-endian EXIF entries for the copyright and artist.
+pub fn exif_entries(stamp: &Stamp) -> Vec<Entry> {
+    [(ARTIST, &stamp.artist), (COPYRIGHT, &stamp.copyright)]
+        .into_iter()
+        .filter_map(|(tag, text)| {
+            let mut value = text.as_ref()?.as_bytes().to_vec();
+            value.push(0);
+            Some(Entry::new(Ifd::Primary, tag, 2, value.len() as u32, value))
+        })
+        .collect()
+}
+
+/// Get the PNG text keywords and values for the copyright and artist.
+pub fn png_text(stamp: &Stamp) -> Vec<(String, String)> {
+    named(&[("Copyright", &stamp.copyright), ("Author", &stamp.artist)])
+}
+
+/// Get the XMP properties for the copyright and artist.
+pub fn xmp_rights(stamp: &Stamp) -> Vec<(String, String)> {
+    named(&[
+        ("dc:rights", &stamp.copyright),
+        ("dc:creator", &stamp.artist),
+    ])
+}
+
+/// Get the XMP property for the licence URL.
+pub fn xmp_license(stamp: &Stamp) -> Vec<(String, String)> {
+    named(&[("xmpRights:WebStatement", &stamp.license_url)])
+}
+
+/// Pair names with the values that are set.
+fn named(fields: &[(&str, &Option<String>)]) -> Vec<(String, String)> {
+    fields
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.as_ref()?.clone())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::exif;
+
+    #[test]
+    fn test_parse() {
+        let stamp = Stamp::parse(
+            "copyright=© 2024 Example, Inc.,artist=Jane Doe,license-url=https://example.org/l?a=1,b=2",
+        )
+        .unwrap();
+        assert_eq!(stamp.copyright.as_deref(), Some("© 2024 Example, Inc."));
+        assert_eq!(stamp.artist.as_deref(), Some("Jane Doe"));
+        assert_eq!(
+            stamp.license_url.as_deref(),
+            Some("https://example.org/l?a=1,b=2")
+        );
+
+        assert_eq!(Stamp::parse(&stamp.to_string()).unwrap(), stamp);
+
+        let stamp = Stamp::parse("artist=Jane").unwrap();
+        assert_eq!(stamp.copyright, None);
+        assert!(!stamp.is_empty());
+
+        assert!(Stamp::parse("owner=Jane").is_err());
+        assert!(Stamp::parse("artist=").is_err());
+        assert!(Stamp::parse("artist=A,artist=B").is_err());
+        assert!(Stamp::parse("").is_err());
+    }
+
+    #[test]
+    fn test_exif_entries() {
+        let stamp = Stamp {
+            copyright: Some(String::from("CC BY 4.0 Example")),
+            artist: Some(String::from("Jane")),
+            license_url: None,
+        };
+        let parsed = exif::parse(&exif::build(&exif_entries(&stamp))).unwrap();
+        let names: Vec<String> = parsed.fields().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, ["Artist", "Copyright"]);
+        assert_eq!(
+            parsed
+                .find(Ifd::Primary, COPYRIGHT)
+                .unwrap()
+                .ascii()
+                .as_deref(),
+            Some("CC BY 4.0 Example")
+        );
+    }
+
+    #[test]
+    fn test_named_fields() {
+        let stamp = Stamp {
+            copyright: Some(String::from("Example")),
+            artist: None,
+            license_url: Some(String::from("https://example.org")),
+        };
+        assert_eq!(
+            png_text(&stamp),
+            [(String::from("Copyright"), String::from("Example"))]
+        );
+        assert_eq!(xmp_rights(&stamp)[0].0, "dc:rights");
+        assert_eq!(xmp_license(&stamp)[0].0, "xmpRights:WebStatement");
+    }
+}
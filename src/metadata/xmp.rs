@@ -0,0 +1,359 @@
+//! XMP decoding and writing.
+//!
+//! XMP packets are RDF/XML. Rather than parse XML fully, this scans for
+//! element and attribute names with a namespace prefix, which is enough to
+//! list the properties a packet carries and read back simple values. Packets
+//! written by PMI are built from scratch with only the properties asked for.
+
+use super::{Category, Field};
+
+/// XMP identifier used by JPEG APP1 segments.
+pub const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// Namespaces of the properties PMI writes.
+const NAMESPACES: &[(&str, &str)] = &[
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("xmpRights", "http://ns.adobe.com/xap/1.0/rights/"),
+];
+
+/// Properties PMI writes as RDF containers, with the container type.
+///
+/// `dc:rights` is a language alternative and `dc:creator` an ordered list
+/// of names; writers that expect those shapes ignore plain text.
+const CONTAINERS: &[(&str, &str)] = &[("dc:rights", "Alt"), ("dc:creator", "Seq")];
+
+/// Prefixes that describe packet structure rather than properties.
+const STRUCTURAL_PREFIXES: &[&str] = &["x", "rdf", "xmlns", "xml", "stEvt", "stRef"];
+
+/// Get the named properties of an XMP packet, in order of first appearance.
+pub fn fields(data: &[u8]) -> Vec<Field> {
+    let data = data.strip_prefix(XMP_HEADER).unwrap_or(data);
+    let text = String::from_utf8_lossy(data);
+
+    let mut names: Vec<String> = Vec::new();
+    let mut add = |name: &str| {
+        if is_property(name) && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    };
+
+    let mut rest = text.as_ref();
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if rest.starts_with('/') || rest.starts_with('?') || rest.starts_with('!') {
+            continue;
+        }
+        let end = rest.find('>').unwrap_or(rest.len());
+        let tag = &rest[..end];
+
+        let mut parts = tag.split(|c: char| c.is_ascii_whitespace());
+        if let Some(element) = parts.next() {
+            add(element.trim_end_matches('/'));
+        }
+        for part in parts {
+            if let Some((attribute, _)) = part.split_once('=') {
+                add(attribute);
+            }
+        }
+        rest = &rest[end..];
+    }
+
+    names
+        .into_iter()
+        .map(|name| {
+            let category = classify(&name);
+            Field::new(name, category)
+        })
+        .collect()
+}
+
+/// Get the value of a simple property, written as an element or attribute.
+///
+/// Returns `None` if the property is missing or holds structured RDF.
+pub fn property(data: &[u8], name: &str) -> Option<String> {
+    let data = data.strip_prefix(XMP_HEADER).unwrap_or(data);
+    let text = String::from_utf8_lossy(data);
+
+    let mut rest = text.as_ref();
+    while let Some(start) = rest.find(name) {
+        let before = text.len() - rest.len() + start;
+        let preceded = text[..before].chars().next_back();
+        rest = &rest[start + name.len()..];
+
+        if preceded == Some('<') {
+            let Some(body) = rest.strip_prefix('>') else {
+                continue;
+            };
+            let end = body.find('<')?;
+            return body[end..]
+                .starts_with(&format!("</{}>", name))
+                .then(|| unescape(&body[..end]));
+        }
+        if preceded.is_some_and(|c| c.is_ascii_whitespace()) {
+            let Some(value) = rest.strip_prefix('=') else {
+                continue;
+            };
+            let quote = value.chars().next().filter(|&c| c == '"' || c == '\'')?;
+            let value = &value[1..];
+            return value.find(quote).map(|end| unescape(&value[..end]));
+        }
+    }
+    None
+}
+
+/// Get the value of a property: a simple value, or the first item of an
+/// `rdf:Alt`, `rdf:Bag` or `rdf:Seq`.
+pub fn value(data: &[u8], name: &str) -> Option<String> {
+    if let Some(value) = property(data, name) {
+        return Some(value);
+    }
+
+    let data = data.strip_prefix(XMP_HEADER).unwrap_or(data);
+    let text = String::from_utf8_lossy(data);
+    let open = format!("<{}>", name);
+    let start = text.find(&open)? + open.len();
+    let body = &text[start..];
+    let body = &body[..body.find(&format!("</{}>", name))?];
+
+    let item = &body[body.find("<rdf:li")?..];
+    let item = &item[item.find('>')? + 1..];
+    Some(unescape(&item[..item.find('<')?]))
+}
+
+/// Build a packet holding the given properties.
+///
+/// Names are qualified (`xmp:CreateDate`); properties whose prefix PMI has
+/// no namespace for are skipped. Values are written as simple text, or as
+/// a single-item container for the properties XMP defines that way.
+pub fn build(properties: &[(String, String)]) -> Vec<u8> {
+    let namespace = |name: &str| {
+        let prefix = name.split_once(':')?.0;
+        NAMESPACES.iter().find(|(p, _)| *p == prefix)
+    };
+
+    let mut declarations = Vec::new();
+    let mut body = String::new();
+    for (name, value) in properties {
+        let Some(&(prefix, uri)) = namespace(name) else {
+            continue;
+        };
+        let declaration = format!(" xmlns:{}=\"{}\"", prefix, uri);
+        if !declarations.contains(&declaration) {
+            declarations.push(declaration);
+        }
+        let value = escape(value);
+        match CONTAINERS.iter().find(|(n, _)| n == name) {
+            Some((_, "Alt")) => body.push_str(&format!(
+                "   <{0}><rdf:Alt><rdf:li xml:lang=\"x-default\">{1}</rdf:li></rdf:Alt></{0}>\n",
+                name, value
+            )),
+            Some((_, container)) => body.push_str(&format!(
+                "   <{0}><rdf:{1}><rdf:li>{2}</rdf:li></rdf:{1}></{0}>\n",
+                name, container, value
+            )),
+            None => body.push_str(&format!("   <{0}>{1}</{0}>\n", name, value)),
+        }
+    }
+
+    format!(
+        "<?xpacket begin=\"\u{FEFF}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+         \x20<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+         \x20 <rdf:Description rdf:about=\"\"{}>\n\
+         {}\
+         \x20 </rdf:Description>\n\
+         \x20</rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>",
+        declarations.concat(),
+        body
+    )
+    .into_bytes()
+}
+
+/// Escape text for use in XML content or attributes.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Undo the escaping of the predefined XML entities.
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Check whether a qualified name is a property rather than RDF structure.
+fn is_property(name: &str) -> bool {
+    match name.split_once(':') {
+        Some((prefix, local)) => {
+            !prefix.is_empty()
+                && !local.is_empty()
+                && !STRUCTURAL_PREFIXES.contains(&prefix)
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, ':' | '_' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+/// Classify an XMP property by its qualified name.
+pub fn classify(name: &str) -> Category {
+    let (prefix, local) = name.split_once(':').unwrap_or(("", name));
+
+    match (prefix, local) {
+        ("exif" | "exifEX", local) if local.starts_with("GPS") => Category::Location,
+        (
+            "photoshop" | "Iptc4xmpCore" | "Iptc4xmpExt",
+            "City" | "State" | "Country" | "CountryCode" | "Location" | "Sublocation"
+            | "LocationCreated" | "LocationShown",
+        ) => Category::Location,
+        ("tiff", "Make" | "Model")
+        | ("aux", _)
+        | ("exifEX", "BodySerialNumber" | "LensMake" | "LensModel" | "LensSerialNumber") => {
+            Category::Device
+        }
+        ("exifEX", "CameraOwnerName") => Category::Authorship,
+        ("dc", "creator" | "rights" | "contributor" | "publisher")
+        | ("xmpRights", _)
+        | ("photoshop", "AuthorsPosition" | "CaptionWriter" | "Credit" | "Source")
+        | ("Iptc4xmpCore", "CreatorContactInfo") => Category::Authorship,
+        ("xmp", "CreateDate" | "ModifyDate" | "MetadataDate")
+        | ("exif", "DateTimeOriginal" | "DateTimeDigitized")
+        | ("photoshop", "DateCreated")
+        | ("tiff", "DateTime")
+        | ("dc", "date") => Category::Time,
+        ("xmp", "CreatorTool") | ("xmpMM", _) | ("photoshop", "History") | ("tiff", "Software") => {
+            Category::Software
+        }
+        ("photoshop", "ICCProfile" | "ColorMode") | ("exif", "ColorSpace") => Category::Colour,
+        _ => Category::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKET: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmp:CreatorTool="Editor 1.0" exif:GPSLatitude="51,30.0N">
+   <dc:creator><rdf:Seq><rdf:li>Jane</rdf:li></rdf:Seq></dc:creator>
+   <xmp:CreateDate>2024-01-02T03:04:05</xmp:CreateDate>
+   <dc:title/>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+    #[test]
+    fn test_fields() {
+        let fields = fields(PACKET.as_bytes());
+        let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "xmp:CreatorTool",
+                "exif:GPSLatitude",
+                "dc:creator",
+                "xmp:CreateDate",
+                "dc:title"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fields_with_app1_header() {
+        let mut data = XMP_HEADER.to_vec();
+        data.extend_from_slice(PACKET.as_bytes());
+        assert_eq!(fields(&data).len(), 5);
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("exif:GPSLongitude"), Category::Location);
+        assert_eq!(classify("photoshop:City"), Category::Location);
+        assert_eq!(classify("tiff:Model"), Category::Device);
+        assert_eq!(classify("aux:SerialNumber"), Category::Device);
+        assert_eq!(classify("xmp:ModifyDate"), Category::Time);
+        assert_eq!(classify("dc:rights"), Category::Authorship);
+        assert_eq!(classify("xmpMM:History"), Category::Software);
+        assert_eq!(classify("dc:title"), Category::Other);
+    }
+
+    #[test]
+    fn test_fields_of_garbage() {
+        assert!(fields(b"\x00\x01<<>>").is_empty());
+    }
+
+    #[test]
+    fn test_property() {
+        let packet = PACKET.as_bytes();
+        assert_eq!(
+            property(packet, "xmp:CreateDate").as_deref(),
+            Some("2024-01-02T03:04:05")
+        );
+        assert_eq!(
+            property(packet, "xmp:CreatorTool").as_deref(),
+            Some("Editor 1.0")
+        );
+        // Structured values and missing properties have no simple value.
+        assert_eq!(property(packet, "dc:creator"), None);
+        assert_eq!(property(packet, "xmp:ModifyDate"), None);
+
+        assert_eq!(value(packet, "dc:creator").as_deref(), Some("Jane"));
+        assert_eq!(
+            value(packet, "xmp:CreatorTool").as_deref(),
+            Some("Editor 1.0")
+        );
+        assert_eq!(value(packet, "dc:title"), None);
+    }
+
+    #[test]
+    fn test_build_round_trip() {
+        let properties = [
+            (String::from("xmp:CreateDate"), String::from("2024-01-02")),
+            (String::from("xmp:Label"), String::from("A & <B>")),
+            (String::from("zz:Unknown"), String::from("dropped")),
+        ];
+        let packet = build(&properties);
+
+        let names: Vec<String> = fields(&packet).into_iter().map(|f| f.name).collect();
+        assert_eq!(names, ["xmp:CreateDate", "xmp:Label"]);
+        assert_eq!(
+            property(&packet, "xmp:CreateDate").as_deref(),
+            Some("2024-01-02")
+        );
+        assert_eq!(property(&packet, "xmp:Label").as_deref(), Some("A & <B>"));
+    }
+
+    #[test]
+    fn test_build_containers() {
+        let properties = [
+            (String::from("dc:rights"), String::from("© 2024 Example")),
+            (String::from("dc:creator"), String::from("Jane Doe")),
+            (
+                String::from("xmpRights:WebStatement"),
+                String::from("https://example.org/licence"),
+            ),
+        ];
+        let packet = build(&properties);
+        let text = String::from_utf8(packet.clone()).unwrap();
+        assert!(text.contains("<rdf:Alt><rdf:li xml:lang=\"x-default\">© 2024 Example"));
+        assert!(text.contains("<dc:creator><rdf:Seq><rdf:li>Jane Doe</rdf:li>"));
+        assert!(text.contains("xmlns:xmpRights=\"http://ns.adobe.com/xap/1.0/rights/\""));
+
+        let names: Vec<String> = fields(&packet).into_iter().map(|f| f.name).collect();
+        assert_eq!(names, ["dc:rights", "dc:creator", "xmpRights:WebStatement"]);
+        for (name, expected) in &properties {
+            assert_eq!(value(&packet, name).as_ref(), Some(expected));
+        }
+    }
+}
@@ -0,0 +1,627 @@
+//! Output file naming.
+//!
+//! Output names come from a template such as `{stem}_clean.{ext}`, and a
+//! collision policy decides what happens when the name is already taken.
+//!
+//! Names can give away as much as metadata: `IMG_20240612_183044.jpg` says
+//! when a photo was taken and `John's iPhone.jpg` whose phone took it.
+//! [`leaks`] spots such names, and [`Anonymize`] replaces the stem, with an
+//! optional private mapping file recording what each file was called.
+
+use crate::digest::{sha256, to_hex};
+use crate::error::{Error, Result};
+use crate::formats::ImageFormat;
+use crate::metadata::dates::Timestamp;
+use crate::report::csv_row;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Template used when cleaned files sit next to their originals.
+const SUFFIX_TEMPLATE: &str = "{stem}_clean.{ext}";
+
+/// Template used when cleaned files go to `--output-dir`.
+const PLAIN_TEMPLATE: &str = "{stem}.{ext}";
+
+/// Placeholder names, in the order help lists them.
+const PLACEHOLDERS: &[&str] = &["stem", "ext", "format", "hash8", "index", "date"];
+
+/// Highest number tried by [`Collision::Number`] before giving up.
+const MAX_NUMBER: usize = 9999;
+
+/// Stem prefixes used by cameras, phones, screenshot tools and messengers,
+/// in lowercase.
+const DEVICE_PREFIXES: &[&str] = &[
+    "img_",
+    "img-",
+    "pxl_",
+    "mvimg_",
+    "pano_",
+    "vid_",
+    "dsc",
+    "dji_",
+    "gopr",
+    "screenshot",
+    "screen shot",
+    "whatsapp image",
+    "signal-",
+    "photo from",
+];
+
+/// Device names that appear as words in stems, in lowercase.
+const DEVICE_NAMES: &[&str] = &["iphone", "ipad", "galaxy", "pixel", "android"];
+
+/// One piece of a name template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    /// Input file name without its extension.
+    Stem,
+    /// Input file extension, as written.
+    Ext,
+    /// Detected format, in lowercase.
+    Format,
+    /// First 8 hex digits of the SHA-256 of the cleaned output.
+    Hash8,
+    /// Position of the file in the run, counting from 1.
+    Index,
+    /// Date of the run, as `YYYY-MM-DD` in UTC.
+    Date,
+}
+
+/// A parsed `--name-template`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    text: String,
+    parts: Vec<Part>,
+}
+
+/// What a template is filled in from.
+#[derive(Debug, Clone, Copy)]
+pub struct NameFields<'a> {
+    /// The input file.
+    pub input: &'a Path,
+    /// Detected format of the input.
+    pub format: ImageFormat,
+    /// Cleaned output data.
+    pub data: &'a [u8],
+    /// Position of the file in the run, counting from 1.
+    pub index: usize,
+    /// Date of the run.
+    pub date: Timestamp,
+    /// Stem to use instead of the input's, when names are anonymised.
+    pub anonymous_stem: Option<&'a str>,
+}
+
+impl NameTemplate {
+    /// Parse a template. `{{` and `}}` stand for literal braces.
+    pub fn parse(text: &str) -> Result<Self> {
+        let error = |reason: String| Error::InvalidArgument {
+            argument: String::from("--name-template"),
+            reason,
+        };
+
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| error(String::from("Unclosed '{'")))?;
+                    let part = match &rest[..end] {
+                        "stem" => Part::Stem,
+                        "ext" => Part::Ext,
+                        "format" => Part::Format,
+                        "hash8" => Part::Hash8,
+                        "index" => Part::Index,
+                        "date" => Part::Date,
+                        name => {
+                            return Err(error(format!(
+                                "Unknown placeholder {{{}}}; expected one of {{{}}}",
+                                name,
+                                PLACEHOLDERS.join("}, {")
+                            )));
+                        }
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(part);
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err(error(String::from("Unmatched '}'; write '}}' for a brace"))),
+                '/' | '\\' => {
+                    return Err(error(String::from("Names cannot contain path separators")));
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        if parts.is_empty() {
+            return Err(error(String::from("Template is empty")));
+        }
+
+        Ok(NameTemplate {
+            text: text.to_string(),
+            parts,
+        })
+    }
+
+    /// Get the template used when no `--name-template` is given.
+    pub fn default_for(output_dir: bool) -> Self {
+        let text = if output_dir {
+            PLAIN_TEMPLATE
+        } else {
+            SUFFIX_TEMPLATE
+        };
+        Self::parse(text).expect("built-in templates are valid")
+    }
+
+    /// Fill in the template.
+    ///
+    /// A `.` just before `{ext}` is dropped when the input has no extension.
+    pub fn render(&self, fields: &NameFields) -> String {
+        let stem = match fields.anonymous_stem {
+            Some(stem) => stem.into(),
+            None => fields
+                .input
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy(),
+        };
+        let ext = fields
+            .input
+            .extension()
+            .map(|e| e.to_string_lossy())
+            .unwrap_or_default();
+
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => name.push_str(text),
+                Part::Stem => name.push_str(&stem),
+                Part::Ext => {
+                    if ext.is_empty() && name.ends_with('.') {
+                        name.pop();
+                    }
+                    name.push_str(&ext);
+                }
+                Part::Format => name.push_str(&fields.format.name().to_lowercase()),
+                Part::Hash8 => name.push_str(&to_hex(&sha256(fields.data))[..8]),
+                Part::Index => name.push_str(&fields.index.to_string()),
+                Part::Date => name.push_str(&fields.date.to_string()[..10]),
+            }
+        }
+        name
+    }
+}
+
+impl fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// What to do when an output file already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Collision {
+    /// Report an error and leave the existing file alone.
+    #[default]
+    Fail,
+    /// Leave the existing file alone and skip the input.
+    Skip,
+    /// Replace the existing file.
+    Overwrite,
+    /// Add `_1`, `_2` and so on before the extension until the name is free.
+    Number,
+}
+
+impl Collision {
+    /// Parse a policy name.
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "fail" => Ok(Collision::Fail),
+            "skip" => Ok(Collision::Skip),
+            "overwrite" => Ok(Collision::Overwrite),
+            "number" => Ok(Collision::Number),
+            _ => Err(Error::InvalidArgument {
+                argument: String::from("--on-collision"),
+                reason: String::from("Expected fail, skip, overwrite or number"),
+            }),
+        }
+    }
+
+    /// Get the policy name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Collision::Fail => "fail",
+            Collision::Skip => "skip",
+            Collision::Overwrite => "overwrite",
+            Collision::Number => "number",
+        }
+    }
+}
+
+/// Get the `n`th alternative to a path, as `photo_n.jpg`.
+pub fn numbered(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(ext) => path.with_file_name(format!("{}_{}.{}", stem, n, ext.to_string_lossy())),
+        None => path.with_file_name(format!("{}_{}", stem, n)),
+    }
+}
+
+/// Get the alternatives [`Collision::Number`] tries, starting with the path
+/// itself.
+pub fn candidates(path: &Path) -> impl Iterator<Item = PathBuf> + '_ {
+    (0..=MAX_NUMBER).map(move |n| match n {
+        0 => path.to_path_buf(),
+        n => numbered(path, n),
+    })
+}
+
+/// Something a file name gives away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameLeak {
+    /// A date, date and time, or Unix timestamp.
+    CaptureTime,
+    /// A camera, phone or app naming scheme, or a device name.
+    Device,
+    /// A possessive such as `John's`.
+    Owner,
+}
+
+impl NameLeak {
+    /// Describe what leaks.
+    pub fn description(&self) -> &'static str {
+        match self {
+            NameLeak::CaptureTime => "capture time",
+            NameLeak::Device => "device",
+            NameLeak::Owner => "owner",
+        }
+    }
+}
+
+/// Find what a file stem gives away.
+pub fn leaks(stem: &str) -> Vec<NameLeak> {
+    let lower = stem.to_lowercase();
+    let mut leaks = Vec::new();
+    if has_date(stem) {
+        leaks.push(NameLeak::CaptureTime);
+    }
+    if DEVICE_PREFIXES
+        .iter()
+        .any(|prefix| lower.starts_with(prefix))
+        || lower
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| DEVICE_NAMES.contains(&word))
+    {
+        leaks.push(NameLeak::Device);
+    }
+    if has_possessive(&lower) {
+        leaks.push(NameLeak::Owner);
+    }
+    leaks
+}
+
+/// Check for `YYYYMMDD`, `YYYY-MM-DD` (or `_`, `.` or space between the
+/// parts) or a Unix timestamp in seconds or milliseconds.
+fn has_date(stem: &str) -> bool {
+    let mut runs: Vec<(usize, &str)> = Vec::new();
+    let mut start = None;
+    for (i, c) in stem.char_indices().chain([(stem.len(), ' ')]) {
+        match (c.is_ascii_digit(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(from)) => {
+                runs.push((from, &stem[from..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    let separated = |i: usize| -> bool {
+        let [(year_at, year), (month_at, month), (day_at, day)] = match runs.get(i..i + 3) {
+            Some(&[a, b, c]) => [a, b, c],
+            _ => return false,
+        };
+        let separator = |at: usize| matches!(stem.as_bytes()[at], b'-' | b'_' | b'.' | b' ');
+        year.len() == 4
+            && month.len() == 2
+            && day.len() == 2
+            && month_at == year_at + 5
+            && day_at == month_at + 3
+            && separator(year_at + 4)
+            && separator(month_at + 2)
+            && is_date(year, month, day)
+    };
+
+    runs.iter().enumerate().any(|(i, &(_, run))| {
+        (run.len() >= 8 && is_date(&run[..4], &run[4..6], &run[6..8]))
+            || (matches!(run.len(), 10 | 13)
+                && run[..10]
+                    .parse::<u64>()
+                    .is_ok_and(|seconds| (1_000_000_000..2_100_000_000).contains(&seconds)))
+            || separated(i)
+    })
+}
+
+/// Check for a plausible capture date.
+fn is_date(year: &str, month: &str, day: &str) -> bool {
+    let number = |text: &str| text.parse::<u32>().unwrap_or(0);
+    (1990..=2099).contains(&number(year))
+        && (1..=12).contains(&number(month))
+        && (1..=31).contains(&number(day))
+}
+
+/// Check for a possessive such as `john's` or `john’s`.
+fn has_possessive(lower: &str) -> bool {
+    ["'s", "\u{2019}s"].iter().any(|mark| {
+        lower.match_indices(mark).any(|(i, _)| {
+            let before = lower[..i].chars().next_back();
+            let after = lower[i + mark.len()..].chars().next();
+            before.is_some_and(char::is_alphabetic) && after.is_none_or(|c| !c.is_alphanumeric())
+        })
+    })
+}
+
+/// How to replace file stems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anonymize {
+    /// 16 random hex digits.
+    Random,
+    /// The first 16 hex digits of the SHA-256 of the cleaned output.
+    Hash,
+    /// `image_0001`, `image_0002` and so on, in the order files are found.
+    Sequential,
+}
+
+impl Anonymize {
+    /// Parse a mode name.
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "random" => Ok(Anonymize::Random),
+            "hash" => Ok(Anonymize::Hash),
+            "sequential" => Ok(Anonymize::Sequential),
+            _ => Err(Error::InvalidArgument {
+                argument: String::from("--anonymize-names"),
+                reason: String::from("Expected random, hash or sequential"),
+            }),
+        }
+    }
+
+    /// Get the mode name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Anonymize::Random => "random",
+            Anonymize::Hash => "hash",
+            Anonymize::Sequential => "sequential",
+        }
+    }
+
+    /// Get the stem for a file, given its cleaned data and its position in
+    /// the run.
+    pub fn stem(&self, data: &[u8], index: usize) -> String {
+        match self {
+            Anonymize::Random => {
+                // Each RandomState has fresh keys, seeded per process.
+                let mut hasher = RandomState::new().build_hasher();
+                hasher.write_u128(
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |elapsed| elapsed.as_nanos()),
+                );
+                hasher.write_usize(index);
+                format!("{:016x}", hasher.finish())
+            }
+            Anonymize::Hash => to_hex(&sha256(data))[..16].to_string(),
+            Anonymize::Sequential => format!("image_{:04}", index),
+        }
+    }
+}
+
+/// Append original and anonymised paths to a mapping file, creating it
+/// with a header if needed.
+///
+/// The file links anonymous names back to the originals, so on Unix it is
+/// created readable by its owner only.
+pub fn write_name_map(path: &Path, renamed: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let io_error = |e| Error::io_with_path(e, path);
+    let is_new = fs::metadata(path).map_or(true, |m| m.len() == 0);
+    let mut text = String::new();
+    if is_new {
+        text.push_str("original,renamed\n");
+    }
+    for (original, renamed) in renamed {
+        text.push_str(&csv_row(&[
+            original.display().to_string(),
+            renamed.display().to_string(),
+        ]));
+        text.push('\n');
+    }
+    let mut file = options.open(path).map_err(io_error)?;
+    file.write_all(text.as_bytes()).map_err(io_error)
+}
+
+/// Get today's date in UTC.
+pub fn today() -> Timestamp {
+    Timestamp::now_utc().date_only()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields<'a>(input: &'a str, data: &'a [u8]) -> NameFields<'a> {
+        NameFields {
+            input: Path::new(input),
+            format: ImageFormat::Jpeg,
+            data,
+            index: 7,
+            date: Timestamp::new(2024, 3, 9, 0, 0, 0).unwrap(),
+            anonymous_stem: None,
+        }
+    }
+
+    #[test]
+    fn test_render_placeholders() {
+        let template = NameTemplate::parse("{date}-{index}-{format}-{stem}.{ext}").unwrap();
+        assert_eq!(
+            template.render(&fields("dir/IMG_1.JPG", b"")),
+            "2024-03-09-7-jpeg-IMG_1.JPG"
+        );
+
+        // SHA-256 of "abc" starts ba7816bf.
+        let template = NameTemplate::parse("{hash8}.{ext}").unwrap();
+        assert_eq!(template.render(&fields("a.jpg", b"abc")), "ba7816bf.jpg");
+
+        let template = NameTemplate::parse("{{{stem}}}").unwrap();
+        assert_eq!(template.render(&fields("a.jpg", b"")), "{a}");
+        assert_eq!(template.to_string(), "{{{stem}}}");
+    }
+
+    #[test]
+    fn test_default_templates() {
+        let data = b"";
+        assert_eq!(
+            NameTemplate::default_for(false).render(&fields("/p/image.jpg", data)),
+            "image_clean.jpg"
+        );
+        assert_eq!(
+            NameTemplate::default_for(false).render(&fields("/p/image", data)),
+            "image_clean"
+        );
+        assert_eq!(
+            NameTemplate::default_for(true).render(&fields("/p/image.jpg", data)),
+            "image.jpg"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for text in ["{size}.jpg", "{stem", "a}b", "dir/{stem}", ""] {
+            assert!(NameTemplate::parse(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_collision_and_numbering() {
+        for policy in ["fail", "skip", "overwrite", "number"] {
+            assert_eq!(Collision::parse(policy).unwrap().name(), policy);
+        }
+        assert!(Collision::parse("rename").is_err());
+
+        let names: Vec<PathBuf> = candidates(Path::new("out/a.jpg")).take(3).collect();
+        assert_eq!(
+            names,
+            [
+                PathBuf::from("out/a.jpg"),
+                PathBuf::from("out/a_1.jpg"),
+                PathBuf::from("out/a_2.jpg")
+            ]
+        );
+        assert_eq!(numbered(Path::new("a"), 3), PathBuf::from("a_3"));
+    }
+
+    #[test]
+    fn test_leaks() {
+        use NameLeak::{CaptureTime, Device, Owner};
+        let cases: &[(&str, &[NameLeak])] = &[
+            ("IMG_20240612_183044", &[CaptureTime, Device]),
+            ("PXL_20231224_090112345", &[CaptureTime, Device]),
+            (
+                "Screenshot from 2024-06-12 18-30-44",
+                &[CaptureTime, Device],
+            ),
+            ("John's iPhone", &[Device, Owner]),
+            ("signal-1718210000000", &[CaptureTime, Device]),
+            ("DSC01234", &[Device]),
+            ("holiday 2024.06.12", &[CaptureTime]),
+            ("logo", &[]),
+            ("invoice-12345678", &[]),
+            ("cats' toys", &[]),
+        ];
+        for (stem, expected) in cases {
+            assert_eq!(leaks(stem), *expected, "{}", stem);
+        }
+    }
+
+    #[test]
+    fn test_anonymous_stems() {
+        assert_eq!(Anonymize::Sequential.stem(b"", 12), "image_0012");
+        assert_eq!(Anonymize::Hash.stem(b"abc", 1), "ba7816bf8f01cfea");
+        let (a, b) = (
+            Anonymize::Random.stem(b"", 1),
+            Anonymize::Random.stem(b"", 1),
+        );
+        assert_eq!(a.len(), 16);
+        assert_ne!(a, b);
+        for mode in ["random", "hash", "sequential"] {
+            assert_eq!(Anonymize::parse(mode).unwrap().name(), mode);
+        }
+
+        let mut fields = fields("IMG_20240612_183044.jpg", b"");
+        fields.anonymous_stem = Some("image_0007");
+        assert_eq!(
+            NameTemplate::default_for(false).render(&fields),
+            "image_0007_clean.jpg"
+        );
+    }
+
+    #[test]
+    fn test_write_name_map() {
+        let path = std::env::temp_dir().join("pmi_naming_map.csv");
+        let _ = fs::remove_file(&path);
+        let renamed = [(
+            PathBuf::from("in/John's iPhone, 2.jpg"),
+            PathBuf::from("out/image_0001.jpg"),
+        )];
+        write_name_map(&path, &renamed).unwrap();
+        write_name_map(&path, &renamed).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "original,renamed\n\
+             \"in/John's iPhone, 2.jpg\",out/image_0001.jpg\n\
+             \"in/John's iPhone, 2.jpg\",out/image_0001.jpg\n"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_today_is_a_date() {
+        let date = today();
+        assert!(date.year >= 2024);
+        assert_eq!((date.hour, date.minute, date.second), (0, 0, 0));
+    }
+}
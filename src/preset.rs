@@ -0,0 +1,228 @@
+//! Named privacy presets and user-defined profiles.
+//!
+//! A preset is a bundle of option settings chosen with `--preset NAME`. The
+//! built-in presets cover common situations; anything else can be written
+//! as a `[profile.NAME]` table in any config file, using the same keys as
+//! the long options:
+//!
+//! ```toml
+//! [profile.family]
+//! gps = "coarsen=25"
+//! dates = "keep-date-only"
+//! keep-orientation = true
+//! ```
+//!
+//! A preset's settings apply on top of the config files and environment, and
+//! options given on the command line take precedence over them.
+
+use crate::error::{Error, Result};
+use crate::toml;
+use std::path::{Path, PathBuf};
+
+/// Table prefix for user profiles.
+const PROFILE_TABLE: &str = "profile.";
+
+/// Option names and values for a built-in preset.
+type Settings = &'static [(&'static str, &'static str)];
+
+/// Built-in presets: name, description and settings.
+const BUILTIN: &[(&str, &str, Settings)] = &[
+    (
+        "social",
+        "Strip everything, but keep colour profiles and orientation so photos look right",
+        &[("icc", "keep"), ("keep-orientation", "true")],
+    ),
+    (
+        "journalist",
+        "Strip everything, including colour profiles and file names, and verify every output",
+        &[
+            ("allowlist", "true"),
+            ("icc", "strip"),
+            ("anonymize-names", "random"),
+            ("verify", "true"),
+        ],
+    ),
+    (
+        "archive",
+        "Keep capture times and a position to within about 5 km",
+        &[("gps", "coarsen=5"), ("dates", "keep")],
+    ),
+    (
+        "forensic-safe",
+        "Verify every output and prove the pixels are unchanged",
+        &[("verify", "true"), ("prove-lossless", "true")],
+    ),
+];
+
+/// One setting in a preset, as a long option name and its value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    /// Long option name without the leading dashes.
+    pub key: String,
+    /// Value as it would be given on the command line.
+    pub value: String,
+    /// Line in the config file, or 0 for built-in presets.
+    pub line: usize,
+}
+
+/// A named set of option settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preset {
+    /// Name given to `--preset`.
+    pub name: String,
+    /// One-line description.
+    pub description: String,
+    /// Settings, in the order they are applied.
+    pub settings: Vec<Setting>,
+    /// Config file the profile was read from, or `None` for built-ins.
+    pub source: Option<PathBuf>,
+}
+
+/// Get the built-in presets.
+pub fn builtin() -> Vec<Preset> {
+    BUILTIN
+        .iter()
+        .map(|(name, description, settings)| Preset {
+            name: name.to_string(),
+            description: description.to_string(),
+            settings: settings
+                .iter()
+                .map(|(key, value)| Setting {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                    line: 0,
+                })
+                .collect(),
+            source: None,
+        })
+        .collect()
+}
+
+/// Collect the `[profile.NAME]` tables from a parsed config file.
+pub fn profiles(entries: &[toml::Entry], path: &Path) -> Result<Vec<Preset>> {
+    let mut profiles: Vec<Preset> = Vec::new();
+    for entry in entries {
+        let Some(name) = entry.table.strip_prefix(PROFILE_TABLE) else {
+            continue;
+        };
+        if name.contains('.') {
+            return Err(Error::config(
+                path,
+                entry.line,
+                format!("'{}' is not a valid profile name", name),
+            ));
+        }
+
+        let setting = Setting {
+            key: entry.key.clone(),
+            value: entry.value.to_string(),
+            line: entry.line,
+        };
+        match profiles.iter_mut().find(|p| p.name == name) {
+            Some(profile) => profile.settings.push(setting),
+            None => profiles.push(Preset {
+                name: name.to_string(),
+                description: format!("Profile from {}", path.display()),
+                settings: vec![setting],
+                source: Some(path.to_path_buf()),
+            }),
+        }
+    }
+    Ok(profiles)
+}
+
+/// Get every available preset: the built-ins, then the user profiles.
+///
+/// A profile replaces a built-in preset or earlier profile of the same name.
+pub fn available(profiles: &[Preset]) -> Vec<Preset> {
+    let mut presets = builtin();
+    for profile in profiles {
+        presets.retain(|p| p.name != profile.name);
+        presets.push(profile.clone());
+    }
+    presets
+}
+
+/// Find a preset or user profile by name.
+pub fn find(name: &str, profiles: &[Preset]) -> Result<Preset> {
+    let presets = available(profiles);
+    let names: Vec<&str> = presets.iter().map(|p| p.name.as_str()).collect();
+    let reason = format!("Unknown preset; available: {}", names.join(", "));
+    presets
+        .iter()
+        .find(|p| p.name == name)
+        .cloned()
+        .ok_or(Error::InvalidArgument {
+            argument: format!("--preset {}", name),
+            reason,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_profiles(text: &str) -> Result<Vec<Preset>> {
+        let path = Path::new("config");
+        profiles(&toml::parse(text, path)?, path)
+    }
+
+    #[test]
+    fn test_builtin_presets() {
+        let names: Vec<String> = builtin().into_iter().map(|p| p.name).collect();
+        assert_eq!(names, ["social", "journalist", "archive", "forensic-safe"]);
+
+        let social = find("social", &[]).unwrap();
+        assert_eq!(social.source, None);
+        assert_eq!(social.settings[0].key, "icc");
+        assert_eq!(social.settings[0].value, "keep");
+    }
+
+    #[test]
+    fn test_user_profiles() {
+        let profiles = parse_profiles(
+            "jobs = 2\n\
+             [profile.family]\n\
+             gps = \"coarsen=25\"\n\
+             keep-orientation = true\n\
+             [profile.social]\n\
+             icc = \"strip\"\n",
+        )
+        .unwrap();
+
+        let family = find("family", &profiles).unwrap();
+        assert_eq!(family.source.as_deref(), Some(Path::new("config")));
+        let settings: Vec<(&str, &str, usize)> = family
+            .settings
+            .iter()
+            .map(|s| (s.key.as_str(), s.value.as_str(), s.line))
+            .collect();
+        assert_eq!(
+            settings,
+            [("gps", "coarsen=25", 3), ("keep-orientation", "true", 4)]
+        );
+
+        // A profile named like a built-in replaces it.
+        let social = find("social", &profiles).unwrap();
+        assert_eq!(social.settings.len(), 1);
+        assert_eq!(social.settings[0].value, "strip");
+        assert_eq!(available(&profiles).len(), 5);
+    }
+
+    #[test]
+    fn test_unknown_preset_lists_names() {
+        let err = find("holiday", &[]).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("social, journalist, archive, forensic-safe")
+        );
+    }
+
+    #[test]
+    fn test_nested_profile_name_is_rejected() {
+        assert!(matches!(
+            parse_profiles("[profile.a.b]\nicc = \"keep\"\n"),
+            Err(Error::Config { line: 2, .. })
+        ));
+    }
+}
@@ -3,34 +3,92 @@
 //! This module handles file discovery, format detection, and processing.
 //! Supports parallel processing for improved performance on multi-core systems.
 
+use crate::atomic::{self, TempFile};
+use crate::backup::Backup;
+use crate::cache::{self, Cache};
 use crate::cli::Config;
 use crate::error::{Error, Result};
-use crate::formats::{detect_format_from_extension, strip_metadata};
+use crate::formats::{
+    ImageFormat, detect_format, detect_format_from_extension, strip_metadata_with, verify_result,
+};
+use crate::fsmeta::{self, HardLinks};
+use crate::lossless::{self, FrameDigest, prove_lossless};
+use crate::metadata::MetadataItem;
+use crate::metadata::risk::{self, Assessment, RiskLevel};
+use crate::naming::{self, Collision, NameFields, NameTemplate};
 use crate::parallel::{self, ThreadPool};
+use crate::report::FileRecord;
+use crate::shred::{self, Removal};
 use crate::terminal::{
     ProcessingStats, ProgressBar, Styled, format_size, print_error, print_info, print_success,
     print_warning, stdout_supports_color,
 };
 use std::fs;
 use std::io::Write;
+use std::mem;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::mpsc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 /// Result of processing a single file.
+///
+/// One is sent per file, so the size of the success variant does not matter.
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum ProcessResult {
     /// File was successfully processed.
     Success {
         input: PathBuf,
         output: PathBuf,
+        format: ImageFormat,
         bytes_removed: u64,
+        /// Metadata blocks removed.
+        removed: Vec<MetadataItem>,
+        /// Privacy risk of the removed metadata.
+        risk: Assessment,
+        /// Names of the EXIF fields written back, such as a coarsened position.
+        retained: Vec<String>,
+        /// Per-frame pixel digests, when a lossless proof was made.
+        proof: Option<Vec<FrameDigest>>,
+        /// How the original was removed, with `--remove-originals`.
+        original: Option<Removal>,
+        /// Cache entry for the file, with `--incremental`.
+        cached: Option<cache::Entry>,
+        /// The image needed no changes: it was left as it was, or its
+        /// output is an exact copy.
+        already_clean: bool,
+        /// Something that could not be kept, such as the owner of a file
+        /// changed in place, without failing the file.
+        warning: Option<String>,
+        timings: Timings,
     },
     /// File was skipped (unsupported format, etc.).
     Skipped { path: PathBuf, reason: String },
     /// Processing failed.
-    Failed { path: PathBuf, error: String },
+    Failed {
+        path: PathBuf,
+        error: String,
+        /// Machine-readable error kind, see [`Error::kind`].
+        kind: &'static str,
+        /// Format, if the file could be read and recognised.
+        format: Option<ImageFormat>,
+        timings: Timings,
+    },
+}
+
+/// Time spent in each processing step of a single file.
+///
+/// Steps that did not run are zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timings {
+    pub read: Duration,
+    pub strip: Duration,
+    pub verify: Duration,
+    pub prove: Duration,
+    pub write: Duration,
+    /// Wall time for the whole file, including steps not listed above.
+    pub total: Duration,
 }
 
 // Make ProcessResult Send + Sync for parallel processing
@@ -41,6 +99,14 @@ pub struct Processor {
     config: Config,
     stats: ProcessingStats,
     start_time: Instant,
+    /// Per-file report records, collected when a report is requested.
+    records: Vec<FileRecord>,
+    /// Inputs and their anonymised outputs, collected for `--name-map`.
+    renamed: Vec<(PathBuf, PathBuf)>,
+    /// Where this run backs up originals, with `--backup`.
+    backup: Option<Backup>,
+    /// Files cleaned by earlier runs, with `--incremental`.
+    cache: Option<Cache>,
 }
 
 impl Processor {
@@ -50,13 +116,24 @@ impl Processor {
             config,
             stats: ProcessingStats::new(),
             start_time: Instant::now(),
+            records: Vec::new(),
+            renamed: Vec::new(),
+            backup: None,
+            cache: None,
         }
     }
 
+    /// Get the report records of the last run, sorted by input path.
+    ///
+    /// Empty unless the configuration requests a report.
+    pub fn records(&self) -> &[FileRecord] {
+        &self.records
+    }
+
     /// Process all files from the configuration paths.
     pub fn run(&mut self) -> Result<ProcessingStats> {
         // Collect files to process.
-        let files = self.collect_files()?;
+        let mut files = self.collect_files()?;
 
         if files.is_empty() {
             if !self.config.quiet {
@@ -65,6 +142,26 @@ impl Processor {
             return Ok(self.stats.clone());
         }
 
+        // Leave out files cleaned by an earlier run and unchanged since.
+        if self.config.incremental {
+            let mut cache = Cache::load(&self.config)?;
+            let found = files.len();
+            files.retain(|path| !cache.is_unchanged(path));
+            let unchanged = found - files.len();
+            if unchanged > 0 && !self.config.quiet {
+                print_info(&format!(
+                    "Skipping {} unchanged file(s) already cleaned (cache: {})",
+                    unchanged,
+                    cache.path().display()
+                ));
+            }
+            self.cache = Some(cache);
+            if files.is_empty() {
+                self.save_cache()?;
+                return Ok(self.stats.clone());
+            }
+        }
+
         let file_count = files.len();
         let num_jobs = self
             .config
@@ -100,6 +197,12 @@ impl Processor {
                         .map_err(|e| Error::io_with_path(e, output_dir))?;
                 }
             }
+            check_root_names(&self.config)?;
+        }
+
+        // Start this run's backup directory and journal.
+        if let (Some(root), false) = (&self.config.backup, self.config.dry_run) {
+            self.backup = Some(Backup::start(root)?);
         }
 
         // Process files (parallel or sequential based on configuration)
@@ -109,18 +212,47 @@ impl Processor {
             self.run_sequential(files)?;
         }
 
+        // Parallel workers finish in any order; keep reports deterministic.
+        self.records.sort_by(|a, b| a.input.cmp(&b.input));
+
+        // Record where anonymised files went.
+        if let Some(ref name_map) = self.config.name_map {
+            if !self.config.dry_run && !self.renamed.is_empty() {
+                self.renamed.sort();
+                naming::write_name_map(name_map, &self.renamed)?;
+            }
+        }
+
+        self.save_cache()?;
+
+        if let (Some(backup), false) = (&self.backup, self.config.quiet) {
+            print_info(&format!(
+                "Originals backed up to {}; undo with: pmi restore {}",
+                backup.dir().display(),
+                backup.journal().display()
+            ));
+        }
+
         self.stats.set_duration(self.start_time.elapsed());
         Ok(self.stats.clone())
     }
 
+    /// Write back the incremental cache, unless this is a dry run.
+    fn save_cache(&self) -> Result<()> {
+        match &self.cache {
+            Some(cache) if !self.config.dry_run => cache.save(),
+            _ => Ok(()),
+        }
+    }
+
     /// Run processing sequentially.
     fn run_sequential(&mut self, files: Vec<PathBuf>) -> Result<()> {
         let mut progress = ProgressBar::new(files.len());
 
-        for path in &files {
+        for (index, path) in files.iter().enumerate() {
             progress.set_current_file(path.display().to_string());
 
-            let result = self.process_file(path);
+            let result = self.process_file(path, index + 1);
             self.handle_result(result);
 
             if !self.config.quiet {
@@ -150,12 +282,13 @@ impl Processor {
         let pool = ThreadPool::new(num_threads);
 
         // Submit all jobs
-        for path in files {
+        for (index, path) in files.into_iter().enumerate() {
             let result_tx = result_tx.clone();
             let config = Arc::clone(&config);
+            let backup = self.backup.clone();
 
             pool.execute(move || {
-                let result = process_file_standalone(&path, &config);
+                let result = process_file_standalone(&path, index + 1, &config, backup.as_ref());
                 let _ = result_tx.send(result);
             });
         }
@@ -187,7 +320,7 @@ impl Processor {
     }
 
     /// Collect all files to process from the configuration paths.
-    fn collect_files(&self) -> Result<Vec<PathBuf>> {
+    pub fn collect_files(&self) -> Result<Vec<PathBuf>> {
         let mut files = Vec::new();
 
         for path in &self.config.paths {
@@ -207,19 +340,20 @@ impl Processor {
         Ok(files)
     }
 
-    /// Collect files from a directory.
+    /// Collect files from a directory, in name order so runs are repeatable.
     fn collect_from_directory(&self, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
-        let entries = fs::read_dir(dir).map_err(|e| Error::io_with_path(e, dir))?;
-
-        for entry in entries {
-            let entry = entry.map_err(|e| Error::io_with_path(e, dir))?;
-            let path = entry.path();
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(dir).map_err(|e| Error::io_with_path(e, dir))? {
+            entries.push(entry.map_err(|e| Error::io_with_path(e, dir))?.path());
+        }
+        entries.sort();
 
+        for path in entries {
             if path.is_file() {
                 if self.is_supported_file(&path) {
                     files.push(path);
                 }
-            } else if path.is_dir() && self.config.recursive {
+            } else if path.is_dir() && self.config.recursive && !self.is_backup_dir(&path) {
                 self.collect_from_directory(&path, files)?;
             }
         }
@@ -227,38 +361,45 @@ impl Processor {
         Ok(())
     }
 
+    /// Check if a directory is the `--backup` directory, whose originals
+    /// must not be cleaned by a later run.
+    fn is_backup_dir(&self, dir: &Path) -> bool {
+        self.config.backup.as_ref().is_some_and(|backup| {
+            match (fs::canonicalize(backup), fs::canonicalize(dir)) {
+                (Ok(backup), Ok(dir)) => backup == dir,
+                _ => false,
+            }
+        })
+    }
+
     /// Check if a file has a supported extension.
     fn is_supported_file(&self, path: &Path) -> bool {
         detect_format_from_extension(path).is_some()
     }
 
     /// Process a single file (instance method for sequential processing).
-    fn process_file(&self, path: &Path) -> ProcessResult {
-        process_file_standalone(path, &self.config)
+    fn process_file(&self, path: &Path, index: usize) -> ProcessResult {
+        process_file_standalone(path, index, &self.config, self.backup.as_ref())
     }
 
-    /// Get the output path for a file.
-    pub fn get_output_path(config: &Config, input: &Path) -> PathBuf {
+    /// Get the output path for a file, before any collision is resolved.
+    pub fn get_output_path(config: &Config, fields: &NameFields) -> PathBuf {
+        let input = fields.input;
         if config.in_place {
-            return input.to_path_buf();
-        }
-
-        if let Some(ref output_dir) = config.output_dir {
-            let file_name = input.file_name().unwrap_or_default();
-            return output_dir.join(file_name);
+            // Anonymised files are renamed where they are.
+            return match fields.anonymous_stem {
+                Some(_) => input.with_file_name(NameTemplate::default_for(true).render(fields)),
+                None => input.to_path_buf(),
+            };
         }
 
-        // Default: add _clean suffix.
-        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
-        let ext = input
-            .extension()
-            .map(|e| e.to_string_lossy())
-            .unwrap_or_default();
-
-        if ext.is_empty() {
-            input.with_file_name(format!("{}_clean", stem))
-        } else {
-            input.with_file_name(format!("{}_clean.{}", stem, ext))
+        let name = config.name_template().render(fields);
+        match config.output_dir {
+            Some(ref output_dir) => match mirrored_path(config, input) {
+                Some(relative) => output_dir.join(relative).with_file_name(name),
+                None => output_dir.join(name),
+            },
+            None => input.with_file_name(name),
         }
     }
 
@@ -266,23 +407,66 @@ impl Processor {
     fn handle_result(&mut self, result: ProcessResult) {
         let color_enabled = stdout_supports_color();
 
+        if self.config.report_format.is_some() {
+            self.records
+                .push(FileRecord::from_result(&result, self.config.dry_run));
+        }
+
         match result {
             ProcessResult::Success {
                 input,
                 output,
                 bytes_removed,
+                risk,
+                retained,
+                proof,
+                original,
+                cached,
+                already_clean,
+                warning,
+                ..
             } => {
-                self.stats.add_success(bytes_removed);
+                if let (Some(cache), Some(entry)) = (&mut self.cache, cached) {
+                    cache.insert(entry);
+                }
+                if already_clean {
+                    self.stats.add_already_clean();
+                } else {
+                    self.stats.add_success(bytes_removed);
+                    self.stats.add_risk(risk.level);
+                }
+                if self.config.name_map.is_some() {
+                    self.renamed.push((input.clone(), output.clone()));
+                }
 
                 if !self.config.quiet {
                     let input_name = input.file_name().unwrap_or_default().to_string_lossy();
-                    let msg = if self.config.dry_run {
+                    let msg = if already_clean {
+                        let input_name =
+                            Styled::with_color_support(input_name.as_ref(), color_enabled).blue();
+                        let output_name = output.file_name().unwrap_or_default().to_string_lossy();
+                        let output_name =
+                            Styled::with_color_support(output_name.as_ref(), color_enabled).blue();
+                        let arrow = Styled::with_color_support("\u{2192}", color_enabled).dim();
+                        if self.config.in_place && input == output {
+                            format!("Already clean {} (left unchanged)", input_name)
+                        } else if self.config.in_place {
+                            format!("Already clean {} {} {}", input_name, arrow, output_name)
+                        } else if self.config.dry_run {
+                            format!("Already clean {} (would copy unchanged)", input_name)
+                        } else {
+                            format!(
+                                "Already clean {} {} {} (copied unchanged)",
+                                input_name, arrow, output_name
+                            )
+                        }
+                    } else if self.config.dry_run {
                         format!(
                             "Would clean {} (would remove {})",
                             Styled::with_color_support(input_name.as_ref(), color_enabled).blue(),
                             format_size(bytes_removed)
                         )
-                    } else if self.config.in_place {
+                    } else if self.config.in_place && input == output {
                         format!(
                             "Cleaned {} (removed {})",
                             Styled::with_color_support(input_name.as_ref(), color_enabled).blue(),
@@ -298,7 +482,35 @@ impl Processor {
                             format_size(bytes_removed)
                         )
                     };
+                    let msg = match original {
+                        Some(removal) => format!("{}; original {}", msg, removal.name()),
+                        None if self.config.dry_run && self.config.remove_originals => {
+                            let verb = if self.config.shred { "shred" } else { "remove" };
+                            format!("{}; would {} original", msg, verb)
+                        }
+                        None => msg,
+                    };
                     print_success(&msg);
+                    if let Some(warning) = &warning {
+                        print_warning(&format!("  {}", warning));
+                    }
+
+                    if self.config.verbose && risk.level > RiskLevel::None {
+                        print_info(&format!(
+                            "  risk: {} ({})",
+                            risk.level,
+                            risk.reasons.join(", ")
+                        ));
+                    }
+                    if self.config.verbose && self.config.anonymize_names.is_none() {
+                        print_name_leaks(&input);
+                    }
+                    if self.config.verbose && !retained.is_empty() {
+                        print_info(&format!("  kept: {}", retained.join(", ")));
+                    }
+                    if self.config.verbose && self.config.prove_lossless {
+                        print_proof(proof.as_deref());
+                    }
                 }
             }
             ProcessResult::Skipped { path, reason } => {
@@ -309,7 +521,7 @@ impl Processor {
                     print_warning(&format!("Skipped {}: {}", name, reason));
                 }
             }
-            ProcessResult::Failed { path, error } => {
+            ProcessResult::Failed { path, error, .. } => {
                 self.stats.add_failure();
 
                 if !self.config.quiet {
@@ -321,91 +533,496 @@ impl Processor {
     }
 }
 
+/// Print what a file name gives away, if anything.
+fn print_name_leaks(input: &Path) {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let leaks: Vec<&str> = naming::leaks(&stem)
+        .iter()
+        .map(|leak| leak.description())
+        .collect();
+    if !leaks.is_empty() {
+        print_info(&format!(
+            "  name reveals: {} (see --anonymize-names)",
+            leaks.join(", ")
+        ));
+    }
+}
+
+/// Print the per-frame digests of a lossless proof.
+fn print_proof(proof: Option<&[FrameDigest]>) {
+    match proof {
+        Some(frames) => {
+            for frame in frames {
+                print_info(&format!(
+                    "  frame {} ({}x{}) pixels identical, sha256 {}",
+                    frame.index,
+                    frame.width,
+                    frame.height,
+                    frame.hex()
+                ));
+            }
+        }
+        None => print_info("  no pixel proof available for this format"),
+    }
+}
+
+/// Get the directories among the input paths.
+fn directory_roots(config: &Config) -> Vec<&PathBuf> {
+    config.paths.iter().filter(|path| path.is_dir()).collect()
+}
+
+/// Get the name a directory root is mirrored under, resolving `.` and `..`.
+fn root_name(root: &Path) -> Option<PathBuf> {
+    match root.file_name() {
+        Some(name) => Some(PathBuf::from(name)),
+        None => fs::canonicalize(root).ok()?.file_name().map(PathBuf::from),
+    }
+}
+
+/// Get the path of an output file relative to `--output-dir` when a
+/// recursive run mirrors the input tree.
+///
+/// Paths are kept relative to the input directory they were found in, so
+/// `a/IMG_0001.jpg` and `b/IMG_0001.jpg` do not collide. With several input
+/// directories, each keeps its own name as the top level. Files named
+/// directly on the command line are not mirrored.
+fn mirrored_path(config: &Config, input: &Path) -> Option<PathBuf> {
+    if !config.recursive {
+        return None;
+    }
+
+    let roots = directory_roots(config);
+    let root = roots
+        .iter()
+        .filter(|root| input.starts_with(root))
+        .max_by_key(|root| root.components().count())?;
+    let relative = input.strip_prefix(root).ok()?;
+
+    if roots.len() == 1 {
+        Some(relative.to_path_buf())
+    } else {
+        Some(root_name(root)?.join(relative))
+    }
+}
+
+/// Check that directory roots mirrored under `--output-dir` have distinct
+/// names, so their files cannot mix.
+fn check_root_names(config: &Config) -> Result<()> {
+    let roots = directory_roots(config);
+    if !config.recursive || config.in_place || roots.len() < 2 {
+        return Ok(());
+    }
+
+    let mut seen: Vec<(PathBuf, &PathBuf)> = Vec::new();
+    for root in roots {
+        let Some(name) = root_name(root) else {
+            return Err(Error::InvalidArgument {
+                argument: root.display().to_string(),
+                reason: String::from("Cannot mirror a directory without a name under --output-dir"),
+            });
+        };
+        if let Some((_, other)) = seen.iter().find(|(seen, _)| *seen == name) {
+            return Err(Error::InvalidArgument {
+                argument: root.display().to_string(),
+                reason: format!(
+                    "Has the same name as {}, so their files would mix under --output-dir",
+                    other.display()
+                ),
+            });
+        }
+        seen.push((name, root));
+    }
+    Ok(())
+}
+
 /// Process a single file (standalone function for parallel execution).
-fn process_file_standalone(path: &Path, config: &Config) -> ProcessResult {
+fn process_file_standalone(
+    path: &Path,
+    index: usize,
+    config: &Config,
+    backup: Option<&Backup>,
+) -> ProcessResult {
+    let started = Instant::now();
+    let mut timings = Timings::default();
+    let mut format = None;
+
+    let result = clean_file(path, index, config, backup, &mut timings, &mut format);
+    timings.total = started.elapsed();
+
+    match result {
+        Ok(Handled::Cleaned(cleaned)) => ProcessResult::Success {
+            input: path.to_path_buf(),
+            output: cleaned.output,
+            format: cleaned.format,
+            bytes_removed: cleaned.bytes_removed,
+            removed: cleaned.removed,
+            risk: cleaned.risk,
+            retained: cleaned.retained,
+            proof: cleaned.proof,
+            original: cleaned.original,
+            cached: cleaned.cached,
+            already_clean: cleaned.already_clean,
+            warning: cleaned.warning,
+            timings,
+        },
+        Ok(Handled::BelowMinRisk(level)) => ProcessResult::Skipped {
+            path: path.to_path_buf(),
+            reason: format!("risk {} is below --min-risk {}", level, config.min_risk),
+        },
+        Ok(Handled::OutputExists(output)) => ProcessResult::Skipped {
+            path: path.to_path_buf(),
+            reason: format!("{} already exists", output.display()),
+        },
+        Ok(Handled::Link(reason)) => ProcessResult::Skipped {
+            path: path.to_path_buf(),
+            reason,
+        },
+        Err(e) => ProcessResult::Failed {
+            path: path.to_path_buf(),
+            error: e.to_string(),
+            kind: e.kind(),
+            format,
+            timings,
+        },
+    }
+}
+
+/// Outcome of handling a single file without error.
+enum Handled {
+    Cleaned(Box<Cleaned>),
+    /// The metadata is less sensitive than `--min-risk`; nothing was written.
+    BelowMinRisk(RiskLevel),
+    /// The output exists and `--on-collision skip` left it alone.
+    OutputExists(PathBuf),
+    /// A link that in-place mode left alone, and why.
+    Link(String),
+}
+
+/// Outcome of successfully cleaning a single file.
+struct Cleaned {
+    output: PathBuf,
+    format: ImageFormat,
+    bytes_removed: u64,
+    removed: Vec<MetadataItem>,
+    risk: Assessment,
+    retained: Vec<String>,
+    proof: Option<Vec<FrameDigest>>,
+    original: Option<Removal>,
+    cached: Option<cache::Entry>,
+    already_clean: bool,
+    warning: Option<String>,
+}
+
+/// Read, strip, check and write a single file, recording each step's time.
+///
+/// The detected format is stored as soon as it is known, so failures later
+/// on can still report it.
+fn clean_file(
+    path: &Path,
+    index: usize,
+    config: &Config,
+    backup: Option<&Backup>,
+    timings: &mut Timings,
+    format: &mut Option<ImageFormat>,
+) -> Result<Handled> {
     // Read the file.
-    let data = match fs::read(path) {
-        Ok(d) => d,
-        Err(e) => {
-            return ProcessResult::Failed {
-                path: path.to_path_buf(),
-                error: Error::io_with_path(e, path).to_string(),
-            };
-        }
-    };
+    let step = Instant::now();
+    let data = fs::read(path).map_err(|e| Error::io_with_path(e, path))?;
+    timings.read = step.elapsed();
+    *format = detect_format(&data);
 
     // Strip metadata.
-    let strip_result = match strip_metadata(&data, path) {
-        Ok(r) => r,
-        Err(e) => {
-            return ProcessResult::Failed {
-                path: path.to_path_buf(),
-                error: e.to_string(),
-            };
+    let step = Instant::now();
+    let mut strip_result = strip_metadata_with(&data, path, &config.strip_options())?;
+    timings.strip = step.elapsed();
+    let removed = mem::take(&mut strip_result.report.removed);
+
+    // Leave files alone whose metadata is not sensitive enough.
+    let risk = risk::assess(&removed);
+    if risk.level < config.min_risk {
+        return Ok(Handled::BelowMinRisk(risk.level));
+    }
+
+    // Verify the cleaned data before anything is written. Originals are
+    // only removed once their cleaned copies have passed.
+    if config.verify || config.remove_originals {
+        let step = Instant::now();
+        verify_result(&strip_result, path)?;
+        timings.verify = step.elapsed();
+    }
+
+    // Prove the pixels are unchanged, where the format can be decoded.
+    let proof = match *format {
+        Some(format) if config.prove_lossless && lossless::supports(format) => {
+            let step = Instant::now();
+            let digests = prove_lossless(&data, &strip_result.data, path)?;
+            timings.prove = step.elapsed();
+            Some(digests)
         }
+        _ => None,
     };
 
+    // Stripping succeeded, so the format was recognised.
+    let format = format.ok_or_else(|| Error::unsupported_format(path, None))?;
+
     // Determine output path.
-    let output_path = Processor::get_output_path(config, path);
+    let anonymous_stem = config
+        .anonymize_names
+        .map(|mode| mode.stem(&strip_result.data, index));
+    let fields = NameFields {
+        input: path,
+        format,
+        data: &strip_result.data,
+        index,
+        date: naming::today(),
+        anonymous_stem: anonymous_stem.as_deref(),
+    };
+    let mut output_path = Processor::get_output_path(config, &fields);
+    let renamed_in_place = config.in_place && output_path != path;
+    let replaces = config.in_place && !renamed_in_place;
+
+    // In-place rewrites go to the file a symlink points to, so the link
+    // keeps working and the image behind it is the one cleaned.
+    if config.in_place
+        && fs::symlink_metadata(path)
+            .map_err(|e| Error::io_with_path(e, path))?
+            .file_type()
+            .is_symlink()
+    {
+        if renamed_in_place {
+            return Ok(Handled::Link(String::from(
+                "is a symbolic link; anonymise the file it points to",
+            )));
+        }
+        output_path = fs::canonicalize(path).map_err(|e| Error::io_with_path(e, path))?;
+    }
 
-    // Check if output exists.
-    if output_path.exists() && !config.force && !config.in_place {
-        return ProcessResult::Failed {
-            path: path.to_path_buf(),
-            error: format!(
-                "Output file '{}' already exists (use --force to overwrite)",
-                output_path.display()
-            ),
+    // Other names for a file changed in place would keep the metadata
+    // unless the shared file itself is rewritten.
+    let links = if config.in_place {
+        fsmeta::link_count(path)?
+    } else {
+        1
+    };
+    let through_inode = links > 1
+        && match config.hard_links {
+            HardLinks::Rewrite => true,
+            HardLinks::Break => false,
+            HardLinks::Skip => {
+                return Ok(Handled::Link(format!(
+                    "has {} hard links (--hard-links skip)",
+                    links
+                )));
+            }
         };
+
+    // Check if output exists. Numbered names are only settled when written,
+    // so parallel workers cannot pick the same one.
+    let collision = config.collision();
+    if !replaces && output_path.exists() {
+        match collision {
+            Collision::Fail => return Err(Error::OutputExists { path: output_path }),
+            Collision::Skip => return Ok(Handled::OutputExists(output_path)),
+            Collision::Overwrite => {}
+            Collision::Number if config.dry_run => {
+                let free = naming::candidates(&output_path).find(|c| !c.exists());
+                output_path = free.unwrap_or(output_path);
+            }
+            Collision::Number => {}
+        }
     }
 
-    // Write output (or simulate for dry run).
-    if config.dry_run {
-        ProcessResult::Success {
-            input: path.to_path_buf(),
+    // An image that needs no changes is not rewritten, so its modification
+    // time stays and backups and rsync see nothing new. The file's own
+    // metadata still follows --fs-metadata. A hard link being broken off
+    // needs a file of its own, so it is written as usual.
+    if strip_result.unchanged && replaces && (links == 1 || through_inode) {
+        let mut cached = None;
+        let mut warning = None;
+        if !config.dry_run {
+            let step = Instant::now();
+            let plan = fsmeta::Plan::new(config.fs_metadata, &output_path, true)?;
+            let file =
+                fs::File::open(&output_path).map_err(|e| Error::io_with_path(e, &output_path))?;
+            warning = plan
+                .apply(&file)
+                .map_err(|e| Error::io_with_path(e, &output_path))?;
+            timings.write = step.elapsed();
+            if config.incremental {
+                cached = Some(cache::Entry::new(&output_path, &data, &output_path)?);
+            }
+        }
+        return Ok(Handled::Cleaned(Box::new(Cleaned {
             output: output_path,
-            bytes_removed: strip_result.bytes_removed,
+            format,
+            bytes_removed: 0,
+            removed,
+            risk,
+            retained: strip_result.retained.names(),
+            proof,
+            original: None,
+            cached,
+            already_clean: true,
+            warning,
+        })));
+    }
+
+    // Refuse before writing anything if the original cannot go.
+    if config.remove_originals {
+        shred::check_removable(path, &output_path)?;
+    }
+
+    // Write output (or simulate for dry run).
+    let mut original = None;
+    let mut cached = None;
+    let mut warning = None;
+    if !config.dry_run {
+        let step = Instant::now();
+        // Mirrored outputs may go in directories that do not exist yet.
+        if let Some(parent) = output_path.parent().filter(|_| config.output_dir.is_some()) {
+            fs::create_dir_all(parent).map_err(|e| Error::io_with_path(e, parent))?;
         }
-    } else {
-        match write_output_standalone(&output_path, &strip_result.data, config.in_place) {
-            Ok(()) => ProcessResult::Success {
-                input: path.to_path_buf(),
-                output: output_path,
-                bytes_removed: strip_result.bytes_removed,
-            },
-            Err(e) => ProcessResult::Failed {
-                path: path.to_path_buf(),
-                error: e,
-            },
+        // Keep the original before anything replaces it.
+        let saved = match backup {
+            Some(backup) => Some(backup.save(path, index, &data)?),
+            None => None,
+        };
+        // Files changed in place keep their owner and mode.
+        let plan = fsmeta::Plan::new(config.fs_metadata, path, config.in_place)?;
+        if through_inode {
+            // Every name for the file sees the cleaned data.
+            warning = rewrite_shared_file(path, &strip_result.data, &plan)?;
+        }
+        if replaces {
+            if !through_inode {
+                warning = write_output_standalone(&output_path, &strip_result.data, &plan)?;
+            }
+        } else if collision == Collision::Overwrite {
+            warning = write_output_standalone(&output_path, &strip_result.data, &plan)?;
+        } else {
+            match create_output(&output_path, &strip_result.data, collision, &plan)? {
+                Some((written, kept)) => {
+                    output_path = written;
+                    warning = kept;
+                }
+                None => return Ok(Handled::OutputExists(output_path)),
+            }
+        }
+        if renamed_in_place {
+            fs::remove_file(path).map_err(|e| Error::io_with_path(e, path))?;
+        }
+        if let (Some(backup), Some(saved)) = (backup, saved) {
+            backup.record(saved, &output_path, &strip_result.data)?;
+        }
+        // The original goes only once the copy on disk is the verified data.
+        if config.remove_originals {
+            let written =
+                fs::read(&output_path).map_err(|e| Error::io_with_path(e, &output_path))?;
+            if written != strip_result.data {
+                return Err(Error::verification_failed(
+                    &output_path,
+                    "written output differs from the verified data; original kept",
+                ));
+            }
+            original = Some(shred::remove(path, config.shred)?);
+        }
+        timings.write = step.elapsed();
+
+        // Remember the file for the next incremental run: the cleaned file
+        // when it replaced the input, otherwise the input beside its output.
+        if config.incremental {
+            cached = match (config.in_place, original) {
+                (true, _) => Some(cache::Entry::new(
+                    &output_path,
+                    &strip_result.data,
+                    &output_path,
+                )?),
+                (false, None) => Some(cache::Entry::new(path, &data, &output_path)?),
+                (false, Some(_)) => None,
+            };
         }
     }
+
+    Ok(Handled::Cleaned(Box::new(Cleaned {
+        output: output_path,
+        format,
+        bytes_removed: strip_result.bytes_removed,
+        removed,
+        risk,
+        retained: strip_result.retained.names(),
+        proof,
+        original,
+        cached,
+        already_clean: strip_result.unchanged,
+        warning,
+    })))
 }
 
 /// Write output to a file atomically (standalone function for parallel execution).
+///
+/// The planned filesystem metadata is set on the temporary file, so a
+/// replacement never appears with the temporary file's times, owner or mode.
+/// Returns the plan's warning, if any.
 fn write_output_standalone(
     path: &Path,
     data: &[u8],
-    in_place: bool,
-) -> std::result::Result<(), String> {
-    if in_place {
-        let temp_path = path.with_extension("pmi_tmp");
-
-        let mut file = fs::File::create(&temp_path)
-            .map_err(|e| format!("Failed to create temp file: {}", e))?;
-        file.write_all(data)
-            .map_err(|e| format!("Failed to write temp file: {}", e))?;
-        file.flush()
-            .map_err(|e| format!("Failed to flush temp file: {}", e))?;
-
-        fs::rename(&temp_path, path).map_err(|e| format!("Failed to rename temp file: {}", e))?;
-    } else {
-        let mut file =
-            fs::File::create(path).map_err(|e| format!("Failed to create output file: {}", e))?;
-        file.write_all(data)
-            .map_err(|e| format!("Failed to write output file: {}", e))?;
-    }
+    plan: &fsmeta::Plan,
+) -> Result<Option<String>> {
+    atomic::replace(path, data, |file| plan.apply(file))
+}
 
-    Ok(())
+/// Write output into an existing file, so every hard link to it sees the
+/// cleaned data.
+///
+/// Unlike a replacement this is not atomic: a crash part way through leaves
+/// a damaged file.
+fn rewrite_shared_file(path: &Path, data: &[u8], plan: &fsmeta::Plan) -> Result<Option<String>> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map_err(|e| Error::io_with_path(e, path))?;
+    file.write_all(data)
+        .and_then(|()| file.set_len(data.len() as u64))
+        .and_then(|()| plan.apply(&file))
+        .and_then(|warning| file.sync_all().map(|()| warning))
+        .map_err(|e| Error::io_with_path(e, path))
+}
+
+/// Write a new output file without replacing one that appeared since it
+/// was checked for.
+///
+/// Returns the path written, which [`Collision::Number`] may have changed,
+/// with the plan's warning, or `None` if the policy is to skip.
+fn create_output(
+    path: &Path,
+    data: &[u8],
+    collision: Collision,
+    plan: &fsmeta::Plan,
+) -> Result<Option<(PathBuf, Option<String>)>> {
+    let candidates: Box<dyn Iterator<Item = PathBuf>> = match collision {
+        Collision::Number => Box::new(naming::candidates(path)),
+        _ => Box::new(std::iter::once(path.to_path_buf())),
+    };
+
+    let mut temp = TempFile::new(path)?;
+    temp.write_all(data)?;
+    let warning = plan
+        .apply(temp.file())
+        .map_err(|e| Error::io_with_path(e, temp.path()))?;
+
+    for candidate in candidates {
+        if temp.persist_new(&candidate)? {
+            return Ok(Some((candidate, warning)));
+        }
+        match collision {
+            Collision::Skip => return Ok(None),
+            Collision::Number => continue,
+            _ => return Err(Error::OutputExists { path: candidate }),
+        }
+    }
+    Err(Error::OutputExists {
+        path: path.to_path_buf(),
+    })
 }
 
 #[cfg(test)]
@@ -425,15 +1042,28 @@ mod tests {
             help: false,
             version: false,
             jobs: Some(1), // Sequential for tests.
+            ..Default::default()
         }
     }
 
+    fn output_path(config: &Config, input: &Path) -> PathBuf {
+        let fields = NameFields {
+            input,
+            format: ImageFormat::Jpeg,
+            data: b"",
+            index: 1,
+            date: naming::today(),
+            anonymous_stem: None,
+        };
+        Processor::get_output_path(config, &fields)
+    }
+
     #[test]
     fn test_get_output_path_default() {
         let config = create_test_config(vec![]);
 
         let input = PathBuf::from("/path/to/image.jpg");
-        let output = Processor::get_output_path(&config, &input);
+        let output = output_path(&config, &input);
         assert_eq!(output, PathBuf::from("/path/to/image_clean.jpg"));
     }
 
@@ -443,7 +1073,7 @@ mod tests {
         config.in_place = true;
 
         let input = PathBuf::from("/path/to/image.jpg");
-        let output = Processor::get_output_path(&config, &input);
+        let output = output_path(&config, &input);
         assert_eq!(output, input);
     }
 
@@ -453,10 +1083,48 @@ mod tests {
         config.output_dir = Some(PathBuf::from("/output"));
 
         let input = PathBuf::from("/path/to/image.jpg");
-        let output = Processor::get_output_path(&config, &input);
+        let output = output_path(&config, &input);
         assert_eq!(output, PathBuf::from("/output/image.jpg"));
     }
 
+    #[test]
+    fn test_get_output_path_template() {
+        let mut config = create_test_config(vec![]);
+        config.output_dir = Some(PathBuf::from("/output"));
+        config.name_template = Some(naming::NameTemplate::parse("{index}-{format}.{ext}").unwrap());
+
+        let output = output_path(&config, Path::new("/path/to/image.JPG"));
+        assert_eq!(output, PathBuf::from("/output/1-jpeg.JPG"));
+    }
+
+    #[test]
+    fn test_get_output_path_mirrors_tree() {
+        let temp_dir = std::env::temp_dir().join("pmi_processor_mirror");
+        let (a, b) = (temp_dir.join("a"), temp_dir.join("b"));
+        fs::create_dir_all(a.join("2024")).unwrap();
+        fs::create_dir_all(&b).unwrap();
+
+        let mut config = create_test_config(vec![a.clone()]);
+        config.output_dir = Some(PathBuf::from("/output"));
+        config.recursive = true;
+        let output = output_path(&config, &a.join("2024").join("x.jpg"));
+        assert_eq!(output, PathBuf::from("/output/2024/x.jpg"));
+
+        // Several roots keep their names; files given directly do not.
+        config.paths = vec![a.clone(), b.clone(), PathBuf::from("/photos/y.jpg")];
+        let output = output_path(&config, &b.join("x.jpg"));
+        assert_eq!(output, PathBuf::from("/output/b/x.jpg"));
+        let output = output_path(&config, Path::new("/photos/y.jpg"));
+        assert_eq!(output, PathBuf::from("/output/y.jpg"));
+
+        // Without recursion, outputs stay flat.
+        config.recursive = false;
+        let output = output_path(&config, &b.join("x.jpg"));
+        assert_eq!(output, PathBuf::from("/output/x.jpg"));
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
     #[test]
     fn test_is_supported_file() {
         let config = create_test_config(vec![]);
@@ -0,0 +1,205 @@
+//! JUnit XML reports.
+//!
+//! Each file is a test case. Files still carrying metadata after a dry run
+//! are failures, files that could not be processed are errors, and cleaned
+//! files pass with the removed blocks listed in `system-out`.
+
+use super::{FileRecord, Outcome};
+use crate::cli::NAME;
+use crate::metadata::Category;
+use crate::terminal::{ProcessingStats, format_size};
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Write a JUnit XML document for a run.
+pub fn write(
+    out: &mut impl Write,
+    records: &[FileRecord],
+    stats: &ProcessingStats,
+) -> io::Result<()> {
+    let count = |f: fn(&FileRecord) -> bool| records.iter().filter(|r| f(r)).count();
+    let counts = format!(
+        "tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{}\"",
+        records.len(),
+        count(FileRecord::is_leaking),
+        count(|r| r.outcome == Outcome::Failed),
+        count(|r| r.outcome == Outcome::Skipped),
+        seconds(stats.duration),
+    );
+
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<testsuites name=\"{}\" {}>", NAME, counts)?;
+    writeln!(out, "  <testsuite name=\"{}\" {}>", NAME, counts)?;
+    for record in records {
+        write_case(out, record)?;
+    }
+    writeln!(out, "  </testsuite>")?;
+    writeln!(out, "</testsuites>")
+}
+
+/// Write the test case for a single file.
+fn write_case(out: &mut impl Write, record: &FileRecord) -> io::Result<()> {
+    let classname = match record.format {
+        Some(format) => format!("{}.{}", NAME, format.name().to_ascii_lowercase()),
+        None => String::from(NAME),
+    };
+    write!(
+        out,
+        "    <testcase classname=\"{}\" name=\"{}\" time=\"{}\"",
+        escape(&classname),
+        escape(&record.input.display().to_string()),
+        seconds(record.timings.total)
+    )?;
+
+    let error = record.error.as_deref().unwrap_or_default();
+    match record.outcome {
+        Outcome::Failed => {
+            writeln!(out, ">")?;
+            writeln!(
+                out,
+                "      <error type=\"{}\" message=\"{}\"/>",
+                escape(record.error_kind.unwrap_or_default()),
+                escape(error)
+            )?;
+        }
+        Outcome::Skipped => {
+            writeln!(out, ">")?;
+            writeln!(out, "      <skipped message=\"{}\"/>", escape(error))?;
+        }
+        _ if record.is_leaking() => {
+            writeln!(out, ">")?;
+            let categories: Vec<&str> = record.categories().iter().map(Category::name).collect();
+            writeln!(
+                out,
+                "      <failure type=\"metadata\" message=\"{} metadata\">{}</failure>",
+                escape(&categories.join(", ")),
+                escape(&item_lines(record))
+            )?;
+        }
+        _ if !record.removed.is_empty() => {
+            writeln!(out, ">")?;
+            writeln!(
+                out,
+                "      <system-out>{}</system-out>",
+                escape(&format!("Removed:\n{}", item_lines(record)))
+            )?;
+        }
+        _ => return writeln!(out, "/>"),
+    }
+
+    writeln!(out, "    </testcase>")
+}
+
+/// List the removed items, one per line, with their decoded fields.
+fn item_lines(record: &FileRecord) -> String {
+    record
+        .removed
+        .iter()
+        .map(|item| {
+            let fields: Vec<&str> = item.fields.iter().map(|f| f.name.as_str()).collect();
+            let size = format_size(item.size() as u64);
+            if fields.is_empty() {
+                format!("{} ({})\n", item.kind, size)
+            } else {
+                format!("{} ({}): {}\n", item.kind, size, fields.join(", "))
+            }
+        })
+        .collect()
+}
+
+/// Format a duration in seconds, as JUnit expects.
+fn seconds(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64())
+}
+
+/// Escape text for XML content and attribute values.
+///
+/// Characters that XML 1.0 cannot represent are replaced.
+fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\n' => out.push_str("&#10;"),
+            '\t' | '\r' => out.push_str(&format!("&#{};", c as u32)),
+            c if (c as u32) < 0x20 => out.push('\u{FFFD}'),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::ImageFormat;
+    use crate::metadata::{Field, MetadataItem};
+    use crate::processor::Timings;
+    use std::path::PathBuf;
+
+    fn record(name: &str, outcome: Outcome) -> FileRecord {
+        FileRecord {
+            input: PathBuf::from(name),
+            output: None,
+            original: None,
+            format: Some(ImageFormat::Png),
+            outcome,
+            bytes_removed: 0,
+            removed: Vec::new(),
+            timings: Timings::default(),
+            error_kind: None,
+            error: None,
+            warning: None,
+        }
+    }
+
+    fn render(records: &[FileRecord]) -> String {
+        let mut out = Vec::new();
+        write(&mut out, records, &ProcessingStats::new()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_one_case_per_file() {
+        let mut leaking = record("a&b.png", Outcome::WouldClean);
+        leaking.removed = vec![
+            MetadataItem::new("tEXt", 33..60, Category::Other)
+                .with_fields(vec![Field::new("Author", Category::Authorship)]),
+        ];
+        let mut failed = record("bad.png", Outcome::Failed);
+        failed.format = None;
+        failed.error_kind = Some("invalid-image");
+        failed.error = Some(String::from("Invalid image 'bad.png': <truncated>"));
+        let clean = record("clean.png", Outcome::WouldClean);
+
+        let xml = render(&[leaking, failed, clean]);
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"pmi\" tests=\"3\" failures=\"1\" errors=\"1\" skipped=\"0\""));
+        assert_eq!(xml.matches("<testcase ").count(), 3);
+        assert!(xml.contains(
+            "<testcase classname=\"pmi.png\" name=\"a&amp;b.png\" time=\"0.000\">\n      <failure type=\"metadata\" message=\"authorship metadata\">tEXt (27 B): Author&#10;</failure>"
+        ));
+        assert!(xml.contains(
+            "<testcase classname=\"pmi\" name=\"bad.png\" time=\"0.000\">\n      <error type=\"invalid-image\" message=\"Invalid image &apos;bad.png&apos;: &lt;truncated&gt;\"/>"
+        ));
+        assert!(xml.contains("name=\"clean.png\" time=\"0.000\"/>\n"));
+        assert!(xml.ends_with("  </testsuite>\n</testsuites>\n"));
+    }
+
+    #[test]
+    fn test_cleaned_file_passes() {
+        let mut cleaned = record("a.png", Outcome::Cleaned);
+        cleaned.removed = vec![MetadataItem::new("tIME", 33..52, Category::Time)];
+        let xml = render(&[cleaned]);
+        assert!(xml.contains("failures=\"0\""));
+        assert!(xml.contains("<system-out>Removed:&#10;tIME (19 B)&#10;</system-out>"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a<b>\"c\"\u{1}"), "a&lt;b&gt;&quot;c&quot;\u{FFFD}");
+    }
+}
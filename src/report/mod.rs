@@ -0,0 +1,737 @@
+//! Machine-readable processing reports.
+//!
+//! With `--report-format`, every processed file becomes a record holding its
+//! paths, format, outcome, bytes removed, the metadata items removed, step
+//! timings and the error kind. The run's `ProcessingStats` follow as a final
+//! summary record. Reports are written as JSON, NDJSON, CSV, SARIF or JUnit
+//! XML, to stdout or to `--report-file`, without any external serialisation
+//! crate.
+
+pub mod junit;
+pub mod sarif;
+
+use crate::cli::Config;
+use crate::error::{Error, Result};
+use crate::formats::ImageFormat;
+use crate::metadata::{self, Category, MetadataItem};
+use crate::processor::{ProcessResult, Timings};
+use crate::shred::Removal;
+use crate::terminal::ProcessingStats;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Report output format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// A single JSON document with a `files` array and a `summary` object.
+    Json,
+    /// One JSON object per line; the summary is the last line.
+    Ndjson,
+    /// One row per file; the summary is the last row.
+    Csv,
+    /// SARIF 2.1.0 log with one rule per metadata category.
+    Sarif,
+    /// JUnit XML with one test case per file.
+    Junit,
+}
+
+impl ReportFormat {
+    /// Parse a format name as given to `--report-format`.
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "json" => Ok(ReportFormat::Json),
+            "ndjson" | "jsonl" => Ok(ReportFormat::Ndjson),
+            "csv" => Ok(ReportFormat::Csv),
+            "sarif" => Ok(ReportFormat::Sarif),
+            "junit" => Ok(ReportFormat::Junit),
+            _ => Err(Error::InvalidArgument {
+                argument: String::from("--report-format"),
+                reason: format!("'{}' is not one of json, ndjson, csv, sarif, junit", value),
+            }),
+        }
+    }
+
+    /// Infer the format from a report file's extension.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?;
+        if ext.eq_ignore_ascii_case("xml") {
+            return Some(ReportFormat::Junit);
+        }
+        Self::parse(ext).ok()
+    }
+
+    /// Get the format name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReportFormat::Json => "json",
+            ReportFormat::Ndjson => "ndjson",
+            ReportFormat::Csv => "csv",
+            ReportFormat::Sarif => "sarif",
+            ReportFormat::Junit => "junit",
+        }
+    }
+}
+
+/// Outcome of processing a single file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Metadata was stripped and the output written.
+    Cleaned,
+    /// Dry run: the file would have been cleaned.
+    WouldClean,
+    /// The file needed no changes and was left as it was, or copied
+    /// unchanged.
+    AlreadyClean,
+    /// The file was skipped.
+    Skipped,
+    /// Processing failed.
+    Failed,
+}
+
+impl Outcome {
+    /// Get the name used in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Cleaned => "cleaned",
+            Outcome::WouldClean => "would-clean",
+            Outcome::AlreadyClean => "already-clean",
+            Outcome::Skipped => "skipped",
+            Outcome::Failed => "failed",
+        }
+    }
+}
+
+/// Report record for a single file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileRecord {
+    /// Input path.
+    pub input: PathBuf,
+    /// Output path, when the file was (or would have been) written.
+    pub output: Option<PathBuf>,
+    /// How the original was removed, with `--remove-originals`.
+    pub original: Option<Removal>,
+    /// Detected format, if the file could be read and recognised.
+    pub format: Option<ImageFormat>,
+    /// What happened to the file.
+    pub outcome: Outcome,
+    /// Bytes of metadata removed.
+    pub bytes_removed: u64,
+    /// Metadata items removed.
+    pub removed: Vec<MetadataItem>,
+    /// Time spent in each processing step.
+    pub timings: Timings,
+    /// Machine-readable error kind, for failures.
+    pub error_kind: Option<&'static str>,
+    /// Error message for failures, or the reason a file was skipped.
+    pub error: Option<String>,
+    /// Something a cleaned file could not keep, such as its owner.
+    pub warning: Option<String>,
+}
+
+impl FileRecord {
+    /// Build the record for a processing result.
+    pub fn from_result(result: &ProcessResult, dry_run: bool) -> Self {
+        match result {
+            ProcessResult::Success {
+                input,
+                output,
+                format,
+                bytes_removed,
+                removed,
+                original,
+                already_clean,
+                warning,
+                timings,
+                ..
+            } => FileRecord {
+                input: input.clone(),
+                output: Some(output.clone()),
+                original: *original,
+                format: Some(*format),
+                outcome: if *already_clean {
+                    Outcome::AlreadyClean
+                } else if dry_run {
+                    Outcome::WouldClean
+                } else {
+                    Outcome::Cleaned
+                },
+                bytes_removed: *bytes_removed,
+                removed: removed.clone(),
+                timings: *timings,
+                error_kind: None,
+                error: None,
+                warning: warning.clone(),
+            },
+            ProcessResult::Skipped { path, reason } => FileRecord {
+                input: path.clone(),
+                output: None,
+                original: None,
+                format: None,
+                outcome: Outcome::Skipped,
+                bytes_removed: 0,
+                removed: Vec::new(),
+                timings: Timings::default(),
+                error_kind: None,
+                error: Some(reason.clone()),
+                warning: None,
+            },
+            ProcessResult::Failed {
+                path,
+                error,
+                kind,
+                format,
+                timings,
+            } => FileRecord {
+                input: path.clone(),
+                output: None,
+                original: None,
+                format: *format,
+                outcome: Outcome::Failed,
+                bytes_removed: 0,
+                removed: Vec::new(),
+                timings: *timings,
+                error_kind: Some(kind),
+                error: Some(error.clone()),
+                warning: None,
+            },
+        }
+    }
+
+    /// Get the distinct categories removed, in display order.
+    pub fn categories(&self) -> Vec<Category> {
+        metadata::categories(&self.removed)
+    }
+
+    /// Check whether the file still contains the metadata in `removed`.
+    ///
+    /// Only dry runs leave metadata behind; cleaned files no longer leak.
+    pub fn is_leaking(&self) -> bool {
+        self.outcome == Outcome::WouldClean && !self.removed.is_empty()
+    }
+}
+
+/// Write a report to `out`.
+pub fn write_report(
+    out: &mut impl Write,
+    format: ReportFormat,
+    records: &[FileRecord],
+    stats: &ProcessingStats,
+    dry_run: bool,
+) -> io::Result<()> {
+    match format {
+        ReportFormat::Json => {
+            writeln!(out, "{{")?;
+            writeln!(out, "  \"files\": [")?;
+            for (i, record) in records.iter().enumerate() {
+                let comma = if i + 1 < records.len() { "," } else { "" };
+                writeln!(out, "    {}{}", object(&file_fields(record)), comma)?;
+            }
+            writeln!(out, "  ],")?;
+            writeln!(
+                out,
+                "  \"summary\": {}",
+                object(&summary_fields(stats, dry_run))
+            )?;
+            writeln!(out, "}}")?;
+        }
+        ReportFormat::Ndjson => {
+            let tag = |kind: &str| (String::from("type"), json_string(kind));
+            for record in records {
+                let mut fields = vec![tag("file")];
+                fields.extend(file_fields(record));
+                writeln!(out, "{}", object(&fields))?;
+            }
+            let mut fields = vec![tag("summary")];
+            fields.extend(summary_fields(stats, dry_run));
+            writeln!(out, "{}", object(&fields))?;
+        }
+        ReportFormat::Csv => {
+            writeln!(out, "{}", CSV_COLUMNS.join(","))?;
+            for record in records {
+                writeln!(out, "{}", csv_row(&file_row(record)))?;
+            }
+            writeln!(out, "{}", csv_row(&summary_row(stats, dry_run)))?;
+        }
+        ReportFormat::Sarif => sarif::write(out, records, stats, dry_run)?,
+        ReportFormat::Junit => junit::write(out, records, stats)?,
+    }
+    Ok(())
+}
+
+/// Write the report requested by the configuration, if any.
+///
+/// Goes to `--report-file` when given and to stdout otherwise.
+pub fn output_report(
+    config: &Config,
+    records: &[FileRecord],
+    stats: &ProcessingStats,
+) -> Result<()> {
+    let Some(format) = config.report_format else {
+        return Ok(());
+    };
+
+    match &config.report_file {
+        Some(path) => {
+            let file = fs::File::create(path).map_err(|e| Error::io_with_path(e, path))?;
+            let mut out = BufWriter::new(file);
+            write_report(&mut out, format, records, stats, config.dry_run)
+                .and_then(|()| out.flush())
+                .map_err(|e| Error::io_with_path(e, path))
+        }
+        None => {
+            let mut out = io::stdout().lock();
+            write_report(&mut out, format, records, stats, config.dry_run)?;
+            Ok(out.flush()?)
+        }
+    }
+}
+
+/// Get the key/value pairs of a file record, with values already encoded.
+fn file_fields(record: &FileRecord) -> Vec<(String, String)> {
+    let removed: Vec<String> = record
+        .removed
+        .iter()
+        .map(|item| {
+            let fields: Vec<String> = item
+                .fields
+                .iter()
+                .map(|f| {
+                    object(&[
+                        (String::from("name"), json_string(&f.name)),
+                        (String::from("category"), json_string(f.category.name())),
+                    ])
+                })
+                .collect();
+            object(&[
+                (String::from("kind"), json_string(&item.kind)),
+                (String::from("offset"), item.range.start.to_string()),
+                (String::from("size"), item.size().to_string()),
+                (
+                    String::from("categories"),
+                    category_array(&item.categories()),
+                ),
+                (String::from("fields"), array(&fields)),
+            ])
+        })
+        .collect();
+
+    let t = &record.timings;
+    let timings = object(&[
+        (String::from("read"), millis(t.read)),
+        (String::from("strip"), millis(t.strip)),
+        (String::from("verify"), millis(t.verify)),
+        (String::from("prove"), millis(t.prove)),
+        (String::from("write"), millis(t.write)),
+        (String::from("total"), millis(t.total)),
+    ]);
+
+    let optional = |value: Option<&str>| value.map_or_else(|| String::from("null"), json_string);
+
+    vec![
+        (String::from("input"), json_path(&record.input)),
+        (
+            String::from("output"),
+            record
+                .output
+                .as_deref()
+                .map_or_else(|| String::from("null"), json_path),
+        ),
+        (
+            String::from("original"),
+            optional(record.original.as_ref().map(Removal::name)),
+        ),
+        (
+            String::from("format"),
+            optional(record.format.as_ref().map(ImageFormat::name)),
+        ),
+        (String::from("result"), json_string(record.outcome.name())),
+        (
+            String::from("bytes_removed"),
+            record.bytes_removed.to_string(),
+        ),
+        (
+            String::from("categories"),
+            category_array(&record.categories()),
+        ),
+        (String::from("removed"), array(&removed)),
+        (String::from("timings_ms"), timings),
+        (String::from("error_kind"), optional(record.error_kind)),
+        (String::from("error"), optional(record.error.as_deref())),
+        (String::from("warning"), optional(record.warning.as_deref())),
+    ]
+}
+
+/// Get the key/value pairs of the summary record.
+fn summary_fields(stats: &ProcessingStats, dry_run: bool) -> Vec<(String, String)> {
+    vec![
+        (String::from("processed"), stats.processed.to_string()),
+        (String::from("failed"), stats.failed.to_string()),
+        (String::from("skipped"), stats.skipped.to_string()),
+        (
+            String::from("already_clean"),
+            stats.already_clean.to_string(),
+        ),
+        (String::from("total"), stats.total().to_string()),
+        (
+            String::from("metadata_removed"),
+            stats.metadata_removed.to_string(),
+        ),
+        (String::from("duration_ms"), millis(stats.duration)),
+        (String::from("dry_run"), dry_run.to_string()),
+    ]
+}
+
+/// CSV header. File rows leave the summary columns empty and vice versa.
+const CSV_COLUMNS: [&str; 22] = [
+    "record",
+    "input",
+    "output",
+    "original",
+    "format",
+    "result",
+    "bytes_removed",
+    "categories",
+    "removed",
+    "read_ms",
+    "strip_ms",
+    "verify_ms",
+    "prove_ms",
+    "write_ms",
+    "total_ms",
+    "error_kind",
+    "error",
+    "warning",
+    "processed",
+    "failed",
+    "skipped",
+    "already_clean",
+];
+
+/// Get the CSV cells of a file record.
+///
+/// Removed items are listed as `kind=category+category`, separated by `;`.
+fn file_row(record: &FileRecord) -> Vec<String> {
+    let categories = |categories: &[Category]| {
+        categories
+            .iter()
+            .map(Category::name)
+            .collect::<Vec<_>>()
+            .join("+")
+    };
+    let removed: Vec<String> = record
+        .removed
+        .iter()
+        .map(|item| format!("{}={}", item.kind, categories(&item.categories())))
+        .collect();
+    let t = &record.timings;
+
+    vec![
+        String::from("file"),
+        record.input.display().to_string(),
+        record
+            .output
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default(),
+        record
+            .original
+            .map(|r| r.name().to_string())
+            .unwrap_or_default(),
+        record
+            .format
+            .map(|f| f.name().to_string())
+            .unwrap_or_default(),
+        record.outcome.name().to_string(),
+        record.bytes_removed.to_string(),
+        record
+            .categories()
+            .iter()
+            .map(Category::name)
+            .collect::<Vec<_>>()
+            .join(";"),
+        removed.join(";"),
+        millis(t.read),
+        millis(t.strip),
+        millis(t.verify),
+        millis(t.prove),
+        millis(t.write),
+        millis(t.total),
+        record.error_kind.unwrap_or_default().to_string(),
+        record.error.clone().unwrap_or_default(),
+        record.warning.clone().unwrap_or_default(),
+        String::new(),
+        String::new(),
+        String::new(),
+        String::new(),
+    ]
+}
+
+/// Get the CSV cells of the summary record.
+fn summary_row(stats: &ProcessingStats, dry_run: bool) -> Vec<String> {
+    let mut row = vec![String::new(); CSV_COLUMNS.len()];
+    row[0] = String::from("summary");
+    row[5] = String::from(if dry_run { "dry-run" } else { "complete" });
+    row[6] = stats.metadata_removed.to_string();
+    row[14] = millis(stats.duration);
+    row[18] = stats.processed.to_string();
+    row[19] = stats.failed.to_string();
+    row[20] = stats.skipped.to_string();
+    row[21] = stats.already_clean.to_string();
+    row
+}
+
+/// Join CSV cells, quoting those that need it (RFC 4180).
+pub(crate) fn csv_row(cells: &[String]) -> String {
+    cells
+        .iter()
+        .map(|cell| {
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Split CSV text into rows of cells, undoing [`csv_row`]'s quoting.
+pub(crate) fn csv_rows(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => cell.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => row.push(mem::take(&mut cell)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                row.push(mem::take(&mut cell));
+                rows.push(mem::take(&mut row));
+            }
+            (false, c) => cell.push(c),
+        }
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Format a duration as milliseconds with microsecond precision.
+fn millis(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+/// Encode a JSON object from already-encoded values.
+fn object(fields: &[(String, String)]) -> String {
+    let body: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("{}:{}", json_string(key), value))
+        .collect();
+    format!("{{{}}}", body.join(","))
+}
+
+/// Encode a JSON array from already-encoded values.
+fn array(values: &[String]) -> String {
+    format!("[{}]", values.join(","))
+}
+
+/// Encode a list of categories as a JSON array of names.
+fn category_array(categories: &[Category]) -> String {
+    let names: Vec<String> = categories.iter().map(|c| json_string(c.name())).collect();
+    array(&names)
+}
+
+/// Encode a path as a JSON string, replacing invalid UTF-8.
+fn json_path(path: &Path) -> String {
+    json_string(&path.to_string_lossy())
+}
+
+/// Encode a JSON string literal.
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_records() -> Vec<FileRecord> {
+        let item = MetadataItem::new("APP1 EXIF", 2..102, Category::Other).with_fields(vec![
+            metadata::Field::new("GPSLatitude", Category::Location),
+            metadata::Field::new("Make", Category::Device),
+        ]);
+        vec![
+            FileRecord {
+                input: PathBuf::from("a.jpg"),
+                output: Some(PathBuf::from("a_clean.jpg")),
+                original: Some(Removal::Shredded),
+                format: Some(ImageFormat::Jpeg),
+                outcome: Outcome::Cleaned,
+                bytes_removed: 100,
+                removed: vec![item],
+                timings: Timings {
+                    total: Duration::from_micros(1500),
+                    ..Default::default()
+                },
+                error_kind: None,
+                error: None,
+                warning: Some(String::from("cannot keep owner 0:0")),
+            },
+            FileRecord {
+                input: PathBuf::from("b,\"c\".png"),
+                output: None,
+                original: None,
+                format: None,
+                outcome: Outcome::Failed,
+                bytes_removed: 0,
+                removed: Vec::new(),
+                timings: Timings::default(),
+                error_kind: Some("invalid-image"),
+                error: Some(String::from("bad\ndata")),
+                warning: None,
+            },
+        ]
+    }
+
+    fn sample_stats() -> ProcessingStats {
+        let mut stats = ProcessingStats::new();
+        stats.add_success(100);
+        stats.add_failure();
+        stats
+    }
+
+    fn render(format: ReportFormat) -> String {
+        let mut out = Vec::new();
+        write_report(&mut out, format, &sample_records(), &sample_stats(), false).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(ReportFormat::parse("JSON").unwrap(), ReportFormat::Json);
+        assert_eq!(ReportFormat::parse("ndjson").unwrap(), ReportFormat::Ndjson);
+        assert_eq!(ReportFormat::parse("csv").unwrap(), ReportFormat::Csv);
+        assert_eq!(ReportFormat::parse("sarif").unwrap(), ReportFormat::Sarif);
+        assert!(ReportFormat::parse("yaml").is_err());
+        assert_eq!(
+            ReportFormat::from_extension(Path::new("results.xml")),
+            Some(ReportFormat::Junit)
+        );
+        assert_eq!(
+            ReportFormat::from_extension(Path::new("out/report.jsonl")),
+            Some(ReportFormat::Ndjson)
+        );
+        assert_eq!(ReportFormat::from_extension(Path::new("report.txt")), None);
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("a\"b\\c\n\u{1}é"), "\"a\\\"b\\\\c\\n\\u0001é\"");
+    }
+
+    #[test]
+    fn test_json_report() {
+        let json = render(ReportFormat::Json);
+        assert!(json.starts_with("{\n  \"files\": [\n"));
+        assert!(json.contains(
+            "{\"input\":\"a.jpg\",\"output\":\"a_clean.jpg\",\"original\":\"shredded\",\"format\":\"JPEG\",\"result\":\"cleaned\",\"bytes_removed\":100,\"categories\":[\"location\",\"device\"]"
+        ));
+        assert!(json.contains("\"kind\":\"APP1 EXIF\",\"offset\":2,\"size\":100"));
+        assert!(json.contains("\"total\":1.500"));
+        assert!(json.contains("\"error\":null,\"warning\":\"cannot keep owner 0:0\""));
+        assert!(json.contains("\"error_kind\":\"invalid-image\",\"error\":\"bad\\ndata\""));
+        assert!(json.contains(
+            "\"summary\": {\"processed\":1,\"failed\":1,\"skipped\":0,\"already_clean\":0,\"total\":2,\"metadata_removed\":100"
+        ));
+        assert!(json.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_ndjson_report() {
+        let ndjson = render(ReportFormat::Ndjson);
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("{\"type\":\"file\",\"input\":\"a.jpg\""));
+        assert!(lines[2].starts_with("{\"type\":\"summary\",\"processed\":1"));
+        assert!(lines[2].ends_with("\"dry_run\":false}"));
+    }
+
+    #[test]
+    fn test_csv_rows_round_trip() {
+        let rows = vec![
+            vec![String::from("a"), String::from("b,\"c\""), String::new()],
+            vec![String::from("two\nlines"), String::from("d")],
+        ];
+        let text: String = rows.iter().map(|row| csv_row(row) + "\n").collect();
+        assert_eq!(csv_rows(&text), rows);
+        assert_eq!(csv_rows("x,y"), [["x", "y"]]);
+    }
+
+    #[test]
+    fn test_csv_report() {
+        let csv = render(ReportFormat::Csv);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next().unwrap(), CSV_COLUMNS.join(","));
+        assert_eq!(
+            lines.next().unwrap(),
+            "file,a.jpg,a_clean.jpg,shredded,JPEG,cleaned,100,location;device,APP1 EXIF=location+device,0.000,0.000,0.000,0.000,0.000,1.500,,,cannot keep owner 0:0,,,,"
+        );
+        // The quoted error spans two lines.
+        assert_eq!(
+            lines.next().unwrap(),
+            "file,\"b,\"\"c\"\".png\",,,,failed,0,,,0.000,0.000,0.000,0.000,0.000,0.000,invalid-image,\"bad"
+        );
+        assert_eq!(lines.next().unwrap(), "data\",,,,,");
+        assert!(
+            lines
+                .next()
+                .unwrap()
+                .starts_with("summary,,,,,complete,100,")
+        );
+        assert!(csv.ends_with(",1,1,0,0\n"));
+    }
+
+    #[test]
+    fn test_record_from_result() {
+        let result = ProcessResult::Failed {
+            path: PathBuf::from("x.gif"),
+            error: String::from("boom"),
+            kind: "io",
+            format: Some(ImageFormat::Gif),
+            timings: Timings::default(),
+        };
+        let record = FileRecord::from_result(&result, false);
+        assert_eq!(record.outcome, Outcome::Failed);
+        assert_eq!(record.error_kind, Some("io"));
+        assert_eq!(record.format, Some(ImageFormat::Gif));
+        assert!(record.output.is_none());
+    }
+}
@@ -0,0 +1,356 @@
+//! SARIF 2.1.0 reports.
+//!
+//! Each metadata category is a rule, and each category found in a file is a
+//! result located at that file, so code-scanning UIs can annotate leaking
+//! images. Results are errors in dry runs, where the metadata is still there,
+//! and notes once it has been removed. Processing failures use a separate
+//! `processing-error` rule.
+
+use super::{FileRecord, array, json_string, object, summary_fields};
+use crate::cli::{NAME, VERSION};
+use crate::metadata::Category;
+use crate::terminal::ProcessingStats;
+use std::io::{self, Write};
+use std::path::{Component, Path};
+
+/// SARIF schema reference.
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Project page, used as the tool's information URI.
+const INFORMATION_URI: &str = env!("CARGO_PKG_REPOSITORY");
+
+/// Rule ID for files that could not be processed.
+const PROCESSING_ERROR: &str = "processing-error";
+
+/// Get the rule ID for a metadata category.
+pub fn rule_id(category: Category) -> String {
+    format!("{}-metadata", category.name())
+}
+
+/// Write a SARIF log for a run.
+pub fn write(
+    out: &mut impl Write,
+    records: &[FileRecord],
+    stats: &ProcessingStats,
+    dry_run: bool,
+) -> io::Result<()> {
+    let mut results = Vec::new();
+    for record in records {
+        results.extend(category_results(record));
+        if let Some(error) = &record.error {
+            if record.error_kind.is_some() {
+                results.push(result(
+                    PROCESSING_ERROR,
+                    Category::ALL.len(),
+                    "error",
+                    error,
+                    location(&record.input, None),
+                ));
+            }
+        }
+    }
+
+    let driver = object(&[
+        (String::from("name"), json_string(NAME)),
+        (String::from("version"), json_string(VERSION)),
+        (String::from("informationUri"), json_string(INFORMATION_URI)),
+        (String::from("rules"), array(&rules())),
+    ]);
+    let invocation = object(&[
+        (
+            String::from("executionSuccessful"),
+            (stats.failed == 0).to_string(),
+        ),
+        (
+            String::from("properties"),
+            object(&summary_fields(stats, dry_run)),
+        ),
+    ]);
+    let run = object(&[
+        (
+            String::from("tool"),
+            object(&[(String::from("driver"), driver)]),
+        ),
+        (String::from("invocations"), array(&[invocation])),
+        (String::from("results"), array(&results)),
+    ]);
+
+    writeln!(
+        out,
+        "{}",
+        object(&[
+            (String::from("$schema"), json_string(SCHEMA)),
+            (String::from("version"), json_string("2.1.0")),
+            (String::from("runs"), array(&[run])),
+        ])
+    )
+}
+
+/// Get the rule descriptors, one per category followed by processing errors.
+fn rules() -> Vec<String> {
+    let rule = |id: &str, name: &str, short: &str, full: &str| {
+        object(&[
+            (String::from("id"), json_string(id)),
+            (String::from("name"), json_string(name)),
+            (
+                String::from("shortDescription"),
+                object(&[(String::from("text"), json_string(short))]),
+            ),
+            (
+                String::from("fullDescription"),
+                object(&[(String::from("text"), json_string(full))]),
+            ),
+        ])
+    };
+
+    let mut rules: Vec<String> = Category::ALL
+        .iter()
+        .map(|category| {
+            let name = category.name();
+            let pascal = format!("{}{}Metadata", name[..1].to_ascii_uppercase(), &name[1..]);
+            rule(
+                &rule_id(*category),
+                &pascal,
+                &format!("Image contains {} metadata", name),
+                category.description(),
+            )
+        })
+        .collect();
+    rules.push(rule(
+        PROCESSING_ERROR,
+        "ProcessingError",
+        "Image could not be processed",
+        "The file could not be read, parsed, verified or written.",
+    ));
+    rules
+}
+
+/// Get one result per category removed from a file.
+fn category_results(record: &FileRecord) -> Vec<String> {
+    let level = if record.is_leaking() { "error" } else { "note" };
+
+    record
+        .categories()
+        .into_iter()
+        .map(|category| {
+            let items: Vec<_> = record
+                .removed
+                .iter()
+                .filter(|item| item.categories().contains(&category))
+                .collect();
+
+            // Name the fields of this category, or the blocks when undecoded.
+            let mut names: Vec<&str> = Vec::new();
+            for item in &items {
+                let fields: Vec<&str> = item
+                    .fields
+                    .iter()
+                    .filter(|f| f.category == category)
+                    .map(|f| f.name.as_str())
+                    .collect();
+                if fields.is_empty() {
+                    names.push(&item.kind);
+                } else {
+                    names.extend(fields);
+                }
+            }
+
+            let message = if record.is_leaking() {
+                format!(
+                    "{} contains {} metadata: {}",
+                    record.input.display(),
+                    category,
+                    names.join(", ")
+                )
+            } else {
+                format!(
+                    "Removed {} metadata from {}: {}",
+                    category,
+                    record.input.display(),
+                    names.join(", ")
+                )
+            };
+            let index = Category::ALL.iter().position(|c| *c == category);
+
+            result(
+                &rule_id(category),
+                index.unwrap_or_default(),
+                level,
+                &message,
+                location(&record.input, items.first().map(|item| &item.range)),
+            )
+        })
+        .collect()
+}
+
+/// Encode a single result.
+fn result(
+    rule_id: &str,
+    rule_index: usize,
+    level: &str,
+    message: &str,
+    location: String,
+) -> String {
+    object(&[
+        (String::from("ruleId"), json_string(rule_id)),
+        (String::from("ruleIndex"), rule_index.to_string()),
+        (String::from("level"), json_string(level)),
+        (
+            String::from("message"),
+            object(&[(String::from("text"), json_string(message))]),
+        ),
+        (String::from("locations"), array(&[location])),
+    ])
+}
+
+/// Encode a file location, with the byte range of the first block if known.
+fn location(path: &Path, range: Option<&std::ops::Range<usize>>) -> String {
+    let mut physical = vec![(
+        String::from("artifactLocation"),
+        object(&[(String::from("uri"), json_string(&artifact_uri(path)))]),
+    )];
+    if let Some(range) = range {
+        physical.push((
+            String::from("region"),
+            object(&[
+                (String::from("byteOffset"), range.start.to_string()),
+                (String::from("byteLength"), range.len().to_string()),
+            ]),
+        ));
+    }
+    object(&[(String::from("physicalLocation"), object(&physical))])
+}
+
+/// Convert a path to a URI reference.
+///
+/// Relative paths stay relative with `/` separators, so results resolve
+/// against the checkout; absolute paths become `file:` URIs.
+pub fn artifact_uri(path: &Path) -> String {
+    let mut segments = Vec::new();
+    let mut absolute = false;
+
+    for component in path.components() {
+        match component {
+            Component::Prefix(prefix) => {
+                // Keep Windows drive letters such as `C:` unescaped.
+                segments.push(prefix.as_os_str().to_string_lossy().replace('\\', "/"));
+                absolute = true;
+            }
+            Component::RootDir => absolute = true,
+            Component::CurDir => {}
+            Component::ParentDir => segments.push(String::from("..")),
+            Component::Normal(name) => segments.push(percent_encode(&name.to_string_lossy())),
+        }
+    }
+
+    let joined = segments.join("/");
+    if absolute {
+        format!("file:///{}", joined)
+    } else {
+        joined
+    }
+}
+
+/// Percent-encode everything but unreserved URI characters.
+fn percent_encode(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::ImageFormat;
+    use crate::metadata::{Field, MetadataItem};
+    use crate::processor::Timings;
+    use crate::report::Outcome;
+    use std::path::PathBuf;
+
+    fn leaking_record() -> FileRecord {
+        let item = MetadataItem::new("APP1 EXIF", 2..102, Category::Other).with_fields(vec![
+            Field::new("GPSLatitude", Category::Location),
+            Field::new("Make", Category::Device),
+        ]);
+        FileRecord {
+            input: PathBuf::from("photos/my cat.jpg"),
+            output: Some(PathBuf::from("photos/my cat_clean.jpg")),
+            original: None,
+            format: Some(ImageFormat::Jpeg),
+            outcome: Outcome::WouldClean,
+            bytes_removed: 100,
+            removed: vec![item, MetadataItem::new("COM", 200..210, Category::Other)],
+            timings: Timings::default(),
+            error_kind: None,
+            error: None,
+            warning: None,
+        }
+    }
+
+    fn render(records: &[FileRecord], dry_run: bool) -> String {
+        let mut out = Vec::new();
+        write(&mut out, records, &ProcessingStats::new(), dry_run).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_results_per_category() {
+        let sarif = render(&[leaking_record()], true);
+        assert!(sarif.starts_with("{\"$schema\":"));
+        assert!(sarif.contains("\"version\":\"2.1.0\""));
+        // One rule per category plus processing errors.
+        assert_eq!(sarif.matches("\"shortDescription\"").count(), 8);
+        assert!(sarif.contains(
+            "\"ruleId\":\"location-metadata\",\"ruleIndex\":0,\"level\":\"error\",\"message\":{\"text\":\"photos/my cat.jpg contains location metadata: GPSLatitude\"}"
+        ));
+        assert!(sarif.contains(
+            "\"uri\":\"photos/my%20cat.jpg\"},\"region\":{\"byteOffset\":2,\"byteLength\":100}"
+        ));
+        assert!(sarif.contains("other metadata: COM\""));
+        assert_eq!(sarif.matches("\"ruleId\"").count(), 3);
+    }
+
+    #[test]
+    fn test_cleaned_files_are_notes() {
+        let mut record = leaking_record();
+        record.outcome = Outcome::Cleaned;
+        let sarif = render(&[record], false);
+        assert!(!sarif.contains("\"level\":\"error\""));
+        assert!(sarif.contains("Removed device metadata from photos/my cat.jpg: Make"));
+    }
+
+    #[test]
+    fn test_processing_error() {
+        let record = FileRecord {
+            input: PathBuf::from("bad.png"),
+            output: None,
+            original: None,
+            format: None,
+            outcome: Outcome::Failed,
+            bytes_removed: 0,
+            removed: Vec::new(),
+            timings: Timings::default(),
+            error_kind: Some("invalid-image"),
+            error: Some(String::from("broken")),
+            warning: None,
+        };
+        let sarif = render(&[record], false);
+        assert!(sarif.contains(
+            "\"ruleId\":\"processing-error\",\"ruleIndex\":7,\"level\":\"error\",\"message\":{\"text\":\"broken\"}"
+        ));
+    }
+
+    #[test]
+    fn test_artifact_uri() {
+        assert_eq!(artifact_uri(Path::new("./a/b c#.png")), "a/b%20c%23.png");
+        assert_eq!(artifact_uri(Path::new("../x.gif")), "../x.gif");
+        #[cfg(unix)]
+        assert_eq!(artifact_uri(Path::new("/tmp/x.jpg")), "file:///tmp/x.jpg");
+    }
+}
@@ -0,0 +1,318 @@
+//! Layered settings from config files and the environment.
+//!
+//! Options can be set, from lowest to highest precedence, in:
+//!
+//! 1. the system file, `/etc/pmi/config`;
+//! 2. the user file, `$XDG_CONFIG_HOME/pmi/config` or `~/.config/pmi/config`;
+//! 3. the project file, `.pmi.toml` in the current directory;
+//! 4. `PMI_*` environment variables, such as `PMI_GPS=coarsen=10`;
+//! 5. the command line.
+//!
+//! Files use top-level `key = value` pairs named after the long options, and
+//! may also define `[profile.NAME]` presets. A preset chosen in any layer
+//! applies between the environment and the command line.
+//!
+//! The project file and the environment are not trusted: the current
+//! directory may be a cloned repository or an unpacked archive. Options that
+//! delete or overwrite files, or that name where output goes, are only taken
+//! from the command line and the system and user files.
+
+use crate::error::{Error, Result};
+use crate::preset::{self, Preset, Setting};
+use crate::toml;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Prefix of the environment variables that set options.
+const ENV_PREFIX: &str = "PMI_";
+
+/// Name of the project config file.
+pub const PROJECT_FILE: &str = ".pmi.toml";
+
+/// Where a setting came from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Source {
+    /// Built-in default.
+    #[default]
+    Default,
+    /// A config file.
+    File(PathBuf),
+    /// An environment variable.
+    Env(String),
+    /// A preset, or a profile read from a config file.
+    Preset { name: String, path: Option<PathBuf> },
+    /// The command line.
+    CommandLine,
+}
+
+impl Source {
+    /// Attach the source to an error in one of its settings.
+    pub fn error(&self, line: usize, err: Error) -> Error {
+        let path = match self {
+            Source::File(path)
+            | Source::Preset {
+                path: Some(path), ..
+            } => path,
+            Source::Env(var) => {
+                return Error::InvalidArgument {
+                    argument: var.clone(),
+                    reason: match err {
+                        Error::InvalidArgument { reason, .. } => reason,
+                        other => other.to_string(),
+                    },
+                };
+            }
+            _ => return err,
+        };
+        Error::config(path, line, err.to_string())
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => f.write_str("default"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Env(var) => write!(f, "environment {}", var),
+            Source::Preset { name, path: None } => write!(f, "preset {}", name),
+            Source::Preset {
+                name,
+                path: Some(path),
+            } => write!(f, "profile {} in {}", name, path.display()),
+            Source::CommandLine => f.write_str("command line"),
+        }
+    }
+}
+
+/// Settings and profiles read from one config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigFile {
+    /// Path the file was read from.
+    pub path: PathBuf,
+    /// Top-level settings, in file order.
+    pub settings: Vec<Setting>,
+    /// `[profile.NAME]` tables.
+    pub profiles: Vec<Preset>,
+}
+
+impl ConfigFile {
+    /// Parse a config file's text.
+    pub fn parse(text: &str, path: &Path) -> Result<Self> {
+        let entries = toml::parse(text, path)?;
+        if let Some(entry) = entries
+            .iter()
+            .find(|e| !e.table.is_empty() && !e.table.starts_with("profile."))
+        {
+            return Err(Error::config(
+                path,
+                entry.line,
+                format!(
+                    "Unknown table [{}]; expected top-level settings or [profile.NAME]",
+                    entry.table
+                ),
+            ));
+        }
+
+        Ok(ConfigFile {
+            path: path.to_path_buf(),
+            settings: entries
+                .iter()
+                .filter(|e| e.table.is_empty())
+                .map(|e| Setting {
+                    key: e.key.clone(),
+                    value: e.value.to_string(),
+                    line: e.line,
+                })
+                .collect(),
+            profiles: preset::profiles(&entries, path)?,
+        })
+    }
+
+    /// Read a config file. A missing file gives `None`.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text, path).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::io_with_path(e, path)),
+        }
+    }
+}
+
+/// The config files and environment variables to read settings from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Environment {
+    /// Trusted config files, from lowest to highest precedence.
+    pub files: Vec<PathBuf>,
+    /// Project file, read after the trusted files.
+    pub project: Option<PathBuf>,
+    /// `PMI_*` environment variables.
+    pub vars: Vec<(String, String)>,
+}
+
+impl Environment {
+    /// Get the standard config file locations and the process environment.
+    pub fn current() -> Self {
+        let mut files = vec![PathBuf::from("/etc/pmi/config")];
+        files.extend(user_config_path());
+
+        Environment {
+            files,
+            project: Some(PathBuf::from(PROJECT_FILE)),
+            vars: env::vars()
+                .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+                .collect(),
+        }
+    }
+
+    /// Read the config files that exist, the project file last.
+    pub fn load_files(&self) -> Result<Vec<ConfigFile>> {
+        let mut files = Vec::new();
+        for path in self.files.iter().chain(&self.project) {
+            files.extend(ConfigFile::load(path)?);
+        }
+        Ok(files)
+    }
+
+    /// Check if a source may set options that delete or overwrite files, or
+    /// that name where output goes.
+    ///
+    /// The project file, profiles read from it and the environment may not.
+    pub fn trusts(&self, source: &Source) -> bool {
+        match source {
+            Source::File(path)
+            | Source::Preset {
+                path: Some(path), ..
+            } => self.project.as_ref() != Some(path),
+            Source::Env(_) => false,
+            Source::Default | Source::Preset { path: None, .. } | Source::CommandLine => true,
+        }
+    }
+
+    /// Get the settings given by environment variables, with the variable
+    /// each came from.
+    ///
+    /// `PMI_KEEP_ORIENTATION` sets `keep-orientation`, and so on. Variables
+    /// that do not name an option are ignored, since other tools also use
+    /// the `PMI_` prefix.
+    pub fn settings(&self, is_option: impl Fn(&str) -> bool) -> Vec<(String, Setting)> {
+        let mut vars: Vec<&(String, String)> = self.vars.iter().collect();
+        vars.sort();
+        vars.into_iter()
+            .filter_map(|(name, value)| {
+                let key = name
+                    .strip_prefix(ENV_PREFIX)?
+                    .to_ascii_lowercase()
+                    .replace('_', "-");
+                is_option(&key).then(|| {
+                    let setting = Setting {
+                        key,
+                        value: value.clone(),
+                        line: 0,
+                    };
+                    (name.clone(), setting)
+                })
+            })
+            .collect()
+    }
+}
+
+/// Get the path of the user config file: `$XDG_CONFIG_HOME/pmi/config`, or
+/// `~/.config/pmi/config`.
+pub fn user_config_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("pmi").join("config"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config_file() {
+        let path = Path::new(".pmi.toml");
+        let file = ConfigFile::parse(
+            "recursive = true\n\
+             gps = \"coarsen=10\"\n\
+             [profile.family]\n\
+             dates = \"keep\"\n",
+            path,
+        )
+        .unwrap();
+        let keys: Vec<(&str, &str, usize)> = file
+            .settings
+            .iter()
+            .map(|s| (s.key.as_str(), s.value.as_str(), s.line))
+            .collect();
+        assert_eq!(keys, [("recursive", "true", 1), ("gps", "coarsen=10", 2)]);
+        assert_eq!(file.profiles[0].name, "family");
+
+        assert!(matches!(
+            ConfigFile::parse("[strip]\nverify = true\n", path),
+            Err(Error::Config { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let path = env::temp_dir().join("pmi_settings_missing").join("config");
+        assert_eq!(ConfigFile::load(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn test_env_settings() {
+        let environment = Environment {
+            files: Vec::new(),
+            project: None,
+            vars: vec![
+                (String::from("PMI_KEEP_ORIENTATION"), String::from("1")),
+                (String::from("PMI_RANK"), String::from("0")),
+                (String::from("PMI_GPS"), String::from("coarsen=5")),
+            ],
+        };
+        let settings = environment.settings(|key| key != "rank");
+        let summary: Vec<(&str, &str, &str)> = settings
+            .iter()
+            .map(|(var, s)| (var.as_str(), s.key.as_str(), s.value.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("PMI_GPS", "gps", "coarsen=5"),
+                ("PMI_KEEP_ORIENTATION", "keep-orientation", "1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_source_errors() {
+        let err = Error::InvalidArgument {
+            argument: String::from("--gps"),
+            reason: String::from("Expected 'remove' or 'coarsen=<km>'"),
+        };
+        let env = Source::Env(String::from("PMI_GPS")).error(0, err);
+        assert_eq!(
+            env.to_string(),
+            "Invalid argument 'PMI_GPS': Expected 'remove' or 'coarsen=<km>'"
+        );
+
+        let err = Error::MissingArgument {
+            argument: String::from("x"),
+        };
+        let file = Source::File(PathBuf::from(".pmi.toml")).error(4, err);
+        assert!(matches!(file, Error::Config { line: 4, .. }));
+        assert_eq!(
+            Source::Preset {
+                name: String::from("social"),
+                path: None
+            }
+            .to_string(),
+            "preset social"
+        );
+    }
+}
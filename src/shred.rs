@@ -0,0 +1,137 @@
+//! Removal of originals once their cleaned copies are safely written.
+//!
+//! Deleting a file only removes its name; the data stays on disk until it
+//! is reused. Shredding overwrites the file with zeros and syncs it before
+//! the name is removed, which clears the data on ordinary filesystems on
+//! spinning disks. It cannot on copy-on-write filesystems (btrfs, ZFS,
+//! APFS), which write the zeros somewhere else, or on SSDs and flash, whose
+//! controllers remap writes; snapshots and backups keep their own copies
+//! either way. There, full-disk encryption is the reliable protection.
+
+use crate::error::{Error, Result};
+use crate::fsmeta;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Size of the buffer of zeros written over a file.
+const CHUNK: usize = 64 * 1024;
+
+/// How an original was removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Removal {
+    /// The name was removed.
+    Removed,
+    /// The contents were overwritten, then the name removed.
+    Shredded,
+}
+
+impl Removal {
+    /// Get the name used in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Removal::Removed => "removed",
+            Removal::Shredded => "shredded",
+        }
+    }
+}
+
+/// Check that removing `path` once `output` is written would remove the
+/// image, before anything is written.
+///
+/// Removing a symlink or one of several hard links would leave the data
+/// where it is, and shredding either would destroy a file the user still
+/// refers to by another name. An output that replaces the original would
+/// be removed with it.
+pub fn check_removable(path: &Path, output: &Path) -> Result<()> {
+    let refuse = |reason: String| Error::Io {
+        source: io::Error::other(reason),
+        path: Some(path.to_path_buf()),
+    };
+    let metadata = fs::symlink_metadata(path).map_err(|e| Error::io_with_path(e, path))?;
+    if metadata.file_type().is_symlink() {
+        return Err(refuse(String::from(
+            "is a symbolic link; remove the file it points to instead",
+        )));
+    }
+    let canonical = |p: &Path| fs::canonicalize(p).ok();
+    if output == path || canonical(output).is_some_and(|o| Some(o) == canonical(path)) {
+        return Err(refuse(String::from(
+            "would be replaced by its own output; use --in-place instead",
+        )));
+    }
+    let links = fsmeta::link_count(path)?;
+    if links > 1 {
+        return Err(refuse(format!(
+            "has {} hard links, so removing it would not remove the image",
+            links
+        )));
+    }
+    Ok(())
+}
+
+/// Remove an original, overwriting its contents first if `shred` is set.
+pub fn remove(path: &Path, shred: bool) -> Result<Removal> {
+    if shred {
+        overwrite(path).map_err(|e| Error::io_with_path(e, path))?;
+    }
+    fs::remove_file(path).map_err(|e| Error::io_with_path(e, path))?;
+    Ok(if shred {
+        Removal::Shredded
+    } else {
+        Removal::Removed
+    })
+}
+
+/// Overwrite a file's contents with zeros and sync them to disk.
+fn overwrite(path: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let zeros = [0u8; CHUNK];
+    let mut remaining = file.metadata()?.len();
+    while remaining > 0 {
+        let n = remaining.min(CHUNK as u64) as usize;
+        file.write_all(&zeros[..n])?;
+        remaining -= n as u64;
+    }
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remove_and_shred() {
+        let dir = std::env::temp_dir().join("pmi_shred");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("photo.jpg");
+
+        fs::write(&path, vec![0xAB; CHUNK + 10]).unwrap();
+        overwrite(&path).unwrap();
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data.len(), CHUNK + 10);
+        assert!(data.iter().all(|&b| b == 0));
+
+        assert_eq!(remove(&path, true).unwrap(), Removal::Shredded);
+        assert!(!path.exists());
+        fs::write(&path, b"x").unwrap();
+        assert_eq!(remove(&path, false).unwrap(), Removal::Removed);
+
+        #[cfg(unix)]
+        {
+            fs::write(&path, b"x").unwrap();
+            let output = dir.join("photo_clean.jpg");
+            check_removable(&path, &output).unwrap();
+            assert!(check_removable(&path, &path).is_err());
+            let link = dir.join("link.jpg");
+            fs::hard_link(&path, &link).unwrap();
+            assert!(check_removable(&path, &output).is_err());
+            fs::remove_file(&link).unwrap();
+            std::os::unix::fs::symlink(&path, &link).unwrap();
+            assert!(check_removable(&link, &output).is_err());
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
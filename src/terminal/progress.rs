@@ -3,6 +3,7 @@
 //! Provides progress indication for batch operations.
 
 use super::colors::{Styled, Symbols, format_size, stdout_supports_color};
+use crate::metadata::risk::RiskLevel;
 use std::io::{self, IsTerminal, Write};
 use std::time::{Duration, Instant};
 
@@ -166,8 +167,12 @@ pub struct ProcessingStats {
     pub failed: usize,
     /// Number of skipped files.
     pub skipped: usize,
+    /// Number of files that needed no changes.
+    pub already_clean: usize,
     /// Total bytes of metadata removed.
     pub metadata_removed: u64,
+    /// Number of processed files at each risk level, indexed by level.
+    pub risk: [usize; RiskLevel::ALL.len()],
     /// Processing duration.
     pub duration: Duration,
 }
@@ -194,6 +199,21 @@ impl ProcessingStats {
         self.skipped += 1;
     }
 
+    /// Add a file that needed no changes.
+    pub fn add_already_clean(&mut self) {
+        self.already_clean += 1;
+    }
+
+    /// Count a processed file at a risk level.
+    pub fn add_risk(&mut self, level: RiskLevel) {
+        self.risk[level as usize] += 1;
+    }
+
+    /// Get the number of processed files at a risk level.
+    pub fn risk_count(&self, level: RiskLevel) -> usize {
+        self.risk[level as usize]
+    }
+
     /// Set the duration.
     pub fn set_duration(&mut self, duration: Duration) {
         self.duration = duration;
@@ -201,7 +221,7 @@ impl ProcessingStats {
 
     /// Get the total number of files encountered.
     pub fn total(&self) -> usize {
-        self.processed + self.failed + self.skipped
+        self.processed + self.failed + self.skipped + self.already_clean
     }
 }
 
@@ -263,6 +283,21 @@ pub fn print_summary(stats: &ProcessingStats, quiet: bool) {
         width = inner_width
     );
 
+    if stats.already_clean > 0 {
+        let clean_str = format!(
+            "  {} Already clean: {} files",
+            symbols.success(),
+            stats.already_clean
+        );
+        println!(
+            "{}{:<width$}{}",
+            vertical,
+            clean_str,
+            vertical,
+            width = inner_width
+        );
+    }
+
     if stats.failed > 0 {
         let failed_str = format!("  {} Failed:      {} files", symbols.error(), stats.failed);
         println!(
@@ -305,6 +340,22 @@ pub fn print_summary(stats: &ProcessingStats, quiet: bool) {
         width = inner_width
     );
 
+    // Files by risk, most sensitive first.
+    for level in RiskLevel::ALL.iter().rev() {
+        let count = stats.risk_count(*level);
+        if *level == RiskLevel::None || count == 0 {
+            continue;
+        }
+        let risk_str = format!("  Risk {:<9} {} files", format!("{}:", level), count);
+        println!(
+            "{}{:<width$}{}",
+            vertical,
+            risk_str,
+            vertical,
+            width = inner_width
+        );
+    }
+
     // Duration.
     let duration_str = format!("  Time elapsed: {:.1}s", stats.duration.as_secs_f64());
     println!(
@@ -431,6 +482,27 @@ mod tests {
         assert_eq!(stats.skipped, 1);
     }
 
+    #[test]
+    fn test_processing_stats_add_already_clean() {
+        let mut stats = ProcessingStats::new();
+        stats.add_already_clean();
+        stats.add_success(10);
+        assert_eq!(stats.already_clean, 1);
+        assert_eq!(stats.processed, 1);
+        assert_eq!(stats.total(), 2);
+    }
+
+    #[test]
+    fn test_processing_stats_add_risk() {
+        let mut stats = ProcessingStats::new();
+        stats.add_risk(RiskLevel::High);
+        stats.add_risk(RiskLevel::High);
+        stats.add_risk(RiskLevel::Low);
+        assert_eq!(stats.risk_count(RiskLevel::High), 2);
+        assert_eq!(stats.risk_count(RiskLevel::Low), 1);
+        assert_eq!(stats.risk_count(RiskLevel::Critical), 0);
+    }
+
     #[test]
     fn test_processing_stats_total() {
         let mut stats = ProcessingStats::new();
@@ -0,0 +1,308 @@
+//! A small TOML subset for configuration files.
+//!
+//! PMI settings are flat `key = value` pairs grouped under tables, so this
+//! parser handles only what those need: comments, `[table]` and dotted
+//! `[table.name]` headers, bare and quoted keys, and string, integer and
+//! boolean values. Anything else is reported with its line number rather
+//! than guessed at.
+
+use crate::error::{Error, Result};
+use std::fmt;
+use std::path::Path;
+
+/// A configuration value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+}
+
+impl fmt::Display for Value {
+    /// Format the value as it would be given on the command line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{}", s),
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+impl Value {
+    /// Format the value as TOML, quoting and escaping strings.
+    pub fn to_toml(&self) -> String {
+        let Value::String(text) = self else {
+            return self.to_string();
+        };
+        let mut quoted = String::from('"');
+        for c in text.chars() {
+            match c {
+                '"' => quoted.push_str("\\\""),
+                '\\' => quoted.push_str("\\\\"),
+                '\n' => quoted.push_str("\\n"),
+                '\t' => quoted.push_str("\\t"),
+                '\r' => quoted.push_str("\\r"),
+                c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", c as u32)),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted
+    }
+}
+
+/// A `key = value` line and the table it appeared under.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Dotted table name, empty for keys before the first header.
+    pub table: String,
+    /// Key within the table.
+    pub key: String,
+    /// Parsed value.
+    pub value: Value,
+    /// Line number, counting from 1.
+    pub line: usize,
+}
+
+/// Parse a configuration file's text into entries, in file order.
+///
+/// `path` is only used for error messages.
+pub fn parse(text: &str, path: &Path) -> Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut table = String::new();
+
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let error = |reason: &str| Error::config(path, line, reason);
+        let content = strip_comment(raw).trim();
+        if content.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = content.strip_prefix('[') {
+            let name = header
+                .strip_suffix(']')
+                .ok_or_else(|| error("Expected ']' after the table name"))?;
+            let parts: Vec<&str> = name.split('.').map(str::trim).collect();
+            if parts.iter().any(|part| !is_bare_key(part)) {
+                return Err(error(&format!("'{}' is not a valid table name", name)));
+            }
+            table = parts.join(".");
+            continue;
+        }
+
+        let (key, value) = content
+            .split_once('=')
+            .ok_or_else(|| error("Expected 'key = value'"))?;
+        let key = parse_key(key.trim()).ok_or_else(|| error("Expected a key before '='"))?;
+        if entries.iter().any(|e| e.table == table && e.key == key) {
+            return Err(error(&format!("'{}' is set more than once", key)));
+        }
+        let value = parse_value(value.trim()).map_err(|reason| error(&reason))?;
+        entries.push(Entry {
+            table: table.clone(),
+            key,
+            value,
+            line,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Remove a trailing `#` comment, ignoring `#` inside strings.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+/// Check if a key can be written without quotes.
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Parse a bare or quoted key.
+fn parse_key(key: &str) -> Option<String> {
+    if is_bare_key(key) {
+        return Some(key.to_string());
+    }
+    match parse_value(key) {
+        Ok(Value::String(key)) if !key.is_empty() => Some(key),
+        _ => None,
+    }
+}
+
+/// Parse a value.
+fn parse_value(value: &str) -> std::result::Result<Value, String> {
+    match value {
+        "" => return Err(String::from("Expected a value after '='")),
+        "true" => return Ok(Value::Boolean(true)),
+        "false" => return Ok(Value::Boolean(false)),
+        _ => {}
+    }
+
+    if let Some(rest) = value.strip_prefix('\'') {
+        return match rest.strip_suffix('\'') {
+            Some(text) if !text.contains('\'') => Ok(Value::String(text.to_string())),
+            _ => Err(String::from("Unterminated string")),
+        };
+    }
+
+    if let Some(rest) = value.strip_prefix('"') {
+        return parse_basic_string(rest).map(Value::String);
+    }
+
+    let digits = value.replace('_', "");
+    digits
+        .parse::<i64>()
+        .map(Value::Integer)
+        .map_err(|_| format!("'{}' is not a string, integer or boolean", value))
+}
+
+/// Parse the rest of a double-quoted string, handling escapes.
+fn parse_basic_string(rest: &str) -> std::result::Result<String, String> {
+    let mut text = String::new();
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                return if chars.as_str().is_empty() {
+                    Ok(text)
+                } else {
+                    Err(String::from("Unexpected text after the closing quote"))
+                };
+            }
+            '\\' => {
+                let escaped = match chars.next() {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('u') => {
+                        let hex: String = chars.by_ref().take(4).collect();
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .filter(|_| hex.len() == 4)
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("Invalid escape '\\u{}'", hex))?
+                    }
+                    other => {
+                        return Err(format!(
+                            "Invalid escape '\\{}'",
+                            other.map(String::from).unwrap_or_default()
+                        ));
+                    }
+                };
+                text.push(escaped);
+            }
+            c => text.push(c),
+        }
+    }
+    Err(String::from("Unterminated string"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(text: &str) -> Result<Vec<Entry>> {
+        parse(text, Path::new("config"))
+    }
+
+    #[test]
+    fn test_parse_tables_and_values() {
+        let entries = parse_str(
+            "# defaults\n\
+             jobs = 4\n\
+             verify = true\n\
+             \n\
+             [profile.family]  # shared albums\n\
+             gps = \"coarsen=25\"\n\
+             \"dates\" = 'keep-date-only'\n\
+             stamp = \"copyright=\\u00A9 Ann # 2024\"\n",
+        )
+        .unwrap();
+
+        let summary: Vec<(&str, &str, String, usize)> = entries
+            .iter()
+            .map(|e| {
+                (
+                    e.table.as_str(),
+                    e.key.as_str(),
+                    e.value.to_string(),
+                    e.line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("", "jobs", String::from("4"), 2),
+                ("", "verify", String::from("true"), 3),
+                ("profile.family", "gps", String::from("coarsen=25"), 6),
+                ("profile.family", "dates", String::from("keep-date-only"), 7),
+                (
+                    "profile.family",
+                    "stamp",
+                    String::from("copyright=© Ann # 2024"),
+                    8
+                ),
+            ]
+        );
+        assert_eq!(entries[0].value, Value::Integer(4));
+        assert_eq!(entries[1].value, Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_parse_errors_have_line_numbers() {
+        let cases = [
+            ("[profile.family\n", 1),
+            ("\n\njobs\n", 3),
+            ("gps = \"coarsen=25\n", 1),
+            ("verify = yes\n", 1),
+            ("jobs = 2\njobs = 3\n", 2),
+            ("= 3\n", 1),
+            ("[a..b]\n", 1),
+        ];
+        for (text, line) in cases {
+            match parse_str(text) {
+                Err(Error::Config { line: got, .. }) => assert_eq!(got, line, "{:?}", text),
+                other => panic!("expected a config error for {:?}, got {:?}", text, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_toml_round_trip() {
+        let value = Value::String(String::from("say \"hi\"\\\tnow\u{1}"));
+        let text = format!("key = {}", value.to_toml());
+        assert_eq!(parse_str(&text).unwrap()[0].value, value);
+        assert_eq!(Value::Integer(-3).to_toml(), "-3");
+        assert_eq!(Value::Boolean(false).to_toml(), "false");
+    }
+
+    #[test]
+    fn test_same_key_in_different_tables() {
+        let entries = parse_str("[profile.a]\nicc = \"keep\"\n[profile.b]\nicc = \"strip\"\n");
+        assert_eq!(entries.unwrap().len(), 2);
+    }
+}
@@ -1,13 +1,34 @@
 //! Integration tests for PMI.
 
-use pmi::cli::Config;
+use pmi::backup::{self, RestoreStatus};
+use pmi::check::{self, CheckStatus, EXIT_METADATA_FOUND, EXIT_PARSE_ERROR};
+use pmi::cleanup;
+use pmi::cli::{Command, Config};
 use pmi::formats::{ImageFormat, detect_format, strip_metadata};
+use pmi::fsmeta;
+use pmi::git::{self, GitTarget};
+use pmi::lossless::{frame_digests, prove_lossless};
+use pmi::metadata::exif::{self, Entry, Ifd};
+use pmi::metadata::gps;
+use pmi::metadata::risk::RiskLevel;
+use pmi::naming::Anonymize;
 use pmi::processor::Processor;
+use pmi::report::{self, Outcome};
+use pmi::settings::{Environment, Source};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 mod helpers {
-    //! Test helpers for creating minimal image files.
+    //! Test helpers for parsing arguments and creating minimal image files.
+
+    use pmi::cli::Config;
+    use pmi::error::Result;
+    use pmi::settings::Environment;
+
+    /// Parse arguments without reading config files or `PMI_*` variables.
+    pub fn parse<const N: usize>(args: [&str; N]) -> Result<Config> {
+        Config::parse_with(args, &Environment::default())
+    }
 
     /// Create a minimal JPEG for testing.
     pub fn create_minimal_jpeg() -> Vec<u8> {
@@ -68,6 +89,32 @@ mod helpers {
         data
     }
 
+    /// Create a progressive JPEG with EXIF metadata, tables between its
+    /// scans and restart markers inside the entropy-coded data.
+    pub fn create_progressive_jpeg(scans: u8) -> Vec<u8> {
+        let minimal = create_minimal_jpeg();
+        let sof = minimal.windows(2).position(|w| w == [0xFF, 0xC0]).unwrap();
+        let dht = minimal.windows(2).position(|w| w == [0xFF, 0xC4]).unwrap();
+        let sos = minimal.windows(2).position(|w| w == [0xFF, 0xDA]).unwrap();
+
+        let mut data = create_jpeg_with_exif();
+        data.truncate(data.windows(2).position(|w| w == [0xFF, 0xC0]).unwrap());
+        // SOF2 and a restart interval.
+        data.extend_from_slice(&[0xFF, 0xC2]);
+        data.extend_from_slice(&minimal[sof + 2..dht]);
+        data.extend_from_slice(&[0xFF, 0xDD, 0x00, 0x04, 0x00, 0x01]);
+
+        for scan in 0..scans {
+            data.extend_from_slice(&minimal[dht..sos]);
+            data.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, scan, 0x3F, 0x00]);
+            data.extend_from_slice(&[0x12, scan, 0xFF, 0x00, 0x34]);
+            data.extend_from_slice(&[0xFF, 0xD0 + (scan % 8), 0x56, 0xFF, 0x00]);
+        }
+
+        data.extend_from_slice(&[0xFF, 0xD9]);
+        data
+    }
+
     /// Create a minimal PNG.
     pub fn create_minimal_png() -> Vec<u8> {
         // PNG signature.
@@ -82,7 +129,7 @@ mod helpers {
         write_png_chunk(&mut data, b"IHDR", &ihdr_data);
 
         // IDAT chunk (minimal).
-        let idat_data = [0x78, 0x9C, 0x62, 0x60, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01];
+        let idat_data = [0x78, 0x9C, 0x63, 0x60, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01];
         write_png_chunk(&mut data, b"IDAT", &idat_data);
 
         // IEND chunk.
@@ -152,7 +199,7 @@ fn test_strip_jpeg_removes_exif() {
 
 #[test]
 fn test_cli_parse_basic() {
-    let config = Config::parse(["pmi", "image.jpg"]).unwrap();
+    let config = helpers::parse(["pmi", "image.jpg"]).unwrap();
     assert_eq!(config.paths.len(), 1);
     assert!(!config.verbose);
     assert!(!config.quiet);
@@ -160,38 +207,38 @@ fn test_cli_parse_basic() {
 
 #[test]
 fn test_cli_parse_flags() {
-    let config = Config::parse(["pmi", "-i", "-v", "image.jpg"]).unwrap();
+    let config = helpers::parse(["pmi", "-i", "-v", "image.jpg"]).unwrap();
     assert!(config.in_place);
     assert!(config.verbose);
 }
 
 #[test]
 fn test_cli_parse_output_dir() {
-    let config = Config::parse(["pmi", "-o", "/output", "image.jpg"]).unwrap();
+    let config = helpers::parse(["pmi", "-o", "/output", "image.jpg"]).unwrap();
     assert_eq!(config.output_dir, Some(PathBuf::from("/output")));
 }
 
 #[test]
 fn test_cli_help_flag() {
-    let config = Config::parse(["pmi", "--help"]).unwrap();
+    let config = helpers::parse(["pmi", "--help"]).unwrap();
     assert!(config.help);
 }
 
 #[test]
 fn test_cli_version_flag() {
-    let config = Config::parse(["pmi", "-V"]).unwrap();
+    let config = helpers::parse(["pmi", "-V"]).unwrap();
     assert!(config.version);
 }
 
 #[test]
 fn test_cli_missing_paths() {
-    let result = Config::parse(["pmi"]);
+    let result = helpers::parse(["pmi"]);
     assert!(result.is_err());
 }
 
 #[test]
 fn test_cli_quiet_verbose_conflict() {
-    let result = Config::parse(["pmi", "-q", "-v", "image.jpg"]);
+    let result = helpers::parse(["pmi", "-q", "-v", "image.jpg"]);
     assert!(result.is_err());
 }
 
@@ -216,6 +263,7 @@ fn test_processor_dry_run() {
         help: false,
         version: false,
         jobs: Some(1),
+        ..Default::default()
     };
 
     let mut processor = Processor::new(config);
@@ -262,6 +310,7 @@ fn test_processor_creates_clean_file() {
         help: false,
         version: false,
         jobs: Some(1),
+        ..Default::default()
     };
 
     let mut processor = Processor::new(config);
@@ -281,3 +330,1185 @@ fn test_processor_creates_clean_file() {
     let _ = fs::remove_file(&test_file);
     let _ = fs::remove_file(&clean_file);
 }
+
+#[test]
+fn test_processor_verify_failure_keeps_original() {
+    // A JPEG truncated before EOI strips "successfully" but fails verification.
+    let temp_dir = std::env::temp_dir().join("pmi_test");
+    let _ = fs::create_dir_all(&temp_dir);
+    let test_file = temp_dir.join("test_verify_truncated.jpg");
+
+    let mut data = helpers::create_jpeg_with_exif();
+    data.truncate(data.len() - 2);
+    fs::write(&test_file, &data).unwrap();
+
+    let config = Config {
+        paths: vec![test_file.clone()],
+        in_place: true,
+        verify: true,
+        quiet: true,
+        jobs: Some(1),
+        ..Default::default()
+    };
+
+    let mut processor = Processor::new(config);
+    let stats = processor.run().unwrap();
+
+    assert_eq!(stats.failed, 1);
+    assert_eq!(stats.processed, 0);
+
+    // Original must be untouched.
+    assert_eq!(fs::read(&test_file).unwrap(), data);
+
+    // Cleanup.
+    let _ = fs::remove_file(&test_file);
+}
+
+#[test]
+fn test_processor_verify_success_writes_output() {
+    let temp_dir = std::env::temp_dir().join("pmi_test");
+    let _ = fs::create_dir_all(&temp_dir);
+    let test_file = temp_dir.join("test_verify_ok.jpg");
+    let clean_file = temp_dir.join("test_verify_ok_clean.jpg");
+    let _ = fs::remove_file(&clean_file);
+
+    fs::write(&test_file, helpers::create_jpeg_with_exif()).unwrap();
+
+    let config = Config {
+        paths: vec![test_file.clone()],
+        verify: true,
+        quiet: true,
+        jobs: Some(1),
+        ..Default::default()
+    };
+
+    let mut processor = Processor::new(config);
+    let stats = processor.run().unwrap();
+
+    assert_eq!(stats.processed, 1);
+    assert!(clean_file.exists());
+
+    // Cleanup.
+    let _ = fs::remove_file(&test_file);
+    let _ = fs::remove_file(&clean_file);
+}
+
+#[test]
+fn test_processor_verify_keeps_every_progressive_scan() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_progressive");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let test_file = temp_dir.join("progressive.jpg");
+    let clean_file = temp_dir.join("progressive_clean.jpg");
+    let data = helpers::create_progressive_jpeg(10);
+    fs::write(&test_file, &data).unwrap();
+
+    let config = Config {
+        paths: vec![test_file.clone()],
+        verify: true,
+        quiet: true,
+        jobs: Some(1),
+        ..Default::default()
+    };
+    let stats = Processor::new(config).run().unwrap();
+
+    assert_eq!(stats.processed, 1);
+    assert_eq!(stats.failed, 0);
+    let cleaned = fs::read(&clean_file).unwrap();
+    assert!(!cleaned.windows(4).any(|w| w == b"Exif"));
+    assert_eq!(
+        cleaned.windows(2).filter(|w| *w == [0xFF, 0xDA]).count(),
+        10
+    );
+    assert!(cleaned.ends_with(&data[data.len() - 9..]));
+
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_frame_digests_survive_strip() {
+    let data = helpers::create_minimal_png();
+    let path = PathBuf::from("test.png");
+    let cleaned = strip_metadata(&data, &path).unwrap();
+
+    let before = frame_digests(&data, &path).unwrap();
+    let after = prove_lossless(&data, &cleaned.data, &path).unwrap();
+    assert_eq!(before, after);
+    assert_eq!(before.len(), 1);
+}
+
+#[test]
+fn test_processor_prove_lossless_writes_output() {
+    let temp_dir = std::env::temp_dir().join("pmi_test");
+    let _ = fs::create_dir_all(&temp_dir);
+    let test_file = temp_dir.join("test_prove_ok.png");
+    let clean_file = temp_dir.join("test_prove_ok_clean.png");
+    let _ = fs::remove_file(&clean_file);
+
+    fs::write(&test_file, helpers::create_minimal_png()).unwrap();
+
+    let config = Config {
+        paths: vec![test_file.clone()],
+        prove_lossless: true,
+        quiet: true,
+        jobs: Some(1),
+        ..Default::default()
+    };
+
+    let mut processor = Processor::new(config);
+    let stats = processor.run().unwrap();
+
+    // The PNG has no metadata, so its output is an exact copy.
+    assert_eq!(stats.already_clean, 1);
+    assert_eq!(
+        fs::read(&clean_file).unwrap(),
+        fs::read(&test_file).unwrap()
+    );
+
+    // Cleanup.
+    let _ = fs::remove_file(&test_file);
+    let _ = fs::remove_file(&clean_file);
+}
+
+#[test]
+fn test_processor_prove_lossless_failure_writes_nothing() {
+    let temp_dir = std::env::temp_dir().join("pmi_test");
+    let _ = fs::create_dir_all(&temp_dir);
+    let test_file = temp_dir.join("test_prove_undecodable.png");
+    let clean_file = temp_dir.join("test_prove_undecodable_clean.png");
+    let _ = fs::remove_file(&clean_file);
+
+    // Break the IDAT Adler-32 so the image data cannot be decoded.
+    let mut data = helpers::create_minimal_png();
+    let idat = data.windows(4).position(|w| w == b"IDAT").unwrap();
+    data[idat + 4 + 9] ^= 0xFF;
+    fs::write(&test_file, &data).unwrap();
+
+    let config = Config {
+        paths: vec![test_file.clone()],
+        prove_lossless: true,
+        quiet: true,
+        jobs: Some(1),
+        ..Default::default()
+    };
+
+    let mut processor = Processor::new(config);
+    let stats = processor.run().unwrap();
+
+    assert_eq!(stats.failed, 1);
+    assert!(!clean_file.exists());
+
+    // Cleanup.
+    let _ = fs::remove_file(&test_file);
+}
+
+#[test]
+fn test_check_reports_without_modifying() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_check");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let dirty = temp_dir.join("dirty.jpg");
+    let clean = temp_dir.join("clean.png");
+    let broken = temp_dir.join("broken.gif");
+
+    let jpeg = helpers::create_jpeg_with_exif();
+    fs::write(&dirty, &jpeg).unwrap();
+    fs::write(&clean, helpers::create_minimal_png()).unwrap();
+
+    let config = helpers::parse(["pmi", "check", temp_dir.to_str().unwrap()]).unwrap();
+    assert_eq!(config.command, Command::Check);
+
+    let results = check::check_paths(&config).unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(check::exit_code(&results), EXIT_METADATA_FOUND);
+
+    let dirty_result = results.iter().find(|r| r.path == dirty).unwrap();
+    assert!(matches!(dirty_result.status, CheckStatus::MetadataFound(_)));
+    let clean_result = results.iter().find(|r| r.path == clean).unwrap();
+    assert_eq!(clean_result.status, CheckStatus::Clean);
+
+    // Nothing was written or changed.
+    assert_eq!(fs::read(&dirty).unwrap(), jpeg);
+    assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), 2);
+
+    // A file that cannot be parsed dominates the exit code.
+    // Global Color Table flag set, but the table is missing.
+    fs::write(&broken, b"GIF89a\x01\x00\x01\x00\xF7\x00\x00").unwrap();
+    let results = check::check_paths(&config).unwrap();
+    assert_eq!(check::exit_code(&results), EXIT_PARSE_ERROR);
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_git_check_staged_and_committed() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_git_check");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+
+    let run = |args: &[&str]| {
+        std::process::Command::new("git")
+            .current_dir(&temp_dir)
+            .args(["-c", "user.name=pmi", "-c", "user.email=pmi@example.com"])
+            .args(args)
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    };
+
+    // Skip when git is not installed.
+    if !run(&["init", "-q"]) {
+        let _ = fs::remove_dir_all(&temp_dir);
+        return;
+    }
+
+    let jpeg = helpers::create_jpeg_with_exif();
+    fs::write(temp_dir.join("dirty.jpg"), &jpeg).unwrap();
+    fs::write(temp_dir.join("clean.png"), helpers::create_minimal_png()).unwrap();
+    fs::write(temp_dir.join("notes.txt"), b"not an image").unwrap();
+    assert!(run(&["add", "."]));
+
+    let results = git::check_changes(&temp_dir, &GitTarget::Staged).unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(check::exit_code(&results), EXIT_METADATA_FOUND);
+    let dirty = results
+        .iter()
+        .find(|r| r.path == Path::new("dirty.jpg"))
+        .unwrap();
+    assert!(matches!(dirty.status, CheckStatus::MetadataFound(_)));
+
+    // Contents come from the object store, not the working tree.
+    assert!(run(&["commit", "-q", "-m", "add images"]));
+    fs::remove_file(temp_dir.join("dirty.jpg")).unwrap();
+    let results = git::check_changes(&temp_dir, &GitTarget::Range(String::from("HEAD"))).unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(check::exit_code(&results), EXIT_METADATA_FOUND);
+
+    // Nothing staged.
+    let results = git::check_changes(&temp_dir, &GitTarget::Staged).unwrap();
+    assert!(results.is_empty());
+
+    // A bad revision is an error.
+    assert!(git::check_changes(&temp_dir, &GitTarget::Range(String::from("nope..HEAD"))).is_err());
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_git_check_sees_images_added_by_merges() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_git_merge");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+
+    let run = |args: &[&str]| {
+        std::process::Command::new("git")
+            .current_dir(&temp_dir)
+            .args(["-c", "user.name=pmi", "-c", "user.email=pmi@example.com"])
+            .args(args)
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    };
+
+    // Skip when git is not installed.
+    if !run(&["init", "-q"]) {
+        let _ = fs::remove_dir_all(&temp_dir);
+        return;
+    }
+
+    fs::write(temp_dir.join("clean.png"), helpers::create_minimal_png()).unwrap();
+    assert!(run(&["add", "."]));
+    assert!(run(&["commit", "-q", "-m", "base"]));
+    assert!(run(&["checkout", "-q", "-b", "side"]));
+    fs::write(temp_dir.join("side.txt"), b"side").unwrap();
+    assert!(run(&["add", "."]));
+    assert!(run(&["commit", "-q", "-m", "side"]));
+    assert!(run(&["checkout", "-q", "-"]));
+    fs::write(temp_dir.join("main.txt"), b"main").unwrap();
+    assert!(run(&["add", "."]));
+    assert!(run(&["commit", "-q", "-m", "main"]));
+
+    // The image exists only in the merge commit.
+    assert!(run(&["merge", "-q", "--no-ff", "--no-commit", "side"]));
+    fs::write(temp_dir.join("dirty.jpg"), helpers::create_jpeg_with_exif()).unwrap();
+    assert!(run(&["add", "."]));
+    assert!(run(&["commit", "-q", "-m", "merge side"]));
+
+    for range in ["HEAD~1..HEAD", "HEAD"] {
+        let results = git::check_changes(&temp_dir, &GitTarget::Range(range.into())).unwrap();
+        assert_eq!(results.len(), 1, "{}", range);
+        assert_eq!(results[0].path, Path::new("dirty.jpg"));
+        assert_eq!(check::exit_code(&results), EXIT_METADATA_FOUND);
+    }
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_report_lists_every_file() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_report");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    fs::write(temp_dir.join("dirty.jpg"), helpers::create_jpeg_with_exif()).unwrap();
+    fs::write(temp_dir.join("clean.png"), helpers::create_minimal_png()).unwrap();
+    fs::write(temp_dir.join("broken.png"), b"not a png").unwrap();
+    let report_file = temp_dir.join("report.ndjson");
+
+    let config = helpers::parse([
+        "pmi",
+        "-n",
+        "-q",
+        "--report-file",
+        report_file.to_str().unwrap(),
+        temp_dir.to_str().unwrap(),
+    ])
+    .unwrap();
+    let mut processor = Processor::new(config.clone());
+    let stats = processor.run().unwrap();
+
+    let records = processor.records();
+    assert_eq!(records.len(), 3);
+    let dirty = records
+        .iter()
+        .find(|r| r.input.ends_with("dirty.jpg"))
+        .unwrap();
+    assert_eq!(dirty.outcome, Outcome::WouldClean);
+    assert_eq!(dirty.format, Some(ImageFormat::Jpeg));
+    assert!(!dirty.removed.is_empty());
+    assert_eq!(
+        dirty.removed.iter().map(|i| i.size() as u64).sum::<u64>(),
+        dirty.bytes_removed
+    );
+    let broken = records
+        .iter()
+        .find(|r| r.input.ends_with("broken.png"))
+        .unwrap();
+    assert_eq!(broken.outcome, Outcome::Failed);
+    assert_eq!(broken.error_kind, Some("unsupported-format"));
+    let clean = records
+        .iter()
+        .find(|r| r.input.ends_with("clean.png"))
+        .unwrap();
+    assert_eq!(clean.outcome, Outcome::AlreadyClean);
+
+    report::output_report(&config, records, &stats).unwrap();
+    let report = fs::read_to_string(&report_file).unwrap();
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[3].starts_with(
+        "{\"type\":\"summary\",\"processed\":1,\"failed\":1,\"skipped\":0,\"already_clean\":1"
+    ));
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_processor_min_risk_skips_less_sensitive_files() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_min_risk");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let input = temp_dir.join("dirty.jpg");
+    let output = temp_dir.join("dirty_clean.jpg");
+    // An undecodable EXIF block and a comment: medium risk.
+    fs::write(&input, helpers::create_jpeg_with_exif()).unwrap();
+
+    let config = helpers::parse(["pmi", "-q", "--min-risk", "high", input.to_str().unwrap()]);
+    let stats = Processor::new(config.unwrap()).run().unwrap();
+    assert_eq!(stats.processed, 0);
+    assert_eq!(stats.skipped, 1);
+    assert!(!output.exists());
+
+    let config = helpers::parse(["pmi", "-q", "--min-risk", "medium", input.to_str().unwrap()]);
+    let stats = Processor::new(config.unwrap()).run().unwrap();
+    assert_eq!(stats.processed, 1);
+    assert_eq!(stats.risk_count(RiskLevel::Medium), 1);
+    assert!(output.exists());
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_processor_gps_coarsen_keeps_rounded_position() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_gps_coarsen");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let input = temp_dir.join("trip.jpg");
+    let output = temp_dir.join("trip_clean.jpg");
+
+    let rational = |values: &[(u32, u32)]| -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|(n, d)| n.to_le_bytes().into_iter().chain(d.to_le_bytes()))
+            .collect()
+    };
+    let entries = [
+        Entry::new(Ifd::Primary, 0x010F, 2, 6, b"Canon\0".to_vec()),
+        Entry::new(Ifd::Gps, 0x0001, 2, 2, b"S\0".to_vec()),
+        Entry::new(
+            Ifd::Gps,
+            0x0002,
+            5,
+            3,
+            rational(&[(33, 1), (51, 1), (2440, 100)]),
+        ),
+        Entry::new(Ifd::Gps, 0x0003, 2, 2, b"E\0".to_vec()),
+        Entry::new(
+            Ifd::Gps,
+            0x0004,
+            5,
+            3,
+            rational(&[(151, 1), (12, 1), (5508, 100)]),
+        ),
+        Entry::new(Ifd::Gps, 0x0006, 5, 1, rational(&[(42, 1)])),
+        Entry::new(
+            Ifd::Gps,
+            0x0007,
+            5,
+            3,
+            rational(&[(10, 1), (20, 1), (30, 1)]),
+        ),
+        Entry::new(Ifd::Gps, 0x0011, 5, 1, rational(&[(270, 1)])),
+    ];
+    let data = pmi::formats::jpeg::insert_exif(
+        &helpers::create_minimal_jpeg(),
+        &exif::build(&entries),
+        &input,
+    )
+    .unwrap();
+    fs::write(&input, data).unwrap();
+
+    let config = helpers::parse([
+        "pmi",
+        "-q",
+        "--verify",
+        "--gps",
+        "coarsen=10",
+        input.to_str().unwrap(),
+    ]);
+    let stats = Processor::new(config.unwrap()).run().unwrap();
+    assert_eq!(stats.processed, 1);
+
+    let cleaned = fs::read(&output).unwrap();
+    let items = pmi::formats::inspect_metadata(&cleaned, &output).unwrap();
+    assert_eq!(items.len(), 1);
+    let start = items[0].range.start + 4;
+    let parsed = exif::parse(&cleaned[start..items[0].range.end]).unwrap();
+    let tags: Vec<(Ifd, u16)> = parsed
+        .entries
+        .iter()
+        .filter(|e| !e.is_pointer())
+        .map(|e| (e.ifd, e.tag))
+        .collect();
+    assert_eq!(
+        tags,
+        [
+            (Ifd::Gps, 0x0000),
+            (Ifd::Gps, 0x0001),
+            (Ifd::Gps, 0x0002),
+            (Ifd::Gps, 0x0003),
+            (Ifd::Gps, 0x0004),
+        ]
+    );
+
+    let original = gps::Position {
+        latitude: -(33.0 + 51.0 / 60.0 + 24.4 / 3600.0),
+        longitude: 151.0 + 12.0 / 60.0 + 55.08 / 3600.0,
+    };
+    let position = gps::position(&parsed).unwrap();
+    let expected = original.coarsen(10.0);
+    assert!((position.latitude - expected.latitude).abs() < 1e-5);
+    assert!((position.longitude - expected.longitude).abs() < 1e-5);
+    assert!((position.latitude - original.latitude).abs() > 1e-3);
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_processor_dates_shift_rewrites_capture_time() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_dates_shift");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let input = temp_dir.join("party.jpg");
+    let output = temp_dir.join("party_clean.jpg");
+
+    let entries = [
+        Entry::new(Ifd::Primary, 0x010F, 2, 6, b"Canon\0".to_vec()),
+        Entry::new(Ifd::Exif, 0x9003, 2, 20, b"2024:01:01 00:30:00\0".to_vec()),
+        Entry::new(Ifd::Exif, 0xA431, 2, 7, b"123456\0".to_vec()),
+    ];
+    let data = pmi::formats::jpeg::insert_exif(
+        &helpers::create_minimal_jpeg(),
+        &exif::build(&entries),
+        &input,
+    )
+    .unwrap();
+    fs::write(&input, data).unwrap();
+
+    let config = helpers::parse([
+        "pmi",
+        "-q",
+        "--verify",
+        "--dates",
+        "shift=-1h",
+        input.to_str().unwrap(),
+    ]);
+    let stats = Processor::new(config.unwrap()).run().unwrap();
+    assert_eq!(stats.processed, 1);
+
+    let cleaned = fs::read(&output).unwrap();
+    let items = pmi::formats::inspect_metadata(&cleaned, &output).unwrap();
+    assert_eq!(items.len(), 1);
+    let start = items[0].range.start + 4;
+    let parsed = exif::parse(&cleaned[start..items[0].range.end]).unwrap();
+    let fields: Vec<String> = parsed.fields().into_iter().map(|f| f.name).collect();
+    assert_eq!(fields, ["DateTimeOriginal"]);
+    assert_eq!(
+        parsed.find(Ifd::Exif, 0x9003).unwrap().ascii().as_deref(),
+        Some("2023:12:31 23:30:00")
+    );
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_processor_stamp_replaces_rights_metadata() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_stamp");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let input = temp_dir.join("photo.jpg");
+    let output = temp_dir.join("photo_clean.jpg");
+    fs::write(&input, helpers::create_jpeg_with_exif()).unwrap();
+
+    let config = helpers::parse([
+        "pmi",
+        "-q",
+        "--verify",
+        "--stamp",
+        "copyright=Example, Inc.,artist=Jane Doe",
+        input.to_str().unwrap(),
+    ]);
+    let stats = Processor::new(config.unwrap()).run().unwrap();
+    assert_eq!(stats.processed, 1);
+
+    let cleaned = fs::read(&output).unwrap();
+    let items = pmi::formats::inspect_metadata(&cleaned, &output).unwrap();
+    assert_eq!(items.len(), 1);
+    let start = items[0].range.start + 4;
+    let parsed = exif::parse(&cleaned[start..items[0].range.end]).unwrap();
+    let fields: Vec<String> = parsed.fields().into_iter().map(|f| f.name).collect();
+    assert_eq!(fields, ["Artist", "Copyright"]);
+    assert_eq!(
+        parsed
+            .find(Ifd::Primary, 0x8298)
+            .unwrap()
+            .ascii()
+            .as_deref(),
+        Some("Example, Inc.")
+    );
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_processor_social_preset_keeps_orientation() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_preset");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let input = temp_dir.join("photo.jpg");
+    let output = temp_dir.join("photo_clean.jpg");
+    let block = exif::build(&[
+        Entry::new(Ifd::Primary, 0x0112, 3, 1, vec![6, 0]),
+        Entry::new(Ifd::Primary, 0x010F, 2, 6, b"Canon\0".to_vec()),
+    ]);
+    let data = pmi::formats::jpeg::insert_exif(&helpers::create_minimal_jpeg(), &block, &input);
+    fs::write(&input, data.unwrap()).unwrap();
+
+    let config =
+        helpers::parse(["pmi", "-q", "--preset", "social", input.to_str().unwrap()]).unwrap();
+    assert!(config.policy(ImageFormat::Jpeg).keep_orientation);
+    let stats = Processor::new(config).run().unwrap();
+    assert_eq!(stats.processed, 1);
+
+    let cleaned = fs::read(&output).unwrap();
+    let items = pmi::formats::inspect_metadata(&cleaned, &output).unwrap();
+    assert_eq!(items.len(), 1);
+    let start = items[0].range.start + 4;
+    let parsed = exif::parse(&cleaned[start..items[0].range.end]).unwrap();
+    let fields: Vec<String> = parsed.fields().into_iter().map(|f| f.name).collect();
+    assert_eq!(fields, ["Orientation"]);
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_processor_uses_settings_from_config_file() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_config_file");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let input = temp_dir.join("photo.jpg");
+    fs::write(&input, helpers::create_jpeg_with_exif()).unwrap();
+    let config_file = temp_dir.join(".pmi.toml");
+    fs::write(&config_file, "in-place = true\nverify = true\n").unwrap();
+
+    let environment = Environment {
+        files: vec![config_file.clone()],
+        project: None,
+        vars: vec![(String::from("PMI_QUIET"), String::from("true"))],
+    };
+    let config = Config::parse_with(["pmi", input.to_str().unwrap()], &environment).unwrap();
+    assert!(config.in_place && config.verify && config.quiet);
+    assert_eq!(config.source("in-place"), Source::File(config_file));
+
+    let stats = Processor::new(config).run().unwrap();
+    assert_eq!(stats.processed, 1);
+    assert!(!temp_dir.join("photo_clean.jpg").exists());
+    let cleaned = fs::read(&input).unwrap();
+    assert!(
+        pmi::formats::inspect_metadata(&cleaned, &input)
+            .unwrap()
+            .is_empty()
+    );
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_processor_mirrors_tree_under_output_dir() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_mirror");
+    let _ = fs::remove_dir_all(&temp_dir);
+    let (a, b) = (temp_dir.join("a"), temp_dir.join("b"));
+    fs::create_dir_all(a.join("2024")).unwrap();
+    fs::create_dir_all(&b).unwrap();
+    fs::write(
+        a.join("2024").join("IMG_0001.jpg"),
+        helpers::create_jpeg_with_exif(),
+    )
+    .unwrap();
+    fs::write(b.join("IMG_0001.jpg"), helpers::create_jpeg_with_exif()).unwrap();
+    let output_dir = temp_dir.join("out");
+
+    let config = Config {
+        paths: vec![a.clone(), b.clone()],
+        output_dir: Some(output_dir.clone()),
+        recursive: true,
+        quiet: true,
+        jobs: Some(2),
+        ..Default::default()
+    };
+    let stats = Processor::new(config).run().unwrap();
+
+    // Same-named files in different directories no longer collide.
+    assert_eq!(stats.processed, 2);
+    assert_eq!(stats.failed, 0);
+    assert!(output_dir.join("a/2024/IMG_0001.jpg").exists());
+    assert!(output_dir.join("b/IMG_0001.jpg").exists());
+
+    // Roots with the same name would mix, so they are refused up front.
+    let nested = temp_dir.join("c").join("a");
+    fs::create_dir_all(&nested).unwrap();
+    let config = Config {
+        paths: vec![a, nested],
+        output_dir: Some(output_dir),
+        recursive: true,
+        quiet: true,
+        ..Default::default()
+    };
+    assert!(Processor::new(config).run().is_err());
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_processor_name_template_and_collisions() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_naming");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let input = temp_dir.join("photo.jpg");
+    fs::write(&input, helpers::create_jpeg_with_exif()).unwrap();
+    let output_dir = temp_dir.join("out");
+
+    let run = |template: &str, on_collision: &str| {
+        let config = Config::parse_with(
+            [
+                "pmi",
+                "-q",
+                "-o",
+                output_dir.to_str().unwrap(),
+                "--name-template",
+                template,
+                "--on-collision",
+                on_collision,
+                input.to_str().unwrap(),
+            ],
+            &Environment::default(),
+        )
+        .unwrap();
+        Processor::new(config).run().unwrap()
+    };
+
+    // Content-addressed names come from the cleaned data.
+    let stats = run("{hash8}.{ext}", "fail");
+    assert_eq!(stats.processed, 1);
+    let names: Vec<String> = fs::read_dir(&output_dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names.len(), 1);
+    assert!(names[0].ends_with(".jpg") && names[0].len() == 12);
+
+    // The same content again is a collision.
+    assert_eq!(run("{hash8}.{ext}", "fail").failed, 1);
+    assert_eq!(run("{hash8}.{ext}", "skip").skipped, 1);
+
+    run("{stem}.{ext}", "number");
+    run("{stem}.{ext}", "number");
+    assert!(output_dir.join("photo.jpg").exists());
+    assert!(output_dir.join("photo_1.jpg").exists());
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_processor_anonymizes_names_in_place() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_anonymize");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let input = temp_dir.join("IMG_20240612_183044.jpg");
+    fs::write(&input, helpers::create_jpeg_with_exif()).unwrap();
+    let name_map = temp_dir.join("names.csv");
+
+    let config = Config::parse_with(
+        [
+            "pmi",
+            "-q",
+            "-i",
+            "--anonymize-names",
+            "sequential",
+            "--name-map",
+            name_map.to_str().unwrap(),
+            input.to_str().unwrap(),
+        ],
+        &Environment::default(),
+    )
+    .unwrap();
+    let stats = Processor::new(config).run().unwrap();
+    assert_eq!(stats.processed, 1);
+
+    // The original name is gone, and only the private map links them.
+    let renamed = temp_dir.join("image_0001.jpg");
+    assert!(!input.exists());
+    assert!(renamed.exists());
+    let map = fs::read_to_string(&name_map).unwrap();
+    assert_eq!(
+        map,
+        format!(
+            "original,renamed\n{},{}\n",
+            input.display(),
+            renamed.display()
+        )
+    );
+
+    // The journalist preset anonymises names too.
+    let config = Config::parse_with(
+        ["pmi", "--preset", "journalist", renamed.to_str().unwrap()],
+        &Environment::default(),
+    )
+    .unwrap();
+    assert_eq!(config.anonymize_names, Some(Anonymize::Random));
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_processor_fs_metadata() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_fs_metadata");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let input = temp_dir.join("photo.jpg");
+    let taken = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_500_000_000);
+
+    let run = |mode: &str| {
+        fs::write(&input, helpers::create_jpeg_with_exif()).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&input)
+            .unwrap()
+            .set_modified(taken)
+            .unwrap();
+        let config = Config::parse_with(
+            [
+                "pmi",
+                "-q",
+                "-i",
+                "--fs-metadata",
+                mode,
+                input.to_str().unwrap(),
+            ],
+            &Environment::default(),
+        )
+        .unwrap();
+        assert_eq!(Processor::new(config).run().unwrap().processed, 1);
+        fs::metadata(&input).unwrap().modified().unwrap()
+    };
+
+    // Normalised files all get the same time, even when replaced in place.
+    assert_eq!(run("normalize"), fsmeta::normalized_time());
+    let stripped = run("strip");
+    assert!(stripped != fsmeta::normalized_time() && stripped != taken);
+
+    // Preserved files keep the input's time.
+    assert_eq!(run("preserve"), taken);
+
+    let config = Config::parse_with(
+        ["pmi", "--fs-metadata", "keep", input.to_str().unwrap()],
+        &Environment::default(),
+    );
+    assert!(config.is_err());
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_processor_in_place_links_and_mode() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let temp_dir = std::env::temp_dir().join("pmi_test_in_place_links");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let original = temp_dir.join("photo.jpg");
+    let other_name = temp_dir.join("copy.jpg");
+
+    let setup = || {
+        let _ = fs::remove_file(&original);
+        let _ = fs::remove_file(&other_name);
+        fs::write(&original, helpers::create_jpeg_with_exif()).unwrap();
+        fs::set_permissions(&original, fs::Permissions::from_mode(0o640)).unwrap();
+        fs::hard_link(&original, &other_name).unwrap();
+    };
+    let run = |policy: &str, path: &Path| {
+        let config = Config::parse_with(
+            [
+                "pmi",
+                "-q",
+                "-i",
+                "--hard-links",
+                policy,
+                path.to_str().unwrap(),
+            ],
+            &Environment::default(),
+        )
+        .unwrap();
+        Processor::new(config).run().unwrap()
+    };
+    let has_metadata = |path: &Path| {
+        let data = fs::read(path).unwrap();
+        strip_metadata(&data, path).unwrap().bytes_removed > 0
+    };
+
+    // Rewriting through the inode cleans every name and keeps the link.
+    setup();
+    assert_eq!(run("rewrite", &original).processed, 1);
+    assert!(!has_metadata(&other_name));
+    assert_eq!(fs::metadata(&original).unwrap().nlink(), 2);
+    assert_eq!(fs::metadata(&original).unwrap().mode() & 0o7777, 0o640);
+
+    // Breaking the link leaves the other name as it was.
+    setup();
+    assert_eq!(run("break", &original).processed, 1);
+    assert!(!has_metadata(&original));
+    assert!(has_metadata(&other_name));
+    assert_eq!(fs::metadata(&original).unwrap().nlink(), 1);
+    assert_eq!(fs::metadata(&original).unwrap().mode() & 0o7777, 0o640);
+
+    // Skipping touches neither.
+    setup();
+    assert_eq!(run("skip", &original).skipped, 1);
+    assert!(has_metadata(&original));
+
+    // Symlinks are followed, so the link survives and its target is clean.
+    let link = temp_dir.join("link.jpg");
+    fs::remove_file(&other_name).unwrap();
+    std::os::unix::fs::symlink(&original, &link).unwrap();
+    assert_eq!(run("rewrite", &link).processed, 1);
+    assert!(
+        fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+    assert!(!has_metadata(&original));
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_processor_remove_originals() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_remove_originals");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let original = temp_dir.join("photo.jpg");
+    let output = temp_dir.join("photo_clean.jpg");
+    fs::write(&original, helpers::create_jpeg_with_exif()).unwrap();
+
+    let run = |extra: &[&str]| {
+        let mut args = vec!["pmi", "-q", "--remove-originals"];
+        args.extend_from_slice(extra);
+        args.push(original.to_str().unwrap());
+        let config = Config::parse_with(args, &Environment::default()).unwrap();
+        Processor::new(config).run().unwrap()
+    };
+
+    // A dry run keeps the original.
+    assert_eq!(run(&["-n", "--shred"]).processed, 1);
+    assert!(original.exists() && !output.exists());
+
+    // The original goes once the verified output is written.
+    assert_eq!(run(&["--shred"]).processed, 1);
+    assert!(!original.exists());
+    let data = fs::read(&output).unwrap();
+    assert_eq!(strip_metadata(&data, &output).unwrap().bytes_removed, 0);
+
+    // Removing one name of a hard-linked file would not remove the image.
+    #[cfg(unix)]
+    {
+        fs::remove_file(&output).unwrap();
+        fs::write(&original, helpers::create_jpeg_with_exif()).unwrap();
+        fs::hard_link(&original, temp_dir.join("copy.jpg")).unwrap();
+        assert_eq!(run(&[]).failed, 1);
+        assert!(original.exists() && !output.exists());
+    }
+
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_processor_incremental_skips_unchanged_files() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_incremental");
+    let _ = fs::remove_dir_all(&temp_dir);
+    let photos = temp_dir.join("photos");
+    fs::create_dir_all(&photos).unwrap();
+    fs::write(photos.join("a.jpg"), helpers::create_jpeg_with_exif()).unwrap();
+    fs::write(photos.join("b.jpg"), helpers::create_jpeg_with_exif()).unwrap();
+    let cache = temp_dir.join("cache.csv");
+
+    let run = |extra: &[&str]| {
+        let mut args = vec!["pmi", "-q", "-i", "--incremental"];
+        args.extend_from_slice(extra);
+        args.extend(["--cache", cache.to_str().unwrap(), photos.to_str().unwrap()]);
+        let config = Config::parse_with(args, &Environment::default()).unwrap();
+        Processor::new(config).run().unwrap()
+    };
+
+    assert_eq!(run(&[]).processed, 2);
+    assert!(cache.exists());
+
+    // Nothing changed, so nothing is read again.
+    assert_eq!(run(&[]).total(), 0);
+
+    // A new image is cleaned.
+    fs::write(photos.join("b.jpg"), helpers::create_jpeg_with_exif()).unwrap();
+    assert_eq!(run(&[]).processed, 1);
+
+    // New settings look at every file again, though these need no changes.
+    assert_eq!(run(&["--gps", "coarsen=10"]).already_clean, 2);
+
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_processor_leaves_clean_files_untouched() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_already_clean");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let path = temp_dir.join("photo.jpg");
+    fs::write(&path, helpers::create_jpeg_with_exif()).unwrap();
+
+    let run = || {
+        let config = helpers::parse(["pmi", "-q", "-i", path.to_str().unwrap()]).unwrap();
+        Processor::new(config).run().unwrap()
+    };
+    assert_eq!(run().processed, 1);
+
+    // A second run finds nothing to change and does not write.
+    let cleaned = fs::read(&path).unwrap();
+    let earlier = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+    fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(earlier)
+        .unwrap();
+    let stats = run();
+    assert_eq!((stats.processed, stats.already_clean), (0, 1));
+    assert_eq!(fs::read(&path).unwrap(), cleaned);
+    assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), earlier);
+
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_cleanup_removes_stale_temp_files() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_cleanup");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(temp_dir.join("nested")).unwrap();
+    let image = temp_dir.join("photo.jpg");
+    fs::write(&image, helpers::create_jpeg_with_exif()).unwrap();
+    let stale = temp_dir.join(".photo.jpg.0123456789abcdef.pmi_tmp");
+    let legacy = temp_dir.join("nested").join("x.pmi_tmp");
+    fs::write(&stale, b"partial").unwrap();
+    fs::write(&legacy, b"someone's file").unwrap();
+
+    let parse = |args: &[&str]| {
+        let mut all = vec!["pmi", "cleanup", "-q"];
+        all.extend_from_slice(args);
+        all.push(temp_dir.to_str().unwrap());
+        Config::parse_with(all, &Environment::default()).unwrap()
+    };
+
+    // Without -r only the top directory is searched.
+    assert_eq!(cleanup::find(&parse(&[])).unwrap(), [stale.as_path()]);
+
+    // A dry run lists them and removes nothing.
+    let found = cleanup::run(&parse(&["-r", "-n"])).unwrap();
+    assert_eq!(found, [stale.as_path()]);
+    assert!(stale.exists());
+
+    // A name earlier versions used is only listed without --legacy.
+    cleanup::run(&parse(&["-r"])).unwrap();
+    assert!(!stale.exists());
+    assert_eq!(fs::read(&legacy).unwrap(), b"someone's file");
+    assert_eq!(
+        cleanup::find_legacy(&parse(&["-r"])).unwrap(),
+        [legacy.as_path()]
+    );
+    assert_eq!(
+        cleanup::run(&parse(&["-r", "--legacy"])).unwrap(),
+        [legacy.as_path()]
+    );
+    assert!(!legacy.exists());
+    assert!(image.exists());
+
+    // Stripping leaves no temporary files behind.
+    let config = Config::parse_with(
+        ["pmi", "-q", "-i", image.to_str().unwrap()],
+        &Environment::default(),
+    )
+    .unwrap();
+    assert_eq!(Processor::new(config).run().unwrap().processed, 1);
+    assert!(cleanup::find(&parse(&["-r"])).unwrap().is_empty());
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}
+
+#[test]
+fn test_backup_journal_and_restore() {
+    let temp_dir = std::env::temp_dir().join("pmi_test_backup");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(temp_dir.join("album")).unwrap();
+    let first = temp_dir.join("a.jpg");
+    let second = temp_dir.join("album").join("b.jpg");
+    let original = helpers::create_jpeg_with_exif();
+    fs::write(&first, &original).unwrap();
+    fs::write(&second, &original).unwrap();
+    // Backups inside the tree are not cleaned by later runs.
+    let backups = temp_dir.join(".backups");
+
+    let clean = |extra: &[&str]| {
+        let mut args = vec!["pmi", "-q", "-r", "-i", "-j", "2", "--backup"];
+        args.push(backups.to_str().unwrap());
+        args.extend_from_slice(extra);
+        args.push(temp_dir.to_str().unwrap());
+        let config = Config::parse_with(args, &Environment::default()).unwrap();
+        Processor::new(config).run().unwrap()
+    };
+    let restore = |journal: &Path, dry_run: bool| {
+        let mut args = vec!["pmi", "restore", "-q"];
+        if dry_run {
+            args.push("-n");
+        }
+        args.push(journal.to_str().unwrap());
+        let config = Config::parse_with(args, &Environment::default()).unwrap();
+        backup::restore(&config).unwrap()
+    };
+    let journal = |n: usize| {
+        let mut runs: Vec<PathBuf> = fs::read_dir(&backups)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        runs.sort();
+        assert_eq!(runs.len(), n);
+        runs[n - 1].join(backup::JOURNAL_NAME)
+    };
+
+    assert_eq!(clean(&[]).processed, 2);
+    assert_ne!(fs::read(&first).unwrap(), original);
+    let entries = backup::read_journal(&journal(1)).unwrap();
+    assert_eq!(entries.len(), 2);
+
+    // A dry run changes nothing; a real one puts both originals back.
+    assert!(
+        restore(&journal(1), true)
+            .iter()
+            .all(|(_, s)| *s == RestoreStatus::Restored)
+    );
+    assert_ne!(fs::read(&first).unwrap(), original);
+    assert!(
+        restore(&journal(1), false)
+            .iter()
+            .all(|(_, s)| *s == RestoreStatus::Restored)
+    );
+    assert_eq!(fs::read(&first).unwrap(), original);
+    assert_eq!(fs::read(&second).unwrap(), original);
+
+    // Restoring again finds the originals already in place.
+    assert!(
+        restore(&journal(1), false)
+            .iter()
+            .all(|(_, s)| *s == RestoreStatus::AlreadyRestored)
+    );
+
+    // Files edited after the run are left alone.
+    assert_eq!(clean(&[]).processed, 2);
+    fs::write(&first, b"edited").unwrap();
+    let results = restore(&journal(2), false);
+    let status = |path: &Path| {
+        let path = fs::canonicalize(path).unwrap();
+        results
+            .iter()
+            .find(|(entry, _)| entry.original == path)
+            .map(|(_, status)| status.clone())
+            .unwrap()
+    };
+    assert!(matches!(status(&first), RestoreStatus::Changed(_)));
+    assert_eq!(status(&second), RestoreStatus::Restored);
+    assert_eq!(fs::read(&first).unwrap(), b"edited");
+
+    // Renamed files get their old names back.
+    fs::write(&first, &original).unwrap();
+    fs::remove_file(&second).unwrap();
+    assert_eq!(clean(&["--anonymize-names", "sequential"]).processed, 1);
+    assert!(!first.exists());
+    restore(&journal(3), false);
+    assert_eq!(fs::read(&first).unwrap(), original);
+    assert!(!temp_dir.join("image_0001.jpg").exists());
+
+    // Cleanup.
+    let _ = fs::remove_dir_all(&temp_dir);
+}